@@ -0,0 +1,143 @@
+//! `dotty add`: move a file from the target into the root and link it back.
+
+use crate::{EntryKind, Options, PlanGroup, backup, expand_tilde, points_into, shorten_home};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::os::unix::fs as unix_fs;
+use std::path::{Path, PathBuf};
+
+/// Where in the root `target` belongs: below the source of a planned directory link
+/// above it, which may be renamed, or else at the same path below `root`.
+fn source_for(
+    target: &Path,
+    rel: &Path,
+    root: &Path,
+    groups: &[PlanGroup],
+    opts: &Options,
+) -> PathBuf {
+    let same_path = root.join(rel);
+    for entry in groups.iter().flat_map(|group| &group.entries) {
+        if !matches!(entry.kind, EntryKind::Dir | EntryKind::Folded) {
+            continue;
+        }
+        let Ok(rest) = target.strip_prefix(&entry.target) else {
+            continue;
+        };
+        let mapped = entry.source.join(rest);
+        if mapped != same_path {
+            println!(
+                "{} {} is linked from {} (rename_to), adding the file there",
+                opts.color.yellow("⚠"),
+                shorten_home(&entry.target, &opts.target),
+                shorten_home(&entry.source, &opts.target)
+            );
+        }
+        return mapped;
+    }
+    same_path
+}
+
+/// Move the file at `path` into `root` at the path it has below the target and link it
+/// back, or with `copy` only copy it there. Refuses anything but a regular file below
+/// the target, and sources that already exist.
+pub fn run(
+    groups: &[PlanGroup],
+    root: &Path,
+    path: &str,
+    copy: bool,
+    opts: &Options,
+) -> Result<()> {
+    let target = std::path::absolute(expand_tilde(path)?)
+        .with_context(|| format!("Failed to resolve {path}"))?;
+    let Ok(rel) = target.strip_prefix(&opts.target) else {
+        bail!(
+            "{} is not inside the target directory {}",
+            target.display(),
+            opts.target.display()
+        );
+    };
+    let meta = fs::symlink_metadata(&target)
+        .with_context(|| format!("Cannot add {}", target.display()))?;
+    if meta.is_symlink() {
+        bail!("{} is already a symlink", target.display());
+    }
+    if points_into(&target, &opts.roots) {
+        bail!("{} is already inside the root", target.display());
+    }
+    if !meta.is_file() {
+        bail!("Only regular files can be added: {}", target.display());
+    }
+    if let Some(entry) = groups
+        .iter()
+        .flat_map(|group| &group.entries)
+        .find(|entry| entry.target == target && entry.kind.claims_target())
+    {
+        bail!(
+            "{} is already planned from {}",
+            target.display(),
+            entry.source.display()
+        );
+    }
+    let source = source_for(&target, rel, root, groups, opts);
+    if fs::symlink_metadata(&source).is_ok() {
+        bail!("Source already exists in the root: {}", source.display());
+    }
+
+    let (short_target, short_source) = (
+        shorten_home(&target, &opts.target),
+        shorten_home(&source, &opts.target),
+    );
+    if opts.dry_run {
+        if copy {
+            println!(
+                "{} Would copy {short_target} to {short_source}",
+                opts.color.green("✔")
+            );
+        } else {
+            println!(
+                "{} Would move {short_target} to {short_source}",
+                opts.color.green("✔")
+            );
+            println!(
+                "{} Would symlink {short_target} -> {short_source}",
+                opts.color.green("✔")
+            );
+        }
+        return Ok(());
+    }
+    if copy {
+        if let Some(parent) = source.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::copy(&target, &source).with_context(|| {
+            format!(
+                "Failed to copy {} to {}",
+                target.display(),
+                source.display()
+            )
+        })?;
+        println!(
+            "{} Copied {short_target} to {short_source}",
+            opts.color.green("✔")
+        );
+        return Ok(());
+    }
+    backup::move_file(&target, &source)?;
+    println!(
+        "{} Moved {short_target} to {short_source}",
+        opts.color.green("✔")
+    );
+    unix_fs::symlink(&source, &target).with_context(|| {
+        format!(
+            "Failed to symlink {} -> {}",
+            target.display(),
+            source.display()
+        )
+    })?;
+    println!(
+        "{} Linked {short_target} -> {short_source}",
+        opts.color.green("✔")
+    );
+    Ok(())
+}
@@ -0,0 +1,76 @@
+//! Where files replaced by dotty are kept (`--backup-dir`).
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Parent of the per-run backup directories used without `--backup-dir`.
+pub const DEFAULT_BACKUP_ROOT: &str = "~/.local/state/dotty/backups";
+
+/// The current UTC time as `YYYYMMDD-HHMMSS`, naming one run's backup directory.
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        rest / 3_600,
+        rest % 3_600 / 60,
+        rest % 60
+    )
+}
+
+/// Where `target` is kept inside `dir`: its path below `target_base`, or its full path
+/// for targets outside of it.
+pub fn backup_path(dir: &Path, target_base: &Path, target: &Path) -> PathBuf {
+    match target.strip_prefix(target_base) {
+        Ok(rel) => dir.join(rel),
+        Err(_) => dir.join(target.strip_prefix("/").unwrap_or(target)),
+    }
+}
+
+/// Move the file or symlink `from` to `to`, creating its parent directories. Falls back to
+/// copying when the backup dir is on another volume; `from` is only removed once the copy
+/// succeeded.
+pub fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_symlink() {
+        let dest = fs::read_link(from)
+            .with_context(|| format!("Failed to read link {}", from.display()))?;
+        std::os::unix::fs::symlink(dest, to)
+            .with_context(|| format!("Failed to create link {}", to.display()))?;
+    } else {
+        fs::copy(from, to).with_context(|| format!("Failed to copy to {}", to.display()))?;
+    }
+    fs::remove_file(from).with_context(|| format!("Failed to remove {}", from.display()))
+}
+
+/// Copy the regular file `from` to `to` with its permissions, creating its parent
+/// directories, and leave `from` in place.
+pub fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::copy(from, to).with_context(|| format!("Failed to copy to {}", to.display()))?;
+    Ok(())
+}
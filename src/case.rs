@@ -0,0 +1,69 @@
+//! Case-insensitive targets, like the default APFS volume on macOS, where `Config` and
+//! `config` are one file.
+
+use crate::config;
+use anyhow::Result;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Whether names in `dir` that differ only by case are different files. `DOTTY_CASE_SENSITIVE`
+/// overrides the answer; otherwise an entry of `dir` is looked up with its case flipped,
+/// or, when there is none to try, a probe file is created (but not in a dry run).
+/// Whatever cannot be told counts as case-sensitive.
+pub fn sensitive(dir: &Path, dry_run: bool) -> Result<bool> {
+    if let Some(sensitive) = config::env_bool("DOTTY_CASE_SENSITIVE")? {
+        return Ok(sensitive);
+    }
+    let names = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|entry| entry.file_name()).collect())
+        .unwrap_or_else(|_| Vec::new());
+    if let Some(found) = names.iter().find_map(|name| same_file_flipped(dir, name)) {
+        return Ok(!found);
+    }
+    if dry_run {
+        return Ok(true);
+    }
+    let probe = OsString::from(format!(".dotty-case-probe-{}", std::process::id()));
+    if File::create(dir.join(&probe)).is_err() {
+        return Ok(true);
+    }
+    let found = same_file_flipped(dir, &probe);
+    let _ = fs::remove_file(dir.join(&probe));
+    Ok(found != Some(true))
+}
+
+/// Whether `name` in `dir`, with the case of its letters flipped, is the same file.
+/// `None` when it has no letters.
+fn same_file_flipped(dir: &Path, name: &OsString) -> Option<bool> {
+    let flipped: Vec<u8> = name
+        .as_bytes()
+        .iter()
+        .map(|byte| match byte {
+            b'a'..=b'z' => byte.to_ascii_uppercase(),
+            b'A'..=b'Z' => byte.to_ascii_lowercase(),
+            _ => *byte,
+        })
+        .collect();
+    if flipped == name.as_bytes() {
+        return None;
+    }
+    let original = fs::symlink_metadata(dir.join(name)).ok()?;
+    let Ok(other) = fs::symlink_metadata(dir.join(OsString::from_vec(flipped))) else {
+        return Some(false);
+    };
+    Some(original.dev() == other.dev() && original.ino() == other.ino())
+}
+
+/// `path` as a case-insensitive volume compares it: lowercased, ASCII letters only for
+/// names that are not UTF-8.
+pub fn fold(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(text) => PathBuf::from(text.to_lowercase()),
+        None => PathBuf::from(OsString::from_vec(
+            path.as_os_str().as_bytes().to_ascii_lowercase(),
+        )),
+    }
+}
@@ -0,0 +1,80 @@
+//! Content comparisons that read files in pieces and stop at the first difference,
+//! instead of loading both sides whole.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Files are compared in pieces of this size.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Fill `buffer` from `reader` as far as it goes, returning how much was read; less than
+/// the buffer only at the end of the file.
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Whether the files `a` and `b` can both be read and have the same content. Files of
+/// different sizes differ without being read.
+pub fn files_equal(a: &Path, b: &Path) -> bool {
+    let (Ok(meta_a), Ok(meta_b)) = (fs::metadata(a), fs::metadata(b)) else {
+        return false;
+    };
+    if meta_a.len() != meta_b.len() {
+        return false;
+    }
+    let (Ok(mut file_a), Ok(mut file_b)) = (File::open(a), File::open(b)) else {
+        return false;
+    };
+    let (mut chunk_a, mut chunk_b) = (vec![0; CHUNK_SIZE], vec![0; CHUNK_SIZE]);
+    loop {
+        let (Ok(read_a), Ok(read_b)) = (
+            read_chunk(&mut file_a, &mut chunk_a),
+            read_chunk(&mut file_b, &mut chunk_b),
+        ) else {
+            return false;
+        };
+        if chunk_a[..read_a] != chunk_b[..read_b] {
+            return false;
+        }
+        if read_a == 0 {
+            return true;
+        }
+    }
+}
+
+/// Whether the file at `path` can be read and holds exactly `content`.
+pub fn file_equals(path: &Path, content: &[u8]) -> bool {
+    let Ok(meta) = fs::metadata(path) else {
+        return false;
+    };
+    if meta.len() != content.len() as u64 {
+        return false;
+    }
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut rest = content;
+    loop {
+        let Ok(read) = read_chunk(&mut file, &mut chunk) else {
+            return false;
+        };
+        if read > rest.len() || chunk[..read] != rest[..read] {
+            return false;
+        }
+        rest = &rest[read..];
+        if read == 0 {
+            return rest.is_empty();
+        }
+    }
+}
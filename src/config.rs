@@ -0,0 +1,94 @@
+//! Persistent defaults read from `dotty.toml` files.
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Per-user config file, read unless `--no-config` is given.
+pub const USER_CONFIG_PATH: &str = "~/.config/dotty/dotty.toml";
+
+/// Config file at the top of a root, shared by every machine using that root.
+pub const ROOT_CONFIG_NAME: &str = "dotty.toml";
+
+/// Defaults for CLI options. Every key is optional; CLI flags take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub root: Option<String>,
+    pub target: Option<String>,
+    pub dry_run: Option<bool>,
+    pub verbose: Option<bool>,
+    pub color: Option<bool>,
+    pub override_identical: Option<bool>,
+    pub excludes: Option<Vec<String>>,
+    /// Variables for companions and templates, overridden by `--var`.
+    pub vars: Option<BTreeMap<String, String>>,
+}
+
+impl Config {
+    /// Parse `path`, returning `None` when it does not exist.
+    pub fn load(path: &Path) -> Result<Option<Config>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        Ok(Some(config))
+    }
+
+    /// Parse the `dotty.toml` inside `root`, which may not pick the root itself.
+    pub fn load_root(root: &Path) -> Result<Option<Config>> {
+        let path = root.join(ROOT_CONFIG_NAME);
+        let config = Config::load(&path)?;
+        if config.as_ref().is_some_and(|c| c.root.is_some()) {
+            bail!(
+                "Invalid key 'root' in {}: a root's own config cannot choose the root",
+                path.display()
+            );
+        }
+        Ok(config)
+    }
+
+    /// Keep every key set in `self` and take the remaining ones from `fallback`.
+    pub fn or(self, fallback: Config) -> Config {
+        Config {
+            root: self.root.or(fallback.root),
+            target: self.target.or(fallback.target),
+            dry_run: self.dry_run.or(fallback.dry_run),
+            verbose: self.verbose.or(fallback.verbose),
+            color: self.color.or(fallback.color),
+            override_identical: self.override_identical.or(fallback.override_identical),
+            excludes: self.excludes.or(fallback.excludes),
+            // Variables are merged by name so a root can provide defaults
+            vars: match (self.vars, fallback.vars) {
+                (Some(vars), Some(mut fallback)) => {
+                    fallback.extend(vars);
+                    Some(fallback)
+                }
+                (vars, fallback) => vars.or(fallback),
+            },
+        }
+    }
+}
+
+/// Read a string setting from the environment; unset and empty both mean "not set".
+pub fn env_string(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Read a boolean setting from the environment, rejecting anything that is not
+/// `1/true/yes` or `0/false/no`.
+pub fn env_bool(name: &str) -> Result<Option<bool>> {
+    let Some(value) = env_string(name) else {
+        return Ok(None);
+    };
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Ok(Some(true)),
+        "0" | "false" | "no" => Ok(Some(false)),
+        _ => bail!("Invalid value for {name}: '{value}' (expected 1/true/yes or 0/false/no)"),
+    }
+}
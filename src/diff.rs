@@ -0,0 +1,216 @@
+//! Unified diffs between what is at a target and what dotty would put there, and
+//! `dotty diff` for a single file.
+
+use crate::which::{self, Owner};
+use crate::{Colorize, EntryKind, Options, PlanEntry, PlanGroup, planned_content, shorten_home};
+use anyhow::{Context, Result, bail};
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// Exit code of `dotty diff` when a target differs from what the plan would put there.
+pub const DIFFERS: u8 = 1;
+
+/// Lines of unchanged context shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// How long diffing may take before it settles for a coarser result, so a huge file
+/// cannot hold up the run.
+const DIFF_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Files above this size get no line stats, to keep dry runs quick.
+const STATS_MAX_BYTES: usize = 1024 * 1024;
+
+/// `bytes` as text, or `None` for binary content (invalid UTF-8 or a NUL byte).
+fn as_text(bytes: &[u8]) -> Option<&str> {
+    if bytes.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()
+}
+
+/// Whether `bytes` is binary content, which gets no diff or line stats.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    as_text(bytes).is_none()
+}
+
+/// A unified diff from `old` to `new`, labelled `old_label` and `new_label`, with removals
+/// in red and additions in green. Only the first `max_lines` lines of hunks are shown,
+/// the rest is counted. Binary content is summarized in one line instead.
+pub fn unified(
+    old: &[u8],
+    new: &[u8],
+    old_label: &str,
+    new_label: &str,
+    max_lines: usize,
+    color: Colorize,
+) -> String {
+    let (Some(old), Some(new)) = (as_text(old), as_text(new)) else {
+        return format!("Binary files {old_label} and {new_label} differ\n");
+    };
+    let diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(old, new);
+    let mut out = format!("--- {old_label}\n+++ {new_label}\n");
+    let mut shown = 0;
+    let mut hidden = 0;
+    let mut unified = diff.unified_diff();
+    unified.context_radius(CONTEXT_LINES);
+    for hunk in unified.iter_hunks() {
+        let header = hunk.header().to_string();
+        let changes = hunk.iter_changes().map(|change| {
+            let text = change.value().trim_end_matches(['\n', '\r']);
+            match change.tag() {
+                ChangeTag::Delete => color.red(&format!("-{text}")),
+                ChangeTag::Insert => color.green(&format!("+{text}")),
+                ChangeTag::Equal => format!(" {text}"),
+            }
+        });
+        for line in std::iter::once(color.blue(&header)).chain(changes) {
+            if shown < max_lines {
+                out.push_str(&line);
+                out.push('\n');
+                shown += 1;
+            } else {
+                hidden += 1;
+            }
+        }
+    }
+    if hidden > 0 {
+        out.push_str(&format!("... {hidden} more diff lines\n"));
+    }
+    out
+}
+
+/// A short note on how much writing `new` over `old` changes, e.g. `+12/-3`, or
+/// `new file, 40 lines` without an `old`. `None` for binary or very large content.
+pub fn line_stats(old: Option<&[u8]>, new: &[u8]) -> Option<String> {
+    if new.len() > STATS_MAX_BYTES || old.is_some_and(|old| old.len() > STATS_MAX_BYTES) {
+        return None;
+    }
+    let new = as_text(new)?;
+    let Some(old) = old else {
+        return Some(format!("new file, {} lines", new.lines().count()));
+    };
+    let diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(as_text(old)?, new);
+    let (mut added, mut removed) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    Some(format!("+{added}/-{removed}"))
+}
+
+/// Whether `path` names a target (`~/...` or absolute) rather than a path in the root.
+pub fn is_target_path(path: &str) -> bool {
+    path.starts_with('~') || Path::new(path).is_absolute()
+}
+
+/// What `entry` would put at its target when `current` is there now.
+fn planned(entry: &PlanEntry, current: Option<&[u8]>, opts: &Options) -> Result<Vec<u8>> {
+    let short = shorten_home(&entry.target, &opts.target);
+    match &entry.kind {
+        EntryKind::Inject(injection) => {
+            let text = current.map(String::from_utf8_lossy);
+            Ok(injection.apply(text.as_deref())?.0.into_bytes())
+        }
+        EntryKind::Dir | EntryKind::Folded => {
+            bail!("{short} is a directory link, there is no file to diff")
+        }
+        _ => planned_content(entry)
+            .with_context(|| format!("Failed to read {}", entry.source.display())),
+    }
+}
+
+/// Print a unified diff from what is at the target of `path` to what the plan would put
+/// there, for a target (`~/.gitconfig`) or a path relative to the root
+/// (`.config/git/config`). Exits with [`DIFFERS`] when any target differs.
+pub fn run(groups: &[PlanGroup], path: &str, opts: &Options) -> Result<ExitCode> {
+    // (target, source, planned content) of each file to diff
+    let mut files: Vec<(PathBuf, PathBuf, Vec<u8>)> = Vec::new();
+    let entries: Vec<&PlanEntry> = if is_target_path(path) {
+        let target = which::resolve(path)?;
+        match which::owner(groups, &target, opts) {
+            Some(Owner::Planned(entries)) => entries,
+            // A file under a linked directory is diffed against its source in the root
+            Some(Owner::InsideDir(_, source)) => {
+                let content = fs::read(&source)
+                    .with_context(|| format!("Failed to read {}", source.display()))?;
+                files.push((target, source, content));
+                Vec::new()
+            }
+            _ => bail!(
+                "No source in the root maps to {}",
+                shorten_home(&target, &opts.target)
+            ),
+        }
+    } else {
+        let entries: Vec<&PlanEntry> = groups
+            .iter()
+            .flat_map(|group| {
+                let source = group.dir.join(path);
+                group
+                    .entries
+                    .iter()
+                    .filter(move |entry| entry.source == source)
+            })
+            .collect();
+        if entries.is_empty() {
+            bail!("Nothing in the root is planned from {path}");
+        }
+        entries
+    };
+    for entry in entries {
+        if !entry.kind.claims_target() {
+            bail!(
+                "{} is not placed: {}",
+                shorten_home(&entry.target, &opts.target),
+                which::action(&entry.kind)
+            );
+        }
+        let current = fs::read(&entry.target).ok();
+        let content = planned(entry, current.as_deref(), opts)?;
+        files.push((entry.target.clone(), entry.source.clone(), content));
+    }
+
+    let mut differs = false;
+    for (target, source, content) in files {
+        let short = shorten_home(&target, &opts.target);
+        if target.is_dir() {
+            bail!("{short} is a directory, there is no file to diff");
+        }
+        let current = fs::read(&target).ok();
+        if current.as_deref() == Some(content.as_slice()) {
+            println!("{} {short} matches the plan", opts.color.green("✔"));
+            continue;
+        }
+        differs = true;
+        let old_label = match current {
+            Some(_) => short,
+            None => format!("{short} (missing)"),
+        };
+        print!(
+            "{}",
+            unified(
+                current.as_deref().unwrap_or_default(),
+                &content,
+                &old_label,
+                &shorten_home(&source, &opts.target),
+                opts.diff_lines,
+                opts.color,
+            )
+        );
+    }
+    Ok(if differs {
+        ExitCode::from(DIFFERS)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
@@ -0,0 +1,84 @@
+//! `dotty edit`: open the source that owns a target in `$EDITOR`.
+
+use crate::status::{self, State};
+use crate::which::{self, Owner};
+use crate::{EntryKind, Options, PlanGroup, config, shorten_home};
+use anyhow::{Context, Result, bail};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Open the source owning `path` (looked up like `dotty which`) in `$EDITOR`, or
+/// `$VISUAL`, warning when the target is written rather than linked. Returns the source
+/// when the user asks to refresh the target afterwards.
+pub fn run(groups: &[PlanGroup], path: &str, opts: &Options) -> Result<Option<PathBuf>> {
+    let target = which::resolve(path)?;
+    let short = shorten_home(&target, &opts.target);
+    let (source, entry) = match which::owner(groups, &target, opts) {
+        Some(Owner::Planned(entries)) => {
+            let entry = entries
+                .iter()
+                .find(|entry| entry.kind.claims_target())
+                .unwrap_or(&entries[0]);
+            (entry.source.clone(), Some(*entry))
+        }
+        Some(Owner::InsideDir(_, source)) => (source, None),
+        Some(Owner::Link(dest)) => (dest, None),
+        None => bail!("No source in the root maps to {short}"),
+    };
+    let linked = entry.is_none_or(|entry| {
+        matches!(
+            entry.kind,
+            EntryKind::File | EntryKind::Dir | EntryKind::Folded | EntryKind::Skipped
+        )
+    });
+    if let Some(entry) = entry
+        && !linked
+    {
+        println!(
+            "{} {short} is a {} written from {}: edits made to it are lost on the next run, \
+             so the source is opened instead",
+            opts.color.red("⚠"),
+            which::action(&entry.kind),
+            shorten_home(&source, &opts.target)
+        );
+    }
+
+    let Some(editor) = config::env_string("EDITOR").or_else(|| config::env_string("VISUAL")) else {
+        bail!(
+            "Neither $EDITOR nor $VISUAL is set, cannot open {}",
+            source.display()
+        );
+    };
+    // Editors are often given with arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(words)
+        .arg(&source)
+        .status()
+        .with_context(|| format!("Failed to start editor {editor}"))?;
+    if !status.success() {
+        bail!("Editor {editor} exited with {status}");
+    }
+
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
+    if linked && status::entry_state(entry, opts)? == Some(State::Linked) {
+        return Ok(None);
+    }
+    let assume_tty = config::env_bool("DOTTY_ASSUME_TTY")?.unwrap_or(false);
+    if !atty::is(atty::Stream::Stdin) && !assume_tty {
+        println!(
+            "{} Run dotty again to refresh {short}",
+            opts.color.blue("ℹ")
+        );
+        return Ok(None);
+    }
+    print!("{} Refresh {short} now? [y/N] ", opts.color.yellow("?"));
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "yes").then_some(source))
+}
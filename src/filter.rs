@@ -0,0 +1,222 @@
+//! Glob based filtering of root-relative paths.
+
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    // Dotfiles are the common case, so `*` should match a leading dot
+    require_literal_leading_dot: false,
+};
+
+/// File name globs for junk that is never worth linking, unless `--no-default-ignores`.
+pub const DEFAULT_IGNORES: &[&str] = &[".DS_Store", ".git", "*.swp", "*~", ".#*"];
+
+/// A glob matched against paths relative to the root (or package), e.g. `--exclude`.
+#[derive(Debug)]
+pub struct PathGlob {
+    source: String,
+    pattern: Pattern,
+    /// For patterns ending in `/**`, matches the directory itself so it can be pruned whole.
+    dir_pattern: Option<Pattern>,
+}
+
+impl PathGlob {
+    pub fn new(source: &str) -> Result<Self> {
+        let pattern =
+            Pattern::new(source).with_context(|| format!("Invalid glob pattern: {source}"))?;
+        let dir_pattern = match source.strip_suffix("/**") {
+            Some(dir) => {
+                Some(Pattern::new(dir).with_context(|| format!("Invalid glob pattern: {source}"))?)
+            }
+            None => None,
+        };
+        Ok(PathGlob {
+            source: source.to_string(),
+            pattern,
+            dir_pattern,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Patterns without a `/` match the file name at any depth (like gitignore),
+    /// all others match the whole relative path.
+    pub fn matches(&self, rel: &Path) -> bool {
+        if !self.source.contains('/') {
+            return rel.file_name().is_some_and(|name| {
+                self.pattern
+                    .matches_with(&name.to_string_lossy(), MATCH_OPTIONS)
+            });
+        }
+        self.pattern.matches_path_with(rel, MATCH_OPTIONS)
+            || self
+                .dir_pattern
+                .as_ref()
+                .is_some_and(|dir| dir.matches_path_with(rel, MATCH_OPTIONS))
+    }
+
+    /// Whether some path below the directory `rel` could match this glob, comparing
+    /// the pattern component by component against the directory's components.
+    pub fn could_match_below(&self, rel: &Path) -> bool {
+        if !self.source.contains('/') {
+            return true;
+        }
+        let mut pattern_parts = self.source.split('/');
+        for component in rel.components() {
+            let Some(part) = pattern_parts.next() else {
+                return false;
+            };
+            if part == "**" {
+                return true;
+            }
+            let component = component.as_os_str().to_string_lossy();
+            if !Pattern::new(part).is_ok_and(|p| p.matches_with(&component, MATCH_OPTIONS)) {
+                return false;
+            }
+        }
+        pattern_parts.next().is_some()
+    }
+}
+
+/// Whether `rel` or one of its parent directories matches any of `globs`.
+pub fn matches_self_or_ancestor(globs: &[PathGlob], rel: &Path) -> bool {
+    rel.ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .any(|ancestor| globs.iter().any(|glob| glob.matches(ancestor)))
+}
+
+/// Whether some path below the directory `rel` could match any of `globs`.
+pub fn could_match_below(globs: &[PathGlob], rel: &Path) -> bool {
+    globs.iter().any(|glob| glob.could_match_below(rel))
+}
+
+/// The first glob in `globs` matching `rel`, if any.
+pub fn first_match<'a>(globs: &'a [PathGlob], rel: &Path) -> Option<&'a PathGlob> {
+    globs.iter().find(|glob| glob.matches(rel))
+}
+
+/// Name of the per-directory ignore file.
+pub const IGNORE_FILE_NAME: &str = ".dottyignore";
+
+/// A single rule of a `.dottyignore` file.
+#[derive(Debug)]
+struct IgnoreRule {
+    /// The line as written, for messages.
+    text: String,
+    pattern: Pattern,
+    negated: bool,
+    dir_only: bool,
+    /// Rules containing a `/` match relative to the ignore file's directory,
+    /// all others match the file name at any depth.
+    anchored: bool,
+}
+
+/// The parsed rules of one `.dottyignore` file, applying to its directory's subtree.
+#[derive(Debug)]
+pub struct IgnoreFile {
+    /// Root-relative directory containing the file.
+    dir: PathBuf,
+    rules: Vec<IgnoreRule>,
+    /// Prepended to the paths checked against the file to make them root-relative: the
+    /// package, for the root's file while a package is walked from its own directory.
+    prefix: PathBuf,
+}
+
+impl IgnoreFile {
+    /// Load `root/rel/.dottyignore` if present.
+    pub fn load(root: &Path, rel: &Path) -> Result<Option<IgnoreFile>> {
+        let path = root.join(rel).join(IGNORE_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut rules = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let text = line.trim_end();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            let (negated, rest) = match text.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, text.strip_prefix('\\').unwrap_or(text)),
+            };
+            let (dir_only, rest) = match rest.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, rest),
+            };
+            let anchored = rest.contains('/');
+            let rest = rest.strip_prefix('/').unwrap_or(rest);
+            let pattern = Pattern::new(rest).with_context(|| {
+                format!(
+                    "Invalid pattern on line {} of {}: {text}",
+                    index + 1,
+                    path.display()
+                )
+            })?;
+            rules.push(IgnoreRule {
+                text: text.to_string(),
+                pattern,
+                negated,
+                dir_only,
+                anchored,
+            });
+        }
+        Ok(Some(IgnoreFile {
+            dir: rel.to_path_buf(),
+            rules,
+            prefix: PathBuf::new(),
+        }))
+    }
+
+    /// This root-level file as it applies to the paths of `package`, which are relative to
+    /// the package directory.
+    pub fn for_package(self, package: &Path) -> IgnoreFile {
+        IgnoreFile {
+            prefix: package.to_path_buf(),
+            ..self
+        }
+    }
+}
+
+/// Check root-relative `rel` against the stack of ignore files that apply to it, outermost
+/// first. The last matching rule wins; returns a description of it when `rel` is ignored.
+pub fn ignored_by(files: &[IgnoreFile], rel: &Path, is_dir: bool) -> Option<String> {
+    let mut last: Option<(&IgnoreFile, &IgnoreRule)> = None;
+    for file in files {
+        let rel = file.prefix.join(rel);
+        let Ok(sub) = rel.strip_prefix(&file.dir) else {
+            continue;
+        };
+        for rule in &file.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let matched = if rule.anchored {
+                rule.pattern.matches_path_with(sub, MATCH_OPTIONS)
+            } else {
+                sub.file_name().is_some_and(|name| {
+                    rule.pattern
+                        .matches_with(&name.to_string_lossy(), MATCH_OPTIONS)
+                })
+            };
+            if matched {
+                last = Some((file, rule));
+            }
+        }
+    }
+    match last {
+        Some((file, rule)) if !rule.negated => Some(format!(
+            "{}: '{}'",
+            file.dir.join(IGNORE_FILE_NAME).display(),
+            rule.text
+        )),
+        _ => None,
+    }
+}
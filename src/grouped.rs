@@ -0,0 +1,113 @@
+//! `--group-output`: the lines about each entry are held back during the walk and
+//! printed afterwards in sections, each sorted by target.
+
+use crate::Options;
+use crate::report::Record;
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::PathBuf;
+
+thread_local! {
+    /// The lines printed while an entry is stowed, when they are being held back.
+    static HELD: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Print `text` to stdout, or hold it back while `hold` runs.
+pub fn out(text: &str) {
+    let held = HELD.with(|held| {
+        held.borrow_mut()
+            .as_mut()
+            .map(|lines| lines.push_str(text))
+            .is_some()
+    });
+    if !held {
+        crate::progress::clear();
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(text.as_bytes());
+    }
+}
+
+/// Run `f`, returning what it printed instead of printing it.
+pub fn hold<T>(f: impl FnOnce() -> T) -> (T, String) {
+    HELD.with(|held| *held.borrow_mut() = Some(String::new()));
+    let result = f();
+    let text = HELD.with(|held| held.borrow_mut().take().unwrap_or_default());
+    (result, text)
+}
+
+/// The sections, in the order they are printed.
+const TITLES: [&str; 7] = [
+    "Already in place",
+    "New links",
+    "Written",
+    "Conflicts",
+    "Skipped",
+    "Other",
+    "Errors",
+];
+
+/// The section for an entry with `record`, or `None` when nothing was counted for it.
+fn title(record: Option<&Record>) -> &'static str {
+    let Some(record) = record else {
+        return "Other";
+    };
+    match record.state {
+        "in_place" => "Already in place",
+        "conflict" => "Conflicts",
+        "skipped" | "excluded" | "ignored" => "Skipped",
+        _ if matches!(record.action, "transform" | "copy" | "inject") => "Written",
+        _ => "New links",
+    }
+}
+
+/// The held-back lines of a run, by section.
+#[derive(Default)]
+pub struct Sections {
+    entries: Vec<(&'static str, PathBuf, String)>,
+}
+
+impl Sections {
+    /// Keep `text`, printed while the entry at `target` was stowed, for its section.
+    pub fn add(&mut self, record: Option<&Record>, target: PathBuf, text: String) {
+        if !text.is_empty() {
+            self.entries.push((title(record), target, text));
+        }
+    }
+
+    /// Keep `text` and the `error` the entry at `target` failed with.
+    pub fn add_error(
+        &mut self,
+        target: PathBuf,
+        text: String,
+        error: &anyhow::Error,
+        opts: &Options,
+    ) {
+        let line = format!("{} {error:#}\n", opts.color.red("✗"));
+        self.entries.push(("Errors", target, text + &line));
+    }
+
+    /// Print every section that has lines, headed by its count.
+    pub fn print(mut self, opts: &Options) {
+        self.entries.sort_by(|a, b| a.1.cmp(&b.1));
+        for section in TITLES {
+            let texts: Vec<_> = self
+                .entries
+                .iter()
+                .filter(|(title, ..)| *title == section)
+                .map(|(.., text)| text)
+                .collect();
+            if texts.is_empty() {
+                continue;
+            }
+            say!(
+                opts,
+                "{} {section} ({})",
+                opts.color.blue("==>"),
+                texts.len()
+            );
+            for text in texts {
+                out(text);
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Content hashes of compared files, kept across runs by path, size and modification
+//! time so files that did not change are compared from their metadata alone.
+
+use crate::compare::{self, CHUNK_SIZE};
+use crate::{Options, expand_tilde};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where the cache lives.
+pub const CACHE_PATH: &str = "~/.local/state/dotty/hashes.json";
+
+/// Bumped when the format changes; caches of other versions are ignored.
+const VERSION: u32 = 1;
+
+/// The cache of this run, when it is used at all.
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    version: u32,
+    #[serde(with = "crate::state_path::keys")]
+    entries: BTreeMap<PathBuf, Cached>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+/// The hash of a file as it was when it had this size and modification time.
+#[derive(Clone, Serialize, Deserialize)]
+struct Cached {
+    size: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+    sha256: String,
+}
+
+/// Start using the cache at `CACHE_PATH`, unless `--no-cache` is given. A cache that
+/// cannot be read is started over.
+pub fn load(opts: &Options) -> Result<()> {
+    if opts.no_cache {
+        return Ok(());
+    }
+    let path = expand_tilde(CACHE_PATH)?;
+    let cache = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Cache>(&content).ok())
+        .filter(|cache| cache.version == VERSION)
+        .unwrap_or(Cache {
+            version: VERSION,
+            entries: BTreeMap::new(),
+            changed: false,
+        });
+    *CACHE.lock().unwrap() = Some(cache);
+    Ok(())
+}
+
+/// Write the cache back when this run hashed new files, leaving out files that are
+/// gone. Nothing is written with `--dry-run`.
+pub fn save(opts: &Options) -> Result<()> {
+    let mut guard = CACHE.lock().unwrap();
+    let Some(cache) = guard.as_mut().filter(|cache| cache.changed) else {
+        return Ok(());
+    };
+    if opts.dry_run {
+        return Ok(());
+    }
+    cache.entries.retain(|path, _| path.exists());
+    let path = expand_tilde(CACHE_PATH)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let content = serde_json::to_string(&cache).context("Failed to encode hash cache")?;
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, content).with_context(|| format!("Failed to write {}", temp.display()))?;
+    fs::rename(&temp, &path)
+        .with_context(|| format!("Failed to replace hash cache {}", path.display()))?;
+    cache.changed = false;
+    Ok(())
+}
+
+/// The SHA-256 of the file at `path`, read in pieces.
+fn hash_file(path: &Path) -> Option<String> {
+    let mut reader = BufReader::with_capacity(CHUNK_SIZE, File::open(path).ok()?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        match reader.read(&mut buffer).ok()? {
+            0 => break,
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+/// The hash of the file at `path` with metadata `meta`, from the cache when its size and
+/// modification time are unchanged.
+fn cached_hash(path: &Path, meta: &fs::Metadata) -> Option<String> {
+    let key = std::path::absolute(path).ok()?;
+    let fresh = |cached: &Cached| {
+        cached.size == meta.size()
+            && cached.mtime == meta.mtime()
+            && cached.mtime_nsec == meta.mtime_nsec()
+    };
+    if let Some(cache) = CACHE.lock().unwrap().as_ref() {
+        match cache.entries.get(&key) {
+            Some(cached) if fresh(cached) => return Some(cached.sha256.clone()),
+            _ => {}
+        }
+    }
+    let sha256 = hash_file(path)?;
+    if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+        let cached = Cached {
+            size: meta.size(),
+            mtime: meta.mtime(),
+            mtime_nsec: meta.mtime_nsec(),
+            sha256: sha256.clone(),
+        };
+        cache.entries.insert(key, cached);
+        cache.changed = true;
+    }
+    Some(sha256)
+}
+
+/// Whether the files `a` and `b` can both be read and have the same content: files of
+/// different sizes never do, the others are compared by hash, or piece by piece when the
+/// cache is not used.
+pub fn same_contents(a: &Path, b: &Path) -> bool {
+    if CACHE.lock().unwrap().is_none() {
+        return compare::files_equal(a, b);
+    }
+    let (Ok(meta_a), Ok(meta_b)) = (fs::metadata(a), fs::metadata(b)) else {
+        return false;
+    };
+    if meta_a.len() != meta_b.len() {
+        return false;
+    }
+    match (cached_hash(a, &meta_a), cached_hash(b, &meta_b)) {
+        (Some(hash_a), Some(hash_b)) => hash_a == hash_b,
+        _ => false,
+    }
+}
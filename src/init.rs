@@ -0,0 +1,104 @@
+//! `dotty init`: scaffold a new root with commented examples.
+
+use crate::{Colorize, HELPERS_FILE_NAMES, config, expand_tilde, filter};
+use anyhow::{Context, Result, bail};
+use std::fs;
+
+const EXAMPLE_FILE: &str = "# An example dotfile: dotty places it at ~/.example-rc.\n\
+# Replace it with your own files, e.g. with `dotty add ~/.zshrc`.\n";
+
+const EXAMPLE_COMPANION: &str = r##"-- Companion of .example-rc: decides whether and how the file is placed.
+-- Return true to link it, false to skip it, or a table with keys such as:
+--   rename_to = ".other-name"                     place it under another name
+--   target = "~/Library/Application Support/x"    place it somewhere else entirely
+--   transform = function(content) ... end         write a processed copy instead of a link
+--   template = true                               render it as a MiniJinja template
+--   link_dir = true                               (directories) link the whole directory
+-- See the README for every key.
+
+-- is_work_machine() comes from dotty_helpers.lua
+if is_work_machine() then
+  return false
+end
+
+return {
+  transform = function(content)
+    return content .. "# placed by dotty on " .. dotty.hostname() .. "\n"
+  end,
+}
+"##;
+
+const EXAMPLE_HELPERS: &str = r#"-- Evaluated once before any companion; the globals defined here can be used by all
+-- of them. This file is never linked.
+
+function is_work_machine()
+  return dotty.hostname() == "work-mbp"
+end
+"#;
+
+const EXAMPLE_IGNORE: &str = "# Files of this root that are never linked, in gitignore syntax:\n\
+# `#` comments, `!` to re-include, a trailing `/` for directories only.\n\
+README.md\n\
+LICENSE\n";
+
+const EXAMPLE_CONFIG: &str = r#"# Defaults for every machine using this root. CLI flags and
+# ~/.config/dotty/dotty.toml win over them.
+
+# target = "~"
+# excludes = ["scripts/**"]
+
+[vars]
+# email = "me@example.com"
+"#;
+
+/// Create the root `dir` with an example file, its companion, a helpers file, a
+/// `.dottyignore` and, with `with_config`, a `dotty.toml`. Refuses a non-empty `dir`
+/// unless `force` is given, and never overwrites files that already exist.
+pub fn run(dir: &str, force: bool, with_config: bool, color: &Colorize) -> Result<()> {
+    let root = expand_tilde(dir)?;
+    if root.exists() && !root.is_dir() {
+        bail!("{} exists and is not a directory", root.display());
+    }
+    if root.is_dir()
+        && fs::read_dir(&root)
+            .with_context(|| format!("Failed to read {}", root.display()))?
+            .next()
+            .is_some()
+        && !force
+    {
+        bail!(
+            "{} is not empty, pass --force to add the example files anyway",
+            root.display()
+        );
+    }
+    fs::create_dir_all(&root).with_context(|| format!("Failed to create {}", root.display()))?;
+
+    let mut files = vec![
+        (".example-rc", EXAMPLE_FILE),
+        (".example-rc.lua", EXAMPLE_COMPANION),
+        (HELPERS_FILE_NAMES[0], EXAMPLE_HELPERS),
+        (filter::IGNORE_FILE_NAME, EXAMPLE_IGNORE),
+    ];
+    if with_config {
+        files.push((config::ROOT_CONFIG_NAME, EXAMPLE_CONFIG));
+    }
+    for (name, content) in files {
+        let path = root.join(name);
+        if fs::symlink_metadata(&path).is_ok() {
+            println!("{} kept existing {}", color.yellow("⚠"), path.display());
+            continue;
+        }
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("{} Created {}", color.green("✔"), path.display());
+    }
+
+    println!("\nNext steps:");
+    println!("  1. Move your dotfiles into the root: dotty --root {dir} add ~/.zshrc");
+    println!("  2. Edit or delete the example .example-rc and its companion .example-rc.lua");
+    println!("  3. Preview what would be linked: dotty --root {dir} --dry-run");
+    println!(
+        "  4. Set root = \"{dir}\" in {} to drop --root",
+        config::USER_CONFIG_PATH
+    );
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! Managed blocks: the part of a target file between two marker lines that dotty owns,
+//! for `inject = true` companions.
+
+use anyhow::{Result, bail};
+use std::ops::Range;
+
+/// Marker lines used when a companion does not declare its own `markers`.
+pub const DEFAULT_MARKERS: [&str; 2] = ["# >>> dotty", "# <<< dotty"];
+
+/// Content to keep between `begin` and `end` lines of a target file.
+#[derive(Clone, Debug)]
+pub struct Injection {
+    pub content: String,
+    pub begin: String,
+    pub end: String,
+}
+
+/// How placing the block changes the target file.
+pub enum BlockChange {
+    /// The file already holds the block as it should be.
+    InPlace,
+    /// The file does not exist and is created with just the block.
+    CreateFile,
+    /// The file has no block yet; it is appended.
+    Add,
+    /// The block is there with different content.
+    Update,
+}
+
+impl Injection {
+    /// The block as written: both markers around the content, each on its own line.
+    fn block(&self) -> String {
+        let newline = if self.content.is_empty() || self.content.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        format!("{}\n{}{newline}{}\n", self.begin, self.content, self.end)
+    }
+
+    /// Byte range of the existing block in `text`, from the start of the begin marker
+    /// line to the end of the end marker line. Markers match whole lines, ignoring
+    /// trailing whitespace.
+    fn find(&self, text: &str) -> Result<Option<Range<usize>>> {
+        let mut start = None;
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            let trimmed = line.trim_end();
+            match start {
+                None if trimmed == self.begin => start = Some(offset),
+                Some(start) if trimmed == self.end => {
+                    return Ok(Some(start..offset + line.len()));
+                }
+                _ => {}
+            }
+            offset += line.len();
+        }
+        if start.is_some() {
+            bail!("'{}' has no matching '{}' line", self.begin, self.end);
+        }
+        Ok(None)
+    }
+
+    /// `text` (or `None` for a missing file) with the block added or replaced, and what
+    /// that changes.
+    pub fn apply(&self, text: Option<&str>) -> Result<(String, BlockChange)> {
+        let block = self.block();
+        let Some(text) = text else {
+            return Ok((block, BlockChange::CreateFile));
+        };
+        match self.find(text)? {
+            Some(range) if text[range.clone()] == block => {
+                Ok((text.to_string(), BlockChange::InPlace))
+            }
+            Some(range) => {
+                let updated = format!("{}{block}{}", &text[..range.start], &text[range.end..]);
+                Ok((updated, BlockChange::Update))
+            }
+            None => {
+                let separator = if text.is_empty() || text.ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                Ok((format!("{text}{separator}{block}"), BlockChange::Add))
+            }
+        }
+    }
+
+    /// `text` without the block, or `None` when it has none.
+    pub fn remove(&self, text: &str) -> Result<Option<String>> {
+        Ok(self
+            .find(text)?
+            .map(|range| format!("{}{}", &text[..range.start], &text[range.end..])))
+    }
+}
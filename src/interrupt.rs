@@ -0,0 +1,29 @@
+//! Ctrl-C during a run: the first one lets the current file finish, then the walk stops
+//! and the summary so far is printed; a second one exits at once.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The exit code of an interrupted run, as a shell reports a process killed by SIGINT.
+pub const EXIT_CODE: u8 = 130;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        // SAFETY: `_exit` is async-signal-safe
+        unsafe { libc::_exit(i32::from(EXIT_CODE)) }
+    }
+}
+
+/// Handle Ctrl-C by setting the flag that [`requested`] reads.
+pub fn install() {
+    // SAFETY: the handler only touches an atomic and calls `_exit`
+    unsafe {
+        libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t);
+    }
+}
+
+/// Whether Ctrl-C was pressed, so no further file should be started.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,183 @@
+//! `--jobs N`: run the transforms of a group and stow its independent entries on a pool
+//! of threads. Each worker evaluates companions on a Lua state of its own. Output and
+//! counts are handed back per entry, so the caller reports them in plan order as if they
+//! had been stowed one by one.
+
+use crate::{
+    ConflictPolicy, EntryKind, Options, PendingTransform, PlanEntry, WalkCounts, grouped,
+    interrupt, load_helpers, lua_api, new_lua, run_pending, stow_entry,
+};
+use anyhow::{Result, anyhow};
+use mlua::Lua;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// What stowing one entry on the pool did.
+pub struct Outcome {
+    pub result: Result<()>,
+    /// The lines it would have printed.
+    pub text: String,
+    /// Its counts alone, to add to the group's.
+    pub counts: WalkCounts,
+}
+
+/// Whether `entry` touches nothing but its own target: a file whose parent directories
+/// are all real ones. A directory reached through a link may be a folded one that an
+/// earlier `Unfold` entry replaces, so files below it, like directory links, unfolds,
+/// injected blocks and files that need new directories, are stowed in order on the
+/// calling thread instead.
+fn independent(entry: &PlanEntry, opts: &Options) -> bool {
+    let parents_real = entry
+        .target
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| *dir != opts.target)
+        .all(|dir| fs::symlink_metadata(dir).is_ok_and(|meta| meta.is_dir()));
+    match entry.kind {
+        EntryKind::File | EntryKind::Hardlink | EntryKind::Transform(_) | EntryKind::Copy => {
+            parents_real
+        }
+        EntryKind::Skipped
+        | EntryKind::Excluded(_)
+        | EntryKind::Filtered
+        | EntryKind::Ignored(_) => true,
+        EntryKind::Dir
+        | EntryKind::Folded
+        | EntryKind::Unfold
+        | EntryKind::FoldConflict(_)
+        | EntryKind::Inject(_) => false,
+    }
+}
+
+/// Stow the independent `entries` on `opts.jobs` threads. The outcome of each is at its
+/// index; the others are `None` and left to the caller. A conflict at an entry whose
+/// policy is `fail` stops the run, so nothing from that entry on is stowed ahead of it.
+pub fn run(entries: &[PlanEntry], opts: &Options) -> Vec<Option<Outcome>> {
+    let stop = entries
+        .iter()
+        .position(|entry| entry.conflict_policy(opts) == ConflictPolicy::Fail)
+        .unwrap_or(entries.len());
+    let pending: Vec<usize> = (0..stop)
+        .filter(|&index| independent(&entries[index], opts))
+        .collect();
+    let outcomes: Vec<Mutex<Option<Outcome>>> = entries.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..opts.jobs.min(pending.len()) {
+            scope.spawn(|| {
+                while let Some(&index) = pending.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    // Entries already started finish; no new ones after Ctrl-C
+                    if interrupt::requested() {
+                        break;
+                    }
+                    let mut counts = WalkCounts::default();
+                    let (result, text) =
+                        grouped::hold(|| stow_entry(&entries[index], opts, &mut counts));
+                    *outcomes[index].lock().unwrap() = Some(Outcome {
+                        result,
+                        text,
+                        counts,
+                    });
+                }
+            });
+        }
+    });
+    outcomes
+        .into_iter()
+        .map(|outcome| outcome.into_inner().unwrap())
+        .collect()
+}
+
+/// A Lua state for a worker running transforms of `root`: like the one the plan was built
+/// on, with the helpers files of `helpers`, the roots walked so far, evaluated again.
+fn worker_lua(root: &Path, helpers: &[PathBuf], opts: &Options) -> Result<Lua> {
+    let lua = new_lua(opts)?;
+    for helpers_root in helpers {
+        lua_api::set_root(&lua, helpers_root)?;
+        lua_api::silently(&lua, || load_helpers(&lua, helpers_root))?;
+    }
+    lua_api::set_root(&lua, root)?;
+    Ok(lua)
+}
+
+/// Run the transforms `walk_dir` left in `entries`, the plan of `root`, on `opts.jobs`
+/// threads and fill in their content and mode. A transform that drops its file leaves
+/// one skipped entry, as in a serial run. The first error in plan order is returned.
+pub fn transform(
+    entries: &mut Vec<PlanEntry>,
+    root: &Path,
+    helpers: &[PathBuf],
+    opts: &Options,
+) -> Result<()> {
+    // One job per transform, however many targets share its output
+    let mut pending: Vec<Arc<PendingTransform>> = Vec::new();
+    for entry in entries.iter() {
+        if let Some(transform) = &entry.pending
+            && !pending.iter().any(|other| Arc::ptr_eq(other, transform))
+        {
+            pending.push(Arc::clone(transform));
+        }
+    }
+    if pending.is_empty() {
+        return Ok(());
+    }
+    type Transformed = Result<Option<(Vec<u8>, Option<u32>)>>;
+    let outputs: Vec<Mutex<Option<Transformed>>> =
+        pending.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..opts.jobs.min(pending.len()) {
+            scope.spawn(|| {
+                let lua = worker_lua(root, helpers, opts);
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(transform) = pending.get(index) else {
+                        break;
+                    };
+                    let output = match &lua {
+                        Ok(lua) => run_pending(lua, transform, opts),
+                        Err(e) => Err(anyhow!("{e:#}")),
+                    };
+                    *outputs[index].lock().unwrap() = Some(output);
+                }
+            });
+        }
+    });
+    let outputs = outputs
+        .into_iter()
+        .map(|output| output.into_inner().unwrap().expect("every transform ran"))
+        .collect::<Result<Vec<_>>>()?;
+    let mut skipped = vec![false; pending.len()];
+    let mut resolved = Vec::with_capacity(entries.len());
+    for mut entry in entries.drain(..) {
+        let Some(transform) = entry.pending.take() else {
+            resolved.push(entry);
+            continue;
+        };
+        let index = pending
+            .iter()
+            .position(|other| Arc::ptr_eq(other, &transform))
+            .expect("collected above");
+        match &outputs[index] {
+            Some((content, mode)) => {
+                entry.kind = EntryKind::Transform(content.clone());
+                entry.mode = *mode;
+                resolved.push(entry);
+            }
+            None if !skipped[index] => {
+                skipped[index] = true;
+                resolved.push(PlanEntry::new(
+                    entry.source,
+                    transform.skip_target.clone(),
+                    EntryKind::Skipped,
+                ));
+            }
+            None => {}
+        }
+    }
+    *entries = resolved;
+    Ok(())
+}
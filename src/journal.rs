@@ -0,0 +1,432 @@
+//! What each run changed, so `dotty undo` can revert the most recent one.
+
+use crate::manifest::hash;
+use crate::{
+    Options, backup, expand_tilde, link_destination, paths_match, same_file, shorten_home,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs as unix_fs;
+use std::path::{Path, PathBuf};
+
+/// Where the journal lives, one run per line.
+pub const JOURNAL_PATH: &str = "~/.local/state/dotty/journal.jsonl";
+
+/// One run that changed the target.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Run {
+    /// When the run happened, as `YYYYMMDD-HHMMSS` in UTC.
+    pub time: String,
+    /// The command line of the run.
+    pub command: String,
+    /// What the run did, in order.
+    pub actions: Vec<Action>,
+}
+
+/// One change to the target.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Action {
+    /// A symlink was created at `target`, pointing at `source`.
+    Symlink {
+        #[serde(with = "crate::state_path")]
+        target: PathBuf,
+        #[serde(with = "crate::state_path")]
+        source: PathBuf,
+    },
+    /// A hard link to `source` was created at `target`.
+    Hardlink {
+        #[serde(with = "crate::state_path")]
+        target: PathBuf,
+        #[serde(with = "crate::state_path")]
+        source: PathBuf,
+    },
+    /// The file at `target` was moved into `source` and replaced by a link (`--adopt`).
+    Adopt {
+        #[serde(with = "crate::state_path")]
+        target: PathBuf,
+        #[serde(with = "crate::state_path")]
+        source: PathBuf,
+    },
+    /// `target` was written, now holding content with `hash`. `previous` is the hash of
+    /// what it held before, if it existed.
+    Write {
+        #[serde(with = "crate::state_path")]
+        target: PathBuf,
+        hash: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous: Option<String>,
+    },
+    /// `target` was moved to `backup` before being replaced.
+    Backup {
+        #[serde(with = "crate::state_path")]
+        target: PathBuf,
+        #[serde(with = "crate::state_path")]
+        backup: PathBuf,
+    },
+    /// `target` was removed: a symlink storing `link`, or a file whose content had `hash`.
+    Remove {
+        #[serde(with = "crate::state_path")]
+        target: PathBuf,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::state_path::option"
+        )]
+        link: Option<PathBuf>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hash: Option<String>,
+    },
+    /// The directory `path` was created.
+    Mkdir {
+        #[serde(with = "crate::state_path")]
+        path: PathBuf,
+    },
+    /// The empty directory `path` was removed.
+    Rmdir {
+        #[serde(with = "crate::state_path")]
+        path: PathBuf,
+    },
+}
+
+impl Action {
+    pub fn symlink(target: &Path, source: &Path) -> Action {
+        Action::Symlink {
+            target: target.to_path_buf(),
+            source: source.to_path_buf(),
+        }
+    }
+
+    pub fn hardlink(target: &Path, source: &Path) -> Action {
+        Action::Hardlink {
+            target: target.to_path_buf(),
+            source: source.to_path_buf(),
+        }
+    }
+
+    pub fn write(target: &Path, content: &[u8], previous: Option<&[u8]>) -> Action {
+        Action::Write {
+            target: target.to_path_buf(),
+            hash: hash(content),
+            previous: previous.map(hash),
+        }
+    }
+
+    /// How `target` looked before it is removed.
+    pub fn remove(target: &Path) -> Action {
+        let link = fs::read_link(target).ok();
+        let hash = match link {
+            Some(_) => None,
+            None => fs::read(target).ok().map(|content| hash(&content)),
+        };
+        Action::Remove {
+            target: target.to_path_buf(),
+            link,
+            hash,
+        }
+    }
+
+    /// The path the action changed.
+    fn path(&self) -> &Path {
+        match self {
+            Action::Symlink { target, .. }
+            | Action::Hardlink { target, .. }
+            | Action::Adopt { target, .. }
+            | Action::Write { target, .. }
+            | Action::Backup { target, .. }
+            | Action::Remove { target, .. } => target,
+            Action::Mkdir { path } | Action::Rmdir { path } => path,
+        }
+    }
+}
+
+/// Add a run doing `actions` to the journal. Dry runs and runs that changed nothing
+/// leave no entry.
+pub fn append(actions: &[Action], opts: &Options) -> Result<()> {
+    if opts.dry_run || actions.is_empty() {
+        return Ok(());
+    }
+    let path = expand_tilde(JOURNAL_PATH)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let run = Run {
+        time: backup::timestamp(),
+        command: format!("dotty {}", args.join(" ")).trim_end().to_string(),
+        actions: actions.to_vec(),
+    };
+    let mut line = serde_json::to_string(&run).context("Failed to encode journal entry")?;
+    line.push('\n');
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("Failed to append to journal {}", path.display()))
+}
+
+/// Every run in the journal at `path`, oldest first.
+fn load(path: &Path) -> Result<Vec<Run>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read journal {}", path.display()))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).with_context(|| {
+                format!("Failed to parse line {} of {}", index + 1, path.display())
+            })
+        })
+        .collect()
+}
+
+/// Replace the journal at `path` with `runs`, through a temporary file.
+fn save(path: &Path, runs: &[Run]) -> Result<()> {
+    let mut content = String::new();
+    for run in runs {
+        content.push_str(&serde_json::to_string(run).context("Failed to encode journal entry")?);
+        content.push('\n');
+    }
+    let temp = path.with_extension("jsonl.tmp");
+    fs::write(&temp, content).with_context(|| format!("Failed to write {}", temp.display()))?;
+    fs::rename(&temp, path).with_context(|| format!("Failed to replace journal {}", path.display()))
+}
+
+/// Whether nothing is at `path`, or an earlier step of the undo removed (or in a dry
+/// run, would remove) it.
+fn is_gone(path: &Path, cleared: &HashSet<PathBuf>) -> bool {
+    cleared.contains(path) || fs::symlink_metadata(path).is_err()
+}
+
+/// The outcome of reverting one action.
+enum Revert {
+    /// Removed what the run created.
+    Removed(String),
+    /// Put back what the run replaced or removed.
+    Restored(String),
+    /// Left alone, for this reason.
+    Left(&'static str),
+}
+
+/// Remove `path` for the undo, or only pretend to in a dry run.
+fn remove(path: &Path, opts: &Options, cleared: &mut HashSet<PathBuf>) -> Result<()> {
+    if !opts.dry_run {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    cleared.insert(path.to_path_buf());
+    Ok(())
+}
+
+/// Revert `action` of `run`, unless its path changed since. `cleared` collects the
+/// paths that are gone afterwards.
+fn revert(
+    action: &Action,
+    run: &Run,
+    opts: &Options,
+    cleared: &mut HashSet<PathBuf>,
+) -> Result<Revert> {
+    let short = |path: &Path| shorten_home(path, &opts.target);
+    let links_to = |target: &Path, source: &Path| -> bool {
+        target.is_symlink()
+            && link_destination(target).is_some_and(|dest| paths_match(&dest, source))
+    };
+    let changed = Revert::Left("changed since the run");
+    Ok(match action {
+        Action::Symlink { target, source } => {
+            if !links_to(target, source) {
+                return Ok(changed);
+            }
+            remove(target, opts, cleared)?;
+            Revert::Removed(format!("link {}", short(target)))
+        }
+        Action::Hardlink { target, source } => {
+            if target.is_symlink() || !same_file(target, source) {
+                return Ok(changed);
+            }
+            remove(target, opts, cleared)?;
+            Revert::Removed(format!("hard link {}", short(target)))
+        }
+        Action::Adopt { target, source } => {
+            if !links_to(target, source) {
+                return Ok(changed);
+            }
+            if !opts.dry_run {
+                fs::remove_file(target)
+                    .with_context(|| format!("Failed to remove {}", target.display()))?;
+                fs::copy(source, target).with_context(|| {
+                    format!(
+                        "Failed to copy {} to {}",
+                        source.display(),
+                        target.display()
+                    )
+                })?;
+            }
+            Revert::Restored(format!("adopted file {}", short(target)))
+        }
+        Action::Write {
+            target,
+            hash: written,
+            previous,
+        } => {
+            let current = if target.is_symlink() {
+                None
+            } else {
+                fs::read(target).ok().map(|content| hash(&content))
+            };
+            if current.as_ref() != Some(written) {
+                return Ok(changed);
+            }
+            // Without a backup the earlier content is lost, so the file stays
+            let backed_up = run.actions.iter().any(
+                |other| matches!(other, Action::Backup { target: backed, .. } if backed == target),
+            );
+            if previous.is_some() && !backed_up {
+                return Ok(Revert::Left("its earlier content was not backed up"));
+            }
+            remove(target, opts, cleared)?;
+            Revert::Removed(format!("file {}", short(target)))
+        }
+        Action::Backup { target, backup } => {
+            if !is_gone(target, cleared) {
+                return Ok(Revert::Left("something is in the way"));
+            }
+            if fs::symlink_metadata(backup).is_err() {
+                return Ok(Revert::Left("the backup is gone"));
+            }
+            if !opts.dry_run {
+                backup::move_file(backup, target)?;
+            }
+            cleared.remove(target);
+            Revert::Restored(format!("{} from {}", short(target), short(backup)))
+        }
+        Action::Remove { target, link, .. } => {
+            let Some(link) = link else {
+                return Ok(Revert::Left("removed files cannot be restored"));
+            };
+            if !is_gone(target, cleared) {
+                return Ok(Revert::Left("something is in the way"));
+            }
+            if !opts.dry_run {
+                unix_fs::symlink(link, target).with_context(|| {
+                    format!(
+                        "Failed to symlink {} -> {}",
+                        target.display(),
+                        link.display()
+                    )
+                })?;
+            }
+            cleared.remove(target);
+            Revert::Restored(format!("link {} -> {}", short(target), link.display()))
+        }
+        Action::Mkdir { path } => {
+            let empty = fs::read_dir(path).is_ok_and(|entries| {
+                entries
+                    .flatten()
+                    .all(|entry| cleared.contains(&entry.path()))
+            });
+            if !empty {
+                return Ok(Revert::Left("not empty"));
+            }
+            if !opts.dry_run {
+                fs::remove_dir(path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            cleared.insert(path.to_path_buf());
+            Revert::Removed(format!("directory {}/", short(path)))
+        }
+        Action::Rmdir { path } => {
+            if !is_gone(path, cleared) {
+                return Ok(Revert::Left("something is in the way"));
+            }
+            if !opts.dry_run {
+                fs::create_dir(path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+            }
+            cleared.remove(path);
+            Revert::Restored(format!("directory {}/", short(path)))
+        }
+    })
+}
+
+/// `dotty undo`: revert the most recent run in the journal, newest change first, and
+/// drop it from the journal. Targets that changed since are left alone with a warning.
+/// With `list`, only show the runs that can be undone.
+pub fn undo(list: bool, opts: &Options) -> Result<()> {
+    let path = expand_tilde(JOURNAL_PATH)?;
+    let mut runs = load(&path)?;
+    if list {
+        if runs.is_empty() {
+            println!("Nothing to undo");
+        }
+        for (index, run) in runs.iter().rev().enumerate() {
+            let changes = if run.actions.len() == 1 {
+                "change"
+            } else {
+                "changes"
+            };
+            println!(
+                "{:>3}  {}  {} ({} {changes})",
+                index + 1,
+                run.time,
+                run.command,
+                run.actions.len()
+            );
+        }
+        return Ok(());
+    }
+    let Some(run) = runs.pop() else {
+        println!("Nothing to undo");
+        return Ok(());
+    };
+
+    println!(
+        "{} Undoing {} from {}",
+        opts.color.blue("ℹ"),
+        run.command,
+        run.time
+    );
+    let (mut reverted, mut left) = (0, 0);
+    let mut cleared = HashSet::new();
+    for action in run.actions.iter().rev() {
+        let (would, done, what) = match revert(action, &run, opts, &mut cleared)? {
+            Revert::Removed(what) => ("Would remove", "Removed", what),
+            Revert::Restored(what) => ("Would restore", "Restored", what),
+            Revert::Left(reason) => {
+                println!(
+                    "{} {} {} ({reason})",
+                    opts.color.yellow("⚠"),
+                    opts.color.yellow("left alone:"),
+                    shorten_home(action.path(), &opts.target)
+                );
+                left += 1;
+                continue;
+            }
+        };
+        let verb = if opts.dry_run { would } else { done };
+        println!("{} {verb} {what}", opts.color.green("↶"));
+        reverted += 1;
+    }
+    if !opts.dry_run {
+        save(&path, &runs)?;
+    }
+    let reverted_label = if opts.dry_run {
+        "to revert"
+    } else {
+        "reverted"
+    };
+    println!(
+        "\nSummary: {} {reverted_label}, {} left alone",
+        opts.color.green(&reverted.to_string()),
+        opts.color.yellow(&left.to_string())
+    );
+    Ok(())
+}
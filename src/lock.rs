@@ -0,0 +1,68 @@
+//! One run that changes the target at a time: an advisory lock on `LOCK_PATH`, held until
+//! the process exits. The kernel drops it when the process dies, so a crash never leaves
+//! it behind.
+
+use crate::{Options, expand_tilde};
+use anyhow::{Context, Result, bail};
+use std::fs::{self, File, TryLockError};
+use std::io::{Read, Seek, Write};
+
+/// Where the lock lives; it holds the PID of the run holding it.
+pub const LOCK_PATH: &str = "~/.local/state/dotty/lock";
+
+/// The lock of this run, released when dropped.
+pub struct Lock {
+    _file: File,
+}
+
+/// Take the lock, or fail naming the run that holds it. With `--wait`, wait for it.
+pub fn acquire(opts: &Options) -> Result<Lock> {
+    let path = expand_tilde(LOCK_PATH)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let holder = holder(&mut file);
+            if !opts.wait {
+                bail!(
+                    "Another dotty run ({holder}) is changing the target; pass --wait to wait for it (lock: {})",
+                    path.display()
+                );
+            }
+            say!(
+                opts,
+                "{} Waiting for another dotty run ({holder}) to finish",
+                opts.color.blue("ℹ")
+            );
+            file.lock()
+                .with_context(|| format!("Failed to lock {}", path.display()))?;
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Failed to lock {}", path.display()));
+        }
+    }
+    file.set_len(0)
+        .and_then(|()| file.rewind())
+        .and_then(|()| write!(file, "{}", std::process::id()))
+        .with_context(|| format!("Failed to write lock {}", path.display()))?;
+    Ok(Lock { _file: file })
+}
+
+/// Who holds the lock in `file`, e.g. `PID 1234`.
+fn holder(file: &mut File) -> String {
+    let mut pid = String::new();
+    match file.read_to_string(&mut pid) {
+        Ok(_) if !pid.trim().is_empty() => format!("PID {}", pid.trim()),
+        _ => "unknown PID".to_string(),
+    }
+}
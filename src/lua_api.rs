@@ -0,0 +1,297 @@
+//! The global `dotty` table that companion scripts use to ask about the machine.
+
+use crate::config::env_string;
+use mlua::{Error, Function, HookTriggers, Lua, Nil, Result, Table, Value, Variadic, VmState};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Name of the global table holding the helpers.
+pub const GLOBAL_NAME: &str = "dotty";
+
+/// The machine's host name. `DOTTY_HOSTNAME` overrides it, e.g. to preview another
+/// machine's links with `--dry-run`.
+pub fn hostname() -> String {
+    env_string("DOTTY_HOSTNAME")
+        .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().to_string())
+}
+
+/// The current user's login name, from `USER` or `LOGNAME`.
+pub fn username() -> Option<String> {
+    env_string("USER").or_else(|| env_string("LOGNAME"))
+}
+
+/// Whether `path` is a regular file with an execute bit set.
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// Whether `name` is an executable, either as a path or found in one of the `PATH`
+/// directories, like the shell would look it up.
+fn command_exists(name: &str) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+    if name.contains('/') {
+        return is_executable(Path::new(name));
+    }
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| is_executable(&dir.join(name))))
+}
+
+/// The root whose companions are being evaluated, for `dotty.read`.
+struct CurrentRoot(PathBuf);
+
+/// Lua's own `package.path`, which the root's entries are put in front of.
+struct DefaultPackagePath(String);
+
+/// Point `dotty.read` and `require` at `root` before walking it. Modules that were
+/// already required stay cached for the rest of the run.
+pub fn set_root(lua: &Lua, root: &Path) -> Result<()> {
+    lua.set_app_data(CurrentRoot(root.to_path_buf()));
+    let default = match lua.app_data_ref::<DefaultPackagePath>() {
+        Some(path) => path.0.clone(),
+        None => String::new(),
+    };
+    let root = root.to_string_lossy();
+    let package: Table = lua.globals().get("package")?;
+    package.set("path", format!("{root}/?.lua;{root}/?/init.lua;{default}"))
+}
+
+/// `path` relative to the current root, e.g. `zsh/.zshrc` for a file of the `zsh` package.
+/// Paths outside of it are returned as they are.
+pub fn root_relative(lua: &Lua, path: &Path) -> PathBuf {
+    match lua.app_data_ref::<CurrentRoot>() {
+        Some(root) => path.strip_prefix(&root.0).unwrap_or(path).to_path_buf(),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Read `rel` below the current root, refusing paths that leave it.
+fn read(lua: &Lua, rel: &str) -> Result<String> {
+    let rel_path = Path::new(rel);
+    if rel.is_empty()
+        || rel_path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(Error::RuntimeError(format!(
+            "dotty.read: '{rel}' must be a path relative to the root without '..'"
+        )));
+    }
+    let Some(root) = lua.app_data_ref::<CurrentRoot>() else {
+        return Err(Error::RuntimeError(
+            "dotty.read: no root is set".to_string(),
+        ));
+    };
+    let path = root.0.join(rel_path);
+    let cannot_read =
+        |e| Error::RuntimeError(format!("dotty.read: cannot read {}: {e}", path.display()));
+    // A symlink inside the root may still lead out of it
+    let real = path.canonicalize().map_err(cannot_read)?;
+    if !root
+        .0
+        .canonicalize()
+        .is_ok_and(|root| real.starts_with(root))
+    {
+        return Err(Error::RuntimeError(format!(
+            "dotty.read: '{rel}' leads outside of the root"
+        )));
+    }
+    std::fs::read_to_string(&real).map_err(cannot_read)
+}
+
+/// How often, in Lua instructions, the time limit is checked.
+const TIMEOUT_CHECK_INTERVAL: u32 = 10_000;
+
+/// The time limit for one call into Lua and, while one runs, when it expires.
+struct Deadline {
+    limit: Duration,
+    at: Option<Instant>,
+}
+
+/// Abort any Lua code run through [`run`] once it takes longer than `limit`.
+pub fn set_timeout(lua: &Lua, limit: Duration) {
+    lua.set_app_data(Deadline { limit, at: None });
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(TIMEOUT_CHECK_INTERVAL),
+        |lua, _debug| {
+            let expired = lua
+                .app_data_ref::<Deadline>()
+                .and_then(|deadline| deadline.at)
+                .is_some_and(|at| Instant::now() >= at);
+            if expired {
+                return Err(Error::RuntimeError("timed out".to_string()));
+            }
+            Ok(VmState::Continue)
+        },
+    );
+}
+
+/// The Lua file whose code is running, for attributing `print` output.
+struct CurrentFile(PathBuf);
+
+/// Whether `print` output is shown (`--verbose` or `--trace-lua`) or swallowed.
+struct TraceOutput(bool);
+
+/// Show or swallow what companions `print`.
+pub fn set_trace(lua: &Lua, enabled: bool) {
+    lua.set_app_data(TraceOutput(enabled));
+}
+
+/// Run `f` with whatever it prints swallowed, whether or not tracing is on.
+pub fn silently<T>(lua: &Lua, f: impl FnOnce() -> T) -> T {
+    let traced = lua
+        .app_data_mut::<TraceOutput>()
+        .is_some_and(|mut trace| std::mem::replace(&mut trace.0, false));
+    let result = f();
+    set_trace(lua, traced);
+    result
+}
+
+/// Replacement for Lua's `print`: one line per call, prefixed with the file it came from,
+/// e.g. `lua[.config/git/config.lua]: ...`, so it cannot be mistaken for dotty's output.
+fn print(lua: &Lua, args: Variadic<Value>) -> Result<()> {
+    if !lua
+        .app_data_ref::<TraceOutput>()
+        .is_some_and(|trace| trace.0)
+    {
+        return Ok(());
+    }
+    let tostring: Function = lua.globals().get("tostring")?;
+    let mut parts = Vec::new();
+    for value in args {
+        let text: String = tostring.call(value)?;
+        parts.push(text);
+    }
+    let file = match lua.app_data_ref::<CurrentFile>() {
+        Some(file) => root_relative(lua, &file.0).display().to_string(),
+        None => "?".to_string(),
+    };
+    eprintln!("lua[{file}]: {}", parts.join("\t"));
+    Ok(())
+}
+
+/// Run `f` on behalf of the Lua file `file`: its prints are attributed to that file and
+/// it is held to the time limit. Running out of time is reported as
+/// "timed out after N seconds" whatever error the aborted script surfaced.
+pub fn run<T>(lua: &Lua, file: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    lua.set_app_data(CurrentFile(file.to_path_buf()));
+    let limit = match lua.app_data_mut::<Deadline>() {
+        Some(mut deadline) => {
+            deadline.at = Some(Instant::now() + deadline.limit);
+            Some(deadline.limit)
+        }
+        None => None,
+    };
+    let result = crate::stats::lua(f);
+    let expired = match lua.app_data_mut::<Deadline>() {
+        Some(mut deadline) => deadline.at.take().is_some_and(|at| Instant::now() >= at),
+        None => false,
+    };
+    match (result, limit) {
+        (Err(_), Some(limit)) if expired => Err(Error::RuntimeError(format!(
+            "timed out after {} seconds (raise the limit with --lua-timeout)",
+            limit.as_secs_f64()
+        ))),
+        (result, _) => result,
+    }
+}
+
+/// The parts of `os` companions keep by default; the rest can change the system.
+const SAFE_OS_FUNCTIONS: &[&str] = &["time", "date", "getenv"];
+
+/// Take away the libraries that let a script touch the system, unless `--unsafe-lua`:
+/// `io`, `debug`, loading code from files or strings, loading native modules, and
+/// everything in `os` but reading the clock and the environment. `dotty.read`, `require`
+/// and `dotty.env` are the sanctioned alternatives. `package.loaded` gets the same
+/// treatment, so `require("os")` hands back the safe `os` and `require("io")` fails.
+pub fn sandbox(lua: &Lua) -> Result<()> {
+    let globals = lua.globals();
+    let package: Table = globals.get("package")?;
+    let loaded: Table = package.get("loaded")?;
+    for name in ["io", "debug", "dofile", "loadfile", "load"] {
+        globals.set(name, Nil)?;
+        loaded.set(name, Nil)?;
+    }
+    let os: Table = globals.get("os")?;
+    let safe_os = lua.create_table()?;
+    for name in SAFE_OS_FUNCTIONS {
+        let function: Value = os.get(*name)?;
+        safe_os.set(*name, function)?;
+    }
+    globals.set("os", &safe_os)?;
+    loaded.set("os", safe_os)?;
+    package.set("preload", lua.create_table()?)?;
+    package.set("loadlib", Nil)?;
+    package.set("cpath", "")?;
+    Ok(())
+}
+
+/// A fresh global environment for one companion: a copy of the real globals (the
+/// sandboxed standard library, `dotty` and whatever the root helpers file defines), so
+/// neither assigning nor removing a global, even through `_G`, reaches another companion.
+pub fn companion_env(lua: &Lua) -> Result<Table> {
+    let env = lua.create_table()?;
+    for pair in lua.globals().pairs::<Value, Value>() {
+        let (name, value) = pair?;
+        env.raw_set(name, value)?;
+    }
+    env.raw_set("_G", &env)?;
+    Ok(env)
+}
+
+/// Register the global `dotty` table on `lua`, before any companion is evaluated.
+/// `vars` are the `--var` values, readable as `dotty.vars` and with `dotty.var(name)`.
+pub fn install(lua: &Lua, vars: &BTreeMap<String, String>) -> Result<()> {
+    let dotty = lua.create_table()?;
+    dotty.set("hostname", lua.create_function(|_, ()| Ok(hostname()))?)?;
+    dotty.set("os", lua.create_function(|_, ()| Ok(std::env::consts::OS))?)?;
+    dotty.set(
+        "arch",
+        lua.create_function(|_, ()| Ok(std::env::consts::ARCH))?,
+    )?;
+    dotty.set(
+        "env",
+        lua.create_function(|_, name: String| Ok(std::env::var(name).ok()))?,
+    )?;
+    dotty.set(
+        "home",
+        lua.create_function(|_, ()| Ok(std::env::var("HOME").ok()))?,
+    )?;
+    dotty.set("username", lua.create_function(|_, ()| Ok(username()))?)?;
+    dotty.set("vars", lua.create_table_from(vars.clone())?)?;
+    let var_values = vars.clone();
+    dotty.set(
+        "var",
+        lua.create_function(move |_, name: String| Ok(var_values.get(&name).cloned()))?,
+    )?;
+    lua.globals().set("print", lua.create_function(print)?)?;
+    dotty.set(
+        "read",
+        lua.create_function(|lua, rel: String| read(lua, &rel))?,
+    )?;
+    // Companions tend to ask about the same few programs, so each lookup is done once
+    let found: RefCell<HashMap<String, bool>> = RefCell::new(HashMap::new());
+    dotty.set(
+        "command_exists",
+        lua.create_function(move |_, name: String| {
+            let mut found = found.borrow_mut();
+            if let Some(exists) = found.get(&name) {
+                return Ok(*exists);
+            }
+            let exists = command_exists(&name);
+            found.insert(name, exists);
+            Ok(exists)
+        })?,
+    )?;
+    lua.globals().set(GLOBAL_NAME, dotty)?;
+
+    let package: Table = lua.globals().get("package")?;
+    let default: String = package.get("path")?;
+    lua.set_app_data(DefaultPackagePath(default));
+    Ok(())
+}
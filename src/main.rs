@@ -1,19 +1,92 @@
+/// `println!` for the decorated, human-readable output, which `--output json` replaces
+/// and `--summary-only` leaves out.
+macro_rules! say {
+    ($opts:expr) => {
+        if $opts.shows($crate::Line::Notice) {
+            $crate::grouped::out("\n")
+        }
+    };
+    ($opts:expr, $($arg:tt)*) => {
+        if $opts.shows($crate::Line::Notice) {
+            $crate::grouped::out(&format!("{}\n", format_args!($($arg)*)))
+        }
+    };
+}
+
+/// `say!` for lines about files that need no attention, such as links created or
+/// already in place, which `--quiet` leaves out.
+macro_rules! progress {
+    ($opts:expr, $($arg:tt)*) => {
+        if $opts.shows($crate::Line::Progress) {
+            $crate::grouped::out(&format!("{}\n", format_args!($($arg)*)))
+        }
+    };
+}
+
+/// `say!` for the closing summary of a run, the one line `--summary-only` keeps.
+macro_rules! summary {
+    ($opts:expr, $($arg:tt)*) => {
+        if $opts.shows($crate::Line::Summary) {
+            $crate::grouped::out(&format!("{}\n", format_args!($($arg)*)))
+        }
+    };
+}
+
+mod add;
+mod backup;
+mod case;
+mod compare;
+mod config;
+mod diff;
+mod edit;
+mod filter;
+mod grouped;
+mod hash_cache;
+mod init;
+mod inject;
+mod interrupt;
+mod jobs;
+mod journal;
+mod lock;
+mod lua_api;
+mod manifest;
+mod normalize;
+mod orphans;
+mod progress;
+mod report;
+mod script;
+mod state_path;
+mod stats;
+mod status;
+mod template;
+mod verify;
+mod which;
+
 use anyhow::{Context, Result, anyhow, bail};
 use clap::Parser;
-use mlua::{Function, Lua, Value};
+use config::Config;
+use filter::{IgnoreFile, PathGlob};
+use mlua::{Function, Lua, RegistryKey, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::fs::read_dir;
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::os::unix::fs as unix_fs;
-use std::path::{Path, PathBuf};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-fn shorten_home(p: &Path) -> String {
-    let p_str = p.to_string_lossy();
-    if let Ok(home) = std::env::var("HOME")
-        && p_str.starts_with(&home)
-    {
-        return format!("~{}", &p_str[home.len()..]);
+/// Display `p` with the target base (home by default) abbreviated to `~`.
+fn shorten_home(p: &Path, base: &Path) -> String {
+    match p.strip_prefix(base) {
+        Ok(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Ok(rest) => format!("~/{}", rest.to_string_lossy()),
+        Err(_) => p.to_string_lossy().to_string(),
     }
-    p_str.to_string()
 }
 
 // Simple color helpers using ANSI escapes (runtime switchable)
@@ -50,6 +123,8 @@ impl Colorize {
     }
 }
 
+const DEFAULT_ROOT: &str = "~/Developer/dotfiles/dotty/";
+
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
@@ -70,6 +145,20 @@ fn expand_tilde(p: &str) -> Result<PathBuf> {
     }
 }
 
+/// Parse `--var KEY=VALUE` arguments, rejecting keys given twice.
+fn parse_vars(args: &[String]) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::new();
+    for arg in args {
+        let Some((key, value)) = arg.split_once('=').filter(|(key, _)| !key.is_empty()) else {
+            bail!("Invalid --var '{arg}': expected KEY=VALUE");
+        };
+        if vars.insert(key.to_string(), value.to_string()).is_some() {
+            bail!("--var {key} is given more than once");
+        }
+    }
+    Ok(vars)
+}
+
 /// Build the companion .lua path for a given source path.
 /// Always appends ".lua" to the full file name to handle names with dots correctly.
 /// e.g. "my.dir" -> "my.dir.lua", "foo" -> "foo.lua", "bar.txt" -> "bar.txt.lua"
@@ -79,93 +168,904 @@ fn companion_lua_path(source: &Path) -> PathBuf {
     source.with_file_name(name)
 }
 
-#[derive(Debug)]
+/// `name` without its `.lua` suffix, if it has one. Works on the raw bytes so names that
+/// are not valid UTF-8 still find their companion.
+fn strip_lua_suffix(name: &OsStr) -> Option<&OsStr> {
+    name.as_bytes().strip_suffix(b".lua").map(OsStr::from_bytes)
+}
+
+/// A file name returned by Lua, byte for byte.
+fn lua_file_name(name: &mlua::String) -> OsString {
+    OsString::from_vec(name.as_bytes().to_vec())
+}
+
+#[derive(Debug, Default)]
 struct LuaDecision {
     include: bool,
-    rename_to: Option<String>,
-    transform: Option<String>,
-    /// When true (directories only), symlink the entire directory instead of recursing.
+    rename_to: Option<OsString>,
+    /// Destinations declared with `target_path`, `target` or `targets`, replacing the
+    /// computed target. Relative ones are below the target base, absolute ones are used as-is.
+    targets: Vec<PathBuf>,
+    /// The transformed content: text, unless it came from `transform_bytes`.
+    transform: Option<Vec<u8>>,
+    /// File mode for the transformed output, returned alongside the content.
+    mode: Option<u32>,
+    /// `link_dir = true` (directories only): symlink the entire directory instead of recursing.
     link: bool,
+    /// `copy = true`: copy the file instead of linking it.
+    copy: bool,
+    /// `hardlink = true`: hard link the file instead of symlinking it.
+    hardlink: bool,
+    /// `relative = true`: link with a path relative to the target's directory.
+    relative: bool,
+    /// `concat_to` (directories only): write the directory's files joined into one file
+    /// at the declared target instead of linking them.
+    concat: bool,
+    /// `inject = true`: the begin and end marker lines of the block to keep in the target.
+    inject: Option<[String; 2]>,
+    /// `on_conflict`: this entry's conflict policy, overriding `--on-conflict`.
+    on_conflict: Option<ConflictPolicy>,
+    /// The transform, when it is left to the `--jobs` pool instead of run right away.
+    pending: Option<Arc<PendingTransform>>,
+}
+
+/// A transform left to a `--jobs` worker, which runs it on a Lua state of its own once
+/// the directory walk is done.
+#[derive(Debug)]
+struct PendingTransform {
+    /// The companion or `.dotty.lua` whose `field` holds the functions.
+    origin: PathBuf,
+    /// `transform` or `transform_bytes`.
+    field: &'static str,
+    source: PathBuf,
+    /// The source's companion, if it has one.
+    lua_file: Option<PathBuf>,
+    /// The first target, which the transform sees.
+    target: PathBuf,
+    /// Where the file is reported as skipped when the transform drops it.
+    skip_target: PathBuf,
+    /// The companion's `mode`, until the transform returns another.
+    mode: Option<u32>,
+    template: bool,
+    expand_env: bool,
+    executable: bool,
+}
+
+/// Name of the directory-level companion whose table applies to every file beneath it.
+const DIR_COMPANION_NAME: &str = ".dotty.lua";
+
+/// Defaults from a directory's `.dotty.lua`. The functions are kept in the Lua registry so
+/// they stay alive while the walk descends into the subtree.
+struct DirDefaults {
+    /// The `.dotty.lua` file itself, for messages.
+    path: PathBuf,
+    include: bool,
+    rename: Option<RegistryKey>,
+    transform: Option<RegistryKey>,
+}
+
+/// Files at the top of a root that are evaluated once before its companions, so the
+/// globals they define are shared by all of them. The first one present is used.
+const HELPERS_FILE_NAMES: &[&str] = &["dotty_helpers.lua", "dotty.lua"];
+
+/// Evaluate the helpers file of `root`, if it has one.
+fn load_helpers(lua: &Lua, root: &Path) -> Result<()> {
+    let Some(path) = HELPERS_FILE_NAMES
+        .iter()
+        .map(|name| root.join(name))
+        .find(|path| path.is_file())
+    else {
+        return Ok(());
+    };
+    eval_lua_file(lua, &path, true)
+        .with_context(|| format!("Failed to load Lua helpers {}", path.display()))?;
+    Ok(())
 }
 
-fn lua_decision(lua: &Lua, lua_file: &Path, source_file: &Path) -> Result<LuaDecision> {
+/// Evaluate `lua_file`. Companions get their own global environment so what one sets does
+/// not leak into the next; only `shared` files (the root helpers) define real globals.
+fn eval_lua_file(lua: &Lua, lua_file: &Path, shared: bool) -> Result<Value> {
     let src = fs::read_to_string(lua_file)
         .with_context(|| format!("Failed to read Lua file: {}", lua_file.display()))?;
-    let chunk = lua.load(&src).set_name(lua_file.to_string_lossy());
-    let value = chunk
-        .eval::<Value>()
-        .map_err(|e| anyhow::anyhow!("Failed to execute Lua chunk: {}", e))?;
-    match value {
-        Value::Boolean(b) => Ok(LuaDecision {
-            include: b,
-            rename_to: None,
+    // `@` makes Lua report positions as `path:line:` instead of `[string "..."]`
+    let mut chunk = lua.load(&src).set_name(format!("@{}", lua_file.display()));
+    if !shared {
+        chunk = chunk.set_environment(lua_api::companion_env(lua)?);
+    }
+    lua_api::run(lua, lua_file, || chunk.eval::<Value>())
+        .map_err(|e| anyhow::anyhow!("Failed to execute Lua chunk {}: {}", lua_file.display(), e))
+}
+
+/// Evaluate `dir/.dotty.lua`, if present.
+fn dir_defaults(lua: &Lua, dir: &Path) -> Result<Option<DirDefaults>> {
+    let lua_file = dir.join(DIR_COMPANION_NAME);
+    if !lua_file.is_file() {
+        return Ok(None);
+    }
+    let value = eval_lua_file(lua, &lua_file, false)
+        .with_context(|| format!("Lua error while processing {}", dir.display()))?;
+    let defaults = match value {
+        Value::Boolean(include) => DirDefaults {
+            path: lua_file.clone(),
+            include,
+            rename: None,
             transform: None,
-            link: false,
-        }),
+        },
         Value::Table(t) => {
-            let rt: Option<String> = t.get("rename_to").unwrap_or_default();
-            if let Some(name) = &rt {
-                if name.contains('/') || name.contains('\\') {
-                    bail!(
-                        "rename_to must be a file name without path separators: {}",
-                        name
-                    );
-                }
-                if name.is_empty() {
-                    bail!("rename_to must not be empty");
-                }
+            let include: Option<bool> = t.get("include").unwrap_or_default();
+            let rename: Option<Function> = t.get("rename").unwrap_or_default();
+            // A function or a list of functions, checked when a file first uses it
+            let transform: Value = t.get("transform").unwrap_or(Value::Nil);
+            DirDefaults {
+                path: lua_file.clone(),
+                include: include.unwrap_or(true),
+                rename: rename.map(|f| lua.create_registry_value(f)).transpose()?,
+                transform: match transform {
+                    Value::Nil => None,
+                    value => Some(lua.create_registry_value(value)?),
+                },
             }
+        }
+        other => bail!(
+            "Lua filter must return boolean or table for {}. Got {}",
+            lua_file.display(),
+            other.type_name()
+        ),
+    };
+    Ok(Some(defaults))
+}
 
-            let link: bool = t
-                .get::<Option<bool>>("link")
-                .unwrap_or_default()
-                .unwrap_or(false);
+/// Decide how to handle `source_file` from its companion (if any), falling back to the
+/// enclosing `.dotty.lua` defaults, deepest first, for `rename_to` and `transform`.
+/// A companion returning plain `true` links the file as-is, without those defaults.
+/// `rel_path` is the source's path relative to the walked directory (a package's own), which
+/// its targets are computed from. With `defer`, a transform whose output becomes a file of
+/// its own is left to the `--jobs` pool.
+fn lua_decision(
+    lua: &Lua,
+    lua_file: Option<&Path>,
+    source_file: &Path,
+    rel_path: &Path,
+    defaults: &[DirDefaults],
+    defer: bool,
+    opts: &Options,
+) -> Result<LuaDecision> {
+    let mut defaults = defaults;
+    let table = match lua_file {
+        Some(lua_file) => match eval_lua_file(lua, lua_file, false)
+            .with_context(|| format!("Lua error while processing {}", source_file.display()))?
+        {
+            Value::Boolean(false) => {
+                return Ok(LuaDecision {
+                    include: false,
+                    ..Default::default()
+                });
+            }
+            Value::Boolean(true) => {
+                defaults = &[];
+                None
+            }
+            Value::Table(t) => Some(t),
+            other => bail!(
+                "Lua filter must return boolean or table for {}. Got {}",
+                lua_file.display(),
+                other.type_name()
+            ),
+        },
+        None => None,
+    };
 
-            let transform_fn: Option<Function> = t.get("transform").unwrap_or_default();
-            let transformed_content = if let Some(func) = transform_fn {
-                if source_file.is_dir() {
-                    bail!(
-                        "transform is not supported for directories: {}",
-                        source_file.display()
-                    );
-                }
-                let original_content = fs::read_to_string(source_file).with_context(|| {
-                    format!(
-                        "Failed to read source file for transform: {}",
-                        source_file.display()
+    let mut rt: Option<OsString> = None;
+    let mut target_path: Option<String> = None;
+    let mut target: Option<String> = None;
+    let mut targets: Option<Vec<String>> = None;
+    let mut concat_to: Option<String> = None;
+    let mut link = false;
+    let mut copy = false;
+    let mut hardlink = false;
+    let mut relative = false;
+    let mut mode = None;
+    let mut executable = false;
+    let mut expand_env = false;
+    let mut template = false;
+    let mut inject = None;
+    let mut on_conflict = None;
+    let mut transform_value = Value::Nil;
+    let mut transform_bytes_value = Value::Nil;
+    if let Some(t) = &table {
+        let rename_to: Option<mlua::String> = t.get("rename_to").unwrap_or_default();
+        rt = rename_to.as_ref().map(lua_file_name);
+        target_path = t.get("target_path").unwrap_or_default();
+        target = t.get("target").unwrap_or_default();
+        targets = t.get("targets").unwrap_or_default();
+        concat_to = t.get("concat_to").unwrap_or_default();
+        // `link_dir` is the documented name, `link` is kept for existing companions
+        let link_dir: Option<bool> = t.get("link_dir").unwrap_or_default();
+        let link_legacy: Option<bool> = t.get("link").unwrap_or_default();
+        link = link_dir.or(link_legacy).unwrap_or(false);
+        let copy_value: Option<bool> = t.get("copy").unwrap_or_default();
+        copy = copy_value.unwrap_or(false);
+        let hardlink_value: Option<bool> = t.get("hardlink").unwrap_or_default();
+        hardlink = hardlink_value.unwrap_or(false);
+        let relative_value: Option<bool> = t.get("relative").unwrap_or_default();
+        relative = relative_value.unwrap_or(false);
+        let mode_value: Option<String> = t.get("mode").unwrap_or_default();
+        if let Some(value) = mode_value {
+            mode = Some(parse_mode(&value).with_context(|| {
+                format!(
+                    "Invalid mode in {}",
+                    lua_file.unwrap_or(source_file).display()
+                )
+            })?);
+        }
+        let executable_value: Option<bool> = t.get("executable").unwrap_or_default();
+        executable = executable_value.unwrap_or(false);
+        let expand_env_value: Option<bool> = t.get("expand_env").unwrap_or_default();
+        expand_env = expand_env_value.unwrap_or(false);
+        let template_value: Option<bool> = t.get("template").unwrap_or_default();
+        template = template_value.unwrap_or(false);
+        let on_conflict_value: Option<String> = t.get("on_conflict").unwrap_or_default();
+        if let Some(value) = on_conflict_value {
+            on_conflict = Some(
+                <ConflictPolicy as clap::ValueEnum>::from_str(&value, false).map_err(|_| {
+                    anyhow!(
+                        "Invalid on_conflict '{}' in {}: expected skip, fail, backup or overwrite",
+                        value,
+                        lua_file.unwrap_or(source_file).display()
                     )
-                })?;
-                let result: String = func
-                    .call(original_content)
-                    .map_err(|e| anyhow!("Lua transform function error: {}", e))?;
-                Some(result)
-            } else {
-                None
-            };
+                })?,
+            );
+        }
+        let inject_value: Option<bool> = t.get("inject").unwrap_or_default();
+        let markers: Option<Vec<String>> = t.get("markers").unwrap_or_default();
+        if inject_value.unwrap_or(false) {
+            if source_file.is_dir() {
+                bail!(
+                    "inject is not supported for directories: {}",
+                    source_file.display()
+                );
+            }
+            inject = Some(match markers.as_deref() {
+                None => inject::DEFAULT_MARKERS.map(str::to_string),
+                Some([begin, end]) if !begin.trim().is_empty() && begin.trim() != end.trim() => {
+                    [begin.trim_end().to_string(), end.trim_end().to_string()]
+                }
+                Some(_) => bail!(
+                    "markers must be two different, non-empty lines for {}",
+                    source_file.display()
+                ),
+            });
+        }
+        transform_value = t.get("transform").unwrap_or(Value::Nil);
+        transform_bytes_value = t.get("transform_bytes").unwrap_or(Value::Nil);
+    }
+    if !transform_bytes_value.is_nil() {
+        let text_only = [
+            ("transform", !transform_value.is_nil()),
+            ("template", template),
+            ("expand_env", expand_env),
+            ("inject", inject.is_some()),
+        ];
+        if let Some((field, _)) = text_only.iter().find(|(_, is_set)| *is_set) {
+            bail!(
+                "transform_bytes cannot be combined with {} for {}",
+                field,
+                source_file.display()
+            );
+        }
+    }
+
+    let declared = [
+        ("rename_to", rt.is_some()),
+        ("target_path", target_path.is_some()),
+        ("target", target.is_some()),
+        ("targets", targets.is_some()),
+        ("concat_to", concat_to.is_some()),
+    ];
+    let set: Vec<&str> = declared
+        .iter()
+        .filter(|(_, is_set)| *is_set)
+        .map(|(field, _)| *field)
+        .collect();
+    if set.len() > 1 {
+        bail!(
+            "{} cannot be combined for {}",
+            set.join(" and "),
+            source_file.display()
+        );
+    }
+    let mut declared_targets = Vec::new();
+    if let Some(path) = &target_path {
+        declared_targets.push(relative_target("target_path", path)?);
+    }
+    if let Some(path) = &target {
+        declared_targets.push(absolute_target("target", path, &opts.target)?);
+    }
+    for path in targets.iter().flatten() {
+        declared_targets.push(if path.starts_with('/') || path.starts_with('~') {
+            absolute_target("targets", path, &opts.target)?
+        } else {
+            relative_target("targets", path)?
+        });
+    }
+    if targets.is_some() && declared_targets.is_empty() {
+        bail!("targets must not be empty for {}", source_file.display());
+    }
+    if let Some(path) = &concat_to {
+        if !source_file.is_dir() {
+            bail!(
+                "concat_to is only supported for directories: {}",
+                source_file.display()
+            );
+        }
+        declared_targets.push(if path.starts_with('/') || path.starts_with('~') {
+            absolute_target("concat_to", path, &opts.target)?
+        } else {
+            relative_target("concat_to", path)?
+        });
+    }
+
+    if set.is_empty()
+        && let Some(d) = defaults.iter().rev().find(|d| d.rename.is_some())
+        && let Some(key) = &d.rename
+    {
+        let rename: Function = lua.registry_value(key)?;
+        // The exact name, even when it is not valid UTF-8
+        let name = lua.create_string(source_file.file_name().unwrap_or_default().as_bytes())?;
+        let renamed: Option<mlua::String> = lua_api::run(lua, &d.path, || rename.call(name))
+            .map_err(|e| {
+                anyhow!(
+                    "Lua rename function error in {} while processing {}: {}",
+                    d.path.display(),
+                    source_file.display(),
+                    e
+                )
+            })?;
+        rt = renamed.as_ref().map(lua_file_name);
+    }
+    if let Some(name) = &rt {
+        if name.as_bytes().contains(&b'/') || name.as_bytes().contains(&b'\\') {
+            bail!(
+                "rename_to must be a file name without path separators: {}",
+                name.to_string_lossy()
+            );
+        }
+        if name.is_empty() {
+            bail!("rename_to must not be empty");
+        }
+    }
+
+    // The file that declared the transform, named when it returns something unusable
+    let mut transform_origin = lua_file.map(Path::to_path_buf);
+    // `transform_bytes` works on raw bytes and takes the place of `transform`
+    let raw = !transform_bytes_value.is_nil();
+    let field = if raw { "transform_bytes" } else { "transform" };
+    if raw {
+        transform_value = transform_bytes_value;
+    } else if transform_value.is_nil()
+        && let Some(d) = defaults.iter().rev().find(|d| d.transform.is_some())
+        && let Some(key) = &d.transform
+    {
+        transform_value = lua.registry_value(key)?;
+        transform_origin = Some(d.path.clone());
+    }
+    let mut decision = LuaDecision {
+        include: true,
+        rename_to: rt,
+        targets: declared_targets,
+        transform: None,
+        mode,
+        link,
+        copy,
+        hardlink,
+        relative,
+        concat: concat_to.is_some(),
+        inject,
+        on_conflict,
+        pending: None,
+    };
+    if !transform_value.is_nil() {
+        let origin = transform_origin.unwrap_or_default();
+        let chain = transform_chain(transform_value, field, &origin)?;
+        if source_file.is_dir() {
+            bail!(
+                "{} is not supported for directories: {}",
+                field,
+                source_file.display()
+            );
+        }
+        // With several targets the transform still runs once and sees the first one
+        let target = entry_targets(rel_path, &decision, opts)?.remove(0);
+        if defer && decision.inject.is_none() {
+            decision.pending = Some(Arc::new(PendingTransform {
+                origin,
+                field,
+                source: source_file.to_path_buf(),
+                lua_file: lua_file.map(Path::to_path_buf),
+                target,
+                skip_target: opts.target.join(rel_path),
+                mode: decision.mode,
+                template,
+                expand_env,
+                executable,
+            }));
+            return Ok(decision);
+        }
+        let Some((content, mode)) =
+            call_transform(lua, &chain, field, &origin, source_file, &target, opts)?
+        else {
+            decision.include = false;
+            return Ok(decision);
+        };
+        decision.transform = Some(content);
+        decision.mode = mode.or(decision.mode);
+    }
+    finish_content(
+        &mut decision,
+        source_file,
+        lua_file,
+        template,
+        expand_env,
+        executable,
+        opts,
+    )?;
+    Ok(decision)
+}
 
-            Ok(LuaDecision {
-                include: true,
-                rename_to: rt,
-                transform: transformed_content,
-                link,
-            })
+/// The functions of a `transform` or `transform_bytes` (`field`) declared in `origin`:
+/// a function or a non-empty list of them.
+fn transform_chain(value: Value, field: &str, origin: &Path) -> Result<Vec<Function>> {
+    Ok(match value {
+        Value::Function(func) => vec![func],
+        Value::Table(list) => {
+            let mut chain = Vec::new();
+            for (index, item) in list.sequence_values::<Value>().enumerate() {
+                match item? {
+                    Value::Function(func) => chain.push(func),
+                    other => bail!(
+                        "{}[{}] in {} must be a function, got {} (a transform list may only contain functions)",
+                        field,
+                        index + 1,
+                        origin.display(),
+                        other.type_name()
+                    ),
+                }
+            }
+            if chain.is_empty() {
+                bail!("{} list in {} must not be empty", field, origin.display());
+            }
+            chain
         }
         other => bail!(
-            "Lua filter must return boolean or table for {}. Got {}",
-            lua_file.display(),
+            "{} in {} must be a function or a list of functions. Got {}",
+            field,
+            origin.display(),
             other.type_name()
         ),
+    })
+}
+
+/// Run the transform `chain` declared in `origin` on the content of `source_file`, which
+/// goes to `target`. Returns the output and the last mode a function returned, or `None`
+/// when a function returned `nil` or `false` and the file should not be installed at all.
+fn call_transform(
+    lua: &Lua,
+    chain: &[Function],
+    field: &str,
+    origin: &Path,
+    source_file: &Path,
+    target: &Path,
+    opts: &Options,
+) -> Result<Option<(Vec<u8>, Option<u32>)>> {
+    // `transform_bytes` works on raw bytes
+    let raw = field == "transform_bytes";
+    let original_content = if raw {
+        transform_source(source_file, origin, field, opts)?
+    } else {
+        transform_input(source_file, origin, field, opts)?.into_bytes()
+    };
+    let context = lua.create_table()?;
+    let path = lua_api::root_relative(lua, source_file);
+    context.set("path", path.to_string_lossy().to_string())?;
+    context.set(
+        "name",
+        source_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+    )?;
+    context.set("target", target.to_string_lossy().to_string())?;
+    // Each function's output is the next one's input; a returned mode
+    // replaces the companion's `mode`
+    let mut content = original_content;
+    let mut mode = None;
+    for (index, func) in chain.iter().enumerate() {
+        let label = if chain.len() > 1 {
+            format!("{}[{}]", field, index + 1)
+        } else {
+            field.to_string()
+        };
+        let input = lua.create_string(&content)?;
+        let (result, second): (Value, Value) = lua_api::run(lua, origin, || {
+            func.call((input, context.clone()))
+        })
+        .map_err(|e| match chain.len() {
+            1 => anyhow!(
+                "Lua {} function error in {} while processing {}: {}",
+                field,
+                origin.display(),
+                source_file.display(),
+                e
+            ),
+            _ => anyhow!(
+                "Lua {} function error in {} of {} while processing {}: {}",
+                field,
+                label,
+                origin.display(),
+                source_file.display(),
+                e
+            ),
+        })?;
+        let mode_value = match result {
+            Value::String(text) => {
+                content = output_bytes(&text, raw)?;
+                // Only a string counts as a mode, so `return content:gsub(...)` still works
+                second
+            }
+            Value::Table(t) => {
+                let text: Option<mlua::String> = t.get("content").map_err(|_| {
+                    anyhow!(
+                        "{} in {} returned a table whose content is not a string for {}",
+                        label,
+                        origin.display(),
+                        source_file.display()
+                    )
+                })?;
+                let Some(text) = text else {
+                    bail!(
+                        "{} in {} returned a table without content for {}",
+                        label,
+                        origin.display(),
+                        source_file.display()
+                    );
+                };
+                content = output_bytes(&text, raw)?;
+                t.get("mode").unwrap_or(Value::Nil)
+            }
+            Value::Nil | Value::Boolean(false) => return Ok(None),
+            other => bail!(
+                "{} in {} must return a string, a table, nil or false for {}. Got {}",
+                label,
+                origin.display(),
+                source_file.display(),
+                other.type_name()
+            ),
+        };
+        if let Value::String(value) = mode_value {
+            let value = value.to_str()?.to_string();
+            mode = Some(parse_mode(&value).with_context(|| {
+                format!(
+                    "Invalid mode returned by {} in {} for {}",
+                    label,
+                    origin.display(),
+                    source_file.display()
+                )
+            })?);
+        }
     }
+    Ok(Some((content, mode)))
 }
 
-#[derive(Clone, Copy, Debug)]
+/// What is left of `decision` after the transform: `template = true` renders the
+/// content, then `expand_env = true` fills in `{{env.NAME}}` placeholders, both on the
+/// transform's output if there is one, and `executable = true` adds the execute bits to
+/// the declared mode, or the source's.
+fn finish_content(
+    decision: &mut LuaDecision,
+    source_file: &Path,
+    lua_file: Option<&Path>,
+    template: bool,
+    expand_env: bool,
+    executable: bool,
+    opts: &Options,
+) -> Result<()> {
+    if template {
+        let content = generated_input(decision, source_file, lua_file, "template", opts)?;
+        decision.transform =
+            Some(template::render(&content, source_file, &opts.vars)?.into_bytes());
+    }
+    if expand_env {
+        let content = generated_input(decision, source_file, lua_file, "expand_env", opts)?;
+        decision.transform = Some(expand_env_placeholders(&content, source_file)?.into_bytes());
+    }
+    if executable && source_file.is_file() {
+        let base = match decision.mode {
+            Some(mode) => mode,
+            None => source_mode(source_file)?,
+        };
+        decision.mode = Some(base | 0o111);
+    }
+    Ok(())
+}
+
+/// Run the transform `pending` describes on `lua`, a `--jobs` worker's own state: its
+/// companion or `.dotty.lua` is evaluated again there to get the functions back. Returns
+/// the content and mode to write, or `None` when the file should not be installed.
+fn run_pending(
+    lua: &Lua,
+    pending: &PendingTransform,
+    opts: &Options,
+) -> Result<Option<(Vec<u8>, Option<u32>)>> {
+    // Whatever it prints was already shown when the plan evaluated it
+    let value = match lua_api::silently(lua, || eval_lua_file(lua, &pending.origin, false))? {
+        Value::Table(t) => t.get(pending.field)?,
+        _ => Value::Nil,
+    };
+    let chain = transform_chain(value, pending.field, &pending.origin)?;
+    let Some((content, mode)) = call_transform(
+        lua,
+        &chain,
+        pending.field,
+        &pending.origin,
+        &pending.source,
+        &pending.target,
+        opts,
+    )?
+    else {
+        return Ok(None);
+    };
+    let mut decision = LuaDecision {
+        include: true,
+        transform: Some(content),
+        mode: mode.or(pending.mode),
+        ..Default::default()
+    };
+    finish_content(
+        &mut decision,
+        &pending.source,
+        pending.lua_file.as_deref(),
+        pending.template,
+        pending.expand_env,
+        pending.executable,
+        opts,
+    )?;
+    Ok(Some((
+        decision.transform.unwrap_or_default(),
+        decision.mode,
+    )))
+}
+
+/// How to handle a target that is in the way of a planned link or file.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+enum ConflictPolicy {
+    /// Report the conflict and leave the target alone.
+    Skip,
+    /// Abort the run at the first conflict.
+    Fail,
+    /// Move the target into the backup dir, then place the entry.
+    Backup,
+    /// Remove the target, then place the entry.
+    Overwrite,
+    /// Back the target up and replace it (`--force`).
+    #[value(skip)]
+    Force,
+}
+
+/// How much of the human-readable output a run prints (`--quiet`, `--summary-only`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Detail {
+    Full,
+    Quiet,
+    SummaryOnly,
+}
+
+/// What a line of the human-readable output is about, printed through `progress!`,
+/// `say!` and `summary!` respectively.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Line {
+    /// A file that needs no attention: linked, written, already in place or skipped.
+    Progress,
+    /// Everything else: conflicts, failures, warnings and headings.
+    Notice,
+    /// The closing summary.
+    Summary,
+}
+
+/// What a stow run prints.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+enum Output {
+    /// Decorated lines and a summary, for people.
+    Text,
+    /// A single JSON document with a record per entry and the summary counts.
+    Json,
+    /// One JSON event per line as each entry is processed, closed by a summary event.
+    Ndjson,
+    /// Tab-separated lines with a fixed keyword per entry (`--porcelain`).
+    #[value(skip)]
+    Porcelain,
+    /// Nothing but the script written to stdout (`--emit-script -`).
+    #[value(skip)]
+    Script,
+}
+
+/// Commands besides the default of stowing the root.
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Move a file from the target into the root at the same path and link it back
+    Add {
+        /// The file to add, e.g. ~/.config/fish/config.fish
+        path: String,
+        /// Copy the file into the root and leave the original in place
+        #[arg(long)]
+        copy: bool,
+    },
+    /// List the symlinks in the target that point into the root, whether their source still
+    /// exists and whether the plan links them again. Fails when any source is missing
+    Orphans {
+        /// Print the list as JSON
+        #[arg(long)]
+        json: bool,
+        /// How many directory levels below the target to search
+        #[arg(long, value_name = "N", default_value_t = 6)]
+        max_depth: usize,
+    },
+    /// Show where each source stands (linked, identical, missing, differs, conflict or
+    /// skipped) without changing anything
+    Status,
+    /// Show a unified diff from what is at a target to what the plan would put there.
+    /// Exits 0 when they match, 1 when they differ and 2 on errors
+    Diff {
+        /// A target such as ~/.gitconfig, or a path relative to the root such as
+        /// .config/git/config
+        path: String,
+    },
+    /// Open the source that owns a target in $EDITOR (or $VISUAL), then offer to refresh
+    /// the target
+    Edit {
+        /// The target path, e.g. ~/.zshrc
+        path: String,
+    },
+    /// Create a new root with an example file, companion, helpers file and `.dottyignore`
+    Init {
+        /// The root directory to create
+        dir: String,
+        /// Add the example files to a directory that is not empty, keeping existing files
+        #[arg(long)]
+        force: bool,
+        /// Also write a starter dotty.toml
+        #[arg(long)]
+        with_config: bool,
+    },
+    /// Revert the most recent run recorded in the journal, leaving alone anything that
+    /// changed since
+    Undo {
+        /// List the runs that can be undone, newest first
+        #[arg(long)]
+        list: bool,
+    },
+    /// Check that everything is already in place without changing anything. Exits 0 when
+    /// it is, 1 when something would change and 2 on conflicts or errors
+    Verify {
+        /// Print nothing, only set the exit code
+        #[arg(long)]
+        quiet: bool,
+    },
+    /// Print the source in the root that owns a target path, how it is placed and
+    /// whether it is in place. Fails when no source maps there
+    Which {
+        /// The target path, e.g. ~/.config/kitty/kitty.conf
+        path: String,
+    },
+}
+
+#[derive(Debug)]
 struct Options {
+    /// The subcommand to run instead of stowing.
+    command: Option<Command>,
     dry_run: bool,
+    /// `verify --quiet`: print nothing, only set the exit code.
+    silent: bool,
+    /// How much of the human-readable output is printed.
+    detail: Detail,
+    /// Print the lines about each entry in sections after the walk (`--group-output`).
+    group_output: bool,
+    /// Print a table of counts per top-level directory before the summary.
+    summary_by_dir: bool,
+    /// Show a live counter on stderr while large plans are stowed.
+    progress: bool,
+    /// Print timings after the summary (`--stats`).
+    stats: bool,
+    /// How many entries are transformed and stowed at once (`--jobs`).
+    jobs: usize,
+    /// Hash every compared file instead of trusting the hash cache (`--no-cache`).
+    no_cache: bool,
+    /// Flush written files and their directories to disk (`--fsync`).
+    fsync: bool,
+    /// Wait for another run holding the lock instead of failing (`--wait`).
+    wait: bool,
+    /// Write the plan as a shell script here instead of stowing (`--emit-script`).
+    emit_script: Option<String>,
+    /// Write a Markdown report of the stow run here (`--report`).
+    report: Option<String>,
+    /// What a stow run prints (`--output`, `--porcelain`); anything but text silences the
+    /// usual lines.
+    output: Output,
     override_identical: bool,
     verbose: bool,
+    unstow: bool,
+    restow: bool,
+    adopt: bool,
+    /// Remove stale symlinks into the root after the walk (`--prune`).
+    prune: bool,
+    /// Remove targets the manifest records that the plan dropped (`--prune-stale`).
+    prune_stale: bool,
+    /// Exit with [`verify::CONFLICTS`] when the walk found conflicts (`--fail-on-conflict`).
+    fail_on_conflict: bool,
+    /// Link files one by one instead of folding directories (`--no-folding`).
+    no_folding: bool,
+    /// Copy every file instead of linking it (`--copy`).
+    copy: bool,
+    /// Hard link every file instead of symlinking it (`--hardlink`).
+    hardlink: bool,
+    /// Create symlinks with paths relative to their directory (`--relative`).
+    relative: bool,
+    /// Allow companion `target` paths outside of the target base (`--allow-outside-home`).
+    allow_outside_home: bool,
+    /// Place targets whose parent directories lead out of the target base through a
+    /// symlink (`--allow-escaped-targets`).
+    allow_escaped_targets: bool,
+    /// Whether the target tells names apart that differ only by case, probed unless
+    /// `--assume-case-sensitive` is given.
+    case_sensitive: bool,
+    /// Run companions with the full Lua standard library (`--unsafe-lua`).
+    unsafe_lua: bool,
+    /// Time limit for each companion evaluation or transform call (`--lua-timeout`).
+    lua_timeout: Duration,
+    /// Largest source file a transform reads, in bytes (`--max-transform-size`).
+    max_transform_size: u64,
+    /// Show what companions print (`--trace-lua`, or `--verbose`).
+    trace_lua: bool,
+    /// Where replaced files are moved (`--backup-dir`, or a new timestamped directory).
+    backup_dir: PathBuf,
+    /// What to do with a target that is in the way (`--on-conflict`).
+    on_conflict: ConflictPolicy,
+    /// Ask about each conflict (`--interactive`, only on a terminal and never in a dry run).
+    interactive: bool,
+    /// Show a diff for each conflict whose content differs (`--diff`).
+    diff: bool,
+    /// Show what changes when a transformed file is rewritten (`--show-changes`).
+    show_changes: bool,
+    /// Diff lines shown per file before the rest is cut off (`--diff-lines`).
+    diff_lines: usize,
+    /// `--var KEY=VALUE` pairs over the config's `[vars]`, available to companions as
+    /// `dotty.vars` and to templates.
+    vars: BTreeMap<String, String>,
+    /// Base directory that targets are created in (`--target`, home by default).
+    target: PathBuf,
+    /// Canonicalized root directories, used to recognize links that point into a root.
+    roots: Vec<PathBuf>,
+    /// `--exclude` globs, matched against root-relative paths.
+    excludes: Vec<PathGlob>,
+    /// `--only` globs; when non-empty, everything not matching one of them is left alone.
+    only: Vec<PathGlob>,
+    /// Built-in junk file globs, empty with `--no-default-ignores`.
+    default_ignores: Vec<PathGlob>,
     color: Colorize,
 }
 
+impl Options {
+    /// Whether a `line` of the human-readable output is printed.
+    fn shows(&self, line: Line) -> bool {
+        self.output == Output::Text
+            && match self.detail {
+                Detail::Full => true,
+                Detail::Quiet => line != Line::Progress,
+                Detail::SummaryOnly => line == Line::Summary,
+            }
+    }
+}
+
 /// Compare two paths for equality using canonicalize when possible,
 /// falling back to direct comparison.
 fn paths_match(a: &Path, b: &Path) -> bool {
@@ -177,417 +1077,3967 @@ fn paths_match(a: &Path, b: &Path) -> bool {
 
 /// Result of attempting to create or verify a symlink at `target` pointing to `source`.
 enum SymlinkResult {
-    /// Symlink was created or already in place.
+    /// Symlink was created (or would be in a dry run).
     Planned,
+    /// Target already is the planned link.
+    InPlace,
     /// Target already exists and conflicts.
     Conflict,
     /// Target exists but is identical (content or link matches).
     Override,
+    /// Target was a stale symlink into the root and now points at the source (`--restow`).
+    Relinked,
+    /// Target was a regular file that was moved into the root and linked (`--adopt`).
+    Adopted,
 }
 
 /// Handle symlink creation/conflict for both files and directories.
 /// `label` is "dir" or "" for log messages.
 fn handle_symlink(
-    source: &Path,
-    target: &Path,
+    entry: &PlanEntry,
     label: &str,
-    opts: Options,
+    opts: &Options,
     content_matches: bool,
+    counts: &mut WalkCounts,
 ) -> Result<SymlinkResult> {
+    let (source, target) = (&entry.source, &entry.target);
     let label_prefix = if label.is_empty() {
         "".to_string()
     } else {
         format!("{label} ")
     };
+    // What the link stores; existing links are compared by where they resolve to
+    let link_value = if entry.relative || opts.relative {
+        relative_link(source, target)
+    } else {
+        source.to_path_buf()
+    };
 
-    // Create parent dirs if not dry-run
-    if !opts.dry_run
-        && let Some(parent) = target.parent()
-    {
-        fs::create_dir_all(parent).with_context(|| {
-            format!(
-                "Failed to create parent directories for {}",
-                target.display()
-            )
-        })?;
-    }
+    create_parent_dirs(target, opts, counts)?;
 
-    if target.exists() || target.is_symlink() {
-        let is_symlink = fs::symlink_metadata(target)
-            .ok()
-            .is_some_and(|m| m.file_type().is_symlink());
-        let link_target_matches = is_symlink
-            && fs::read_link(target)
+    // A dry run cannot unfold directory links first, so judge targets below one by the
+    // link the unfold would leave there
+    let unfolded = if opts.dry_run {
+        unfolded_path(target, opts)
+    } else {
+        None
+    };
+    let exists = match &unfolded {
+        Some(after) => after.exists(),
+        None => target.exists() || target.is_symlink(),
+    };
+    if exists {
+        let is_symlink = unfolded.is_some()
+            || fs::symlink_metadata(target)
                 .ok()
-                .is_some_and(|link_dest| paths_match(&link_dest, source));
+                .is_some_and(|m| m.file_type().is_symlink());
+        let link_target_matches = match &unfolded {
+            Some(after) => paths_match(after, source),
+            None => {
+                is_symlink
+                    && link_destination(target)
+                        .is_some_and(|link_dest| paths_match(&link_dest, source))
+            }
+        };
         let identical = link_target_matches || content_matches;
 
         if link_target_matches {
             if opts.dry_run || opts.verbose {
-                println!(
+                progress!(
+                    opts,
                     "{} Would link {label_prefix}(already in place) {} -> {}",
                     opts.color.green("✔"),
-                    shorten_home(target),
-                    shorten_home(source)
+                    shorten_home(target, &opts.target),
+                    shorten_home(source, &opts.target)
                 );
             }
-            return Ok(SymlinkResult::Planned);
+            return Ok(SymlinkResult::InPlace);
         }
 
-        if opts.override_identical && identical && !opts.dry_run && !target.is_dir() {
-            println!(
-                "{} override identical: {} <- {}",
-                opts.color.green("↻"),
-                shorten_home(target),
-                shorten_home(source)
-            );
-            let _ = fs::remove_file(target);
-            unix_fs::symlink(source, target).with_context(|| {
+        if opts.restow && is_symlink && link_points_into(target, &opts.roots) {
+            if opts.dry_run {
+                progress!(
+                    opts,
+                    "{} Would relink {label_prefix}{} -> {}",
+                    opts.color.green("↻"),
+                    shorten_home(target, &opts.target),
+                    shorten_home(source, &opts.target)
+                );
+            } else {
+                counts.journal.push(journal::Action::remove(target));
+                stats::mutate(|| fs::remove_file(target))
+                    .with_context(|| format!("Failed to remove {}", target.display()))?;
+                stats::mutate(|| unix_fs::symlink(&link_value, target)).with_context(|| {
+                    format!(
+                        "Failed to symlink {} -> {}",
+                        target.display(),
+                        source.display()
+                    )
+                })?;
+                counts
+                    .journal
+                    .push(journal::Action::symlink(target, source));
+                progress!(
+                    opts,
+                    "{} Relinked {label_prefix}{} -> {}",
+                    opts.color.green("↻"),
+                    shorten_home(target, &opts.target),
+                    shorten_home(source, &opts.target)
+                );
+            }
+            return Ok(SymlinkResult::Relinked);
+        }
+
+        if unfolded.is_none() && is_dangling(target) {
+            replace_dangling(target, opts, counts)?;
+            return create_symlink(source, target, &link_value, &label_prefix, opts, counts);
+        }
+
+        if opts.override_identical && identical && !target.is_dir() {
+            return override_identical(entry, opts, counts, |counts| {
+                create_symlink(source, target, &link_value, &label_prefix, opts, counts)
+            });
+        }
+
+        let target_is_regular_file = fs::symlink_metadata(target)
+            .ok()
+            .is_some_and(|m| m.file_type().is_file());
+        if opts.adopt && target_is_regular_file && source.is_file() {
+            if opts.dry_run {
+                progress!(
+                    opts,
+                    "{} Would adopt {} into {}",
+                    opts.color.green("⇐"),
+                    shorten_home(target, &opts.target),
+                    shorten_home(source, &opts.target)
+                );
+                return Ok(SymlinkResult::Adopted);
+            }
+            // Copy instead of rename since the root may live on a different volume
+            stats::mutate(|| fs::copy(target, source)).with_context(|| {
+                format!(
+                    "Failed to adopt {} into {}",
+                    target.display(),
+                    source.display()
+                )
+            })?;
+            stats::mutate(|| fs::remove_file(target))
+                .with_context(|| format!("Failed to remove adopted {}", target.display()))?;
+            stats::mutate(|| unix_fs::symlink(&link_value, target)).with_context(|| {
                 format!(
                     "Failed to symlink {} -> {}",
                     target.display(),
                     source.display()
                 )
             })?;
-            println!(
-                "{} Linked {label_prefix}{} -> {}",
-                opts.color.green("✔"),
-                shorten_home(target),
-                shorten_home(source)
+            counts.journal.push(journal::Action::Adopt {
+                target: target.clone(),
+                source: source.clone(),
+            });
+            progress!(
+                opts,
+                "{} Adopted {} into {}",
+                opts.color.green("⇐"),
+                shorten_home(target, &opts.target),
+                shorten_home(source, &opts.target)
             );
-            return Ok(SymlinkResult::Override);
+            return Ok(SymlinkResult::Adopted);
         }
 
-        // Real conflict
-        let mut state = String::new();
-        if opts.dry_run || opts.verbose {
-            state = if identical {
-                opts.color.green("identical")
-            } else {
-                opts.color.yellow("differs")
-            };
+        // Real conflict, unless --on-conflict clears the way
+        if resolve_conflict(entry, opts, counts)? {
+            return create_symlink(source, target, &link_value, &label_prefix, opts, counts);
         }
-        let state_suffix = if state.is_empty() {
-            String::new()
-        } else {
-            format!(" ({state})")
-        };
-        println!(
-            "{} {} {} <- {}{state_suffix}",
-            opts.color.red("✗"),
-            opts.color.red("exists"),
-            shorten_home(target),
-            shorten_home(source),
-        );
+        return Ok(report_conflict(entry, identical, opts));
+    }
+
+    create_symlink(source, target, &link_value, &label_prefix, opts, counts)
+}
+
+/// Replace the identical target of `entry` with what `place` puts there
+/// (`--override-identical`), backing the target up first. A dry run only reports it.
+fn override_identical(
+    entry: &PlanEntry,
+    opts: &Options,
+    counts: &mut WalkCounts,
+    place: impl FnOnce(&mut WalkCounts) -> Result<SymlinkResult>,
+) -> Result<SymlinkResult> {
+    let target = &entry.target;
+    print_override_identical(&entry.source, target, opts);
+    if !back_up(target, opts, counts) {
         return Ok(SymlinkResult::Conflict);
     }
+    if opts.dry_run {
+        return Ok(SymlinkResult::Override);
+    }
+    // The backup usually moved it away already
+    if fs::symlink_metadata(target).is_ok() {
+        stats::mutate(|| fs::remove_file(target))
+            .with_context(|| format!("Failed to remove {}", target.display()))?;
+    }
+    place(counts)?;
+    Ok(SymlinkResult::Override)
+}
 
-    // No conflict — create symlink
+/// Report that the target of `entry` is in the way, with whether it matches the source
+/// in a dry run or with `--verbose`.
+fn report_conflict(entry: &PlanEntry, identical: bool, opts: &Options) -> SymlinkResult {
+    let state_suffix = if opts.dry_run || opts.verbose {
+        let state = if identical {
+            opts.color.green("identical")
+        } else {
+            opts.color.yellow("differs")
+        };
+        format!(" ({state})")
+    } else {
+        String::new()
+    };
+    say!(
+        opts,
+        "{} {} ({}) {} <- {}{state_suffix}",
+        opts.color.red("✗"),
+        opts.color.red("exists"),
+        describe_conflict(&entry.target, opts),
+        shorten_home(&entry.target, &opts.target),
+        shorten_home(&entry.source, &opts.target),
+    );
+    print_conflict_diff(entry, opts);
+    SymlinkResult::Conflict
+}
+
+/// Link `target` to `source`, storing `link_value`, once nothing is in the way.
+fn create_symlink(
+    source: &Path,
+    target: &Path,
+    link_value: &Path,
+    label_prefix: &str,
+    opts: &Options,
+    counts: &mut WalkCounts,
+) -> Result<SymlinkResult> {
     if opts.dry_run {
-        println!(
+        progress!(
+            opts,
             "{} Would symlink {label_prefix}{} -> {}",
             opts.color.green("✔"),
-            shorten_home(target),
-            shorten_home(source)
+            shorten_home(target, &opts.target),
+            shorten_home(source, &opts.target)
         );
     } else {
-        unix_fs::symlink(source, target).with_context(|| {
+        stats::mutate(|| unix_fs::symlink(link_value, target)).with_context(|| {
             format!(
                 "Failed to symlink {} -> {}",
                 target.display(),
                 source.display()
             )
         })?;
-        println!(
+        counts
+            .journal
+            .push(journal::Action::symlink(target, source));
+        progress!(
+            opts,
             "{} Linked {label_prefix}{} -> {}",
             opts.color.green("✔"),
-            shorten_home(target),
-            shorten_home(source)
+            shorten_home(target, &opts.target),
+            shorten_home(source, &opts.target)
         );
     }
     Ok(SymlinkResult::Planned)
 }
 
-fn process(root: &Path, opts: Options) -> Result<()> {
-    let home = PathBuf::from(std::env::var("HOME").context("HOME must be set")?);
-    let lua = Lua::new();
-
-    #[derive(Default)]
-    struct WalkCounts {
-        planned: usize,
-        conflicts: usize,
-        skips: usize,
-        overrides: usize,
-    }
-    fn walk_dir(
-        root: &Path,
-        rel: &Path,
-        home: &Path,
-        lua: &Lua,
-        opts: Options,
-    ) -> Result<WalkCounts> {
-        let mut planned: usize = 0;
-        let mut conflicts: usize = 0;
-        let mut skips: usize = 0;
-        let mut overrides: usize = 0;
-        for entry in read_dir(root.join(rel))
-            .with_context(|| format!("Failed to read dir {}", root.join(rel).display()))?
-        {
-            let entry = entry?;
-            let path = entry.path();
-            let rel_path = rel.join(entry.file_name());
-            let file_name = entry.file_name();
-            let file_name_str = file_name.to_string_lossy();
-
-            if let Some(base_name) = file_name_str.strip_suffix(".lua") {
-                // Check if this is a companion file by seeing if there's a corresponding non-.lua entry
-                let corresponding = root.join(rel).join(base_name);
-                if corresponding.exists() {
-                    // This is a companion file, skip it
-                    continue;
-                }
-                // This is a standalone .lua file, process it normally
-            }
+/// `source` as seen from the directory containing `target`, for relative links.
+fn relative_link(source: &Path, target: &Path) -> PathBuf {
+    let from = canonicalize_lenient(target.parent().unwrap_or(Path::new("/")));
+    let to = canonicalize_lenient(source);
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut path = PathBuf::new();
+    for _ in from.components().skip(common) {
+        path.push("..");
+    }
+    for component in to.components().skip(common) {
+        path.push(component);
+    }
+    path
+}
 
-            if path.is_dir() {
-                // Skip symlinks-to-directories in source root to prevent circular recursion
-                let meta = fs::symlink_metadata(&path)
-                    .with_context(|| format!("Failed to read metadata for {}", path.display()))?;
-                if meta.file_type().is_symlink() {
-                    continue;
-                }
+/// Whether the files `a` and `b` can both be read and have the same content.
+fn same_contents(a: &Path, b: &Path) -> bool {
+    stats::compare(|| hash_cache::same_contents(a, b))
+}
 
-                // Check for companion .lua file
-                let dir_companion = companion_lua_path(&path);
-                if dir_companion.is_file() {
-                    let decision = lua_decision(lua, &dir_companion, &path)?;
-                    if !decision.include {
-                        if opts.dry_run {
-                            println!(
-                                "{} Skipped by lua: {}",
-                                opts.color.blue("ℹ"),
-                                shorten_home(&home.join(&rel_path))
-                            );
-                        }
-                        skips += 1;
-                        continue;
-                    }
-                    if decision.link {
-                        let target_rel_path = if let Some(new_name) = &decision.rename_to {
-                            rel_path.with_file_name(new_name)
-                        } else {
-                            rel_path.to_path_buf()
-                        };
-                        let target = home.join(&target_rel_path);
-
-                        match handle_symlink(&path, &target, "dir", opts, false)? {
-                            SymlinkResult::Planned => planned += 1,
-                            SymlinkResult::Conflict => conflicts += 1,
-                            SymlinkResult::Override => {
-                                planned += 1;
-                                overrides += 1;
-                            }
-                        }
-                        continue;
+/// Whether `a` and `b` are the same file (device and inode), e.g. hard links to each other.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::symlink_metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Like `handle_symlink`, but create a hard link. Hard links only work within one device,
+/// anything else is an error, raised before the target is touched.
+fn handle_hardlink(
+    entry: &PlanEntry,
+    opts: &Options,
+    counts: &mut WalkCounts,
+) -> Result<SymlinkResult> {
+    let (source, target) = (&entry.source, &entry.target);
+    check_same_device(source, target)?;
+    create_parent_dirs(target, opts, counts)?;
+
+    if is_dangling(target) {
+        replace_dangling(target, opts, counts)?;
+        return create_hardlink(source, target, opts, counts);
+    }
+    if let Ok(meta) = fs::symlink_metadata(target) {
+        if same_file(target, source) {
+            if opts.dry_run || opts.verbose {
+                progress!(
+                    opts,
+                    "{} Would hardlink (already in place) {} -> {}",
+                    opts.color.green("✔"),
+                    shorten_home(target, &opts.target),
+                    shorten_home(source, &opts.target)
+                );
+            }
+            return Ok(SymlinkResult::InPlace);
+        }
+        let identical = if meta.file_type().is_symlink() {
+            link_destination(target).is_some_and(|link_dest| paths_match(&link_dest, source))
+        } else {
+            meta.is_file() && same_contents(target, source)
+        };
+        if opts.override_identical && identical {
+            return override_identical(entry, opts, counts, |counts| {
+                create_hardlink(source, target, opts, counts)
+            });
+        }
+        if resolve_conflict(entry, opts, counts)? {
+            return create_hardlink(source, target, opts, counts);
+        }
+        return Ok(report_conflict(entry, identical, opts));
+    }
+
+    create_hardlink(source, target, opts, counts)
+}
+
+/// Whether `target` is a symlink whose destination does not exist.
+fn is_dangling(target: &Path) -> bool {
+    target.is_symlink() && !target.exists()
+}
+
+/// Remove the dangling symlink at `target` so the planned link or file can take its
+/// place. A dry run only tells that it would.
+fn replace_dangling(target: &Path, opts: &Options, counts: &mut WalkCounts) -> Result<()> {
+    let destination = fs::read_link(target).unwrap_or_default();
+    if opts.dry_run {
+        progress!(
+            opts,
+            "{} Would replace (dangling) {} -> {}",
+            opts.color.yellow("↻"),
+            shorten_home(target, &opts.target),
+            destination.display()
+        );
+    } else {
+        counts.journal.push(journal::Action::remove(target));
+        stats::mutate(|| fs::remove_file(target))
+            .with_context(|| format!("Failed to remove {}", target.display()))?;
+        progress!(
+            opts,
+            "{} replaced dangling symlink {} -> {}",
+            opts.color.yellow("↻"),
+            shorten_home(target, &opts.target),
+            destination.display()
+        );
+    }
+    counts.dangling += 1;
+    Ok(())
+}
+
+/// Create the missing parent directories of `target`. A dry run announces each of them
+/// once instead; with `--verbose`, real runs log the ones they create.
+fn create_parent_dirs(target: &Path, opts: &Options, counts: &mut WalkCounts) -> Result<()> {
+    let Some(parent) = target.parent() else {
+        return Ok(());
+    };
+    let mut missing: Vec<&Path> = parent
+        .ancestors()
+        .take_while(|dir| fs::symlink_metadata(dir).is_err())
+        .collect();
+    missing.reverse();
+    for dir in missing {
+        if !counts.new_dirs.insert(dir.to_path_buf()) {
+            continue;
+        }
+        if opts.dry_run {
+            progress!(
+                opts,
+                "{} Would create directory {}/",
+                opts.color.green("✔"),
+                shorten_home(dir, &opts.target)
+            );
+            continue;
+        }
+        stats::mutate(|| fs::create_dir(dir)).with_context(|| {
+            format!(
+                "Failed to create parent directories for {}",
+                target.display()
+            )
+        })?;
+        counts.journal.push(journal::Action::Mkdir {
+            path: dir.to_path_buf(),
+        });
+        if opts.verbose {
+            progress!(
+                opts,
+                "{} Created directory {}/",
+                opts.color.green("✔"),
+                shorten_home(dir, &opts.target)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Report that the identical `target` is (or in a dry run, would be) replaced by the
+/// planned link or file for `source` (`--override-identical`).
+fn print_override_identical(source: &Path, target: &Path, opts: &Options) {
+    let action = if opts.dry_run {
+        "Would override identical"
+    } else {
+        "override identical:"
+    };
+    progress!(
+        opts,
+        "{} {action} {} <- {}",
+        opts.color.green("↻"),
+        shorten_home(target, &opts.target),
+        shorten_home(source, &opts.target)
+    );
+}
+
+/// Fail unless a hard link at `target` can point to `source`, i.e. they are on one device.
+fn check_same_device(source: &Path, target: &Path) -> Result<()> {
+    // Compare against the closest existing parent, which is where the link will live
+    let source_dev = fs::metadata(source)
+        .with_context(|| format!("Failed to read metadata for {}", source.display()))?
+        .dev();
+    let target_dev = target
+        .ancestors()
+        .skip(1)
+        .find_map(|dir| fs::metadata(dir).ok())
+        .map(|meta| meta.dev());
+    if target_dev != Some(source_dev) {
+        bail!(
+            "Cannot hardlink {} to {}: they are on different devices",
+            target.display(),
+            source.display()
+        );
+    }
+    Ok(())
+}
+
+/// Hard link `target` to `source` once nothing is in the way.
+fn create_hardlink(
+    source: &Path,
+    target: &Path,
+    opts: &Options,
+    counts: &mut WalkCounts,
+) -> Result<SymlinkResult> {
+    if opts.dry_run {
+        progress!(
+            opts,
+            "{} Would hardlink {} -> {}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target),
+            shorten_home(source, &opts.target)
+        );
+    } else {
+        hard_link(source, target)?;
+        counts
+            .journal
+            .push(journal::Action::hardlink(target, source));
+        progress!(
+            opts,
+            "{} Hardlinked {} -> {}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target),
+            shorten_home(source, &opts.target)
+        );
+    }
+    Ok(SymlinkResult::Planned)
+}
+
+fn hard_link(source: &Path, target: &Path) -> Result<()> {
+    stats::mutate(|| fs::hard_link(source, target)).with_context(|| {
+        format!(
+            "Failed to hardlink {} -> {}",
+            target.display(),
+            source.display()
+        )
+    })
+}
+
+/// What to do with a single source entry found while walking the root.
+#[derive(Clone)]
+enum EntryKind {
+    /// Companion lua returned `false`.
+    Skipped,
+    /// Matched the given `--exclude` glob.
+    Excluded(String),
+    /// Outside of every `--only` glob.
+    Filtered,
+    /// Matched an ignore rule; holds a description of the rule.
+    Ignored(String),
+    /// Symlink a single file.
+    File,
+    /// Symlink a whole directory (`link = true` in its companion).
+    Dir,
+    /// Symlink a whole directory whose target does not exist yet, instead of its files.
+    Folded,
+    /// Replace a directory symlink into a root with a real directory before the entries
+    /// below it are placed.
+    Unfold,
+    /// A directory that could be folded, but whose target is a real directory with other
+    /// entries in it; holds how many. Its files are still linked one by one.
+    FoldConflict(usize),
+    /// Write the transformed content as a regular file.
+    Transform(Vec<u8>),
+    /// Copy the source file instead of linking it (`copy = true`).
+    Copy,
+    /// Hard link the source file instead of symlinking it (`hardlink = true`).
+    Hardlink,
+    /// Keep the content as a marked block inside the target file (`inject = true`).
+    Inject(inject::Injection),
+}
+
+impl EntryKind {
+    /// Whether this entry claims its target path, i.e. would create something there.
+    fn claims_target(&self) -> bool {
+        !matches!(
+            self,
+            EntryKind::Skipped
+                | EntryKind::Excluded(_)
+                | EntryKind::Filtered
+                | EntryKind::Ignored(_)
+                | EntryKind::Unfold
+                | EntryKind::FoldConflict(_)
+        )
+    }
+}
+
+/// A source entry paired with the target path it maps to.
+struct PlanEntry {
+    source: PathBuf,
+    target: PathBuf,
+    kind: EntryKind,
+    /// Link with a path relative to the target's directory (`relative = true`).
+    relative: bool,
+    /// File mode from the companion's `mode` or the transform. Written files get it,
+    /// linked files have it applied to their source.
+    mode: Option<u32>,
+    /// The companion's `on_conflict`, if it declared one.
+    on_conflict: Option<ConflictPolicy>,
+    /// The target is an absolute path declared by the companion, placed as-is without
+    /// checking where its parent directories lead.
+    declared_absolute: bool,
+    /// The transform whose output this entry writes, until the `--jobs` pool has run it.
+    pending: Option<Arc<PendingTransform>>,
+}
+
+impl PlanEntry {
+    /// An entry that no companion settings apply to.
+    fn new(source: PathBuf, target: PathBuf, kind: EntryKind) -> Self {
+        PlanEntry {
+            source,
+            target,
+            kind,
+            relative: false,
+            mode: None,
+            on_conflict: None,
+            declared_absolute: false,
+            pending: None,
+        }
+    }
+
+    /// An entry placing `source` at one of the targets of its companion's `decision`.
+    fn decided(source: PathBuf, target: PathBuf, kind: EntryKind, decision: &LuaDecision) -> Self {
+        PlanEntry {
+            relative: decision.relative,
+            mode: decision.mode,
+            on_conflict: decision.on_conflict,
+            declared_absolute: decision.targets.contains(&target),
+            pending: decision.pending.clone(),
+            source,
+            target,
+            kind,
+        }
+    }
+
+    /// The conflict policy for this entry: its companion's, or else `--on-conflict`.
+    fn conflict_policy(&self, opts: &Options) -> ConflictPolicy {
+        self.on_conflict.unwrap_or(opts.on_conflict)
+    }
+}
+
+/// Target path relative to the target base, taking `rename_to` into account.
+fn target_rel_path(rel_path: &Path, decision: &LuaDecision) -> PathBuf {
+    if let Some(new_name) = &decision.rename_to {
+        rel_path.with_file_name(new_name)
+    } else {
+        rel_path.to_path_buf()
+    }
+}
+
+/// What a pair of `--x` and `--no-x` flags chose, if either was given.
+fn cli_flag(on: bool, off: bool) -> Option<bool> {
+    (on || off).then_some(on)
+}
+
+/// Parse an octal file mode such as `"0755"` or `"644"`.
+fn parse_mode(mode: &str) -> Result<u32> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    match u32::from_str_radix(digits, 8) {
+        Ok(value) if !digits.is_empty() && value <= 0o7777 => Ok(value),
+        _ => bail!("'{mode}' is not an octal file mode like \"0644\""),
+    }
+}
+
+/// Parse a size like `4M` for `--max-transform-size`: bytes, or with a `K`, `M` or `G`
+/// suffix for powers of 1024.
+fn parse_size(size: &str) -> Result<u64> {
+    let (digits, unit) = match size.trim().char_indices().last() {
+        Some((at, suffix)) if suffix.is_ascii_alphabetic() => (&size.trim()[..at], Some(suffix)),
+        _ => (size.trim(), None),
+    };
+    let factor: u64 = match unit.map(|unit| unit.to_ascii_uppercase()) {
+        None => 1,
+        Some('K') => 1 << 10,
+        Some('M') => 1 << 20,
+        Some('G') => 1 << 30,
+        Some(_) => bail!("'{size}' is not a size like 512K or 4M"),
+    };
+    match digits.parse::<u64>() {
+        Ok(number) => number
+            .checked_mul(factor)
+            .ok_or_else(|| anyhow!("'{size}' is too large")),
+        Err(_) => bail!("'{size}' is not a size like 512K or 4M"),
+    }
+}
+
+/// The content a transform returned in `text`: any bytes for `transform_bytes` (`raw`),
+/// UTF-8 text otherwise.
+fn output_bytes(text: &mlua::String, raw: bool) -> Result<Vec<u8>> {
+    Ok(if raw {
+        text.as_bytes().to_vec()
+    } else {
+        text.to_str()?.to_string().into_bytes()
+    })
+}
+
+/// Read `source_file` for `field`, declared in `origin`, as raw bytes. It must be no
+/// larger than `--max-transform-size` so a transform cannot pull a huge file into memory.
+fn transform_source(
+    source_file: &Path,
+    origin: &Path,
+    field: &str,
+    opts: &Options,
+) -> Result<Vec<u8>> {
+    let size = fs::metadata(source_file)
+        .with_context(|| {
+            format!(
+                "Failed to read source file for {}: {}",
+                field,
+                source_file.display()
+            )
+        })?
+        .len();
+    if size > opts.max_transform_size {
+        bail!(
+            "{} is {} bytes, more than the {} bytes --max-transform-size allows for the {} in {}",
+            source_file.display(),
+            size,
+            opts.max_transform_size,
+            field,
+            origin.display()
+        );
+    }
+    fs::read(source_file).with_context(|| {
+        format!(
+            "Failed to read source file for {}: {}",
+            field,
+            source_file.display()
+        )
+    })
+}
+
+/// Read `source_file` for `field` (`transform`, `template` or `expand_env`) declared in
+/// `origin`, like [`transform_source`], and check that it is text.
+fn transform_input(
+    source_file: &Path,
+    origin: &Path,
+    field: &str,
+    opts: &Options,
+) -> Result<String> {
+    let content = transform_source(source_file, origin, field, opts)?;
+    // Only `transform` has a raw counterpart to point to
+    let hint = if field == "transform" {
+        "; use transform_bytes for raw content"
+    } else {
+        ""
+    };
+    let text_error = |what: &str| {
+        anyhow!(
+            "{} requires text content, but {} is {} (declared in {}){}",
+            field,
+            source_file.display(),
+            what,
+            origin.display(),
+            hint
+        )
+    };
+    if content.contains(&0) {
+        return Err(text_error("binary"));
+    }
+    String::from_utf8(content).map_err(|_| text_error("not valid UTF-8"))
+}
+
+/// The content `field` (`template` or `expand_env`) works on: the output of the steps
+/// before it, or else the source file.
+fn generated_input(
+    decision: &mut LuaDecision,
+    source_file: &Path,
+    lua_file: Option<&Path>,
+    field: &str,
+    opts: &Options,
+) -> Result<String> {
+    if let Some(content) = decision.transform.take() {
+        // Only `transform` gets here, which always returns text
+        return Ok(String::from_utf8(content)?);
+    }
+    if source_file.is_dir() {
+        bail!(
+            "{} is not supported for directories: {}",
+            field,
+            source_file.display()
+        );
+    }
+    transform_input(source_file, lua_file.unwrap_or(source_file), field, opts)
+}
+
+/// Replace `{{env.NAME}}` placeholders in `content` with environment values, or with the
+/// default in `{{env.NAME|default}}` when the variable is unset. Other `{{...}}` text is
+/// left alone. `file` is named when a variable without default is missing.
+fn expand_env_placeholders(content: &str, file: &Path) -> Result<String> {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let placeholder = &rest[start..start + 2 + end + 2];
+        rest = &after[end + 2..];
+        let Some(spec) = after[..end].trim().strip_prefix("env.") else {
+            out.push_str(placeholder);
+            continue;
+        };
+        let (name, default) = match spec.split_once('|') {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (spec.trim(), None),
+        };
+        if name.is_empty() {
+            bail!(
+                "Placeholder {} in {} has no variable name",
+                placeholder,
+                file.display()
+            );
+        }
+        match (std::env::var(name), default) {
+            (Ok(value), _) => out.push_str(&value),
+            (Err(_), Some(default)) => out.push_str(default),
+            (Err(_), None) => bail!(
+                "Environment variable {} used by {} in {} is not set",
+                name,
+                placeholder,
+                file.display()
+            ),
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// A declared destination relative to the target base, e.g. `target_path`.
+fn relative_target(field: &str, value: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(value);
+    if path.as_os_str().is_empty()
+        || !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("{field} must be a relative path without '..': {value}");
+    }
+    Ok(path)
+}
+
+/// A declared absolute or `~/` destination, e.g. `target`. `~` is the target base `base`,
+/// which is only `$HOME` when `--target` is not given.
+fn absolute_target(field: &str, value: &str, base: &Path) -> Result<PathBuf> {
+    let path = match value.strip_prefix('~') {
+        Some("") => base.to_path_buf(),
+        Some(rest) if rest.starts_with('/') => base.join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(value),
+    };
+    if !path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+        bail!("{field} must be an absolute or ~/ path without '..': {value}");
+    }
+    Ok(path)
+}
+
+/// Absolute target paths of an entry: the computed one, or every declared destination.
+/// Absolute declared paths must stay below the target base unless `--allow-outside-home`.
+fn entry_targets(rel_path: &Path, decision: &LuaDecision, opts: &Options) -> Result<Vec<PathBuf>> {
+    if decision.targets.is_empty() {
+        return Ok(vec![opts.target.join(target_rel_path(rel_path, decision))]);
+    }
+    let mut targets = Vec::new();
+    for declared in &decision.targets {
+        let target = opts.target.join(declared);
+        if !opts.allow_outside_home && !target.starts_with(&opts.target) {
+            bail!(
+                "target {} of {} is outside of {} (pass --allow-outside-home to allow this)",
+                target.display(),
+                rel_path.display(),
+                opts.target.display()
+            );
+        }
+        targets.push(target);
+    }
+    Ok(targets)
+}
+
+/// Per-directory state that applies to a directory's whole subtree during the walk,
+/// outermost first.
+#[derive(Default)]
+struct DirLayers {
+    /// `.dottyignore` files of the current directory and its parents.
+    ignores: Vec<IgnoreFile>,
+    /// `.dotty.lua` defaults of the current directory and its parents.
+    defaults: Vec<DirDefaults>,
+}
+
+/// What is found at a target path while planning.
+enum TargetState {
+    Missing,
+    /// A symlink, with its destination resolved against the link's parent.
+    Link(PathBuf),
+    /// A regular file or real directory.
+    Other,
+}
+
+/// Where `target` will point once the directory symlink into a root above it has been
+/// unfolded, which happens before anything below it is placed. `None` when no ancestor
+/// below the target base is such a link.
+fn unfolded_path(target: &Path, opts: &Options) -> Option<PathBuf> {
+    for ancestor in target.ancestors().skip(1) {
+        if ancestor == opts.target || !ancestor.starts_with(&opts.target) {
+            break;
+        }
+        if let Some(dest) = link_destination(ancestor)
+            && points_into(&dest, &opts.roots)
+        {
+            let rel = target.strip_prefix(ancestor).ok()?;
+            return Some(dest.join(rel));
+        }
+    }
+    None
+}
+
+fn target_state(target: &Path, opts: &Options) -> TargetState {
+    let target = &normalize::existing_spelling(target, &opts.target);
+    if let Some(after) = unfolded_path(target, opts) {
+        return if after.exists() {
+            TargetState::Link(after)
+        } else {
+            TargetState::Missing
+        };
+    }
+    match fs::symlink_metadata(target) {
+        Err(_) => TargetState::Missing,
+        Ok(meta) if meta.file_type().is_symlink() => {
+            TargetState::Link(link_destination(target).unwrap_or_default())
+        }
+        Ok(_) => TargetState::Other,
+    }
+}
+
+/// How many entries of the real directory `target` are not in `source`, i.e. keep it from
+/// being folded into a link to `source`. Links into a root, even stale ones, are dotty's
+/// own and do not count. Zero when `target` is no directory.
+fn extra_entries(source: &Path, target: &Path, opts: &Options) -> Result<usize> {
+    if !fs::symlink_metadata(target).is_ok_and(|meta| meta.is_dir()) {
+        return Ok(0);
+    }
+    let mut extra = 0;
+    for entry in
+        read_dir(target).with_context(|| format!("Failed to read dir {}", target.display()))?
+    {
+        let name = entry?.file_name();
+        if fs::symlink_metadata(source.join(&name)).is_err()
+            && !link_points_into(&target.join(&name), &opts.roots)
+        {
+            extra += 1;
+        }
+    }
+    Ok(extra)
+}
+
+/// Walk `root/rel` and collect one `PlanEntry` per source entry, evaluating companion
+/// lua files along the way. Stowing and unstowing are both driven by this plan.
+/// `others` are the roots or packages stowed alongside `root`; directories also present
+/// there are never folded, since their targets are shared.
+///
+/// Returns whether every file below `root/rel` is linked as-is at the same relative path,
+/// which is what allows folding the directory into a single symlink.
+fn walk_dir(
+    root: &Path,
+    rel: &Path,
+    lua: &Lua,
+    opts: &Options,
+    others: &[PathBuf],
+    layers: &mut DirLayers,
+    plan: &mut Vec<PlanEntry>,
+) -> Result<bool> {
+    let target_base = &opts.target;
+    let own_defaults = dir_defaults(lua, &root.join(rel))?;
+    if own_defaults.as_ref().is_some_and(|d| !d.include) {
+        plan.push(PlanEntry::new(
+            root.join(rel),
+            target_base.join(rel),
+            EntryKind::Skipped,
+        ));
+        return Ok(false);
+    }
+    let start = plan.len();
+    // Anything not linked as-is (companions, ignore files, skipped entries) prevents folding
+    let mut clean = true;
+    let pushed_defaults = own_defaults.is_some();
+    layers.defaults.extend(own_defaults);
+    let own_ignore_file = IgnoreFile::load(root, rel)?;
+    let pushed_ignore_file = own_ignore_file.is_some();
+    layers.ignores.extend(own_ignore_file);
+    let mut entries = read_dir(root.join(rel))
+        .with_context(|| format!("Failed to read dir {}", root.join(rel).display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    // read_dir order depends on the filesystem; sorting keeps output and plans stable
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let rel_path = rel.join(entry.file_name());
+        let file_name = entry.file_name();
+        stats::examined();
+
+        if file_name == filter::IGNORE_FILE_NAME
+            || file_name == DIR_COMPANION_NAME
+            || (rel.as_os_str().is_empty()
+                && (file_name == config::ROOT_CONFIG_NAME
+                    || HELPERS_FILE_NAMES.iter().any(|name| file_name == *name)))
+        {
+            clean = false;
+            continue;
+        }
+
+        if let Some(base_name) = strip_lua_suffix(&file_name) {
+            // Check if this is a companion file by seeing if there's a corresponding non-.lua entry
+            let corresponding = root.join(rel).join(base_name);
+            if corresponding.exists() {
+                // This is a companion file, skip it
+                clean = false;
+                continue;
+            }
+            // This is a standalone .lua file, process it normally
+        }
+
+        if let Some(glob) = filter::first_match(&opts.default_ignores, &rel_path) {
+            plan.push(PlanEntry::new(
+                path,
+                target_base.join(&rel_path),
+                EntryKind::Ignored(format!("default rule '{}'", glob.as_str())),
+            ));
+            continue;
+        }
+
+        if let Some(rule) = filter::ignored_by(&layers.ignores, &rel_path, path.is_dir()) {
+            plan.push(PlanEntry::new(
+                path,
+                target_base.join(&rel_path),
+                EntryKind::Ignored(rule),
+            ));
+            continue;
+        }
+
+        // Excluded paths are pruned before any companion lua runs
+        if let Some(glob) = filter::first_match(&opts.excludes, &rel_path) {
+            plan.push(PlanEntry::new(
+                path,
+                target_base.join(&rel_path),
+                EntryKind::Excluded(glob.as_str().to_string()),
+            ));
+            continue;
+        }
+
+        let outside_only =
+            !opts.only.is_empty() && !filter::matches_self_or_ancestor(&opts.only, &rel_path);
+        if outside_only && !(path.is_dir() && filter::could_match_below(&opts.only, &rel_path)) {
+            plan.push(PlanEntry::new(
+                path,
+                target_base.join(&rel_path),
+                EntryKind::Filtered,
+            ));
+            continue;
+        }
+
+        if path.is_dir() {
+            // Skip symlinks-to-directories in source root to prevent circular recursion
+            let meta = fs::symlink_metadata(&path)
+                .with_context(|| format!("Failed to read metadata for {}", path.display()))?;
+            if meta.file_type().is_symlink() {
+                clean = false;
+                continue;
+            }
+
+            // Check for companion .lua file. Directories only traversed to reach `--only`
+            // matches further down are transparent.
+            let dir_companion = companion_lua_path(&path);
+            if !outside_only && dir_companion.is_file() {
+                let decision = lua_decision(
+                    lua,
+                    Some(&dir_companion),
+                    &path,
+                    &rel_path,
+                    &[],
+                    false,
+                    opts,
+                )?;
+                if !decision.include {
+                    plan.push(PlanEntry::new(
+                        path,
+                        target_base.join(&rel_path),
+                        EntryKind::Skipped,
+                    ));
+                    continue;
+                }
+                if decision.concat {
+                    clean = false;
+                    let Some((content, mode)) =
+                        concat_fragments(root, &rel_path, lua, opts, layers, plan)?
+                    else {
+                        plan.push(PlanEntry::new(
+                            path,
+                            target_base.join(&rel_path),
+                            EntryKind::Skipped,
+                        ));
+                        continue;
+                    };
+                    for target in entry_targets(&rel_path, &decision, opts)? {
+                        let kind = EntryKind::Transform(content.clone().into_bytes());
+                        plan.push(PlanEntry {
+                            mode: Some(decision.mode.unwrap_or(mode)),
+                            ..PlanEntry::decided(path.clone(), target, kind, &decision)
+                        });
+                    }
+                    continue;
+                }
+                // With `--copy` or `--hardlink`, linked directories are handled file by file
+                if decision.link && !opts.copy && !opts.hardlink {
+                    for target in entry_targets(&rel_path, &decision, opts)? {
+                        plan.push(PlanEntry {
+                            mode: None,
+                            ..PlanEntry::decided(path.clone(), target, EntryKind::Dir, &decision)
+                        });
                     }
-                    // If link is not set, fall through to normal recursion
+                    continue;
+                }
+                // If link is not set, fall through to normal recursion
+            }
+
+            // Recurse into directories, then fold the subtree into one symlink when possible
+            let sub_start = plan.len();
+            let sub_clean = walk_dir(root, &rel_path, lua, opts, others, layers, plan)?;
+            clean &= sub_clean;
+            let target = target_base.join(&rel_path);
+            let foldable = sub_clean
+                && !opts.no_folding
+                && !opts.copy
+                && !opts.hardlink
+                && plan.len() > sub_start
+                && !others.iter().any(|other| other.join(&rel_path).exists());
+            let (fold, unfold) = match target_state(&target, opts) {
+                TargetState::Missing => (foldable, false),
+                // A link left by an earlier fold stays when it still fits, a stale one is
+                // relinked or reported, and anything else gets unfolded to make room.
+                TargetState::Link(dest) if points_into(&dest, &opts.roots) => {
+                    let own = paths_match(&dest, &path);
+                    let fold = (opts.unstow && own)
+                        || (foldable && (own || opts.restow || !dest.is_dir()));
+                    (fold, !fold && !opts.unstow && dest.is_dir())
                 }
+                TargetState::Link(_) | TargetState::Other => (false, false),
+            };
+            if fold {
+                plan.truncate(sub_start);
+                plan.push(PlanEntry::new(path, target, EntryKind::Folded));
+            } else if unfold {
+                plan.insert(sub_start, PlanEntry::new(path, target, EntryKind::Unfold));
+            } else if foldable && !opts.unstow {
+                let extra = extra_entries(&path, &target, opts)?;
+                if extra > 0 {
+                    let kind = EntryKind::FoldConflict(extra);
+                    plan.insert(sub_start, PlanEntry::new(path, target, kind));
+                }
+            }
+            continue;
+        }
+
+        // Only symlink or transform actual files
+        if path.is_file() {
+            let companion = companion_lua_path(&path);
+
+            let decision = lua_decision(
+                lua,
+                Some(companion.as_path()).filter(|c| c.exists()),
+                &path,
+                &rel_path,
+                &layers.defaults,
+                opts.jobs > 1,
+                opts,
+            )?;
+
+            if !decision.include {
+                plan.push(PlanEntry::new(
+                    path,
+                    target_base.join(&rel_path),
+                    EntryKind::Skipped,
+                ));
+                continue;
+            }
+
+            // The transform ran once; every target gets the same content
+            let kind = match (&decision.inject, &decision.transform) {
+                // Filled in once the pool has run it
+                _ if decision.pending.is_some() => EntryKind::Transform(Vec::new()),
+                (Some([begin, end]), transform) => EntryKind::Inject(inject::Injection {
+                    content: match transform {
+                        // `transform_bytes` cannot be combined with `inject`
+                        Some(content) => String::from_utf8(content.clone())?,
+                        None => fs::read_to_string(&path).with_context(|| {
+                            format!("Failed to read source file for inject: {}", path.display())
+                        })?,
+                    },
+                    begin: begin.clone(),
+                    end: end.clone(),
+                }),
+                (None, Some(content)) => EntryKind::Transform(content.clone()),
+                (None, None) if decision.copy || opts.copy => EntryKind::Copy,
+                (None, None) if decision.hardlink || opts.hardlink => EntryKind::Hardlink,
+                (None, None) => EntryKind::File,
+            };
+            for target in entry_targets(&rel_path, &decision, opts)? {
+                plan.push(PlanEntry::decided(
+                    path.clone(),
+                    target,
+                    kind.clone(),
+                    &decision,
+                ));
+            }
+        }
+    }
+    if pushed_ignore_file {
+        layers.ignores.pop();
+    }
+    if pushed_defaults {
+        layers.defaults.pop();
+    }
+    clean &= plan[start..].iter().all(|entry| {
+        matches!(entry.kind, EntryKind::File | EntryKind::Folded)
+            && entry.mode.is_none()
+            && entry
+                .source
+                .strip_prefix(root)
+                .is_ok_and(|rel| target_base.join(rel) == entry.target)
+    });
+    Ok(clean)
+}
+
+/// Join the files directly inside `root/rel` (a `concat_to` directory), sorted by name, and
+/// return the content with the first fragment's mode. The directory's own `.dottyignore`
+/// and `.dotty.lua` apply to its fragments like to the files of any other directory.
+/// Returns `None` when no fragment is left.
+fn concat_fragments(
+    root: &Path,
+    rel: &Path,
+    lua: &Lua,
+    opts: &Options,
+    layers: &mut DirLayers,
+    plan: &mut Vec<PlanEntry>,
+) -> Result<Option<(String, u32)>> {
+    let dir = root.join(rel);
+    let own_defaults = dir_defaults(lua, &dir)?;
+    if own_defaults.as_ref().is_some_and(|d| !d.include) {
+        return Ok(None);
+    }
+    let pushed_defaults = own_defaults.is_some();
+    layers.defaults.extend(own_defaults);
+    let own_ignore_file = IgnoreFile::load(root, rel)?;
+    let pushed_ignore_file = own_ignore_file.is_some();
+    layers.ignores.extend(own_ignore_file);
+    let joined = join_fragments(&dir, rel, lua, opts, layers, plan);
+    if pushed_ignore_file {
+        layers.ignores.pop();
+    }
+    if pushed_defaults {
+        layers.defaults.pop();
+    }
+    joined
+}
+
+/// The fragments of [`concat_fragments`], joined. Each fragment's companion can still skip
+/// or transform it; skipped and ignored fragments are added to `plan` for reporting.
+fn join_fragments(
+    dir: &Path,
+    rel: &Path,
+    lua: &Lua,
+    opts: &Options,
+    layers: &DirLayers,
+    plan: &mut Vec<PlanEntry>,
+) -> Result<Option<(String, u32)>> {
+    let mut fragments: Vec<PathBuf> = read_dir(dir)
+        .with_context(|| format!("Failed to read dir {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    fragments.sort();
+    let mut content = String::new();
+    let mut mode = None;
+    for fragment in &fragments {
+        let file_name = fragment.file_name().unwrap_or_default();
+        let rel_path = rel.join(file_name);
+        let is_companion = strip_lua_suffix(file_name).is_some_and(|base| dir.join(base).exists());
+        if !fragment.is_file()
+            || is_companion
+            || file_name == filter::IGNORE_FILE_NAME
+            || file_name == DIR_COMPANION_NAME
+        {
+            continue;
+        }
+        let ignored = match filter::first_match(&opts.default_ignores, &rel_path) {
+            Some(glob) => Some(format!("default rule '{}'", glob.as_str())),
+            None => filter::ignored_by(&layers.ignores, &rel_path, false),
+        };
+        if let Some(rule) = ignored {
+            plan.push(PlanEntry::new(
+                fragment.clone(),
+                opts.target.join(&rel_path),
+                EntryKind::Ignored(rule),
+            ));
+            continue;
+        }
+        let companion = companion_lua_path(fragment);
+        let decision = lua_decision(
+            lua,
+            Some(companion.as_path()).filter(|c| c.exists()),
+            fragment,
+            &rel_path,
+            &layers.defaults,
+            false,
+            opts,
+        )?;
+        if !decision.include {
+            plan.push(PlanEntry::new(
+                fragment.clone(),
+                opts.target.join(&rel_path),
+                EntryKind::Skipped,
+            ));
+            continue;
+        }
+        // Keep a fragment without a final newline from running into the next one
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        match decision.transform {
+            Some(transformed) => content.push_str(&String::from_utf8(transformed).map_err(|_| {
+                anyhow!(
+                    "concat_to requires text content, but transform_bytes returned bytes that are not valid UTF-8 for {}",
+                    fragment.display()
+                )
+            })?),
+            None => content.push_str(
+                &fs::read_to_string(fragment)
+                    .with_context(|| format!("Failed to read fragment {}", fragment.display()))?,
+            ),
+        }
+        if mode.is_none() {
+            mode = Some(source_mode(fragment)?);
+        }
+    }
+    Ok(mode.map(|mode| (content, mode)))
+}
+
+fn conflicts_label(count: usize) -> &'static str {
+    if count == 1 { "conflict" } else { "conflicts" }
+}
+
+/// What is in the way at a conflicting target.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ConflictKind {
+    File,
+    Directory,
+    Symlink,
+    Dangling,
+    Other,
+}
+
+impl ConflictKind {
+    fn of(target: &Path) -> ConflictKind {
+        match fs::symlink_metadata(target) {
+            Ok(meta) if meta.is_symlink() && target.exists() => ConflictKind::Symlink,
+            Ok(meta) if meta.is_symlink() => ConflictKind::Dangling,
+            Ok(meta) if meta.is_dir() => ConflictKind::Directory,
+            Ok(meta) if meta.is_file() => ConflictKind::File,
+            _ => ConflictKind::Other,
+        }
+    }
+
+    /// The kind for messages and, with `count` of them, the summary.
+    fn label(self, count: usize) -> &'static str {
+        let one = count == 1;
+        match self {
+            ConflictKind::File if one => "file",
+            ConflictKind::File => "files",
+            ConflictKind::Directory if one => "directory",
+            ConflictKind::Directory => "directories",
+            ConflictKind::Symlink if one => "symlink",
+            ConflictKind::Symlink => "symlinks",
+            ConflictKind::Dangling if one => "dangling symlink",
+            ConflictKind::Dangling => "dangling symlinks",
+            ConflictKind::Other => "other",
+        }
+    }
+}
+
+/// What is in the way at `target`, for its conflict message, with where a symlink
+/// points, e.g. `symlink → ~/old-dotfiles/gitconfig`.
+fn describe_conflict(target: &Path, opts: &Options) -> String {
+    let kind = ConflictKind::of(target);
+    match fs::read_link(target) {
+        Ok(destination) if matches!(kind, ConflictKind::Symlink | ConflictKind::Dangling) => {
+            format!(
+                "{} → {}",
+                kind.label(1),
+                shorten_home(&destination, &opts.target)
+            )
+        }
+        _ => kind.label(1).to_string(),
+    }
+}
+
+#[derive(Default)]
+struct WalkCounts {
+    planned: usize,
+    /// Links and files that were already as planned.
+    in_place: usize,
+    conflicts: usize,
+    skips: usize,
+    overrides: usize,
+    relinked: usize,
+    adopted: usize,
+    excluded: usize,
+    filtered: usize,
+    ignored: usize,
+    folded: usize,
+    unfolded: usize,
+    copied: usize,
+    /// Replaced files moved into the backup dir.
+    backups: usize,
+    /// Conflicting targets removed by `--on-conflict=overwrite`.
+    overwritten: usize,
+    /// Conflicting targets replaced by `--force`.
+    forced: usize,
+    /// Dangling symlinks replaced by the planned link or file.
+    dangling: usize,
+    /// Stale symlinks into the root removed by `--prune`.
+    pruned: usize,
+    /// Targets from earlier runs removed by `--prune-stale`.
+    stale: usize,
+    /// Directories dotty created that `--prune` or `--prune-stale` left empty and removed.
+    removed_dirs: usize,
+    /// Targets claimed by two sources of one group, neither of which was placed.
+    duplicates: usize,
+    /// Conflicts by what is in the way.
+    conflict_kinds: BTreeMap<ConflictKind, usize>,
+    /// Missing parent directories of targets, created or (in a dry run) to create.
+    new_dirs: BTreeSet<PathBuf>,
+    /// What a real run changed, in order, for `dotty undo`.
+    journal: Vec<journal::Action>,
+    /// Links removed, or in a dry run to remove, for finding emptied directories.
+    gone: BTreeSet<PathBuf>,
+    /// What happened to each entry, for `--output json`.
+    records: Vec<report::Record>,
+}
+
+impl WalkCounts {
+    /// Count a conflict at `target`, by what is in the way.
+    fn conflict(&mut self, target: &Path) {
+        self.conflicts += 1;
+        *self
+            .conflict_kinds
+            .entry(ConflictKind::of(target))
+            .or_default() += 1;
+    }
+
+    fn add(&mut self, other: &WalkCounts) {
+        self.planned += other.planned;
+        self.in_place += other.in_place;
+        self.conflicts += other.conflicts;
+        self.skips += other.skips;
+        self.overrides += other.overrides;
+        self.relinked += other.relinked;
+        self.adopted += other.adopted;
+        self.excluded += other.excluded;
+        self.filtered += other.filtered;
+        self.ignored += other.ignored;
+        self.folded += other.folded;
+        self.unfolded += other.unfolded;
+        self.copied += other.copied;
+        self.backups += other.backups;
+        self.overwritten += other.overwritten;
+        self.forced += other.forced;
+        self.dangling += other.dangling;
+        self.pruned += other.pruned;
+        self.stale += other.stale;
+        self.removed_dirs += other.removed_dirs;
+        for (kind, count) in &other.conflict_kinds {
+            *self.conflict_kinds.entry(*kind).or_default() += count;
+        }
+        self.new_dirs.extend(other.new_dirs.iter().cloned());
+        self.journal.extend(other.journal.iter().cloned());
+        self.gone.extend(other.gone.iter().cloned());
+        self.records.extend(other.records.iter().cloned());
+    }
+
+    fn record(&mut self, result: SymlinkResult, target: &Path) {
+        match result {
+            SymlinkResult::Planned => self.planned += 1,
+            SymlinkResult::InPlace => self.in_place += 1,
+            SymlinkResult::Conflict => self.conflict(target),
+            SymlinkResult::Override => {
+                self.planned += 1;
+                self.overrides += 1;
+            }
+            SymlinkResult::Relinked => self.relinked += 1,
+            SymlinkResult::Adopted => {
+                self.planned += 1;
+                self.adopted += 1;
+            }
+        }
+    }
+}
+
+/// Where the parent directories of `entry`'s target really are, when a symlink among
+/// them leads out of the target base and every root, e.g. `~/.config` pointing at an old
+/// volume. `None` when they stay inside, and for declared absolute targets.
+fn escaped_parent(entry: &PlanEntry, opts: &Options) -> Option<PathBuf> {
+    if opts.allow_escaped_targets
+        || entry.declared_absolute
+        || !entry.target.starts_with(&opts.target)
+    {
+        return None;
+    }
+    // Missing directories are created below the nearest one that exists
+    let parent = entry
+        .target
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| fs::symlink_metadata(ancestor).is_ok())?;
+    let real = fs::canonicalize(parent).ok()?;
+    let inside = |base: &Path| fs::canonicalize(base).is_ok_and(|base| real.starts_with(base));
+    if inside(&opts.target) || opts.roots.iter().any(|root| inside(root)) {
+        None
+    } else {
+        Some(real)
+    }
+}
+
+/// Whether `entry`'s target, with the symlinks above it resolved, is its source or a
+/// directory containing it, as with a `target_path` leading back into the root.
+fn targets_own_source(entry: &PlanEntry) -> bool {
+    let real = |path: &Path| -> Option<PathBuf> {
+        let parent = fs::canonicalize(path.parent()?).ok()?;
+        Some(parent.join(path.file_name()?))
+    };
+    match (real(&entry.target), real(&entry.source)) {
+        (Some(target), Some(source)) => source.starts_with(target),
+        _ => false,
+    }
+}
+
+/// Link or write a single plan entry into place.
+fn stow_entry(entry: &PlanEntry, opts: &Options, counts: &mut WalkCounts) -> Result<()> {
+    let path = &entry.source;
+    let target = &entry.target;
+    let places = !matches!(
+        entry.kind,
+        EntryKind::Skipped
+            | EntryKind::Excluded(_)
+            | EntryKind::Filtered
+            | EntryKind::Ignored(_)
+            | EntryKind::FoldConflict(_)
+    );
+    if places && let Some(real) = escaped_parent(entry, opts) {
+        say!(
+            opts,
+            "{} Conflict: escapes target, {} would be placed in {} (pass --allow-escaped-targets to allow this)",
+            opts.color.red("✗"),
+            shorten_home(target, &opts.target),
+            real.display()
+        );
+        counts.conflict(target);
+        return Ok(());
+    }
+    if places && targets_own_source(entry) {
+        say!(
+            opts,
+            "{} Skipped {}: it is the source itself",
+            opts.color.yellow("⚠"),
+            shorten_home(target, &opts.target)
+        );
+        return Ok(());
+    }
+    match &entry.kind {
+        EntryKind::Skipped => {
+            if opts.dry_run {
+                progress!(
+                    opts,
+                    "{} Skipped by lua: {}",
+                    opts.color.blue("ℹ"),
+                    shorten_home(target, &opts.target)
+                );
+            }
+            counts.skips += 1;
+        }
+        EntryKind::Excluded(pattern) => {
+            if opts.dry_run || opts.verbose {
+                progress!(
+                    opts,
+                    "{} Excluded by '{pattern}': {}",
+                    opts.color.blue("ℹ"),
+                    shorten_home(target, &opts.target)
+                );
+            }
+            counts.excluded += 1;
+        }
+        EntryKind::Filtered => counts.filtered += 1,
+        EntryKind::Ignored(rule) => {
+            if opts.verbose {
+                progress!(
+                    opts,
+                    "{} Ignored ({rule}): {}",
+                    opts.color.blue("ℹ"),
+                    shorten_home(target, &opts.target)
+                );
+            }
+            counts.ignored += 1;
+        }
+        EntryKind::Dir => {
+            let result = handle_symlink(entry, "dir", opts, false, counts)?;
+            counts.record(result, target);
+        }
+        EntryKind::Folded => {
+            let result = handle_symlink(entry, "dir", opts, false, counts)?;
+            // One already in place was folded by an earlier run
+            if matches!(result, SymlinkResult::Planned) {
+                counts.folded += 1;
+            }
+            counts.record(result, target);
+        }
+        EntryKind::Unfold => unfold_dir(path, target, opts, counts)?,
+        EntryKind::FoldConflict(extra) => {
+            let entries = if *extra == 1 { "entry" } else { "entries" };
+            say!(
+                opts,
+                "{} Conflict: cannot fold dir {}, it has {extra} other {entries}; linking its files one by one",
+                opts.color.red("✗"),
+                shorten_home(target, &opts.target)
+            );
+            counts.conflict(target);
+        }
+        EntryKind::Hardlink => {
+            let result = handle_hardlink(entry, opts, counts)?;
+            if let Some(mode) = entry.mode
+                && !matches!(result, SymlinkResult::Conflict)
+            {
+                chmod_source(path, mode, opts)?;
+            }
+            counts.record(result, target);
+        }
+        EntryKind::File => {
+            let content_matches = {
+                let is_symlink = target
+                    .symlink_metadata()
+                    .ok()
+                    .is_some_and(|m| m.file_type().is_symlink());
+                target.is_file() && !is_symlink && path.is_file() && same_contents(target, path)
+            };
+            let result = handle_symlink(entry, "", opts, content_matches, counts)?;
+            // A symlink has no mode of its own, so the requested one goes on the source
+            if let Some(mode) = entry.mode
+                && !matches!(result, SymlinkResult::Conflict)
+            {
+                chmod_source(path, mode, opts)?;
+            }
+            counts.record(result, target);
+        }
+        EntryKind::Transform(transformed_content) => {
+            // Without a declared mode the output keeps the source's permissions
+            let mode = match entry.mode {
+                Some(mode) => mode,
+                None => source_mode(path)?,
+            };
+            let result = write_entry(
+                entry,
+                transformed_content,
+                WriteKind::Transformed,
+                mode,
+                opts,
+                counts,
+            )?;
+            match result {
+                WriteResult::Conflict => counts.conflict(target),
+                WriteResult::Written | WriteResult::Override => counts.planned += 1,
+                WriteResult::InPlace => counts.in_place += 1,
+            }
+        }
+        EntryKind::Inject(injection) => {
+            match inject_block(path, target, injection, entry.mode, opts, counts)? {
+                WriteResult::Conflict => counts.conflict(target),
+                WriteResult::Written | WriteResult::Override => counts.planned += 1,
+                WriteResult::InPlace => counts.in_place += 1,
+            }
+        }
+        EntryKind::Copy => {
+            let content =
+                fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let mode = match entry.mode {
+                Some(mode) => mode,
+                None => source_mode(path)?,
+            };
+            match write_entry(entry, &content, WriteKind::Copy, mode, opts, counts)? {
+                WriteResult::Conflict => counts.conflict(target),
+                WriteResult::Written => counts.copied += 1,
+                WriteResult::InPlace => counts.in_place += 1,
+                WriteResult::Override => {
+                    counts.copied += 1;
+                    counts.overrides += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Permission bits of `source`.
+fn source_mode(source: &Path) -> Result<u32> {
+    let meta = fs::metadata(source)
+        .with_context(|| format!("Failed to read metadata for {}", source.display()))?;
+    Ok(meta.mode() & 0o7777)
+}
+
+/// Give a linked source file `mode`, unless it already has it.
+fn chmod_source(source: &Path, mode: u32, opts: &Options) -> Result<()> {
+    let current = source_mode(source)?;
+    if current == mode {
+        return Ok(());
+    }
+    if opts.dry_run {
+        progress!(
+            opts,
+            "{} Would change mode of source {} from {current:04o} to {mode:04o}",
+            opts.color.green("✔"),
+            shorten_home(source, &opts.target)
+        );
+        return Ok(());
+    }
+    stats::mutate(|| fs::set_permissions(source, fs::Permissions::from_mode(mode)))
+        .with_context(|| format!("Failed to set permissions of {}", source.display()))?;
+    progress!(
+        opts,
+        "{} Changed mode of source {} from {current:04o} to {mode:04o}",
+        opts.color.green("✔"),
+        shorten_home(source, &opts.target)
+    );
+    Ok(())
+}
+
+/// Result of placing a regular file with `write_entry`.
+enum WriteResult {
+    /// Written (or would be in a dry run).
+    Written,
+    /// Target already has the planned content.
+    InPlace,
+    Conflict,
+    /// A symlink to the source was replaced by a copy (`--override-identical`).
+    Override,
+}
+
+/// What `write_entry` places, for its messages and how a symlink in the way is treated.
+#[derive(Clone, Copy, PartialEq)]
+enum WriteKind {
+    Transformed,
+    Copy,
+}
+
+impl WriteKind {
+    fn noun(self) -> &'static str {
+        match self {
+            WriteKind::Transformed => "transformed file",
+            WriteKind::Copy => "copy",
+        }
+    }
+}
+
+/// Place `content` at `target` as a regular file with `mode`, for transformed files and
+/// copies. A transformed file overwrites anything but a directory unless it is a regular
+/// file already holding `content` with that mode. Copies treat a symlink in the way as a
+/// conflict, unless it points at the source and `--override-identical` is given.
+fn write_entry(
+    entry: &PlanEntry,
+    content: &[u8],
+    kind: WriteKind,
+    mode: u32,
+    opts: &Options,
+    counts: &mut WalkCounts,
+) -> Result<WriteResult> {
+    let (source, target) = (&entry.source, &entry.target);
+    create_parent_dirs(target, opts, counts)?;
+
+    if target.is_dir() {
+        say!(
+            opts,
+            "{} Conflict: cannot write file, target is a directory: {}",
+            opts.color.red("✗"),
+            shorten_home(target, &opts.target)
+        );
+        return Ok(WriteResult::Conflict);
+    }
+
+    // A broken link is simply replaced; a dry run still sees it afterwards
+    let dangling = is_dangling(target);
+    if dangling {
+        replace_dangling(target, opts, counts)?;
+    }
+    let is_symlink = target.is_symlink() && !dangling;
+    let mut result = WriteResult::Written;
+    let noun = kind.noun();
+    let copy = kind == WriteKind::Copy;
+    if is_symlink && copy {
+        let points_to_source =
+            link_destination(target).is_some_and(|link_dest| paths_match(&link_dest, source));
+        if opts.override_identical && points_to_source {
+            print_override_identical(source, target, opts);
+            result = WriteResult::Override;
+        } else if !resolve_conflict(entry, opts, counts)? {
+            say!(
+                opts,
+                "{} {} {} <- {}",
+                opts.color.red("✗"),
+                opts.color.red("is a symlink, expected regular file:"),
+                shorten_home(target, &opts.target),
+                shorten_home(source, &opts.target)
+            );
+            print_conflict_diff(entry, opts);
+            return Ok(WriteResult::Conflict);
+        }
+    }
+    let current = fs::symlink_metadata(target)
+        .ok()
+        .filter(|meta| meta.is_file());
+    let current_mode = current.as_ref().map(|meta| meta.mode() & 0o7777);
+    // Copies carry their source's timestamps, so one with the source's size and
+    // modification time is taken as unchanged without reading it
+    let source_meta = if copy {
+        fs::metadata(source).ok()
+    } else {
+        None
+    };
+    let copy_is_fresh = match (&current, &source_meta) {
+        (Some(current), Some(source)) => {
+            current.len() == source.len()
+                && current.mtime() == source.mtime()
+                && current.mtime_nsec() == source.mtime_nsec()
+        }
+        _ => false,
+    };
+    let content_is_identical = current_mode == Some(mode)
+        && (copy_is_fresh || stats::compare(|| compare::file_equals(target, content)));
+    if content_is_identical {
+        if opts.dry_run || opts.verbose {
+            progress!(
+                opts,
+                "{} Would write (already in place) {} from {}",
+                opts.color.green("✔"),
+                shorten_home(target, &opts.target),
+                shorten_home(source, &opts.target)
+            );
+        }
+        return Ok(WriteResult::InPlace);
+    }
+    // Needed whole for the line stats, the diff and the journal
+    let existing = current_mode.and_then(|_| fs::read(target).ok());
+    // How much a transformed file changes, shown with the action
+    let stats_note = if !copy && (opts.dry_run || opts.verbose) {
+        match diff::line_stats(existing.as_deref(), content) {
+            Some(stats) if existing.as_deref() != Some(content) => format!(" ({stats})"),
+            // `transform_bytes` output can be binary; its size is all there is to show
+            None if diff::is_binary(content) => format!(" (binary, {} bytes)", content.len()),
+            _ => String::new(),
+        }
+    } else {
+        String::new()
+    };
+    // What changes in a transformed file that is rewritten (`--show-changes`)
+    let changes = match &existing {
+        Some(old) if !copy && old != content && (opts.show_changes || opts.verbose) => {
+            Some(old.as_slice())
+        }
+        _ => None,
+    };
+    // Mention permission changes; new copies simply keep their source's mode
+    let mode_note = match current_mode {
+        Some(current) if current != mode => format!(" (mode {current:04o} -> {mode:04o})"),
+        None if !copy => format!(" (mode {mode:04o})"),
+        _ => String::new(),
+    };
+
+    let target_existed = target.exists() || is_symlink;
+    // A file with other content is kept aside before it is overwritten
+    // Copied, so the target is there until the new content replaces it
+    if current_mode.is_some() && !back_up_copy(target, opts, counts) {
+        return Ok(WriteResult::Conflict);
+    }
+    if opts.dry_run {
+        let action = if target_existed { "overwrite" } else { "write" };
+        progress!(
+            opts,
+            "{} Would {action} {noun} {} from {}{stats_note}{mode_note}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target),
+            shorten_home(source, &opts.target)
+        );
+        if let Some(old) = changes {
+            print_diff(old, content, entry, Line::Progress, opts);
+        }
+        return Ok(result);
+    }
+
+    let times = source_meta.as_ref().and_then(|meta| {
+        Some(
+            fs::FileTimes::new()
+                .set_accessed(meta.accessed().ok()?)
+                .set_modified(meta.modified().ok()?),
+        )
+    });
+    stats::mutate(|| write_atomically(target, content, mode, times, opts))
+        .with_context(|| format!("Failed to write {noun} {}", target.display()))?;
+    counts
+        .journal
+        .push(journal::Action::write(target, content, existing.as_deref()));
+    let action = if target_existed { "Overwrote" } else { "Wrote" };
+    progress!(
+        opts,
+        "{} {action} {noun} {} from {}{stats_note}{mode_note}",
+        opts.color.green("✔"),
+        shorten_home(target, &opts.target),
+        shorten_home(source, &opts.target)
+    );
+    if let Some(old) = changes {
+        print_diff(old, content, entry, Line::Progress, opts);
+    }
+    Ok(result)
+}
+
+/// Replace `target` with a regular file holding `content` with `mode`, so it never holds
+/// partial content: the content goes to a temporary file in the same directory (on the
+/// same filesystem), which is then renamed over the target. A symlink at the target is
+/// replaced, never written through into its destination, e.g. one left by an unfold.
+/// The file gets `times` when given. With `--fsync` the file and then its directory are
+/// flushed to disk; failing that is reported for the file without failing the write.
+fn write_atomically(
+    target: &Path,
+    content: &[u8],
+    mode: u32,
+    times: Option<fs::FileTimes>,
+    opts: &Options,
+) -> Result<()> {
+    let mut temp_name = OsString::from(".");
+    temp_name.push(target.file_name().unwrap_or_default());
+    temp_name.push(".dotty-tmp");
+    let temp = target.with_file_name(temp_name);
+    // Left behind by an interrupted run
+    if fs::symlink_metadata(&temp).is_ok_and(|meta| !meta.is_dir()) {
+        fs::remove_file(&temp).with_context(|| format!("Failed to remove {}", temp.display()))?;
+    }
+    let written = (|| -> Result<()> {
+        // Private until the content is complete, in case it is a secret
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&temp)
+            .with_context(|| format!("Failed to create temporary file {}", temp.display()))?;
+        file.write_all(content)
+            .with_context(|| format!("Failed to write temporary file {}", temp.display()))?;
+        fs::set_permissions(&temp, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set permissions of {}", temp.display()))?;
+        if let Some(times) = times {
+            file.set_times(times)
+                .with_context(|| format!("Failed to set times of {}", temp.display()))?;
+        }
+        if opts.fsync
+            && let Err(e) = file.sync_all()
+        {
+            sync_failed(target, &e, opts);
+        }
+        if let Err(e) = fs::rename(&temp, target) {
+            if !target.is_symlink() {
+                return Err(e).with_context(|| format!("Failed to replace {}", target.display()));
+            }
+            // A link that cannot be replaced in one step is removed first
+            fs::remove_file(target)
+                .with_context(|| format!("Failed to remove {}", target.display()))?;
+            fs::rename(&temp, target)
+                .with_context(|| format!("Failed to replace {}", target.display()))?;
+        }
+        Ok(())
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    // The rename itself is only durable once the directory is flushed
+    if opts.fsync
+        && written.is_ok()
+        && let Some(parent) = target.parent()
+        && let Err(e) = fs::File::open(parent).and_then(|dir| dir.sync_all())
+    {
+        sync_failed(target, &e, opts);
+    }
+    written
+}
+
+/// Report that `target` may not have reached the disk (`--fsync`).
+fn sync_failed(target: &Path, error: &std::io::Error, opts: &Options) {
+    say!(
+        opts,
+        "{} Failed to sync {} to disk: {error}",
+        opts.color.yellow("⚠"),
+        shorten_home(target, &opts.target)
+    );
+}
+
+/// Add or update the managed block of `injection` in `target`, leaving the rest of the
+/// file alone. A new file gets `mode`, or the source's permissions.
+fn inject_block(
+    source: &Path,
+    target: &Path,
+    injection: &inject::Injection,
+    mode: Option<u32>,
+    opts: &Options,
+    counts: &mut WalkCounts,
+) -> Result<WriteResult> {
+    if target.is_symlink() || target.is_dir() {
+        say!(
+            opts,
+            "{} Conflict: cannot inject a block, target is not a regular file: {}",
+            opts.color.red("✗"),
+            shorten_home(target, &opts.target)
+        );
+        return Ok(WriteResult::Conflict);
+    }
+    let existing = if target.exists() {
+        Some(
+            fs::read_to_string(target)
+                .with_context(|| format!("Failed to read {}", target.display()))?,
+        )
+    } else {
+        None
+    };
+    let (updated, change) = match injection.apply(existing.as_deref()) {
+        Ok(applied) => applied,
+        Err(e) => {
+            say!(
+                opts,
+                "{} Conflict: {e} in {}",
+                opts.color.red("✗"),
+                shorten_home(target, &opts.target)
+            );
+            return Ok(WriteResult::Conflict);
+        }
+    };
+    let (would, done) = match change {
+        inject::BlockChange::InPlace => {
+            if opts.dry_run || opts.verbose {
+                progress!(
+                    opts,
+                    "{} Block already in place in {} from {}",
+                    opts.color.green("✔"),
+                    shorten_home(target, &opts.target),
+                    shorten_home(source, &opts.target)
+                );
+            }
+            return Ok(WriteResult::InPlace);
+        }
+        inject::BlockChange::CreateFile => {
+            ("create block in new file", "Created block in new file")
+        }
+        inject::BlockChange::Add => ("add block to", "Added block to"),
+        inject::BlockChange::Update => ("update block in", "Updated block in"),
+    };
+    if existing.is_none() {
+        create_parent_dirs(target, opts, counts)?;
+    }
+    if opts.dry_run {
+        progress!(
+            opts,
+            "{} Would {would} {} from {}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target),
+            shorten_home(source, &opts.target)
+        );
+        return Ok(WriteResult::Written);
+    }
+    // An existing file keeps its permissions
+    let mode = match (&existing, mode) {
+        (Some(_), _) => source_mode(target)?,
+        (None, Some(mode)) => mode,
+        (None, None) => source_mode(source)?,
+    };
+    stats::mutate(|| write_atomically(target, updated.as_bytes(), mode, None, opts))
+        .with_context(|| format!("Failed to write {}", target.display()))?;
+    counts.journal.push(journal::Action::write(
+        target,
+        updated.as_bytes(),
+        existing.as_deref().map(str::as_bytes),
+    ));
+    progress!(
+        opts,
+        "{} {done} {} from {}",
+        opts.color.green("✔"),
+        shorten_home(target, &opts.target),
+        shorten_home(source, &opts.target)
+    );
+    Ok(WriteResult::Written)
+}
+
+/// Take the managed block of `injection` out of `target`, keeping the rest of the file.
+fn unstow_block(
+    target: &Path,
+    injection: &inject::Injection,
+    opts: &Options,
+    counts: &mut UnstowCounts,
+) -> Result<()> {
+    let text = if target.is_file() && !target.is_symlink() {
+        fs::read_to_string(target)
+            .with_context(|| format!("Failed to read {}", target.display()))?
+    } else {
+        String::new()
+    };
+    let remaining = match injection.remove(&text) {
+        Ok(Some(remaining)) => remaining,
+        Ok(None) => {
+            if opts.dry_run || opts.verbose {
+                progress!(
+                    opts,
+                    "{} No block in {}",
+                    opts.color.blue("ℹ"),
+                    shorten_home(target, &opts.target)
+                );
+            }
+            counts.missing += 1;
+            return Ok(());
+        }
+        Err(e) => {
+            say!(
+                opts,
+                "{} {} {e} in {}",
+                opts.color.red("✗"),
+                opts.color.yellow("kept:"),
+                shorten_home(target, &opts.target)
+            );
+            counts.kept += 1;
+            return Ok(());
+        }
+    };
+    if opts.dry_run {
+        progress!(
+            opts,
+            "{} Would remove block from {}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target)
+        );
+    } else {
+        let mode = source_mode(target)?;
+        stats::mutate(|| write_atomically(target, remaining.as_bytes(), mode, None, opts))
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+        counts.journal.push(journal::Action::write(
+            target,
+            remaining.as_bytes(),
+            Some(text.as_bytes()),
+        ));
+        progress!(
+            opts,
+            "{} Removed block from {}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target)
+        );
+    }
+    counts.removed += 1;
+    Ok(())
+}
+
+/// Move the file or symlink at `target` into the backup dir before dotty replaces it, or
+/// say where it would go in a dry run. Returns `false` after reporting a failure, in which
+/// case the target must be left alone. Directories are not backed up.
+fn back_up(target: &Path, opts: &Options, counts: &mut WalkCounts) -> bool {
+    store_backup(target, false, opts, counts)
+}
+
+/// Like [`back_up`], but copy a regular file and leave it in place, for a target that is
+/// then replaced in one step and must not go missing in between.
+fn back_up_copy(target: &Path, opts: &Options, counts: &mut WalkCounts) -> bool {
+    store_backup(target, true, opts, counts)
+}
+
+/// Back `target` up, copying it with `keep` when it is a regular file and moving it
+/// otherwise.
+fn store_backup(target: &Path, keep: bool, opts: &Options, counts: &mut WalkCounts) -> bool {
+    if !fs::symlink_metadata(target).is_ok_and(|meta| !meta.is_dir()) {
+        return true;
+    }
+    let dest = backup::backup_path(&opts.backup_dir, &opts.target, target);
+    let store = if keep && !target.is_symlink() {
+        backup::copy_file
+    } else {
+        backup::move_file
+    };
+    if opts.dry_run {
+        progress!(
+            opts,
+            "{} Would back up {} to {}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target),
+            shorten_home(&dest, &opts.target)
+        );
+    } else if let Err(e) = store(target, &dest) {
+        say!(
+            opts,
+            "{} Failed to back up {} to {}, leaving it in place: {e:#}",
+            opts.color.red("✗"),
+            shorten_home(target, &opts.target),
+            shorten_home(&dest, &opts.target)
+        );
+        return false;
+    } else {
+        counts.journal.push(journal::Action::Backup {
+            target: target.to_path_buf(),
+            backup: dest.clone(),
+        });
+        progress!(
+            opts,
+            "{} Backed up {} to {}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target),
+            shorten_home(&dest, &opts.target)
+        );
+    }
+    counts.backups += 1;
+    true
+}
+
+/// Clear the conflicting target of `entry` as its conflict policy asks: move it into the
+/// backup dir or remove it. Returns whether it is out of the way (or would be, in a dry
+/// run), so the planned link or file can be put there. Directories are never replaced.
+fn resolve_conflict(entry: &PlanEntry, opts: &Options, counts: &mut WalkCounts) -> Result<bool> {
+    let target = &entry.target;
+    if fs::symlink_metadata(target).is_ok_and(|meta| meta.is_dir()) {
+        return Ok(false);
+    }
+    // A companion's own policy is never asked about
+    let policy = if opts.interactive && entry.on_conflict.is_none() {
+        ask_conflict(entry, opts)?
+    } else {
+        entry.conflict_policy(opts)
+    };
+    match policy {
+        ConflictPolicy::Skip | ConflictPolicy::Fail => Ok(false),
+        ConflictPolicy::Backup => Ok(back_up(target, opts, counts)),
+        ConflictPolicy::Overwrite => {
+            if opts.dry_run {
+                say!(
+                    opts,
+                    "{} Would overwrite {}",
+                    opts.color.yellow("↻"),
+                    shorten_home(target, &opts.target)
+                );
+            } else {
+                counts.journal.push(journal::Action::remove(target));
+                stats::mutate(|| fs::remove_file(target))
+                    .with_context(|| format!("Failed to remove {}", target.display()))?;
+                say!(
+                    opts,
+                    "{} Overwrote {}",
+                    opts.color.yellow("↻"),
+                    shorten_home(target, &opts.target)
+                );
+            }
+            counts.overwritten += 1;
+            Ok(true)
+        }
+        ConflictPolicy::Force => {
+            if !back_up(target, opts, counts) {
+                return Ok(false);
+            }
+            let action = if opts.dry_run {
+                "Would force"
+            } else {
+                "forced:"
+            };
+            say!(
+                opts,
+                "{} {action} {} <- {}",
+                opts.color.yellow("↻"),
+                shorten_home(target, &opts.target),
+                shorten_home(&entry.source, &opts.target)
+            );
+            counts.forced += 1;
+            Ok(true)
+        }
+    }
+}
+
+/// What `entry` would put at its target, for diffs. `None` for directories and blocks.
+fn planned_content(entry: &PlanEntry) -> Option<Vec<u8>> {
+    match &entry.kind {
+        EntryKind::Transform(content) => Some(content.clone()),
+        EntryKind::File | EntryKind::Copy | EntryKind::Hardlink => fs::read(&entry.source).ok(),
+        _ => None,
+    }
+}
+
+/// With `--diff`, show how the file at `entry`'s conflicting target differs from what
+/// dotty would put there. Nothing is shown when they are identical or either side is not
+/// a file.
+fn print_conflict_diff(entry: &PlanEntry, opts: &Options) {
+    if !opts.diff || !entry.target.is_file() {
+        return;
+    }
+    let (Ok(current), Some(planned)) = (fs::read(&entry.target), planned_content(entry)) else {
+        return;
+    };
+    if current != planned {
+        print_diff(&current, &planned, entry, Line::Notice, opts);
+    }
+}
+
+/// Print the diff from `old`, at `entry`'s target, to `new`, from its source, as part
+/// of the output about that entry shown as a `line`.
+fn print_diff(old: &[u8], new: &[u8], entry: &PlanEntry, line: Line, opts: &Options) {
+    if !opts.shows(line) {
+        return;
+    }
+    grouped::out(&diff::unified(
+        old,
+        new,
+        &shorten_home(&entry.target, &opts.target),
+        &shorten_home(&entry.source, &opts.target),
+        opts.diff_lines,
+        opts.color,
+    ));
+}
+
+/// Ask how to resolve the conflict at `entry`'s target (`--interactive`), showing a diff
+/// on request. Running out of input skips the remaining conflicts.
+fn ask_conflict(entry: &PlanEntry, opts: &Options) -> Result<ConflictPolicy> {
+    let target = shorten_home(&entry.target, &opts.target);
+    let mut answer = String::new();
+    loop {
+        print!(
+            "{} Conflict at {target} <- {}: [s]kip, [o]verwrite, [b]ackup, [d]iff, [q]uit? ",
+            opts.color.yellow("?"),
+            shorten_home(&entry.source, &opts.target)
+        );
+        std::io::stdout().flush()?;
+        answer.clear();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            println!();
+            return Ok(ConflictPolicy::Skip);
+        }
+        match answer.trim() {
+            "s" => return Ok(ConflictPolicy::Skip),
+            "o" => return Ok(ConflictPolicy::Overwrite),
+            "b" => return Ok(ConflictPolicy::Backup),
+            "q" => bail!("Stopped at the conflict at {target}"),
+            "d" => match (fs::read(&entry.target), planned_content(entry)) {
+                (Ok(current), Some(planned)) => print!(
+                    "{}",
+                    diff::unified(
+                        &current,
+                        &planned,
+                        &target,
+                        &shorten_home(&entry.source, &opts.target),
+                        opts.diff_lines,
+                        opts.color,
+                    )
+                ),
+                _ => say!(opts, "No content to compare for {target}"),
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Canonicalize the longest existing prefix of `path` and append the missing rest,
+/// so dangling link destinations can still be compared against a canonical root.
+fn canonicalize_lenient(path: &Path) -> PathBuf {
+    let mut missing = Vec::new();
+    let mut current = path;
+    loop {
+        if let Ok(canonical) = fs::canonicalize(current) {
+            return missing
+                .iter()
+                .rev()
+                .fold(canonical, |acc, name| acc.join(name));
+        }
+        match (current.parent(), current.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                current = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// The destination of the symlink `target`, with relative destinations resolved
+/// against the symlink's parent directory.
+fn link_destination(target: &Path) -> Option<PathBuf> {
+    let dest = fs::read_link(target).ok()?;
+    Some(match target.parent() {
+        Some(parent) if dest.is_relative() => parent.join(dest),
+        _ => dest,
+    })
+}
+
+/// Whether `path` lies inside one of `roots`, which are expected to be canonicalized.
+fn points_into(path: &Path, roots: &[PathBuf]) -> bool {
+    let path = canonicalize_lenient(path);
+    roots.iter().any(|root| path.starts_with(root))
+}
+
+/// Whether `target` is a symlink whose destination lies inside one of `roots`.
+fn link_points_into(target: &Path, roots: &[PathBuf]) -> bool {
+    link_destination(target).is_some_and(|dest| points_into(&dest, roots))
+}
+
+/// Remove symlinks that point into a root at something that no longer exists, e.g. after
+/// a file was renamed in the repo (`--prune`). Only the directories the plan places
+/// entries in, and their parents up to the target base, are searched; links at planned
+/// targets are left to the plan.
+fn prune(groups: &[PlanGroup], opts: &Options, counts: &mut WalkCounts) -> Result<()> {
+    let targets: BTreeSet<&Path> = groups
+        .iter()
+        .flat_map(|group| &group.entries)
+        .map(|entry| entry.target.as_path())
+        .collect();
+    let mut dirs = BTreeSet::new();
+    for target in &targets {
+        for dir in target.ancestors().skip(1) {
+            if !dir.starts_with(&opts.target) || !dirs.insert(dir) {
+                break;
+            }
+        }
+    }
+    for dir in dirs {
+        // Never look inside a linked directory, which is part of the root
+        if dir.is_symlink() || !dir.is_dir() {
+            continue;
+        }
+        let mut stale = Vec::new();
+        for item in
+            read_dir(dir).with_context(|| format!("Failed to read dir {}", dir.display()))?
+        {
+            let path = item?.path();
+            if path.is_symlink()
+                && !path.exists()
+                && !targets.contains(path.as_path())
+                && link_points_into(&path, &opts.roots)
+            {
+                stale.push(path);
+            }
+        }
+        stale.sort();
+        for link in stale {
+            let destination = fs::read_link(&link).unwrap_or_default();
+            if opts.dry_run {
+                progress!(
+                    opts,
+                    "{} Would prune {} -> {}",
+                    opts.color.green("✔"),
+                    shorten_home(&link, &opts.target),
+                    shorten_home(&destination, &opts.target)
+                );
+            } else {
+                counts.journal.push(journal::Action::remove(&link));
+                stats::mutate(|| fs::remove_file(&link))
+                    .with_context(|| format!("Failed to remove {}", link.display()))?;
+                progress!(
+                    opts,
+                    "{} Pruned {} -> {}",
+                    opts.color.green("✔"),
+                    shorten_home(&link, &opts.target),
+                    shorten_home(&destination, &opts.target)
+                );
+            }
+            counts.gone.insert(link);
+            counts.pruned += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Atomically exchange the entries at `a` and `b`, which must both exist. A plain rename
+/// cannot put a directory over a symlink.
+fn swap_paths(a: &Path, b: &Path) -> std::io::Result<()> {
+    let a = std::ffi::CString::new(a.as_os_str().as_bytes())?;
+    let b = std::ffi::CString::new(b.as_os_str().as_bytes())?;
+    // SAFETY: both are valid, NUL-terminated paths that outlive the call
+    let result = unsafe { libc::renamex_np(a.as_ptr(), b.as_ptr(), libc::RENAME_SWAP) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Replace the directory symlink at `target` with a real directory holding one symlink per
+/// entry of the linked directory, so new links can be placed next to them. When the link
+/// pointed at `source` itself, the directory starts out empty since the plan recreates its
+/// entries. The directory is assembled next to the link and swapped with it in one step, so
+/// an interrupted run leaves either the old link or the complete directory behind.
+fn unfold_dir(source: &Path, target: &Path, opts: &Options, counts: &mut WalkCounts) -> Result<()> {
+    // Already unfolded together with a parent
+    let Some(dest) = link_destination(target) else {
+        return Ok(());
+    };
+    let mut names = Vec::new();
+    if !paths_match(&dest, source) {
+        for entry in
+            read_dir(&dest).with_context(|| format!("Failed to read dir {}", dest.display()))?
+        {
+            names.push(entry?.file_name());
+        }
+        names.sort();
+    }
+
+    let links = if names.len() == 1 { "link" } else { "links" };
+    if opts.dry_run {
+        progress!(
+            opts,
+            "{} Would unfold dir {} ({} {links} into {})",
+            opts.color.green("⇲"),
+            shorten_home(target, &opts.target),
+            names.len(),
+            shorten_home(&dest, &opts.target)
+        );
+        counts.unfolded += 1;
+        return Ok(());
+    }
+
+    let mut staging_name = std::ffi::OsString::from(".");
+    staging_name.push(target.file_name().unwrap_or_default());
+    staging_name.push(".dotty-unfold");
+    let staging = target.with_file_name(staging_name);
+    if staging.exists() {
+        // Left over from an interrupted run
+        stats::mutate(|| fs::remove_dir_all(&staging))
+            .with_context(|| format!("Failed to remove {}", staging.display()))?;
+    }
+    stats::mutate(|| fs::create_dir(&staging))
+        .with_context(|| format!("Failed to create {}", staging.display()))?;
+    for name in &names {
+        let link_value = if opts.relative {
+            relative_link(&dest.join(name), &target.join(name))
+        } else {
+            dest.join(name)
+        };
+        stats::mutate(|| unix_fs::symlink(link_value, staging.join(name))).with_context(|| {
+            format!(
+                "Failed to symlink {} -> {}",
+                staging.join(name).display(),
+                dest.join(name).display()
+            )
+        })?;
+    }
+    stats::mutate(|| swap_paths(&staging, target))
+        .with_context(|| format!("Failed to move {} into place", staging.display()))?;
+    // The old link is now where the directory was assembled
+    stats::mutate(|| fs::remove_file(&staging))
+        .with_context(|| format!("Failed to remove {}", staging.display()))?;
+    progress!(
+        opts,
+        "{} Unfolded dir {} ({} {links} into {})",
+        opts.color.green("⇲"),
+        shorten_home(target, &opts.target),
+        names.len(),
+        shorten_home(&dest, &opts.target)
+    );
+    counts.unfolded += 1;
+    Ok(())
+}
+
+#[derive(Default)]
+struct UnstowCounts {
+    removed: usize,
+    kept: usize,
+    missing: usize,
+    /// What a real run changed, in order, for `dotty undo`.
+    journal: Vec<journal::Action>,
+    /// Links removed, or in a dry run to remove, for finding emptied directories.
+    gone: BTreeSet<PathBuf>,
+}
+
+/// Remove a single plan entry's target if it is still what dotty would have created.
+fn unstow_entry(entry: &PlanEntry, opts: &Options, counts: &mut UnstowCounts) -> Result<()> {
+    let target = &entry.target;
+    if !entry.kind.claims_target() {
+        return Ok(());
+    }
+    if !target.exists() && !target.is_symlink() {
+        if opts.dry_run || opts.verbose {
+            progress!(
+                opts,
+                "{} Not present: {}",
+                opts.color.blue("ℹ"),
+                shorten_home(target, &opts.target)
+            );
+        }
+        counts.missing += 1;
+        return Ok(());
+    }
+    // Only the block is dotty's; the rest of the file stays
+    if let EntryKind::Inject(injection) = &entry.kind {
+        return unstow_block(target, injection, opts, counts);
+    }
+
+    let (removable, label) = match &entry.kind {
+        EntryKind::Transform(content) => {
+            let is_symlink = target
+                .symlink_metadata()
+                .ok()
+                .is_some_and(|m| m.file_type().is_symlink());
+            let unchanged = !is_symlink && stats::compare(|| compare::file_equals(target, content));
+            if !unchanged {
+                say!(
+                    opts,
+                    "{} {} {}",
+                    opts.color.red("✗"),
+                    opts.color.yellow("modified, not removed:"),
+                    shorten_home(target, &opts.target)
+                );
+                counts.kept += 1;
+                return Ok(());
+            }
+            (true, "transformed file ")
+        }
+        EntryKind::Copy => {
+            let unchanged = !target.is_symlink() && same_contents(target, &entry.source);
+            if !unchanged {
+                say!(
+                    opts,
+                    "{} {} {}",
+                    opts.color.red("✗"),
+                    opts.color.yellow("modified, not removed:"),
+                    shorten_home(target, &opts.target)
+                );
+                counts.kept += 1;
+                return Ok(());
+            }
+            (true, "copy ")
+        }
+        EntryKind::Dir | EntryKind::Folded => (link_points_into(target, &opts.roots), "dir "),
+        EntryKind::Hardlink => (same_file(target, &entry.source), "hard link "),
+        _ => (link_points_into(target, &opts.roots), ""),
+    };
+
+    if !removable {
+        say!(
+            opts,
+            "{} {} {} (not a symlink into the root)",
+            opts.color.red("✗"),
+            opts.color.yellow("kept"),
+            shorten_home(target, &opts.target)
+        );
+        counts.kept += 1;
+        return Ok(());
+    }
+
+    if opts.dry_run {
+        progress!(
+            opts,
+            "{} Would remove {label}{}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target)
+        );
+    } else {
+        counts.journal.push(journal::Action::remove(target));
+        stats::mutate(|| fs::remove_file(target))
+            .with_context(|| format!("Failed to remove {}", target.display()))?;
+        progress!(
+            opts,
+            "{} Removed {label}{}",
+            opts.color.green("✔"),
+            shorten_home(target, &opts.target)
+        );
+    }
+    counts.gone.insert(target.clone());
+    counts.removed += 1;
+    Ok(())
+}
+
+/// The plan for one walked directory: a whole root, or a single package inside a root.
+struct PlanGroup {
+    /// Directory that was walked; its children map directly under the target base.
+    dir: PathBuf,
+    /// Package name when packages were selected on the command line.
+    package: Option<String>,
+    entries: Vec<PlanEntry>,
+}
+
+/// Build the plan groups for every root, restricted to `packages` when any were given.
+/// Unknown package names are an error listing the available ones.
+fn plan_groups(
+    roots: &[PathBuf],
+    packages: &[String],
+    lua: &Lua,
+    opts: &Options,
+) -> Result<Vec<PlanGroup>> {
+    let mut dirs: Vec<(PathBuf, Option<String>)> = Vec::new();
+    if packages.is_empty() {
+        dirs.extend(roots.iter().map(|root| (root.clone(), None)));
+    } else {
+        for package in packages {
+            if package.contains('/') || package.starts_with('.') {
+                bail!("Package must be a top-level directory name: {package}");
+            }
+            if !roots.iter().any(|root| root.join(package).is_dir()) {
+                bail!(
+                    "Unknown package '{package}'. Available packages: {}",
+                    available_packages(roots)?.join(", ")
+                );
+            }
+        }
+        for root in roots {
+            for package in packages {
+                let dir = root.join(package);
+                if dir.is_dir() {
+                    dirs.push((dir, Some(package.clone())));
+                }
+            }
+        }
+    }
+
+    let mut groups = Vec::new();
+    // Roots whose helpers file already ran; several packages can share one root
+    let mut loaded_helpers: Vec<PathBuf> = Vec::new();
+    for (index, (dir, package)) in dirs.iter().enumerate() {
+        let others: Vec<PathBuf> = dirs
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(_, (other_dir, _))| other_dir.clone())
+            .collect();
+        // Packages live directly in their root, which `dotty.read` and `require` resolve against
+        let root = match package {
+            Some(_) => dir.parent().unwrap_or(dir),
+            None => dir,
+        };
+        lua_api::set_root(lua, root)?;
+        if !loaded_helpers.iter().any(|loaded| loaded == root) {
+            load_helpers(lua, root)?;
+            loaded_helpers.push(root.to_path_buf());
+        }
+        // A package is walked from its own directory, but the root's .dottyignore covers it too
+        let mut layers = DirLayers::default();
+        if let Some(package) = package {
+            layers.ignores.extend(
+                IgnoreFile::load(root, Path::new(""))?
+                    .map(|file| file.for_package(Path::new(package))),
+            );
+        }
+        let mut entries = Vec::new();
+        walk_dir(
+            dir,
+            Path::new(""),
+            lua,
+            opts,
+            &others,
+            &mut layers,
+            &mut entries,
+        )?;
+        if opts.jobs > 1 {
+            jobs::transform(&mut entries, root, &loaded_helpers, opts)?;
+        }
+        // An entry already at the target under another spelling of its name is the target
+        for entry in &mut entries {
+            entry.target = normalize::existing_spelling(&entry.target, &opts.target);
+        }
+        groups.push(PlanGroup {
+            dir: dir.clone(),
+            package: package.clone(),
+            entries,
+        });
+    }
+    Ok(groups)
+}
+
+/// Sorted, de-duplicated names of the top-level directories in `roots`.
+fn available_packages(roots: &[PathBuf]) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for root in roots {
+        for entry in
+            read_dir(root).with_context(|| format!("Failed to read dir {}", root.display()))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.path().is_dir() && !name.starts_with('.') {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Print a heading naming the directory the following lines belong to, when there are
+/// several roots or packages.
+fn print_group_heading(group: &PlanGroup, group_count: usize, opts: &Options) {
+    if group_count > 1 {
+        progress!(
+            opts,
+            "{} {}",
+            opts.color.blue("==>"),
+            shorten_home(&group.dir, &opts.target)
+        );
+    }
+}
 
-                // Recurse into directories
-                let sub = walk_dir(root, &rel_path, home, lua, opts)?;
-                planned += sub.planned;
-                conflicts += sub.conflicts;
-                skips += sub.skips;
-                overrides += sub.overrides;
+/// When two sources of one group map to the same target, or one lands inside a
+/// directory the other links, drop both and report the pair as an error; whichever came
+/// last would otherwise silently win. Blocks injected into one file share it. On a
+/// case-insensitive target, targets differing only by case are the same. Returns the
+/// number of such pairs.
+fn drop_duplicate_targets(groups: &mut [PlanGroup], opts: &Options) -> usize {
+    let key = |path: &Path| {
+        if opts.case_sensitive {
+            path.to_path_buf()
+        } else {
+            case::fold(path)
+        }
+    };
+    let mut duplicates = 0;
+    for group in groups {
+        let mut claims: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+        for (index, entry) in group.entries.iter().enumerate() {
+            if entry.kind.claims_target() {
+                claims.entry(key(&entry.target)).or_default().push(index);
+            }
+        }
+        let entries = &group.entries;
+        let mut dropped = vec![false; entries.len()];
+        for (index, entry) in entries.iter().enumerate() {
+            if !entry.kind.claims_target() {
                 continue;
             }
+            let same_target = claims[&key(&entry.target)].iter().find(|&&other| {
+                other < index
+                    && entries[other].source != entry.source
+                    && !matches!(
+                        (&entries[other].kind, &entry.kind),
+                        (EntryKind::Inject(_), EntryKind::Inject(_))
+                    )
+            });
+            let linked_dir = entry.target.ancestors().skip(1).find_map(|ancestor| {
+                claims.get(&key(ancestor))?.iter().find(|&&other| {
+                    matches!(entries[other].kind, EntryKind::Dir | EntryKind::Folded)
+                        && !entry.source.starts_with(&entries[other].source)
+                })
+            });
+            let Some(&other) = same_target.or(linked_dir) else {
+                continue;
+            };
+            duplicates += 1;
+            dropped[index] = true;
+            dropped[other] = true;
+            let (first, second) = (&entries[other].target, &entry.target);
+            if first != second && !second.starts_with(first) {
+                say!(
+                    opts,
+                    "{} {} {} and {} are one file on this case-insensitive target, so neither {} nor {} is placed (pass --assume-case-sensitive if they are not)",
+                    opts.color.red("✗"),
+                    opts.color.red("Case collision:"),
+                    shorten_home(first, &opts.target),
+                    shorten_home(second, &opts.target),
+                    shorten_home(&entries[other].source, &opts.target),
+                    shorten_home(&entry.source, &opts.target)
+                );
+                continue;
+            }
+            say!(
+                opts,
+                "{} {} {} is claimed by both {} and {}, placing neither",
+                opts.color.red("✗"),
+                opts.color.red("Duplicate target:"),
+                shorten_home(&entry.target, &opts.target),
+                shorten_home(&entries[other].source, &opts.target),
+                shorten_home(&entry.source, &opts.target)
+            );
+        }
+        let mut dropped = dropped.into_iter();
+        group.entries.retain(|_| !dropped.next().unwrap_or(false));
+    }
+    duplicates
+}
 
-            // Only symlink or transform actual files
-            if path.is_file() {
-                let companion = companion_lua_path(&path);
-
-                let decision = if companion.exists() {
-                    lua_decision(lua, &companion, &path)?
-                } else {
-                    LuaDecision {
-                        include: true,
-                        rename_to: None,
-                        transform: None,
-                        link: false,
-                    }
-                };
+/// When several groups plan the same target, keep only the entry of the last group and
+/// report every entry that lost. Returns the number of collisions.
+fn resolve_collisions(groups: &mut [PlanGroup], opts: &Options) -> usize {
+    let mut owners: HashMap<PathBuf, (usize, PathBuf)> = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        for entry in &group.entries {
+            if entry.kind.claims_target() {
+                owners.insert(entry.target.clone(), (index, entry.source.clone()));
+            }
+        }
+    }
 
-                if !decision.include {
-                    if opts.dry_run {
-                        println!(
-                            "{} Skipped by lua: {}",
-                            opts.color.blue("ℹ"),
-                            shorten_home(&home.join(&rel_path))
-                        );
+    let mut collisions = 0;
+    for (index, group) in groups.iter_mut().enumerate() {
+        group.entries.retain(|entry| {
+            if !entry.kind.claims_target() {
+                return true;
+            }
+            match owners.get(&entry.target) {
+                Some((owner, winner)) if *owner != index => {
+                    collisions += 1;
+                    if opts.silent {
+                        return false;
                     }
-                    skips += 1;
-                    continue;
+                    say!(
+                        opts,
+                        "{} {} {} from {} is overridden by {}",
+                        opts.color.yellow("⚠"),
+                        opts.color.yellow("collision"),
+                        shorten_home(&entry.target, &opts.target),
+                        shorten_home(&entry.source, &opts.target),
+                        shorten_home(winner, &opts.target)
+                    );
+                    false
                 }
+                _ => true,
+            }
+        });
+    }
+    collisions
+}
 
-                let target_rel_path = if let Some(new_name) = &decision.rename_to {
-                    rel_path.with_file_name(new_name)
-                } else {
-                    rel_path.to_path_buf()
-                };
-                let target = home.join(&target_rel_path);
-
-                // Handle transformed files (write/override)
-                if let Some(transformed_content) = &decision.transform {
-                    if !opts.dry_run
-                        && let Some(parent) = target.parent()
-                    {
-                        fs::create_dir_all(parent).with_context(|| {
-                            format!(
-                                "Failed to create parent directories for {}",
-                                target.display()
-                            )
-                        })?;
-                    }
+fn unstow(groups: &[PlanGroup], opts: &Options) -> Result<()> {
+    let mut totals = UnstowCounts::default();
+    for group in groups {
+        print_group_heading(group, groups.len(), opts);
+        for entry in &group.entries {
+            if interrupt::requested() {
+                break;
+            }
+            unstow_entry(entry, opts, &mut totals)?;
+        }
+    }
+    if interrupt::requested() {
+        say!(
+            opts,
+            "{} Interrupted, the remaining entries were not processed",
+            opts.color.yellow("⚠")
+        );
+    }
+    let removed_dirs = manifest::remove_empty_dirs(&totals.gone, opts, &mut totals.journal)?;
+    journal::append(&totals.journal, opts)?;
+    hash_cache::save(opts)?;
+    manifest::update(groups, &BTreeSet::new(), opts)?;
+    let removed_label = if opts.dry_run { "to remove" } else { "removed" };
+    let removed_dirs = if removed_dirs > 0 {
+        format!(
+            ", {} empty dirs {removed_label}",
+            opts.color.green(&removed_dirs.to_string())
+        )
+    } else {
+        String::new()
+    };
+    summary!(
+        opts,
+        "{}Summary: {} {}, {} kept, {} missing{removed_dirs}{}",
+        summary_gap(opts),
+        opts.color.green(&totals.removed.to_string()),
+        removed_label,
+        opts.color.yellow(&totals.kept.to_string()),
+        opts.color.blue(&totals.missing.to_string()),
+        interrupted_note(),
+    );
+    if opts.stats {
+        stats::print(opts);
+    }
+    Ok(())
+}
 
-                    if target.is_dir() {
-                        println!(
-                            "{} Conflict: cannot write file, target is a directory: {}",
-                            opts.color.red("✗"),
-                            shorten_home(&target)
-                        );
-                        conflicts += 1;
-                        continue;
-                    }
+fn process(roots: &[PathBuf], packages: &[String], opts: &Options) -> Result<ExitCode> {
+    if opts.command.is_none() {
+        interrupt::install();
+    }
+    // Runs that may change the target hold the lock until they return
+    let _lock = if opts.dry_run || matches!(opts.command, Some(Command::Orphans { .. })) {
+        None
+    } else {
+        Some(lock::acquire(opts)?)
+    };
+    // Undoing replays the journal and needs no plan
+    if let Some(Command::Undo { list }) = &opts.command {
+        journal::undo(*list, opts)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    hash_cache::load(opts)?;
+    let lua = new_lua(opts)?;
 
-                    let content_is_identical = target.is_file()
-                        && fs::read(&target).ok().as_deref()
-                            == Some(transformed_content.as_bytes());
-
-                    if content_is_identical {
-                        planned += 1;
-                        if opts.dry_run || opts.verbose {
-                            println!(
-                                "{} Would write (already in place) {} from {}",
-                                opts.color.green("✔"),
-                                shorten_home(&target),
-                                shorten_home(&path)
-                            );
-                        }
-                        continue;
-                    }
+    let mut groups = plan_groups(roots, packages, &lua, opts)?;
+    let duplicates = drop_duplicate_targets(&mut groups, opts);
+    let collisions = resolve_collisions(&mut groups, opts);
+    match &opts.command {
+        Some(Command::Orphans { json, max_depth }) => {
+            orphans::run(&groups, *json, *max_depth, opts)?
+        }
+        Some(Command::Status) => status::run(&groups, collisions, opts)?,
+        Some(Command::Add { path, copy }) => add::run(&groups, &roots[0], path, *copy, opts)?,
+        Some(Command::Verify { .. }) => return verify::run(&groups, opts),
+        Some(Command::Which { path }) => which::run(&groups, path, opts)?,
+        Some(Command::Diff { path }) => return diff::run(&groups, path, opts),
+        Some(Command::Edit { path }) => {
+            let Some(source) = edit::run(&groups, path, opts)? else {
+                return Ok(ExitCode::SUCCESS);
+            };
+            // Plan again so a transform sees the edited source, then place just that file
+            let mut groups = plan_groups(roots, packages, &lua, opts)?;
+            let duplicates = drop_duplicate_targets(&mut groups, opts);
+            resolve_collisions(&mut groups, opts);
+            for group in &mut groups {
+                group.entries.retain(|entry| entry.source == source);
+            }
+            return stow(&groups, 0, duplicates, opts);
+        }
+        Some(Command::Undo { .. }) => unreachable!("undo returns before planning"),
+        Some(Command::Init { .. }) => unreachable!("init returns before reading the root"),
+        None if opts.unstow => {
+            unstow(&groups, opts)?;
+            if interrupt::requested() {
+                return Ok(ExitCode::from(interrupt::EXIT_CODE));
+            }
+        }
+        None => match &opts.emit_script {
+            Some(path) => script::run(&groups, path, opts)?,
+            None => return stow(&groups, collisions, duplicates, opts),
+        },
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Keep what a stow run did: its journal entry for `undo`, the hash cache and the
+/// manifest. Runs that fail partway are recorded too.
+fn record_run(groups: &[PlanGroup], totals: &WalkCounts, opts: &Options) -> Result<()> {
+    journal::append(&totals.journal, opts)?;
+    hash_cache::save(opts)?;
+    manifest::update(groups, &totals.new_dirs, opts)
+}
 
-                    let target_existed = target.exists();
-                    if opts.dry_run {
-                        let action = if target_existed { "overwrite" } else { "write" };
-                        println!(
-                            "{} Would {action} transformed file {} from {}",
-                            opts.color.green("✔"),
-                            shorten_home(&target),
-                            shorten_home(&path)
-                        );
+/// Link, copy, write and inject every planned entry, group by group.
+fn stow(
+    groups: &[PlanGroup],
+    collisions: usize,
+    duplicates: usize,
+    opts: &Options,
+) -> Result<ExitCode> {
+    let mut totals = WalkCounts {
+        duplicates,
+        ..WalkCounts::default()
+    };
+    let mut package_totals: Vec<(String, WalkCounts)> = Vec::new();
+    let mut sections = grouped::Sections::default();
+    let mut dir_totals: Vec<(String, report::Tally)> = Vec::new();
+    let entry_count = groups.iter().map(|group| group.entries.len()).sum();
+    let mut progress = progress::Progress::new(entry_count, opts);
+    for group in groups {
+        if interrupt::requested() {
+            break;
+        }
+        if !opts.group_output {
+            print_group_heading(group, groups.len(), opts);
+        }
+        // Directories seen in earlier groups are not announced again
+        let mut counts = WalkCounts {
+            new_dirs: totals.new_dirs.clone(),
+            ..WalkCounts::default()
+        };
+        let mut outcomes = if opts.jobs > 1 {
+            jobs::run(&group.entries, opts)
+        } else {
+            Vec::new()
+        };
+        for (index, entry) in group.entries.iter().enumerate() {
+            let outcome = outcomes.get_mut(index).and_then(Option::take);
+            // After Ctrl-C only entries the pool already stowed are still reported
+            if outcome.is_none() && interrupt::requested() {
+                continue;
+            }
+            let conflicts = counts.conflicts;
+            let before = report::Tally::of(&counts);
+            let (result, text) = match outcome {
+                // Stowed on the pool: report it as if it had been stowed right here
+                Some(outcome) => {
+                    counts.add(&outcome.counts);
+                    if opts.group_output {
+                        (outcome.result, outcome.text)
                     } else {
-                        fs::write(&target, transformed_content).with_context(|| {
-                            format!("Failed to write transformed file {}", target.display())
-                        })?;
-                        let action = if target_existed { "Overwrote" } else { "Wrote" };
-                        println!(
-                            "{} {action} transformed file {} from {}",
-                            opts.color.green("✔"),
-                            shorten_home(&target),
-                            shorten_home(&path)
-                        );
+                        grouped::out(&outcome.text);
+                        (outcome.result, String::new())
                     }
-                    planned += 1;
-                    continue;
                 }
-
-                // Handle symlinks via shared helper
-                let content_matches = {
-                    let is_symlink = target
-                        .symlink_metadata()
-                        .ok()
-                        .is_some_and(|m| m.file_type().is_symlink());
-                    target.is_file()
-                        && !is_symlink
-                        && path.is_file()
-                        && fs::read(&target).ok() == fs::read(&path).ok()
-                };
-
-                match handle_symlink(&path, &target, "", opts, content_matches)? {
-                    SymlinkResult::Planned => planned += 1,
-                    SymlinkResult::Conflict => conflicts += 1,
-                    SymlinkResult::Override => {
-                        planned += 1;
-                        overrides += 1;
+                None if opts.group_output => grouped::hold(|| stow_entry(entry, opts, &mut counts)),
+                None => (stow_entry(entry, opts, &mut counts), String::new()),
+            };
+            progress.tick(entry, opts);
+            if opts.summary_by_dir {
+                let dir = top_level_dir(entry, group);
+                let after = report::Tally::of(&counts);
+                match dir_totals.iter_mut().find(|(name, _)| *name == dir) {
+                    Some((_, tally)) => tally.add(before, after),
+                    None => {
+                        let mut tally = report::Tally::default();
+                        tally.add(before, after);
+                        dir_totals.push((dir, tally));
+                    }
+                }
+            }
+            let record = match result {
+                Ok(())
+                    if opts.output != Output::Text
+                        || opts.report.is_some()
+                        || opts.group_output =>
+                {
+                    report::record(entry, before, report::Tally::of(&counts), opts)
+                }
+                _ => None,
+            };
+            if let Some(record) = &record {
+                if matches!(opts.output, Output::Ndjson | Output::Porcelain) {
+                    report::emit(record, opts)?;
+                }
+                if opts.output == Output::Json || opts.report.is_some() {
+                    counts.records.push(record.clone());
+                }
+            }
+            let result = result.and_then(|()| {
+                if entry.conflict_policy(opts) == ConflictPolicy::Fail
+                    && counts.conflicts > conflicts
+                {
+                    let origin = match entry.on_conflict {
+                        Some(_) => "on_conflict = \"fail\"",
+                        None => "--on-conflict=fail",
+                    };
+                    bail!(
+                        "Conflict at {} ({origin})",
+                        shorten_home(&entry.target, &opts.target)
+                    );
+                }
+                Ok(())
+            });
+            if opts.group_output {
+                match &result {
+                    Ok(()) => sections.add(record.as_ref(), entry.target.clone(), text),
+                    Err(e) => {
+                        sections.add_error(entry.target.clone(), text, e, opts);
+                        std::mem::take(&mut sections).print(opts);
                     }
                 }
             }
+            if let Err(e) = result {
+                if matches!(opts.output, Output::Ndjson | Output::Porcelain) {
+                    report::emit_error(entry, &e, opts)?;
+                }
+                totals.add(&counts);
+                if let Some(path) = &opts.report {
+                    report::write_markdown(path, &totals, collisions, Some((entry, &e)), opts)?;
+                }
+                // What was done before the error, and by the pool ahead of it, can be undone
+                for outcome in outcomes.iter_mut().filter_map(Option::take) {
+                    totals.journal.extend(outcome.counts.journal);
+                }
+                record_run(groups, &totals, opts)?;
+                return Err(e);
+            }
         }
-        Ok(WalkCounts {
-            planned,
-            conflicts,
-            skips,
-            overrides,
-        })
+        totals.add(&counts);
+        if let Some(package) = &group.package {
+            match package_totals.iter_mut().find(|(name, _)| name == package) {
+                Some((_, existing)) => existing.add(&counts),
+                None => package_totals.push((package.clone(), counts)),
+            }
+        }
+    }
+    drop(progress);
+    sections.print(opts);
+    // An interrupted run records what it did but removes nothing more
+    let interrupted = interrupt::requested();
+    if interrupted {
+        say!(
+            opts,
+            "{} Interrupted, the remaining entries were not processed",
+            opts.color.yellow("⚠")
+        );
+    }
+    if opts.prune && !interrupted {
+        prune(groups, opts, &mut totals)?;
+    }
+    if opts.prune_stale && !interrupted {
+        manifest::prune_stale(groups, opts, &mut totals)?;
+    }
+    if (opts.prune || opts.prune_stale) && !interrupted {
+        totals.removed_dirs = manifest::remove_empty_dirs(&totals.gone, opts, &mut totals.journal)?;
     }
+    record_run(groups, &totals, opts)?;
 
-    let totals = walk_dir(root, Path::new(""), &home, &lua, opts)?;
-    let conflicts_label = if totals.conflicts == 1 {
-        "conflict"
+    if opts.output != Output::Text {
+        report::print(&totals, collisions, opts)?;
+    }
+    let planned_label = if opts.dry_run { "planned" } else { "linked" };
+    if !package_totals.is_empty() {
+        say!(opts, "\nPackages:");
+        for (package, counts) in &package_totals {
+            say!(
+                opts,
+                "  {package}: {} {planned_label}, {} {}, {} skipped by lua",
+                opts.color.green(&counts.planned.to_string()),
+                opts.color.red(&counts.conflicts.to_string()),
+                conflicts_label(counts.conflicts),
+                opts.color.blue(&counts.skips.to_string()),
+            );
+        }
+    }
+    if opts.summary_by_dir {
+        print_dir_totals(&mut dir_totals, opts);
+    }
+    print_summary(&totals, collisions, groups.len(), opts);
+    if opts.stats {
+        stats::print(opts);
+    }
+    if let Some(path) = &opts.report {
+        report::write_markdown(path, &totals, collisions, None, opts)?;
+    }
+    if interrupted {
+        return Ok(ExitCode::from(interrupt::EXIT_CODE));
+    }
+    if opts.fail_on_conflict && totals.conflicts > 0 {
+        return Ok(ExitCode::from(verify::CONFLICTS));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The first component of `entry`'s source below the directory of its `group`, or `.`
+/// for files directly inside it (`--summary-by-dir`).
+fn top_level_dir(entry: &PlanEntry, group: &PlanGroup) -> String {
+    let rel = entry
+        .source
+        .strip_prefix(&group.dir)
+        .unwrap_or(&entry.source);
+    let mut components = rel.components();
+    match (components.next(), components.next()) {
+        (Some(first), Some(_)) => first.as_os_str().to_string_lossy().into_owned(),
+        // A linked directory belongs to itself, not to the root
+        (Some(first), None) if matches!(entry.kind, EntryKind::Dir | EntryKind::Folded) => {
+            first.as_os_str().to_string_lossy().into_owned()
+        }
+        _ => ".".to_string(),
+    }
+}
+
+/// Print the counts of each top-level directory of the root as an aligned table
+/// (`--summary-by-dir`).
+fn print_dir_totals(dir_totals: &mut [(String, report::Tally)], opts: &Options) {
+    dir_totals.sort_by(|a, b| a.0.cmp(&b.0));
+    let planned_label = if opts.dry_run { "planned" } else { "linked" };
+    let width = dir_totals
+        .iter()
+        .map(|(dir, _)| dir.chars().count())
+        .chain(["directory".len()])
+        .max()
+        .unwrap_or_default();
+    say!(opts);
+    say!(
+        opts,
+        "  {:<width$}  {planned_label:>7}  in place  conflicts  skipped",
+        "directory"
+    );
+    for (dir, tally) in dir_totals.iter() {
+        say!(
+            opts,
+            "  {dir:<width$}  {:>7}  {:>8}  {:>9}  {:>7}",
+            tally.planned,
+            tally.in_place,
+            tally.conflicts,
+            tally.skips
+        );
+    }
+}
+
+/// The blank line before the summary, left out with `--quiet` and `--summary-only` so
+/// a clean run prints the summary line alone.
+fn summary_gap(opts: &Options) -> &'static str {
+    if opts.detail == Detail::Full {
+        "\n"
     } else {
-        "conflicts"
-    };
+        ""
+    }
+}
+
+/// Print the closing summary line of a run over `group_count` groups.
+fn print_summary(totals: &WalkCounts, collisions: usize, group_count: usize, opts: &Options) {
     let planned_label = if opts.dry_run { "planned" } else { "linked" };
     let skipped_label = "skipped by lua";
-    println!(
-        "\nSummary: {} {}, {} {}, {} {}, {} overrides",
+    let relinked = if opts.restow {
+        format!(
+            ", {} relinked",
+            opts.color.green(&totals.relinked.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let adopted = if opts.adopt {
+        format!(
+            ", {} adopted",
+            opts.color.green(&totals.adopted.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let excluded = if opts.excludes.is_empty() {
+        String::new()
+    } else {
+        format!(
+            ", {} excluded",
+            opts.color.blue(&totals.excluded.to_string())
+        )
+    };
+    let filtered = if opts.only.is_empty() {
+        String::new()
+    } else {
+        format!(
+            ", {} outside --only",
+            opts.color.blue(&totals.filtered.to_string())
+        )
+    };
+    let ignored = if totals.ignored > 0 {
+        format!(", {} ignored", opts.color.blue(&totals.ignored.to_string()))
+    } else {
+        String::new()
+    };
+    let folded = if totals.folded > 0 {
+        let label = if totals.folded == 1 {
+            "folded dir"
+        } else {
+            "folded dirs"
+        };
+        format!(", {} {label}", opts.color.green(&totals.folded.to_string()))
+    } else {
+        String::new()
+    };
+    let unfolded = if totals.unfolded > 0 {
+        let label = if totals.unfolded == 1 {
+            "unfolded dir"
+        } else {
+            "unfolded dirs"
+        };
+        format!(
+            ", {} {label}",
+            opts.color.green(&totals.unfolded.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let conflict_kinds = if totals.conflict_kinds.is_empty() {
+        String::new()
+    } else {
+        let kinds: Vec<String> = totals
+            .conflict_kinds
+            .iter()
+            .map(|(kind, count)| format!("{count} {}", kind.label(*count)))
+            .collect();
+        format!(" ({})", kinds.join(", "))
+    };
+    let in_place = if totals.in_place > 0 {
+        format!(
+            ", {} already in place",
+            opts.color.green(&totals.in_place.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let dangling = if totals.dangling > 0 {
+        let label = if opts.dry_run {
+            "dangling to replace"
+        } else {
+            "dangling replaced"
+        };
+        format!(
+            ", {} {label}",
+            opts.color.yellow(&totals.dangling.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let pruned = if totals.pruned > 0 {
+        let label = if opts.dry_run { "to prune" } else { "pruned" };
+        format!(
+            ", {} {label}",
+            opts.color.yellow(&totals.pruned.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let duplicates = if totals.duplicates > 0 {
+        format!(
+            ", {} duplicate targets",
+            opts.color.red(&totals.duplicates.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let stale = if totals.stale > 0 {
+        let label = if opts.dry_run {
+            "stale to remove"
+        } else {
+            "stale removed"
+        };
+        format!(", {} {label}", opts.color.yellow(&totals.stale.to_string()))
+    } else {
+        String::new()
+    };
+    let removed_dirs = if totals.removed_dirs > 0 {
+        let label = if opts.dry_run {
+            "empty dirs to remove"
+        } else {
+            "empty dirs removed"
+        };
+        format!(
+            ", {} {label}",
+            opts.color.yellow(&totals.removed_dirs.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let new_dirs = if totals.new_dirs.is_empty() {
+        String::new()
+    } else {
+        let label = if opts.dry_run {
+            "dirs to create"
+        } else {
+            "dirs created"
+        };
+        format!(
+            ", {} {label}",
+            opts.color.green(&totals.new_dirs.len().to_string())
+        )
+    };
+    let collisions = if group_count > 1 {
+        format!(
+            ", {} collisions",
+            opts.color.yellow(&collisions.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let copied = if totals.copied > 0 {
+        let label = if opts.dry_run { "to copy" } else { "copied" };
+        format!(", {} {label}", opts.color.green(&totals.copied.to_string()))
+    } else {
+        String::new()
+    };
+    let backups = if totals.backups > 0 {
+        let label = if opts.dry_run {
+            "to back up"
+        } else {
+            "backed up"
+        };
+        format!(
+            ", {} {label}",
+            opts.color.green(&totals.backups.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let overwritten = if totals.overwritten > 0 {
+        let label = if opts.dry_run {
+            "to overwrite"
+        } else {
+            "overwritten"
+        };
+        format!(
+            ", {} {label}",
+            opts.color.yellow(&totals.overwritten.to_string())
+        )
+    } else {
+        String::new()
+    };
+    let forced = if totals.forced > 0 {
+        let label = if opts.dry_run { "to force" } else { "forced" };
+        format!(
+            ", {} {label}",
+            opts.color.yellow(&totals.forced.to_string())
+        )
+    } else {
+        String::new()
+    };
+    summary!(
+        opts,
+        "{}Summary: {} {}{in_place}, {} {}{conflict_kinds}, {} {}, {} overrides{copied}{backups}{overwritten}{forced}{relinked}{adopted}{excluded}{filtered}{ignored}{folded}{unfolded}{dangling}{pruned}{stale}{removed_dirs}{new_dirs}{collisions}{duplicates}{}",
+        summary_gap(opts),
         opts.color.green(&totals.planned.to_string()),
         planned_label,
         opts.color.red(&totals.conflicts.to_string()),
-        conflicts_label,
+        conflicts_label(totals.conflicts),
         opts.color.blue(&totals.skips.to_string()),
         skipped_label,
         opts.color.green(&totals.overrides.to_string()),
+        interrupted_note(),
     );
-    Ok(())
 }
 
-fn main() -> Result<()> {
-    // This tool is intended for macOS only
-    #[cfg(not(target_os = "macos"))]
-    compile_error!("This tool only supports macOS (target_os=macos)");
-    #[derive(Parser, Debug)]
-    #[command(author, version, about)]
-    struct Cli {
-        /// Root directory that contains dotfiles to stow
-        #[arg(short, long, default_value = "~/Developer/dotfiles/dotty/")]
-        root: String,
-        /// Dry run: only print operations, do not modify filesystem
-        #[arg(long)]
-        dry_run: bool,
-        /// If set, when a conflict target has identical content, delete it and create the symlink
-        #[arg(long)]
-        override_identical: bool,
-        /// Verbose output
-        #[arg(long)]
-        verbose: bool,
-        /// Disable colored output
-        #[arg(long)]
-        no_color: bool,
+/// The note closing the summary of a run stopped by Ctrl-C.
+fn interrupted_note() -> &'static str {
+    if interrupt::requested() {
+        " (interrupted)"
+    } else {
+        ""
     }
+}
 
-    let cli = Cli::parse();
-    let root_path = expand_tilde(&cli.root)?;
-    if !root_path.is_dir() {
-        bail!("Root directory is not a directory: {}", root_path.display());
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Root directory that contains dotfiles to stow [default: ~/Developer/dotfiles/dotty/].
+    /// Repeat or separate with commas to stow several roots, later roots win on collisions
+    #[arg(short, long, value_delimiter = ',', global = true)]
+    root: Vec<String>,
+    /// Only stow these top-level directories (packages) of the root, GNU stow style.
+    /// Each package directory is transparent: its children map directly under the target
+    packages: Vec<String>,
+    /// Directory to create links in [default: ~]
+    #[arg(short, long, global = true)]
+    target: Option<String>,
+    /// Dry run: only print operations, do not modify filesystem
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Make changes even when a config file or DOTTY_DRY_RUN asks for a dry run
+    #[arg(long, global = true, conflicts_with = "dry_run")]
+    no_dry_run: bool,
+    /// If set, when a conflict target has identical content, delete it and create the symlink
+    #[arg(long)]
+    override_identical: bool,
+    /// Turn off an `override_identical` set in a config file
+    #[arg(long, conflicts_with = "override_identical")]
+    no_override_identical: bool,
+    /// Verbose output
+    #[arg(long, global = true)]
+    verbose: bool,
+    /// Turn off a `verbose` set in a config file
+    #[arg(long, global = true, conflicts_with = "verbose")]
+    no_verbose: bool,
+    /// When another run is changing the target, wait for it to finish instead of failing
+    #[arg(long, global = true)]
+    wait: bool,
+    /// Print only conflicts, errors and the summary, no lines for files that are
+    /// linked, written, already in place or skipped
+    #[arg(long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print nothing but the summary line, not even conflicts; their count is in the
+    /// summary
+    #[arg(long, conflicts_with_all = ["quiet", "verbose"])]
+    summary_only: bool,
+    /// Print the lines about each file after the walk, in sections (already in place,
+    /// new links, written, conflicts, skipped, errors) sorted by target
+    #[arg(long, conflicts_with_all = ["unstow", "interactive"])]
+    group_output: bool,
+    /// Print the planned, in place, conflict and skip counts of each top-level directory
+    /// of the root before the summary; files directly in the root count under "."
+    #[arg(long, conflicts_with = "unstow")]
+    summary_by_dir: bool,
+    /// Print after the summary where the run spent its time: wall time, files
+    /// examined, Lua evaluations, content comparisons and filesystem changes
+    #[arg(long)]
+    stats: bool,
+    /// Transform and stow up to N files at once, each worker with its own Lua state;
+    /// their lines are still printed in plan order
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        conflicts_with = "interactive"
+    )]
+    jobs: usize,
+    /// Hash every compared file again instead of trusting hashes cached for files whose
+    /// size and modification time are unchanged
+    #[arg(long)]
+    no_cache: bool,
+    /// Flush each transformed, copied or injected file and its directory to disk before
+    /// going on, so they survive a power loss right after the run
+    #[arg(long)]
+    fsync: bool,
+    /// Disable colored output
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Remove symlinks pointing into the root and unmodified transformed files instead of creating them
+    #[arg(long, conflicts_with = "restow")]
+    unstow: bool,
+    /// Replace existing symlinks that point into the root with links to the current source
+    #[arg(long)]
+    restow: bool,
+    /// Move conflicting regular files from the target into the root, then link them
+    #[arg(long, conflicts_with = "unstow")]
+    adopt: bool,
+    /// After linking, remove symlinks that point into the root at files that no longer exist
+    #[arg(long, conflicts_with = "unstow")]
+    prune: bool,
+    /// After linking, remove targets placed by earlier runs (per the manifest) whose
+    /// source is gone or no longer planned
+    #[arg(long, conflicts_with = "unstow")]
+    prune_stale: bool,
+    /// Exit with code 2 after the summary when any conflict was found, also with --dry-run
+    #[arg(long, conflicts_with = "unstow")]
+    fail_on_conflict: bool,
+    /// Link files one by one instead of folding directories into a single symlink,
+    /// unfolding directory links from earlier runs
+    #[arg(long)]
+    no_folding: bool,
+    /// Copy files instead of symlinking them, e.g. when the root lives on an external volume
+    #[arg(long)]
+    copy: bool,
+    /// Create hard links instead of symlinks (source and target must share a device)
+    #[arg(long, conflicts_with = "copy")]
+    hardlink: bool,
+    /// Create symlinks relative to the link's directory instead of absolute ones
+    #[arg(long)]
+    relative: bool,
+    /// Allow a companion's `target` to point outside of the target directory
+    #[arg(long)]
+    allow_outside_home: bool,
+    /// Place files even when a symlinked parent directory leads out of the target directory
+    #[arg(long)]
+    allow_escaped_targets: bool,
+    /// Treat the target as case-sensitive instead of probing it
+    #[arg(long)]
+    assume_case_sensitive: bool,
+    /// Give companion scripts the full Lua standard library (io, os, debug)
+    #[arg(long)]
+    unsafe_lua: bool,
+    /// Show what companion scripts print, prefixed with their file (implied by --verbose)
+    #[arg(long)]
+    trace_lua: bool,
+    /// Abort a companion or transform that runs longer than this many seconds
+    #[arg(long, value_name = "SECS", default_value_t = 5.0)]
+    lua_timeout: f64,
+    /// Refuse to transform source files larger than this, in bytes or with a K, M or G
+    /// suffix
+    #[arg(long, value_name = "SIZE", default_value = "4M")]
+    max_transform_size: String,
+    /// Skip root-relative paths matching this glob (repeatable), e.g. 'scripts/**' or '*.md'
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Only process root-relative paths matching this glob (repeatable), e.g. '.config/nvim/**'
+    #[arg(long, value_name = "GLOB")]
+    only: Vec<String>,
+    /// What to do when a target is in the way: report it and go on (skip), abort the run
+    /// (fail), move it into the backup dir (backup) or remove it (overwrite)
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = ConflictPolicy::Skip)]
+    on_conflict: ConflictPolicy,
+    /// Replace conflicting files and symlinks with the planned link or file, moving them
+    /// into the backup dir first. Directories are never replaced
+    #[arg(long, conflicts_with = "on_conflict")]
+    force: bool,
+    /// Ask what to do at each conflict: skip, overwrite, backup, show a diff or quit.
+    /// Needs a terminal on stdin and is ignored with --dry-run
+    #[arg(long, conflicts_with_all = ["on_conflict", "force"])]
+    interactive: bool,
+    /// Show a unified diff for each conflict whose content differs from the source
+    #[arg(long)]
+    diff: bool,
+    /// Show a unified diff of what changes when a transformed file is rewritten
+    /// (also shown with --verbose)
+    #[arg(long)]
+    show_changes: bool,
+    /// Show at most this many lines of each diff
+    #[arg(long, value_name = "N", default_value_t = 40)]
+    diff_lines: usize,
+    /// Move files that dotty replaces here, keeping their path below the target
+    /// [default: ~/.local/state/dotty/backups/<timestamp>/]
+    #[arg(long, value_name = "DIR")]
+    backup_dir: Option<String>,
+    /// Set a variable for companions, read with dotty.var("KEY") (repeatable)
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    vars: Vec<String>,
+    /// Also link junk files that are ignored by default (.DS_Store, .git, *.swp, *~, .#*)
+    #[arg(long)]
+    no_default_ignores: bool,
+    /// Print a single JSON document with a record per entry and the summary counts
+    /// (json), or one JSON event per line as entries are processed (ndjson), instead
+    /// of the usual lines
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        default_value_t = Output::Text,
+        conflicts_with_all = ["unstow", "interactive"]
+    )]
+    output: Output,
+    /// Print one tab-separated line per file (KEYWORD, target, source) and a closing
+    /// SUMMARY line instead of the usual lines; the format is stable across releases
+    #[arg(long, conflicts_with_all = ["output", "unstow", "interactive"])]
+    porcelain: bool,
+    /// Write the plan as a POSIX shell script to this file (or stdout with -) instead
+    /// of stowing, for machines where dotty cannot run. Implies --dry-run
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["unstow", "output", "porcelain"])]
+    emit_script: Option<String>,
+    /// Also write a Markdown report of the run to this file: what was linked, written,
+    /// already in place, conflicting, skipped or failed, and the summary counts
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["unstow", "emit_script"])]
+    report: Option<String>,
+    /// Do not read ~/.config/dotty/dotty.toml or the root's dotty.toml
+    #[arg(long, global = true)]
+    no_config: bool,
+}
+
+/// A Lua state for evaluating companions: with the `dotty` table, sandboxed unless
+/// `--unsafe-lua`, and held to `--lua-timeout`.
+fn new_lua(opts: &Options) -> Result<Lua> {
+    let lua = if opts.unsafe_lua {
+        // SAFETY: the user asked for the debug library, which can break Lua's invariants
+        unsafe { Lua::unsafe_new() }
+    } else {
+        Lua::new()
+    };
+    lua_api::install(&lua, &opts.vars)?;
+    if !opts.unsafe_lua {
+        lua_api::sandbox(&lua)?;
+    }
+    lua_api::set_timeout(&lua, opts.lua_timeout);
+    // Traced lines would break machine-readable output
+    lua_api::set_trace(
+        &lua,
+        (opts.verbose || opts.trace_lua) && opts.output == Output::Text,
+    );
+    Ok(lua)
+}
+
+/// Carry out the command `cli` asks for.
+fn run(cli: Cli) -> Result<ExitCode> {
+    // The root does not exist yet, so init needs none of the settings below
+    if let Some(Command::Init {
+        dir,
+        force,
+        with_config,
+    }) = &cli.command
+    {
+        let color = Colorize(atty::is(atty::Stream::Stdout) && !cli.no_color);
+        init::run(dir, *force, *with_config, &color)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let user_config = if cli.no_config {
+        Config::default()
+    } else {
+        Config::load(&expand_tilde(config::USER_CONFIG_PATH)?)?.unwrap_or_default()
+    };
+    // Settings taken from the environment, reported with --verbose
+    let mut from_env: Vec<String> = Vec::new();
+    let env_root = config::env_string("DOTTY_ROOT");
+    let env_target = config::env_string("DOTTY_TARGET");
+    let env_dry_run = config::env_bool("DOTTY_DRY_RUN")?;
+    let env_no_color = config::env_bool("DOTTY_NO_COLOR")?;
+
+    let roots = if !cli.root.is_empty() {
+        cli.root.clone()
+    } else if let Some(root) = env_root {
+        from_env.push(format!("root from DOTTY_ROOT={root}"));
+        root.split(',').map(str::to_string).collect()
+    } else if let Some(root) = &user_config.root {
+        vec![root.clone()]
+    } else {
+        vec![DEFAULT_ROOT.to_string()]
+    };
+    let mut root_paths = Vec::new();
+    let mut canonical_roots = Vec::new();
+    for root in &roots {
+        let root_path = expand_tilde(root)?;
+        if !root_path.is_dir() {
+            bail!("Root directory is not a directory: {}", root_path.display());
+        }
+        canonical_roots.push(
+            fs::canonicalize(&root_path)
+                .with_context(|| format!("Failed to resolve root {}", root_path.display()))?,
+        );
+        root_paths.push(root_path);
+    }
+
+    // Precedence: CLI flags, then the user config, then the configs of the roots
+    let mut root_config = Config::default();
+    if !cli.no_config {
+        for root_path in &root_paths {
+            if let Some(config) = Config::load_root(root_path)? {
+                root_config = config.or(root_config);
+            }
+        }
+    }
+    let config = user_config.or(root_config);
+
+    let target = match (cli.target, env_target) {
+        (Some(target), _) => target,
+        (None, Some(target)) => {
+            from_env.push(format!("target from DOTTY_TARGET={target}"));
+            target
+        }
+        (None, None) => config.target.unwrap_or_else(|| "~".to_string()),
+    };
+    let target_path = expand_tilde(&target)?;
+    if !target_path.is_dir() {
+        bail!(
+            "Target directory is not a directory: {}",
+            target_path.display()
+        );
+    }
+    // Stowing a root into itself would link its files onto themselves
+    let canonical_target = fs::canonicalize(&target_path)
+        .with_context(|| format!("Failed to resolve target {}", target_path.display()))?;
+    for root in &canonical_roots {
+        if *root == canonical_target {
+            bail!(
+                "Root {} is the target directory itself, so every file would be linked onto itself; pass the dotfiles directory as --root",
+                root.display()
+            );
+        }
+        if canonical_target.starts_with(root) {
+            bail!(
+                "Target directory {} is inside root {}, so the links would be stowed again",
+                canonical_target.display(),
+                root.display()
+            );
+        }
+    }
+    let lua_timeout = match Duration::try_from_secs_f64(cli.lua_timeout) {
+        Ok(timeout) if !timeout.is_zero() => timeout,
+        _ => bail!(
+            "--lua-timeout must be a positive number of seconds, got {}",
+            cli.lua_timeout
+        ),
+    };
+    let max_transform_size =
+        parse_size(&cli.max_transform_size).context("Invalid --max-transform-size")?;
+    if cli.jobs == 0 {
+        bail!("--jobs must be at least 1");
     }
+    let backup_dir = match &cli.backup_dir {
+        Some(dir) => expand_tilde(dir)?,
+        None => expand_tilde(backup::DEFAULT_BACKUP_ROOT)?.join(backup::timestamp()),
+    };
+    let mut vars = config.vars.unwrap_or_default();
+    vars.extend(parse_vars(&cli.vars)?);
     let stdout_is_tty = atty::is(atty::Stream::Stdout);
-    let color = Colorize(stdout_is_tty && !cli.no_color);
+    let stdin_is_tty = atty::is(atty::Stream::Stdin);
+    // Lets scripts and tests answer `--interactive` prompts through a pipe
+    let assume_tty = config::env_bool("DOTTY_ASSUME_TTY")?.unwrap_or(false);
+    let dry_run = match (cli_flag(cli.dry_run, cli.no_dry_run), env_dry_run) {
+        (Some(value), _) => value,
+        (None, Some(value)) => {
+            from_env.push(format!("dry_run from DOTTY_DRY_RUN ({value})"));
+            value
+        }
+        (None, None) => config.dry_run.unwrap_or(false),
+    };
+    let no_color = match env_no_color {
+        Some(value) if !cli.no_color => {
+            from_env.push(format!("no_color from DOTTY_NO_COLOR ({value})"));
+            value
+        }
+        _ => cli.no_color || !config.color.unwrap_or(true),
+    };
+    let output = if cli.porcelain {
+        Output::Porcelain
+    } else if cli.emit_script.as_deref() == Some("-") {
+        Output::Script
+    } else {
+        cli.output
+    };
+    if (output != Output::Text || cli.emit_script.is_some() || cli.report.is_some())
+        && cli.command.is_some()
+    {
+        bail!(
+            "--output, --porcelain, --emit-script and --report only apply to stowing, not to subcommands"
+        );
+    }
+    let color = Colorize(stdout_is_tty && !no_color && output == Output::Text);
+    let only = match &cli.command {
+        // Only the companion of the file being diffed needs to run
+        Some(Command::Diff { path }) if !diff::is_target_path(path) => {
+            vec![PathGlob::new(&glob::Pattern::escape(path))?]
+        }
+        _ => cli
+            .only
+            .iter()
+            .map(|glob| PathGlob::new(glob))
+            .collect::<Result<_>>()?,
+    };
     let opts = Options {
-        dry_run: cli.dry_run,
-        override_identical: cli.override_identical,
-        verbose: cli.verbose,
+        // Status reports like a dry run and must never change anything
+        dry_run: dry_run
+            || cli.emit_script.is_some()
+            || matches!(
+                cli.command,
+                Some(
+                    Command::Status
+                        | Command::Verify { .. }
+                        | Command::Which { .. }
+                        | Command::Diff { .. }
+                )
+            ),
+        silent: matches!(cli.command, Some(Command::Verify { quiet: true })),
+        group_output: cli.group_output,
+        summary_by_dir: cli.summary_by_dir,
+        stats: cli.stats,
+        jobs: cli.jobs,
+        no_cache: cli.no_cache,
+        fsync: cli.fsync,
+        wait: cli.wait,
+        // The counter shares the terminal with the colored lines on stdout
+        progress: (stdout_is_tty && atty::is(atty::Stream::Stderr) || assume_tty)
+            && !no_color
+            && output == Output::Text,
+        detail: if cli.summary_only {
+            Detail::SummaryOnly
+        } else if cli.quiet {
+            Detail::Quiet
+        } else {
+            Detail::Full
+        },
+        output,
+        emit_script: cli.emit_script,
+        report: cli.report,
+        command: cli.command,
+        override_identical: cli_flag(cli.override_identical, cli.no_override_identical)
+            .or(config.override_identical)
+            .unwrap_or(false),
+        verbose: cli_flag(cli.verbose, cli.no_verbose)
+            .or(config.verbose)
+            .unwrap_or(false),
+        unstow: cli.unstow,
+        restow: cli.restow,
+        adopt: cli.adopt,
+        prune: cli.prune,
+        prune_stale: cli.prune_stale,
+        fail_on_conflict: cli.fail_on_conflict,
+        no_folding: cli.no_folding,
+        copy: cli.copy,
+        hardlink: cli.hardlink,
+        relative: cli.relative,
+        allow_outside_home: cli.allow_outside_home,
+        allow_escaped_targets: cli.allow_escaped_targets,
+        case_sensitive: cli.assume_case_sensitive || case::sensitive(&target_path, dry_run)?,
+        unsafe_lua: cli.unsafe_lua,
+        lua_timeout,
+        max_transform_size,
+        trace_lua: cli.trace_lua,
+        vars,
+        backup_dir,
+        interactive: cli.interactive && !dry_run && (stdin_is_tty || assume_tty),
+        diff: cli.diff,
+        show_changes: cli.show_changes,
+        diff_lines: cli.diff_lines,
+        on_conflict: if cli.force {
+            ConflictPolicy::Force
+        } else {
+            cli.on_conflict
+        },
+        target: target_path,
+        roots: canonical_roots,
+        excludes: config
+            .excludes
+            .unwrap_or_default()
+            .iter()
+            .chain(&cli.exclude)
+            .map(|glob| PathGlob::new(glob))
+            .collect::<Result<_>>()?,
+        only,
+        default_ignores: if cli.no_default_ignores {
+            Vec::new()
+        } else {
+            filter::DEFAULT_IGNORES
+                .iter()
+                .map(|glob| PathGlob::new(glob))
+                .collect::<Result<_>>()?
+        },
         color,
     };
-    process(&root_path, opts)
+    if opts.verbose {
+        for setting in &from_env {
+            say!(opts, "{} {setting}", opts.color.blue("ℹ"));
+        }
+    }
+    process(&root_paths, &cli.packages, &opts)
+}
+
+fn main() -> ExitCode {
+    stats::start();
+    // This tool is intended for macOS only
+    #[cfg(not(target_os = "macos"))]
+    compile_error!("This tool only supports macOS (target_os=macos)");
+    let cli = Cli::parse();
+    // verify and diff report errors with their own exit code, and verify --quiet hides them
+    let quiet = matches!(cli.command, Some(Command::Verify { quiet: true }));
+    let error_code = match &cli.command {
+        Some(Command::Verify { .. } | Command::Diff { .. }) => ExitCode::from(verify::CONFLICTS),
+        _ => ExitCode::FAILURE,
+    };
+    match run(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            if !quiet {
+                eprintln!("Error: {e:?}");
+            }
+            error_code
+        }
+    }
 }
@@ -0,0 +1,305 @@
+//! The record of every target dotty has placed, kept across runs.
+
+use crate::journal::Action;
+use crate::shorten_home;
+use crate::status::{State, entry_state};
+use crate::{EntryKind, Options, PlanEntry, PlanGroup, WalkCounts};
+use crate::{expand_tilde, link_destination, link_points_into, paths_match, same_file};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the manifest lives.
+pub const MANIFEST_PATH: &str = "~/.local/state/dotty/manifest.json";
+
+/// Bumped when the format changes in a way older versions cannot read.
+const VERSION: u32 = 1;
+
+/// Every target dotty created or found in place, by absolute target path.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    #[serde(with = "crate::state_path::keys")]
+    pub entries: BTreeMap<PathBuf, ManifestEntry>,
+    /// Directories dotty created for its targets, which it may remove once empty.
+    #[serde(default, with = "crate::state_path::set")]
+    pub dirs: BTreeSet<PathBuf>,
+}
+
+/// One placed target.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub kind: ManifestKind,
+    #[serde(with = "crate::state_path")]
+    pub source: PathBuf,
+    /// `sha256:<hex>` of what was written, for transformed and copied files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// How a target was placed.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestKind {
+    Symlink,
+    Hardlink,
+    Transform,
+    Copy,
+    Inject,
+}
+
+impl ManifestKind {
+    fn word(&self) -> &'static str {
+        match self {
+            ManifestKind::Symlink => "symlink",
+            ManifestKind::Hardlink => "hard link",
+            ManifestKind::Transform => "transformed file",
+            ManifestKind::Copy => "copy",
+            ManifestKind::Inject => "injected block",
+        }
+    }
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            version: VERSION,
+            entries: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Read the manifest at `path`, or an empty one when there is none yet.
+    pub fn load(path: &Path) -> Result<Manifest> {
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse manifest {}", path.display()))
+    }
+
+    /// Write the manifest to `path` through a temporary file next to it, so an
+    /// interrupted run never leaves half a manifest behind.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut content =
+            serde_json::to_string_pretty(self).context("Failed to encode manifest")?;
+        content.push('\n');
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, content).with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, path)
+            .with_context(|| format!("Failed to replace manifest {}", path.display()))
+    }
+}
+
+/// `sha256:<hex>` of `content`.
+pub fn hash(content: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(content))
+}
+
+/// The manifest record for `entry` once it is in place.
+fn record(entry: &PlanEntry) -> Result<Option<ManifestEntry>> {
+    let (kind, hash) = match &entry.kind {
+        EntryKind::File | EntryKind::Dir | EntryKind::Folded => (ManifestKind::Symlink, None),
+        EntryKind::Hardlink => (ManifestKind::Hardlink, None),
+        EntryKind::Transform(content) => (ManifestKind::Transform, Some(hash(content))),
+        EntryKind::Copy => {
+            let content = fs::read(&entry.source)
+                .with_context(|| format!("Failed to read {}", entry.source.display()))?;
+            (ManifestKind::Copy, Some(hash(&content)))
+        }
+        EntryKind::Inject(_) => (ManifestKind::Inject, None),
+        _ => return Ok(None),
+    };
+    Ok(Some(ManifestEntry {
+        kind,
+        source: entry.source.clone(),
+        hash,
+    }))
+}
+
+/// Bring the manifest up to date after a real run: planned entries that are in place
+/// are recorded along with the directories in `new_dirs`, and targets and directories
+/// that no longer exist are dropped. Everything else recorded by earlier runs is kept.
+/// Nothing is written with `--dry-run`.
+pub fn update(groups: &[PlanGroup], new_dirs: &BTreeSet<PathBuf>, opts: &Options) -> Result<()> {
+    if opts.dry_run {
+        return Ok(());
+    }
+    let path = expand_tilde(MANIFEST_PATH)?;
+    let mut manifest = Manifest::load(&path)?;
+    for entry in groups.iter().flat_map(|group| &group.entries) {
+        match entry_state(entry, opts)? {
+            Some(State::Linked | State::Identical) => {
+                if let Some(record) = record(entry)? {
+                    manifest.entries.insert(entry.target.clone(), record);
+                }
+            }
+            _ if fs::symlink_metadata(&entry.target).is_err() => {
+                manifest.entries.remove(&entry.target);
+            }
+            _ => {}
+        }
+    }
+    // Links that were unfolded or replaced are no longer dotty's
+    manifest.entries.retain(|target, record| match record.kind {
+        ManifestKind::Symlink => target.is_symlink(),
+        _ => fs::symlink_metadata(target).is_ok(),
+    });
+    manifest.dirs.extend(new_dirs.iter().cloned());
+    manifest
+        .dirs
+        .retain(|dir| dir.is_dir() && !dir.is_symlink());
+    manifest.version = VERSION;
+    manifest.save(&path)?;
+    if opts.verbose {
+        progress!(
+            opts,
+            "{} Updated manifest {}",
+            opts.color.blue("ℹ"),
+            shorten_home(&path, &opts.target)
+        );
+    }
+    Ok(())
+}
+
+/// After links were removed (`gone`), remove the directories dotty created that are now
+/// empty, deepest first, walking up until a directory that is not empty or that dotty did
+/// not create. A dry run lists them instead. Returns how many there were.
+pub fn remove_empty_dirs(
+    gone: &BTreeSet<PathBuf>,
+    opts: &Options,
+    journal: &mut Vec<Action>,
+) -> Result<usize> {
+    let manifest = Manifest::load(&expand_tilde(MANIFEST_PATH)?)?;
+    let mut candidates: Vec<&Path> = gone
+        .iter()
+        .flat_map(|path| {
+            path.ancestors()
+                .skip(1)
+                .take_while(|dir| manifest.dirs.contains(*dir))
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    candidates.sort_by_key(|dir| Reverse(dir.components().count()));
+
+    let mut gone = gone.clone();
+    let mut removed = 0;
+    for dir in candidates {
+        // A dry run counts what it would have removed as gone already
+        let empty = fs::read_dir(dir).is_ok_and(|mut entries| {
+            entries.all(|entry| entry.is_ok_and(|e| gone.contains(&e.path())))
+        });
+        if !empty || dir.is_symlink() {
+            continue;
+        }
+        if opts.dry_run {
+            progress!(
+                opts,
+                "{} Would remove empty directory {}/",
+                opts.color.green("✔"),
+                shorten_home(dir, &opts.target)
+            );
+        } else {
+            fs::remove_dir(dir).with_context(|| format!("Failed to remove {}", dir.display()))?;
+            journal.push(Action::Rmdir {
+                path: dir.to_path_buf(),
+            });
+            progress!(
+                opts,
+                "{} Removed empty directory {}/",
+                opts.color.green("✔"),
+                shorten_home(dir, &opts.target)
+            );
+        }
+        gone.insert(dir.to_path_buf());
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Whether `target` still is what dotty placed for `record`: a link to its source, a hard
+/// link of it, or a file with the recorded content. Injected blocks never qualify.
+fn looks_placed(target: &Path, record: &ManifestEntry, opts: &Options) -> bool {
+    match record.kind {
+        ManifestKind::Symlink => {
+            link_points_into(target, &opts.roots)
+                && link_destination(target).is_some_and(|dest| paths_match(&dest, &record.source))
+        }
+        ManifestKind::Hardlink => !target.is_symlink() && same_file(target, &record.source),
+        ManifestKind::Transform | ManifestKind::Copy => {
+            !target.is_symlink()
+                && fs::read(target).ok().map(|content| hash(&content)) == record.hash
+        }
+        ManifestKind::Inject => false,
+    }
+}
+
+/// Remove targets the manifest records from earlier runs that the plan no longer places,
+/// because their source was deleted, renamed or is skipped by its companion
+/// (`--prune-stale`). Only sources below the walked directories count, and none hidden
+/// by `--only` or `--exclude`. A target is only removed while it still looks the way
+/// dotty left it.
+pub fn prune_stale(groups: &[PlanGroup], opts: &Options, counts: &mut WalkCounts) -> Result<()> {
+    let manifest = Manifest::load(&expand_tilde(MANIFEST_PATH)?)?;
+    let entries = || groups.iter().flat_map(|group| &group.entries);
+    let planned: BTreeSet<&Path> = entries()
+        .filter(|entry| entry.kind.claims_target() || matches!(entry.kind, EntryKind::Unfold))
+        .map(|entry| entry.target.as_path())
+        .collect();
+    let hidden: Vec<&Path> = entries()
+        .filter(|entry| matches!(entry.kind, EntryKind::Filtered | EntryKind::Excluded(_)))
+        .map(|entry| entry.source.as_path())
+        .collect();
+
+    for (target, record) in &manifest.entries {
+        let walked = groups
+            .iter()
+            .any(|group| record.source.starts_with(&group.dir));
+        if !walked
+            || hidden.iter().any(|dir| record.source.starts_with(dir))
+            || target.ancestors().any(|path| planned.contains(path))
+            || fs::symlink_metadata(target).is_err()
+        {
+            continue;
+        }
+        let what = format!(
+            "{} ({} from {})",
+            shorten_home(target, &opts.target),
+            record.kind.word(),
+            shorten_home(&record.source, &opts.target)
+        );
+        if !looks_placed(target, record, opts) {
+            say!(
+                opts,
+                "{} {} {what}, changed since dotty placed it",
+                opts.color.red("✗"),
+                opts.color.yellow("kept stale")
+            );
+            continue;
+        }
+        if opts.dry_run {
+            progress!(opts, "{} Would remove stale {what}", opts.color.green("✔"));
+        } else {
+            counts.journal.push(Action::remove(target));
+            fs::remove_file(target)
+                .with_context(|| format!("Failed to remove {}", target.display()))?;
+            progress!(opts, "{} Removed stale {what}", opts.color.green("✔"));
+        }
+        counts.gone.insert(target.clone());
+        counts.stale += 1;
+    }
+    Ok(())
+}
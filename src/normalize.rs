@@ -0,0 +1,45 @@
+//! Names spelled in another Unicode normalization: macOS volumes and some tools store
+//! `café` decomposed (NFD), while sources are usually composed (NFC). A target that is
+//! missing under its own spelling is looked up under the other, so the entry already
+//! there is recognized instead of being reported as a conflict or placed a second time.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// `target` spelled as the entries on disk: each missing name below `base` is replaced
+/// by a sibling that is the same name in another normalization, if there is one.
+pub fn existing_spelling(target: &Path, base: &Path) -> PathBuf {
+    if target.to_str().is_none_or(str::is_ascii)
+        || target == base
+        || !target.starts_with(base)
+        || fs::symlink_metadata(target).is_ok()
+    {
+        return target.to_path_buf();
+    }
+    let (Some(parent), Some(name)) = (target.parent(), target.file_name()) else {
+        return target.to_path_buf();
+    };
+    let parent = existing_spelling(parent, base);
+    let spelled = parent.join(name);
+    if fs::symlink_metadata(&spelled).is_ok() {
+        return spelled;
+    }
+    let Some(name) = name.to_str() else {
+        return spelled;
+    };
+    let sibling = fs::read_dir(&parent).ok().and_then(|entries| {
+        entries
+            .flatten()
+            .map(|entry| entry.file_name())
+            .find(|other| {
+                other
+                    .to_str()
+                    .is_some_and(|other| other.nfc().eq(name.nfc()))
+            })
+    });
+    match sibling {
+        Some(other) => parent.join(other),
+        None => spelled,
+    }
+}
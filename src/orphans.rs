@@ -0,0 +1,125 @@
+//! `dotty orphans`: symlinks in the target that point into the root.
+
+use crate::{EntryKind, Options, PlanGroup, link_destination, link_points_into};
+use crate::{points_into, shorten_home};
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A symlink into the root found in the target.
+struct Link {
+    path: PathBuf,
+    destination: PathBuf,
+    /// Whether the file or directory it points at still exists.
+    exists: bool,
+    /// Whether the current plan links this path again.
+    planned: bool,
+}
+
+/// A [`Link`] in `--json` output, with its paths as text.
+#[derive(Serialize)]
+struct JsonLink {
+    link: String,
+    destination: String,
+    exists: bool,
+    planned: bool,
+}
+
+/// Collect the symlinks below `dir` that point into a root, going at most `depth` levels
+/// deeper. Linked directories and the roots themselves are not entered, and unreadable
+/// directories are passed over.
+fn find_links(dir: &Path, depth: usize, opts: &Options, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.is_symlink() {
+            if link_points_into(&path, &opts.roots) {
+                found.push(path);
+            }
+        } else if meta.is_dir() && depth > 1 && !points_into(&path, &opts.roots) {
+            find_links(&path, depth - 1, opts, found);
+        }
+    }
+}
+
+/// List every symlink in the target (up to `max_depth` levels deep) that points into a
+/// root, sorted, as text or with `json` as a JSON array. Links whose source is gone are
+/// orphans and make the command fail, so it can serve as a health check.
+pub fn run(groups: &[PlanGroup], json: bool, max_depth: usize, opts: &Options) -> Result<()> {
+    let planned: BTreeSet<&Path> = groups
+        .iter()
+        .flat_map(|group| &group.entries)
+        .filter(|entry| {
+            matches!(
+                entry.kind,
+                EntryKind::File | EntryKind::Dir | EntryKind::Folded
+            )
+        })
+        .map(|entry| entry.target.as_path())
+        .collect();
+    let mut paths = Vec::new();
+    find_links(&opts.target, max_depth, opts, &mut paths);
+    paths.sort();
+    let links: Vec<Link> = paths
+        .into_iter()
+        .map(|path| Link {
+            destination: link_destination(&path).unwrap_or_default(),
+            exists: path.exists(),
+            planned: planned.contains(path.as_path()),
+            path,
+        })
+        .collect();
+
+    if json {
+        let items: Vec<JsonLink> = links
+            .iter()
+            .map(|link| JsonLink {
+                link: link.path.to_string_lossy().to_string(),
+                destination: link.destination.to_string_lossy().to_string(),
+                exists: link.exists,
+                planned: link.planned,
+            })
+            .collect();
+        let text = serde_json::to_string_pretty(&items).context("Failed to encode orphans")?;
+        println!("{text}");
+    } else {
+        for link in &links {
+            let (mark, state) = if link.exists {
+                (opts.color.green("✔"), "exists")
+            } else {
+                (opts.color.red("✗"), "missing")
+            };
+            let plan = if link.planned {
+                "planned"
+            } else {
+                "not planned"
+            };
+            println!(
+                "{mark} {} -> {} ({state}, {plan})",
+                shorten_home(&link.path, &opts.target),
+                shorten_home(&link.destination, &opts.target)
+            );
+        }
+    }
+
+    let orphans = links.iter().filter(|link| !link.exists).count();
+    if !json {
+        println!(
+            "\nSummary: {} links into the root, {} orphaned",
+            opts.color.green(&links.len().to_string()),
+            opts.color.red(&orphans.to_string())
+        );
+    }
+    if orphans > 0 {
+        let noun = if orphans == 1 { "symlink" } else { "symlinks" };
+        bail!("Found {orphans} orphaned {noun} pointing at missing files in the root");
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! A live `[processed/total] file` counter on stderr while a large plan is stowed.
+
+use crate::{Options, PlanEntry, shorten_home};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Plans with at most this many entries finish too fast to need a counter.
+const THRESHOLD: usize = 200;
+
+/// Longer paths are cut from the left so the counter stays on one line.
+const MAX_PATH: usize = 60;
+
+/// How often the counter is redrawn at most.
+const INTERVAL: Duration = Duration::from_millis(80);
+
+/// Whether the counter is on screen, so stdout lines clear it first.
+static DRAWN: AtomicBool = AtomicBool::new(false);
+
+/// Erase the counter, if it is drawn, so the next line starts at the left edge.
+pub fn clear() {
+    if DRAWN.swap(false, Ordering::Relaxed) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K");
+        let _ = stderr.flush();
+    }
+}
+
+/// The counter of one stow run; it is erased when dropped.
+pub struct Progress {
+    total: usize,
+    done: usize,
+    last_drawn: Option<Instant>,
+    enabled: bool,
+}
+
+impl Progress {
+    /// A counter over `total` entries, shown only when `--progress` applies and the plan
+    /// is larger than `THRESHOLD`.
+    pub fn new(total: usize, opts: &Options) -> Progress {
+        Progress {
+            total,
+            done: 0,
+            last_drawn: None,
+            enabled: opts.progress && total > THRESHOLD,
+        }
+    }
+
+    /// Count `entry` as processed and redraw the counter when it is due.
+    pub fn tick(&mut self, entry: &PlanEntry, opts: &Options) {
+        self.done += 1;
+        if !self.enabled
+            || (self.done < self.total && self.last_drawn.is_some_and(|at| at.elapsed() < INTERVAL))
+        {
+            return;
+        }
+        self.last_drawn = Some(Instant::now());
+        let path = shorten_home(&entry.target, &opts.target);
+        let count = path.chars().count();
+        let path = if count > MAX_PATH {
+            let rest: String = path.chars().skip(count - MAX_PATH + 1).collect();
+            format!("…{rest}")
+        } else {
+            path
+        };
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K[{}/{}] {path}", self.done, self.total);
+        let _ = stderr.flush();
+        DRAWN.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        clear();
+    }
+}
@@ -0,0 +1,442 @@
+//! `--output json`, `--output ndjson` and `--porcelain`: one record per planned entry
+//! and the summary counts, for scripts wrapping dotty. `--report` writes the same
+//! records as a Markdown file for people.
+
+use crate::{
+    EntryKind, Options, Output, PlanEntry, WalkCounts, backup, describe_conflict, shorten_home,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+/// Version of the document's layout, raised whenever a field changes meaning or goes away.
+pub const VERSION: u32 = 1;
+
+/// What happened, or in a dry run would happen, to one entry.
+#[derive(Clone, Serialize)]
+pub struct Record {
+    pub source: String,
+    pub target: String,
+    /// How the entry is placed: `symlink`, `dir_symlink`, `hardlink`, `transform`, `copy`,
+    /// `inject`, `unfold` or `none` for entries that place nothing.
+    pub action: &'static str,
+    /// `planned`, `in_place`, `conflict`, `relinked`, `adopted`, `skipped`, `excluded`
+    /// or `ignored`.
+    pub state: &'static str,
+    /// Why an entry is a conflict, skipped, excluded or ignored.
+    pub reason: Option<String>,
+    /// Whether this run changed the target.
+    pub executed: bool,
+    /// Whether an identical target was replaced (`--override-identical`).
+    #[serde(skip)]
+    pub overridden: bool,
+}
+
+/// The counters of a run, the same ones the human-readable summary shows.
+#[derive(Serialize)]
+struct Summary {
+    planned: usize,
+    in_place: usize,
+    conflicts: usize,
+    skipped: usize,
+    overrides: usize,
+    copied: usize,
+    backups: usize,
+    overwritten: usize,
+    forced: usize,
+    relinked: usize,
+    adopted: usize,
+    excluded: usize,
+    filtered: usize,
+    ignored: usize,
+    folded: usize,
+    unfolded: usize,
+    dangling: usize,
+    pruned: usize,
+    stale: usize,
+    removed_dirs: usize,
+    new_dirs: usize,
+    collisions: usize,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u32,
+    dry_run: bool,
+    entries: &'a [Record],
+    summary: Summary,
+}
+
+/// One line of `--output ndjson`: a record with its event type.
+#[derive(Serialize)]
+struct Event<'a> {
+    event: &'static str,
+    version: u32,
+    #[serde(flatten)]
+    record: &'a Record,
+}
+
+#[derive(Serialize)]
+struct ErrorEvent {
+    event: &'static str,
+    version: u32,
+    source: String,
+    target: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct SummaryEvent {
+    event: &'static str,
+    version: u32,
+    dry_run: bool,
+    summary: Summary,
+}
+
+/// The counters that tell what happened to one entry, taken before and after it is
+/// stowed.
+#[derive(Clone, Copy, Default)]
+pub struct Tally {
+    pub planned: usize,
+    pub in_place: usize,
+    pub conflicts: usize,
+    pub skips: usize,
+    relinked: usize,
+    adopted: usize,
+    excluded: usize,
+    ignored: usize,
+    copied: usize,
+    unfolded: usize,
+    overrides: usize,
+}
+
+impl Tally {
+    pub fn of(counts: &WalkCounts) -> Tally {
+        Tally {
+            planned: counts.planned,
+            in_place: counts.in_place,
+            conflicts: counts.conflicts,
+            skips: counts.skips,
+            relinked: counts.relinked,
+            adopted: counts.adopted,
+            excluded: counts.excluded,
+            ignored: counts.ignored,
+            copied: counts.copied,
+            unfolded: counts.unfolded,
+            overrides: counts.overrides,
+        }
+    }
+
+    /// Add what changed from `before` to `after`.
+    pub fn add(&mut self, before: Tally, after: Tally) {
+        self.planned += after.planned - before.planned;
+        self.in_place += after.in_place - before.in_place;
+        self.conflicts += after.conflicts - before.conflicts;
+        self.skips += after.skips - before.skips;
+        self.relinked += after.relinked - before.relinked;
+        self.adopted += after.adopted - before.adopted;
+        self.excluded += after.excluded - before.excluded;
+        self.ignored += after.ignored - before.ignored;
+        self.copied += after.copied - before.copied;
+        self.unfolded += after.unfolded - before.unfolded;
+        self.overrides += after.overrides - before.overrides;
+    }
+}
+
+fn action(kind: &EntryKind) -> &'static str {
+    match kind {
+        EntryKind::File => "symlink",
+        EntryKind::Dir | EntryKind::Folded => "dir_symlink",
+        EntryKind::Hardlink => "hardlink",
+        EntryKind::Transform(_) => "transform",
+        EntryKind::Copy => "copy",
+        EntryKind::Inject(_) => "inject",
+        EntryKind::Unfold => "unfold",
+        EntryKind::FoldConflict(_)
+        | EntryKind::Skipped
+        | EntryKind::Excluded(_)
+        | EntryKind::Filtered
+        | EntryKind::Ignored(_) => "none",
+    }
+}
+
+/// `path` made absolute, as text.
+fn absolute(path: &Path) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The record of `entry`, judged by how stowing it changed the counters from `before` to
+/// `after`. Entries outside `--only` get none.
+pub fn record(entry: &PlanEntry, before: Tally, after: Tally, opts: &Options) -> Option<Record> {
+    let (state, reason) = if after.conflicts > before.conflicts {
+        ("conflict", Some(describe_conflict(&entry.target, opts)))
+    } else if after.skips > before.skips {
+        ("skipped", Some("skipped by lua".to_string()))
+    } else if after.excluded > before.excluded {
+        let EntryKind::Excluded(pattern) = &entry.kind else {
+            unreachable!("only excluded entries are counted as excluded")
+        };
+        ("excluded", Some(format!("excluded by '{pattern}'")))
+    } else if after.ignored > before.ignored {
+        let EntryKind::Ignored(rule) = &entry.kind else {
+            unreachable!("only ignored entries are counted as ignored")
+        };
+        ("ignored", Some(rule.clone()))
+    } else if after.relinked > before.relinked {
+        ("relinked", None)
+    } else if after.adopted > before.adopted {
+        ("adopted", None)
+    } else if after.planned > before.planned
+        || after.copied > before.copied
+        || after.unfolded > before.unfolded
+    {
+        ("planned", None)
+    } else if after.in_place > before.in_place {
+        ("in_place", None)
+    } else {
+        return None;
+    };
+    Some(Record {
+        source: absolute(&entry.source),
+        target: absolute(&entry.target),
+        action: action(&entry.kind),
+        state,
+        reason,
+        executed: !opts.dry_run && matches!(state, "planned" | "relinked" | "adopted"),
+        overridden: after.overrides > before.overrides,
+    })
+}
+
+/// Write `line` to stdout and flush it, so readers see each event as it happens.
+fn emit_line(line: &str) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{line}")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// `text` for a `--porcelain` field: backslashes, tabs and newlines are escaped so each
+/// record stays on one line.
+fn porcelain_field(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+/// The `--porcelain` keyword for `record`, or `None` for entries it does not list.
+fn porcelain_keyword(record: &Record) -> Option<&'static str> {
+    Some(match record.state {
+        "conflict" => "CONFLICT",
+        "skipped" | "excluded" | "ignored" => "SKIP",
+        "in_place" => "INPLACE",
+        _ if record.overridden => "OVERRIDE",
+        _ => match record.action {
+            "transform" | "copy" | "inject" => "WRITE",
+            "unfold" => return None,
+            _ => "LINK",
+        },
+    })
+}
+
+/// With `--output ndjson` or `--porcelain`, print the line for `record` right away.
+/// ndjson events are `planned` or `linked` for entries a dry run or real run places (also
+/// when already in place), `conflict`, or `skipped` for skipped, excluded and ignored
+/// entries.
+pub fn emit(record: &Record, opts: &Options) -> Result<()> {
+    if opts.output == Output::Porcelain {
+        let Some(keyword) = porcelain_keyword(record) else {
+            return Ok(());
+        };
+        return emit_line(&format!(
+            "{keyword}\t{}\t{}",
+            porcelain_field(&record.target),
+            porcelain_field(&record.source)
+        ));
+    }
+    let event = match record.state {
+        "conflict" => "conflict",
+        "skipped" | "excluded" | "ignored" => "skipped",
+        _ if opts.dry_run => "planned",
+        _ => "linked",
+    };
+    emit_line(&serde_json::to_string(&Event {
+        event,
+        version: VERSION,
+        record,
+    })?)
+}
+
+/// With `--output ndjson`, print an `error` event for the entry that failed with `error`;
+/// with `--porcelain`, an `ERROR` line.
+pub fn emit_error(entry: &PlanEntry, error: &anyhow::Error, opts: &Options) -> Result<()> {
+    let (source, target) = (absolute(&entry.source), absolute(&entry.target));
+    if opts.output == Output::Porcelain {
+        return emit_line(&format!(
+            "ERROR\t{}\t{}",
+            porcelain_field(&target),
+            porcelain_field(&source)
+        ));
+    }
+    emit_line(&serde_json::to_string(&ErrorEvent {
+        event: "error",
+        version: VERSION,
+        source,
+        target,
+        message: format!("{error:#}"),
+    })?)
+}
+
+/// The summary counters of `totals`.
+fn summary(totals: &WalkCounts, collisions: usize) -> Summary {
+    Summary {
+        planned: totals.planned,
+        in_place: totals.in_place,
+        conflicts: totals.conflicts,
+        skipped: totals.skips,
+        overrides: totals.overrides,
+        copied: totals.copied,
+        backups: totals.backups,
+        overwritten: totals.overwritten,
+        forced: totals.forced,
+        relinked: totals.relinked,
+        adopted: totals.adopted,
+        excluded: totals.excluded,
+        filtered: totals.filtered,
+        ignored: totals.ignored,
+        folded: totals.folded,
+        unfolded: totals.unfolded,
+        dangling: totals.dangling,
+        pruned: totals.pruned,
+        stale: totals.stale,
+        removed_dirs: totals.removed_dirs,
+        new_dirs: totals.new_dirs.len(),
+        collisions,
+    }
+}
+
+/// Print the end of a run: the JSON document with the records collected in `totals`,
+/// the `summary` event that closes an ndjson stream, or the `SUMMARY` line of
+/// `--porcelain`.
+pub fn print(totals: &WalkCounts, collisions: usize, opts: &Options) -> Result<()> {
+    if opts.output == Output::Porcelain {
+        return emit_line(&format!(
+            "SUMMARY\tplanned={}\tin_place={}\tconflicts={}\tskipped={}\toverrides={}",
+            totals.planned, totals.in_place, totals.conflicts, totals.skips, totals.overrides
+        ));
+    }
+    if opts.output == Output::Ndjson {
+        return emit_line(&serde_json::to_string(&SummaryEvent {
+            event: "summary",
+            version: VERSION,
+            dry_run: opts.dry_run,
+            summary: summary(totals, collisions),
+        })?);
+    }
+    let document = Document {
+        version: VERSION,
+        dry_run: opts.dry_run,
+        entries: &totals.records,
+        summary: summary(totals, collisions),
+    };
+    println!("{}", serde_json::to_string_pretty(&document)?);
+    Ok(())
+}
+
+/// The sections of a `--report`, in order.
+const SECTIONS: [&str; 5] = [
+    "Linked",
+    "Written",
+    "Already in place",
+    "Conflicts",
+    "Skipped",
+];
+
+/// The `--report` section that lists `record`.
+fn section(record: &Record) -> &'static str {
+    match record.state {
+        "in_place" => "Already in place",
+        "conflict" => "Conflicts",
+        "skipped" | "excluded" | "ignored" => "Skipped",
+        _ if matches!(record.action, "transform" | "copy" | "inject") => "Written",
+        _ => "Linked",
+    }
+}
+
+/// Write the Markdown `--report` of a stow run to `path`: a section per outcome with
+/// home-shortened paths, the entry that stopped the run with `error`, and the summary
+/// counts.
+pub fn write_markdown(
+    path: &str,
+    totals: &WalkCounts,
+    collisions: usize,
+    error: Option<(&PlanEntry, &anyhow::Error)>,
+    opts: &Options,
+) -> Result<()> {
+    let short = |path: &str| shorten_home(Path::new(path), &opts.target);
+    let run = if opts.dry_run { "dry run" } else { "run" };
+    let mut out = format!(
+        "# dotty report\n\nGenerated {} UTC ({run}) for target {}.\n",
+        backup::timestamp(),
+        opts.target.display()
+    );
+    for title in SECTIONS {
+        out.push_str(&format!("\n## {title}\n\n"));
+        let records: Vec<_> = totals
+            .records
+            .iter()
+            .filter(|record| section(record) == title && record.action != "unfold")
+            .collect();
+        if records.is_empty() {
+            out.push_str("None.\n");
+        }
+        for record in records {
+            out.push_str(&format!(
+                "- `{}` ← `{}`",
+                short(&record.target),
+                short(&record.source)
+            ));
+            if record.action != "none" {
+                out.push_str(&format!(" ({})", record.action));
+            }
+            if let Some(reason) = &record.reason {
+                out.push_str(&format!(": {reason}"));
+            }
+            out.push('\n');
+        }
+    }
+    out.push_str("\n## Errors\n\n");
+    match error {
+        Some((entry, error)) => out.push_str(&format!(
+            "- `{}` ← `{}`: {error:#}\n",
+            shorten_home(&entry.target, &opts.target),
+            shorten_home(&entry.source, &opts.target)
+        )),
+        None => out.push_str("None.\n"),
+    }
+
+    let summary = summary(totals, collisions);
+    out.push_str("\n## Summary\n\n| Count | Value |\n| --- | ---: |\n");
+    for (label, value) in [
+        ("planned", summary.planned),
+        ("already in place", summary.in_place),
+        ("conflicts", summary.conflicts),
+        ("skipped by lua", summary.skipped),
+        ("relinked", summary.relinked),
+        ("adopted", summary.adopted),
+        ("copied", summary.copied),
+        ("overrides", summary.overrides),
+        ("backups", summary.backups),
+        ("excluded", summary.excluded),
+        ("ignored", summary.ignored),
+        ("collisions", summary.collisions),
+    ] {
+        out.push_str(&format!("| {label} | {value} |\n"));
+    }
+    std::fs::write(path, out).with_context(|| format!("Failed to write report {path}"))?;
+    say!(opts, "{} Wrote report to {path}", opts.color.green("✔"));
+    Ok(())
+}
@@ -0,0 +1,165 @@
+//! `--emit-script`: the plan as a POSIX shell script, for machines where dotty cannot run.
+
+use crate::status::{self, State};
+use crate::{EntryKind, Options, PlanEntry, PlanGroup, backup, describe_conflict};
+use crate::{relative_link, shorten_home, source_mode};
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// `path` quoted for the shell, made absolute so the script works from any directory.
+fn quote(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    quote_str(&path.to_string_lossy())
+}
+
+fn quote_str(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// The commands that write `content` to `target`: a heredoc, or `printf` for content
+/// that does not end in a newline, which a heredoc would add. Binary content is written
+/// with `printf` octal escapes.
+fn write_file(content: &[u8], target: &Path) -> String {
+    let content = match std::str::from_utf8(content) {
+        Ok(text) if !content.contains(&0) => text,
+        _ => {
+            let escaped: String = content.iter().map(|byte| format!("\\{byte:03o}")).collect();
+            return format!("printf '{escaped}' > {}\n", quote(target));
+        }
+    };
+    if !content.ends_with('\n') {
+        return format!("printf '%s' {} > {}\n", quote_str(content), quote(target));
+    }
+    // A delimiter that cannot end the heredoc early
+    let mut delimiter = "DOTTY_EOF".to_string();
+    while content.lines().any(|line| line == delimiter) {
+        delimiter.push('_');
+    }
+    format!(
+        "cat > {} <<'{delimiter}'\n{content}{delimiter}\n",
+        quote(target)
+    )
+}
+
+/// Append the commands that place `entry`, or a comment on why it is left out, to `out`.
+/// `dirs` holds the directories created so far.
+fn entry_commands(
+    entry: &PlanEntry,
+    opts: &Options,
+    dirs: &mut BTreeSet<std::path::PathBuf>,
+    out: &mut String,
+) -> Result<()> {
+    let (source, target) = (&entry.source, &entry.target);
+    let short = shorten_home(target, &opts.target);
+    let Some(state) = status::entry_state(entry, opts)? else {
+        if matches!(entry.kind, EntryKind::Unfold) {
+            out.push_str(&format!(
+                "# WARNING: {short} is a directory link to unfold, which scripts do not do\n"
+            ));
+        }
+        return Ok(());
+    };
+    match state {
+        State::Linked | State::Identical => {
+            out.push_str(&format!("# in place: {short}\n"));
+            return Ok(());
+        }
+        State::Skipped => {
+            out.push_str(&format!("# skipped by lua: {short}\n"));
+            return Ok(());
+        }
+        State::Conflict => {
+            out.push_str(&format!(
+                "# WARNING: conflict at {short} ({}), left alone\n",
+                describe_conflict(target, opts)
+            ));
+            return Ok(());
+        }
+        State::Missing | State::Differs => {}
+    }
+    if let EntryKind::Inject(_) = entry.kind {
+        out.push_str(&format!(
+            "# WARNING: {short} gets an injected block, which scripts do not write\n"
+        ));
+        return Ok(());
+    }
+
+    if let Some(parent) = target.parent()
+        && !parent.is_dir()
+        && dirs.insert(parent.to_path_buf())
+    {
+        out.push_str(&format!("mkdir -p {}\n", quote(parent)));
+    }
+    // Dangling links and links replaced by a written file go first
+    if target.is_symlink() {
+        out.push_str(&format!("rm -f {}\n", quote(target)));
+    }
+    match &entry.kind {
+        EntryKind::File | EntryKind::Dir | EntryKind::Folded => {
+            let link = if entry.relative || opts.relative {
+                quote_str(&relative_link(source, target).to_string_lossy())
+            } else {
+                quote(source)
+            };
+            out.push_str(&format!("ln -s {link} {}\n", quote(target)));
+            if let Some(mode) = entry.mode {
+                out.push_str(&format!("chmod {mode:04o} {}\n", quote(source)));
+            }
+        }
+        EntryKind::Hardlink => {
+            out.push_str(&format!("ln {} {}\n", quote(source), quote(target)));
+        }
+        EntryKind::Transform(content) => {
+            out.push_str(&write_file(content, target));
+            let mode = match entry.mode {
+                Some(mode) => mode,
+                None => source_mode(source)?,
+            };
+            out.push_str(&format!("chmod {mode:04o} {}\n", quote(target)));
+        }
+        EntryKind::Copy => {
+            out.push_str(&format!("cp {} {}\n", quote(source), quote(target)));
+            if let Some(mode) = entry.mode {
+                out.push_str(&format!("chmod {mode:04o} {}\n", quote(target)));
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Write the plan as a shell script to `path`, or to stdout for `-`. Entries that are in
+/// place, skipped or in conflict become comments; nothing else is changed.
+pub fn run(groups: &[PlanGroup], path: &str, opts: &Options) -> Result<()> {
+    let mut out = format!(
+        "#!/bin/sh\n# Generated by dotty on {} (UTC), stowing {} into {}\nset -e\n",
+        backup::timestamp(),
+        groups
+            .iter()
+            .map(|group| group.dir.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        opts.target.display()
+    );
+    let mut dirs = BTreeSet::new();
+    for group in groups {
+        out.push('\n');
+        for entry in &group.entries {
+            entry_commands(entry, opts, &mut dirs, &mut out)?;
+        }
+    }
+
+    if path == "-" {
+        std::io::stdout().write_all(out.as_bytes())?;
+        return Ok(());
+    }
+    fs::write(path, &out).with_context(|| format!("Failed to write script {path}"))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make {path} executable"))?;
+    say!(opts, "{} Wrote script to {path}", opts.color.green("✔"));
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! Paths in the state files (manifest, journal, hash cache), stored so that names that are
+//! not valid UTF-8 survive the round trip through JSON. Such a path is written as a NUL
+//! followed by the hex of its bytes; a real path never contains NUL, so every other string
+//! is read as the path it spells. Use with `#[serde(with = "crate::state_path")]`, or the
+//! submodules for optional paths, sets and map keys.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+/// `path` as a JSON string: itself when it is UTF-8, encoded otherwise.
+fn encode(path: &Path) -> String {
+    match path.to_str() {
+        Some(text) => text.to_string(),
+        None => {
+            let hex: String = path
+                .as_os_str()
+                .as_bytes()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            format!("\0{hex}")
+        }
+    }
+}
+
+/// The path that [`encode`] turned into `text`.
+fn decode<E: serde::de::Error>(text: String) -> Result<PathBuf, E> {
+    let Some(hex) = text.strip_prefix('\0') else {
+        return Ok(PathBuf::from(text));
+    };
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|at| {
+            hex.get(at..at + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| E::custom(format!("invalid encoded path {:?}", text)))?;
+    Ok(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    encode(path).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    decode(String::deserialize(deserializer)?)
+}
+
+/// For `Option<PathBuf>` fields.
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        path: &Option<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path.as_deref().map(encode).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PathBuf>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(decode)
+            .transpose()
+    }
+}
+
+/// For `BTreeSet<PathBuf>` fields.
+pub mod set {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        paths: &BTreeSet<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| encode(path)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeSet<PathBuf>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(decode)
+            .collect()
+    }
+}
+
+/// For `BTreeMap<PathBuf, V>` fields, whose keys must be strings in JSON.
+pub mod keys {
+    use super::*;
+
+    pub fn serialize<S: Serializer, V: Serialize>(
+        map: &BTreeMap<PathBuf, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(path, value)| (encode(path), value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<PathBuf, V>, D::Error> {
+        BTreeMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(path, value)| Ok((decode(path)?, value)))
+            .collect()
+    }
+}
@@ -0,0 +1,87 @@
+//! `--stats`: where a run spent its time, measured with instants around Lua
+//! evaluations, content comparisons and filesystem changes.
+
+use crate::Options;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static FILES: AtomicU64 = AtomicU64::new(0);
+static LUA_RUNS: AtomicU64 = AtomicU64::new(0);
+static LUA_NANOS: AtomicU64 = AtomicU64::new(0);
+static COMPARE_NANOS: AtomicU64 = AtomicU64::new(0);
+static MUTATE_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Note when the run started, for the wall time.
+pub fn start() {
+    STARTED.get_or_init(Instant::now);
+}
+
+/// Count one file or directory of the root as examined.
+pub fn examined() {
+    FILES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Run `f` and add its time to `nanos`.
+fn time<T>(nanos: &AtomicU64, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+    nanos.fetch_add(elapsed, Ordering::Relaxed);
+    result
+}
+
+/// Run `f`, a Lua evaluation, counting it and its time.
+pub fn lua<T>(f: impl FnOnce() -> T) -> T {
+    LUA_RUNS.fetch_add(1, Ordering::Relaxed);
+    time(&LUA_NANOS, f)
+}
+
+/// Run `f`, a comparison of file contents, adding its time.
+pub fn compare<T>(f: impl FnOnce() -> T) -> T {
+    time(&COMPARE_NANOS, f)
+}
+
+/// Run `f`, a change to the filesystem, adding its time.
+pub fn mutate<T>(f: impl FnOnce() -> T) -> T {
+    time(&MUTATE_NANOS, f)
+}
+
+/// `duration` for people: milliseconds below a second, seconds above.
+fn human(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+/// Print the collected numbers after the summary.
+pub fn print(opts: &Options) {
+    let nanos = |counter: &AtomicU64| Duration::from_nanos(counter.load(Ordering::Relaxed));
+    let wall = STARTED.get().map(Instant::elapsed).unwrap_or_default();
+    say!(opts, "\nStats:");
+    say!(opts, "  wall time            {}", human(wall));
+    say!(
+        opts,
+        "  files examined       {}",
+        FILES.load(Ordering::Relaxed)
+    );
+    say!(
+        opts,
+        "  lua evaluations      {} ({})",
+        LUA_RUNS.load(Ordering::Relaxed),
+        human(nanos(&LUA_NANOS))
+    );
+    say!(
+        opts,
+        "  content comparisons  {}",
+        human(nanos(&COMPARE_NANOS))
+    );
+    say!(
+        opts,
+        "  filesystem changes   {}",
+        human(nanos(&MUTATE_NANOS))
+    );
+}
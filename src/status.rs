@@ -0,0 +1,175 @@
+//! `dotty status`: where each planned entry stands, without changing anything.
+
+use crate::compare;
+use crate::{EntryKind, Options, PlanEntry, PlanGroup, TargetState, WalkCounts};
+use crate::{
+    describe_conflict, is_dangling, paths_match, print_group_heading, print_summary, same_file,
+    shorten_home, source_mode, target_state,
+};
+use anyhow::{Context, Result};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+/// Where one entry stands.
+#[derive(Clone, Copy, PartialEq)]
+pub enum State {
+    /// The link is in place.
+    Linked,
+    /// The written file or block already has the planned content.
+    Identical,
+    /// Nothing is at the target yet.
+    Missing,
+    /// A written file or block would be updated.
+    Differs,
+    /// Something else is in the way.
+    Conflict,
+    /// The companion returned `false`.
+    Skipped,
+}
+
+impl State {
+    pub fn word(self) -> &'static str {
+        match self {
+            State::Linked => "linked",
+            State::Identical => "identical",
+            State::Missing => "missing",
+            State::Differs => "differs",
+            State::Conflict => "conflict",
+            State::Skipped => "skipped",
+        }
+    }
+}
+
+/// The state of a symlinked entry.
+fn link_state(entry: &PlanEntry, opts: &Options) -> State {
+    match target_state(&entry.target, opts) {
+        TargetState::Missing => State::Missing,
+        TargetState::Link(dest) if paths_match(&dest, &entry.source) => State::Linked,
+        // A broken link is replaced without asking
+        TargetState::Link(_) if is_dangling(&entry.target) => State::Missing,
+        TargetState::Link(_) | TargetState::Other => State::Conflict,
+    }
+}
+
+/// The state of a written entry whose target should hold `content`.
+fn file_state(entry: &PlanEntry, content: &[u8]) -> Result<State> {
+    let target = &entry.target;
+    let Ok(meta) = fs::symlink_metadata(target) else {
+        return Ok(State::Missing);
+    };
+    if meta.is_symlink() {
+        return Ok(match &entry.kind {
+            _ if is_dangling(target) => State::Missing,
+            // A transform replaces links, a copy only those made by --override-identical
+            EntryKind::Transform(_) => State::Differs,
+            _ => State::Conflict,
+        });
+    }
+    if !meta.is_file() {
+        return Ok(State::Conflict);
+    }
+    let mode = match entry.mode {
+        Some(mode) => mode,
+        None => source_mode(&entry.source)?,
+    };
+    if meta.mode() & 0o7777 == mode && compare::file_equals(target, content) {
+        Ok(State::Identical)
+    } else {
+        Ok(State::Differs)
+    }
+}
+
+/// The state of `entry`, or `None` for entries that place nothing of their own.
+pub fn entry_state(entry: &PlanEntry, opts: &Options) -> Result<Option<State>> {
+    let (source, target) = (&entry.source, &entry.target);
+    let state = match &entry.kind {
+        EntryKind::Excluded(_)
+        | EntryKind::Filtered
+        | EntryKind::Ignored(_)
+        | EntryKind::Unfold
+        | EntryKind::FoldConflict(_) => return Ok(None),
+        EntryKind::Skipped => State::Skipped,
+        EntryKind::File | EntryKind::Dir | EntryKind::Folded => link_state(entry, opts),
+        EntryKind::Hardlink => {
+            if same_file(target, source) {
+                State::Linked
+            } else if fs::symlink_metadata(target).is_err() || is_dangling(target) {
+                State::Missing
+            } else {
+                State::Conflict
+            }
+        }
+        EntryKind::Transform(content) => file_state(entry, content)?,
+        EntryKind::Copy => {
+            let content =
+                fs::read(source).with_context(|| format!("Failed to read {}", source.display()))?;
+            file_state(entry, &content)?
+        }
+        EntryKind::Inject(injection) => {
+            if target.is_symlink() || target.is_dir() {
+                State::Conflict
+            } else {
+                let existing = fs::read_to_string(target).ok();
+                match injection.apply(existing.as_deref()) {
+                    Ok((_, crate::inject::BlockChange::InPlace)) => State::Identical,
+                    Ok((_, crate::inject::BlockChange::CreateFile)) => State::Missing,
+                    Ok(_) => State::Differs,
+                    Err(_) => State::Conflict,
+                }
+            }
+        }
+    };
+    Ok(Some(state))
+}
+
+/// Print `entry`'s target after its state, colored and padded so the targets line up,
+/// with what is in the way for conflicts.
+pub fn print_state(entry: &PlanEntry, state: State, opts: &Options) {
+    let word = format!("{:<9}", state.word());
+    let word = match state {
+        State::Linked | State::Identical => opts.color.green(&word),
+        State::Missing | State::Differs => opts.color.yellow(&word),
+        State::Conflict => opts.color.red(&word),
+        State::Skipped => opts.color.blue(&word),
+    };
+    let detail = if state == State::Conflict {
+        format!(" ({})", describe_conflict(&entry.target, opts))
+    } else {
+        String::new()
+    };
+    println!(
+        "{word} {}{detail}",
+        shorten_home(&entry.target, &opts.target)
+    );
+}
+
+/// Print one aligned line per planned entry with its state, then the usual summary.
+/// Nothing is created or written.
+pub fn run(groups: &[PlanGroup], collisions: usize, opts: &Options) -> Result<()> {
+    let mut totals = WalkCounts::default();
+    for group in groups {
+        print_group_heading(group, groups.len(), opts);
+        for entry in &group.entries {
+            match &entry.kind {
+                EntryKind::Excluded(_) => totals.excluded += 1,
+                EntryKind::Filtered => totals.filtered += 1,
+                EntryKind::Ignored(_) => totals.ignored += 1,
+                _ => {}
+            }
+            let Some(state) = entry_state(entry, opts)? else {
+                continue;
+            };
+            let copy = matches!(entry.kind, EntryKind::Copy);
+            match state {
+                State::Linked | State::Identical => totals.in_place += 1,
+                State::Missing | State::Differs if copy => totals.copied += 1,
+                State::Missing | State::Differs => totals.planned += 1,
+                State::Conflict => totals.conflict(&entry.target),
+                State::Skipped => totals.skips += 1,
+            }
+            print_state(entry, state, opts);
+        }
+    }
+    print_summary(&totals, collisions, groups.len(), opts);
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! Rendering of sources whose companion returns `template = true`.
+
+use crate::lua_api;
+use anyhow::{Result, anyhow};
+use minijinja::{Environment, UndefinedBehavior, context};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Render `content` read from `file`. The variables are available by name and as the
+/// `vars` map, next to a `dotty` object with the machine facts also offered to Lua.
+/// Using a variable that is not set is an error rather than an empty string.
+pub fn render(content: &str, file: &Path, vars: &BTreeMap<String, String>) -> Result<String> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    // Keep the source's trailing newline, which Jinja drops by default
+    env.set_keep_trailing_newline(true);
+    let name = file.display().to_string();
+    let facts = context! {
+        hostname => lua_api::hostname(),
+        os => std::env::consts::OS,
+        arch => std::env::consts::ARCH,
+        username => lua_api::username(),
+        home => std::env::var("HOME").ok(),
+    };
+    let ctx = context! {
+        vars => vars,
+        dotty => facts,
+        ..minijinja::Value::from_serialize(vars)
+    };
+    // The error names the template as `file:line`
+    env.render_named_str(&name, content, ctx)
+        .map_err(|e| anyhow!("Failed to render template: {e}"))
+}
@@ -0,0 +1,59 @@
+//! `dotty verify`: whether everything is already in place, as an exit code.
+
+use crate::status::{State, entry_state, print_state};
+use crate::{Options, PlanGroup, conflicts_label};
+use anyhow::Result;
+use std::process::ExitCode;
+
+/// Exit code when something would be linked, written or updated.
+pub const CHANGES: u8 = 1;
+
+/// Exit code when something is in the way, or the run failed.
+pub const CONFLICTS: u8 = 2;
+
+/// List the entries that are not in place and pick the exit code: 0 when nothing would
+/// change, [`CHANGES`] when something would and [`CONFLICTS`] when anything conflicts.
+/// Nothing is created or written.
+pub fn run(groups: &[PlanGroup], opts: &Options) -> Result<ExitCode> {
+    let (mut in_place, mut changes, mut conflicts) = (0, 0, 0);
+    for entry in groups.iter().flat_map(|group| &group.entries) {
+        let Some(state) = entry_state(entry, opts)? else {
+            continue;
+        };
+        match state {
+            State::Linked | State::Identical | State::Skipped => {
+                in_place += 1;
+                continue;
+            }
+            State::Missing | State::Differs => changes += 1,
+            State::Conflict => conflicts += 1,
+        }
+        if !opts.silent {
+            print_state(entry, state, opts);
+        }
+    }
+
+    if !opts.silent {
+        if changes + conflicts == 0 {
+            println!(
+                "{} Everything is in place ({in_place} entries)",
+                opts.color.green("✔")
+            );
+        } else {
+            println!(
+                "\nVerify: {} in place, {} to change, {} {}",
+                opts.color.green(&in_place.to_string()),
+                opts.color.yellow(&changes.to_string()),
+                opts.color.red(&conflicts.to_string()),
+                conflicts_label(conflicts),
+            );
+        }
+    }
+    Ok(if conflicts > 0 {
+        ExitCode::from(CONFLICTS)
+    } else if changes > 0 {
+        ExitCode::from(CHANGES)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
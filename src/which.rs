@@ -0,0 +1,103 @@
+//! `dotty which`: the source in the root that owns a target path.
+
+use crate::status::{self, State};
+use crate::{EntryKind, Options, PlanEntry, PlanGroup, expand_tilde, link_destination};
+use crate::{points_into, shorten_home};
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+
+/// What owns a target path.
+pub enum Owner<'a> {
+    /// Entries the plan maps to the path itself.
+    Planned(Vec<&'a PlanEntry>),
+    /// A directory linked as a whole, with the source of the path inside it.
+    InsideDir(&'a PlanEntry, PathBuf),
+    /// An existing symlink into the root that nothing plans, with its destination.
+    Link(PathBuf),
+}
+
+/// The absolute path of a target given on the command line, `~` expanded.
+pub fn resolve(path: &str) -> Result<PathBuf> {
+    std::path::absolute(expand_tilde(path)?).with_context(|| format!("Failed to resolve {path}"))
+}
+
+/// Look up what owns `target`: planned entries first, then a linked directory above
+/// it, then a symlink into the root.
+pub fn owner<'a>(groups: &'a [PlanGroup], target: &Path, opts: &Options) -> Option<Owner<'a>> {
+    let entries = groups.iter().flat_map(|group| &group.entries);
+    let planned: Vec<&PlanEntry> = entries
+        .clone()
+        .filter(|entry| {
+            entry.target == target
+                && (entry.kind.claims_target() || matches!(entry.kind, EntryKind::Skipped))
+        })
+        .collect();
+    if !planned.is_empty() {
+        return Some(Owner::Planned(planned));
+    }
+    if let Some((entry, rest)) = entries
+        .filter(|entry| matches!(entry.kind, EntryKind::Dir | EntryKind::Folded))
+        .find_map(|entry| Some((entry, target.strip_prefix(&entry.target).ok()?)))
+    {
+        return Some(Owner::InsideDir(entry, entry.source.join(rest)));
+    }
+    link_destination(target)
+        .filter(|dest| points_into(dest, &opts.roots))
+        .map(Owner::Link)
+}
+
+/// How `kind` places its target.
+pub fn action(kind: &EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Dir | EntryKind::Folded => "directory symlink",
+        EntryKind::Transform(_) => "transform",
+        EntryKind::Copy => "copy",
+        EntryKind::Hardlink => "hard link",
+        EntryKind::Inject(_) => "injected block",
+        EntryKind::Skipped => "skipped by its companion",
+        _ => "symlink",
+    }
+}
+
+/// "in place", or the state that keeps `entry` from being in place.
+fn placement(entry: &PlanEntry, opts: &Options) -> Result<String> {
+    Ok(match status::entry_state(entry, opts)? {
+        Some(State::Linked | State::Identical) => "in place".to_string(),
+        Some(state) => format!("not in place: {}", state.word()),
+        None => "not placed".to_string(),
+    })
+}
+
+/// Print the source that the plan maps to `path`, how it is placed and whether it is in
+/// place. A path below a linked directory is traced through the directory link, and a
+/// symlink into the root that nothing plans is reported with its destination. Fails when
+/// no source maps to `path`.
+pub fn run(groups: &[PlanGroup], path: &str, opts: &Options) -> Result<()> {
+    let target = resolve(path)?;
+    let short = shorten_home(&target, &opts.target);
+    match owner(groups, &target, opts) {
+        Some(Owner::Planned(entries)) => {
+            for entry in entries {
+                println!(
+                    "{short} <- {} ({}, {})",
+                    entry.source.display(),
+                    action(&entry.kind),
+                    placement(entry, opts)?
+                );
+            }
+        }
+        Some(Owner::InsideDir(entry, source)) => println!(
+            "{short} <- {} (inside directory symlink {}, {})",
+            source.display(),
+            shorten_home(&entry.target, &opts.target),
+            placement(entry, opts)?
+        ),
+        Some(Owner::Link(dest)) => println!(
+            "{short} -> {} ({}symlink into the root, but nothing plans it)",
+            dest.display(),
+            if dest.exists() { "" } else { "dangling " }
+        ),
+        None => bail!("No source in the root maps to {short}"),
+    }
+    Ok(())
+}
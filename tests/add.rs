@@ -0,0 +1,129 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(home.join(".config/fish/conf.d")).unwrap();
+    fs::write(home.join(".config/fish/conf.d/abbr.fish"), b"abbr g git").unwrap();
+    (tmp, root, home)
+}
+
+fn add(root: &Path, home: &Path, path: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("add")
+        .arg(path)
+        .arg("--root")
+        .arg(root)
+        .arg("--no-color");
+    cmd.env("HOME", home);
+    cmd
+}
+
+#[test]
+fn add_moves_nested_file_into_root_and_links_back() {
+    let (_tmp, root, home) = setup();
+    let target = home.join(".config/fish/conf.d/abbr.fish");
+
+    add(&root, &home, &target)
+        .assert()
+        .success()
+        .stdout(contains("Moved ~/.config/fish/conf.d/abbr.fish to"))
+        .stdout(contains("Linked ~/.config/fish/conf.d/abbr.fish ->"));
+
+    let source = root.join(".config/fish/conf.d/abbr.fish");
+    assert_eq!(fs::read(&source).unwrap(), b"abbr g git");
+    assert_eq!(fs::read_link(&target).unwrap(), source);
+
+    // The next run finds it in place
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("verify")
+        .arg("--root")
+        .arg(&root)
+        .env("HOME", &home);
+    cmd.assert().code(0);
+}
+
+#[test]
+fn add_refuses_existing_sources_and_links() {
+    let (_tmp, root, home) = setup();
+    let target = home.join(".config/fish/conf.d/abbr.fish");
+    fs::create_dir_all(root.join(".config/fish/conf.d")).unwrap();
+    fs::write(root.join(".config/fish/conf.d/abbr.fish"), b"repo").unwrap();
+
+    add(&root, &home, &target)
+        .assert()
+        .failure()
+        .stderr(contains("already planned from"));
+    assert_eq!(fs::read(&target).unwrap(), b"abbr g git");
+
+    // Skipped by its companion, but still taken
+    fs::write(
+        root.join(".config/fish/conf.d/abbr.fish.lua"),
+        b"return false",
+    )
+    .unwrap();
+    add(&root, &home, &target)
+        .assert()
+        .failure()
+        .stderr(contains("Source already exists in the root"));
+
+    fs::remove_file(&target).unwrap();
+    std::os::unix::fs::symlink(home.join(".config/fish/conf.d"), &target).unwrap();
+    add(&root, &home, &target)
+        .assert()
+        .failure()
+        .stderr(contains("is already a symlink"));
+}
+
+#[test]
+fn add_dry_run_and_copy() {
+    let (_tmp, root, home) = setup();
+    let target = home.join(".config/fish/conf.d/abbr.fish");
+    let source = root.join(".config/fish/conf.d/abbr.fish");
+
+    add(&root, &home, &target)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("Would move ~/.config/fish/conf.d/abbr.fish to"))
+        .stdout(contains("Would symlink"));
+    assert!(!source.exists());
+    assert!(target.is_file() && !target.is_symlink());
+
+    add(&root, &home, &target)
+        .arg("--copy")
+        .assert()
+        .success()
+        .stdout(contains("Copied ~/.config/fish/conf.d/abbr.fish to"));
+    assert_eq!(fs::read(&source).unwrap(), b"abbr g git");
+    assert!(!target.is_symlink());
+}
+
+#[test]
+fn add_warns_about_renamed_directories() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(root.join(".config/fish-shell")).unwrap();
+    fs::write(root.join(".config/fish-shell/config.fish"), b"set x").unwrap();
+    fs::write(
+        root.join(".config/fish-shell.lua"),
+        b"return { link_dir = true, rename_to = 'fish' }",
+    )
+    .unwrap();
+
+    add(&root, &home, &home.join(".config/fish/conf.d/abbr.fish"))
+        .assert()
+        .success()
+        .stdout(contains("~/.config/fish is linked from"))
+        .stdout(contains("(rename_to), adding the file there"));
+    assert_eq!(
+        fs::read(root.join(".config/fish-shell/conf.d/abbr.fish")).unwrap(),
+        b"abbr g git"
+    );
+    assert!(!root.join(".config/fish").exists());
+}
@@ -0,0 +1,76 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn adopt_moves_target_into_root_and_links() {
+    let (_tmp, root, home) = dirs();
+
+    fs::write(root.join("a.txt"), b"repo version").unwrap();
+    fs::write(home.join("a.txt"), b"machine version").unwrap();
+
+    let mut dry = Command::cargo_bin("dotty").unwrap();
+    dry.arg("--root")
+        .arg(&root)
+        .arg("--adopt")
+        .arg("--dry-run")
+        .arg("--no-color");
+    dry.env("HOME", &home);
+    dry.assert()
+        .success()
+        .stdout(contains("Would adopt"))
+        .stdout(contains("1 adopted"));
+    assert_eq!(fs::read(root.join("a.txt")).unwrap(), b"repo version");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--adopt")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Adopted"))
+        .stdout(contains("Summary: 1 linked, 0 conflicts"))
+        .stdout(contains("1 adopted"));
+
+    assert_eq!(fs::read(root.join("a.txt")).unwrap(), b"machine version");
+    assert_eq!(
+        fs::read_link(home.join("a.txt")).unwrap(),
+        root.join("a.txt")
+    );
+}
+
+#[test]
+fn adopt_refuses_directories_and_transforms() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("mydir")).unwrap();
+    fs::create_dir_all(home.join("mydir")).unwrap();
+
+    fs::write(root.join("mydir.lua"), b"return { link = true }").unwrap();
+    fs::write(root.join("mydir/a.txt"), b"A").unwrap();
+    fs::write(root.join("t.txt"), b"template").unwrap();
+    fs::write(
+        root.join("t.txt.lua"),
+        b"return { transform = function(c) return c end }",
+    )
+    .unwrap();
+    fs::create_dir_all(home.join("t.txt")).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--adopt")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Summary: 0 linked, 2 conflicts"))
+        .stdout(contains("0 adopted"));
+
+    assert_eq!(fs::read(root.join("t.txt")).unwrap(), b"template");
+    assert!(home.join("mydir").is_dir() && !home.join("mydir").is_symlink());
+}
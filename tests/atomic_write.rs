@@ -0,0 +1,86 @@
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::{PermissionsExt, symlink};
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".gitconfig"), "[user]\n  name = me\n").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        r##"return { transform = function(content) return content .. "# generated\n" end }"##,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn a_failed_write_leaves_the_old_content_whole() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".gitconfig"), "old content\n").unwrap();
+    // Nothing can be created where the temporary file goes
+    fs::create_dir(home.join("..gitconfig.dotty-tmp")).unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to create temporary file"));
+
+    assert_eq!(
+        fs::read_to_string(home.join(".gitconfig")).unwrap(),
+        "old content\n"
+    );
+}
+
+#[test]
+fn writes_replace_the_target_and_leave_no_temporary_file() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".gitconfig"), "old content\n").unwrap();
+    // A temporary file left behind by an interrupted run
+    fs::write(home.join("..gitconfig.dotty-tmp"), "[user]\n").unwrap();
+    fs::set_permissions(root.join(".gitconfig"), fs::Permissions::from_mode(0o600)).unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Overwrote transformed file"));
+
+    assert_eq!(
+        fs::read_to_string(home.join(".gitconfig")).unwrap(),
+        "[user]\n  name = me\n# generated\n"
+    );
+    let mode = fs::metadata(home.join(".gitconfig"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(!home.join("..gitconfig.dotty-tmp").exists());
+}
+
+#[test]
+fn override_identical_replaces_the_link_itself() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("app.conf"), "setting = 1\n").unwrap();
+    fs::write(root.join("app.conf.lua"), "return { copy = true }").unwrap();
+    symlink(root.join("app.conf"), home.join("app.conf")).unwrap();
+
+    dotty(&root, &home)
+        .arg("--override-identical")
+        .assert()
+        .success();
+
+    assert!(!home.join("app.conf").is_symlink());
+    assert_eq!(
+        fs::read_to_string(home.join("app.conf")).unwrap(),
+        "setting = 1\n"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("app.conf")).unwrap(),
+        "setting = 1\n"
+    );
+    assert!(!home.join(".app.conf.dotty-tmp").exists());
+}
@@ -0,0 +1,131 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::create_dir_all(home.join(".config/app")).unwrap();
+    fs::write(root.join(".config/app/settings"), b"new").unwrap();
+    fs::write(
+        root.join(".config/app/settings.lua"),
+        r#"return { transform = function(content) return content .. "!" end }"#,
+    )
+    .unwrap();
+    fs::write(home.join(".config/app/settings"), b"edited in home").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn overwritten_file_is_moved_to_backup_dir() {
+    let (tmp, root, home) = setup();
+    let backups = tmp.path().join("backups");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--backup-dir")
+        .arg(&backups)
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Backed up ~/.config/app/settings to"))
+        .stdout(contains("1 backed up"));
+
+    assert_eq!(
+        fs::read_to_string(home.join(".config/app/settings")).unwrap(),
+        "new!"
+    );
+    assert_eq!(
+        fs::read_to_string(backups.join(".config/app/settings")).unwrap(),
+        "edited in home"
+    );
+}
+
+#[test]
+fn default_backup_dir_is_timestamped_state_dir() {
+    let (_tmp, root, home) = setup();
+    fs::remove_file(root.join(".config/app/settings.lua")).unwrap();
+    fs::write(home.join(".config/app/settings"), b"new").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--override-identical")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success().stdout(contains(
+        "Backed up ~/.config/app/settings to ~/.local/state/dotty/backups/",
+    ));
+
+    assert!(home.join(".config/app/settings").is_symlink());
+    let runs: Vec<_> = fs::read_dir(home.join(".local/state/dotty/backups"))
+        .unwrap()
+        .collect();
+    assert_eq!(runs.len(), 1);
+    let run = runs[0].as_ref().unwrap().path();
+    assert_eq!(run.file_name().unwrap().len(), "20260101-120000".len());
+    assert_eq!(
+        fs::read_to_string(run.join(".config/app/settings")).unwrap(),
+        "new"
+    );
+}
+
+#[test]
+fn dry_run_tells_where_the_backup_would_go() {
+    let (tmp, root, home) = setup();
+    let backups = tmp.path().join("home/saved");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--backup-dir")
+        .arg(&backups)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains(
+            "Would back up ~/.config/app/settings to ~/saved/.config/app/settings",
+        ))
+        .stdout(contains("1 to back up"));
+
+    assert!(!backups.exists());
+    assert_eq!(
+        fs::read_to_string(home.join(".config/app/settings")).unwrap(),
+        "edited in home"
+    );
+}
+
+#[test]
+fn failed_backup_leaves_the_target_alone() {
+    let (tmp, root, home) = setup();
+    // A file where the backup dir should be makes creating it fail
+    let backups = tmp.path().join("backups");
+    fs::write(&backups, b"").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--backup-dir")
+        .arg(&backups)
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Failed to back up ~/.config/app/settings"))
+        .stdout(contains("1 conflict"))
+        .stdout(contains("backed up").not());
+
+    assert_eq!(
+        fs::read_to_string(home.join(".config/app/settings")).unwrap(),
+        "edited in home"
+    );
+}
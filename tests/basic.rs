@@ -36,6 +36,6 @@ fn dry_run_reports_plans_conflicts_and_skips() {
         .stdout(predicate::str::contains("exists "))
         .stdout(predicate::str::contains("Skipped by lua"))
         .stdout(predicate::str::contains(
-            "Summary: 1 planned, 1 conflict, 1 skipped by lua",
+            "Summary: 1 planned, 1 conflict (1 file), 1 skipped by lua",
         ));
 }
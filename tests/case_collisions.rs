@@ -0,0 +1,72 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".app")).unwrap();
+    fs::write(root.join(".app/Config"), "upper").unwrap();
+    fs::write(root.join(".app/config"), "lower").unwrap();
+    fs::write(root.join(".zshrc"), "x").unwrap();
+    (tmp, root, home)
+}
+
+fn dotty(root: &Path, home: &Path) -> Command {
+    let mut cmd = common::dotty(root, home);
+    cmd.arg("--no-folding");
+    cmd
+}
+
+#[test]
+fn names_differing_by_case_collide_on_a_case_insensitive_target() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home)
+        .env("DOTTY_CASE_SENSITIVE", "0")
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "✗ Case collision: ~/.app/Config and ~/.app/config are one file on this case-insensitive target, so neither {} nor {} is placed",
+            root.join(".app/Config").display(),
+            root.join(".app/config").display()
+        )))
+        .stdout(contains("1 duplicate targets"));
+    assert!(!home.join(".app/Config").exists());
+    assert!(!home.join(".app/config").exists());
+    assert!(home.join(".zshrc").is_symlink());
+}
+
+#[test]
+fn case_sensitive_targets_get_both() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home).assert().success();
+    assert!(home.join(".app/Config").is_symlink());
+    assert!(home.join(".app/config").is_symlink());
+    // The probe file is gone again
+    assert!(fs::read_dir(&home).unwrap().all(|entry| {
+        !entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .contains("probe")
+    }));
+}
+
+#[test]
+fn assume_case_sensitive_skips_the_check() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home)
+        .arg("--assume-case-sensitive")
+        .env("DOTTY_CASE_SENSITIVE", "0")
+        .assert()
+        .success();
+    assert!(home.join(".app/Config").is_symlink());
+    assert!(home.join(".app/config").is_symlink());
+}
@@ -0,0 +1,25 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A temporary directory holding an empty `root` to stow from and `home` to stow into.
+pub fn dirs() -> (TempDir, PathBuf, PathBuf) {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+    (tmp, root, home)
+}
+
+/// `dotty` stowing `root` into `home`, without colors.
+pub fn dotty(root: &Path, home: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).arg("--no-color");
+    cmd.env("HOME", home);
+    cmd
+}
@@ -0,0 +1,86 @@
+use assert_cmd::Command;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+/// Three and a half 64 KiB chunks of text, so transforms can read it too.
+fn big(last: u8) -> Vec<u8> {
+    let mut content: Vec<u8> = (0..229_376u32).map(|i| b'a' + (i % 26) as u8).collect();
+    *content.last_mut().unwrap() = last;
+    content
+}
+
+fn setup(
+    companion: Option<&str>,
+    target_last: u8,
+) -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("blob.txt"), big(b'a')).unwrap();
+    if let Some(companion) = companion {
+        fs::write(root.join("blob.txt.lua"), companion).unwrap();
+    }
+    fs::write(home.join("blob.txt"), big(target_last)).unwrap();
+    (tmp, root, home)
+}
+
+fn dry_run(root: &Path, home: &Path, args: &[&str]) -> String {
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(root)
+        .arg("--no-color")
+        .arg("--dry-run")
+        .arg("--no-cache")
+        .args(args)
+        .env("HOME", home)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn conflicts_tell_a_different_last_byte() {
+    let (_tmp, root, home) = setup(None, b'a');
+    assert!(dry_run(&root, &home, &[]).contains("~/blob.txt <- "));
+    assert!(dry_run(&root, &home, &[]).contains("(identical)"));
+
+    let (_tmp, root, home) = setup(None, b'b');
+    assert!(dry_run(&root, &home, &[]).contains("(differs)"));
+}
+
+#[test]
+fn override_identical_needs_every_chunk_to_match() {
+    let (_tmp, root, home) = setup(None, b'a');
+    let out = dry_run(&root, &home, &["--override-identical"]);
+    assert!(
+        out.contains("Would override identical ~/blob.txt <- "),
+        "{out}"
+    );
+
+    let (_tmp, root, home) = setup(None, b'b');
+    let out = dry_run(&root, &home, &["--override-identical"]);
+    assert!(!out.contains("override identical"), "{out}");
+    assert!(out.contains("(differs)"), "{out}");
+}
+
+#[test]
+fn transforms_compare_against_the_output() {
+    let companion = "return { transform = function(c) return c end }";
+    let (_tmp, root, home) = setup(Some(companion), b'a');
+    let out = dry_run(&root, &home, &[]);
+    assert!(
+        out.contains("Would write (already in place) ~/blob.txt"),
+        "{out}"
+    );
+
+    let (_tmp, root, home) = setup(Some(companion), b'b');
+    let out = dry_run(&root, &home, &[]);
+    assert!(
+        out.contains("Would overwrite transformed file ~/blob.txt"),
+        "{out}"
+    );
+}
@@ -0,0 +1,113 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("ssh/config.d")).unwrap();
+    fs::write(root.join("ssh/config.d/20-homelab"), b"Host nas\n").unwrap();
+    fs::write(root.join("ssh/config.d/00-base"), b"Host *\n").unwrap();
+    fs::write(root.join("ssh/config.d/10-work"), b"Host bastion").unwrap();
+    fs::write(
+        root.join("ssh/config.d/10-work.lua"),
+        r#"return dotty.hostname() == "work-mbp""#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("ssh/config.d.lua"),
+        r#"return { concat_to = ".ssh/config" }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn concat_to_joins_fragments_sorted_by_name() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "work-mbp");
+    cmd.assert()
+        .success()
+        .stdout(contains("Wrote transformed file ~/.ssh/config"));
+
+    let target = home.join(".ssh/config");
+    assert!(!target.is_symlink());
+    assert_eq!(
+        fs::read_to_string(&target).unwrap(),
+        "Host *\nHost bastion\nHost nas\n"
+    );
+    assert!(!home.join("ssh").exists());
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "work-mbp");
+    cmd.assert()
+        .success()
+        .stdout(contains("Would write (already in place) ~/.ssh/config"));
+}
+
+#[test]
+fn fragment_companions_can_skip_fragments() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "home-mini");
+    cmd.assert().success().stdout(contains("1 skipped by lua"));
+
+    assert_eq!(
+        fs::read_to_string(home.join(".ssh/config")).unwrap(),
+        "Host *\nHost nas\n"
+    );
+}
+
+#[test]
+fn existing_file_is_replaced_like_a_transform() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".ssh")).unwrap();
+    fs::write(home.join(".ssh/config"), b"Host old\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "home-mini");
+    cmd.assert()
+        .success()
+        .stdout(contains("Would overwrite transformed file ~/.ssh/config"));
+    assert_eq!(
+        fs::read_to_string(home.join(".ssh/config")).unwrap(),
+        "Host old\n"
+    );
+}
+
+#[test]
+fn the_concat_dirs_own_ignore_file_and_defaults_apply() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("ssh/config.d/.dottyignore"), b"20-homelab\n").unwrap();
+    fs::write(
+        root.join("ssh/config.d/.dotty.lua"),
+        br#"return { transform = function(c) return "; " .. c end }"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "home-mini");
+    cmd.assert().success().stdout(contains("1 ignored"));
+
+    assert_eq!(
+        fs::read_to_string(home.join(".ssh/config")).unwrap(),
+        "; Host *\n"
+    );
+}
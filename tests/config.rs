@@ -0,0 +1,145 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(home.join(".config/dotty")).unwrap();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(root.join("notes.md"), b"notes").unwrap();
+    (tmp, root, home)
+}
+
+fn write_user_config(home: &std::path::Path, content: &str) {
+    fs::write(home.join(".config/dotty/dotty.toml"), content).unwrap();
+}
+
+#[test]
+fn user_config_provides_defaults() {
+    let (_tmp, root, home) = setup();
+    write_user_config(
+        &home,
+        &format!("root = {:?}\ndry_run = true\nexcludes = [\"*.md\"]\n", root),
+    );
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Would symlink ~/a.txt"))
+        .stdout(contains("Summary: 1 planned"))
+        .stdout(contains("1 excluded"));
+    assert!(!home.join("a.txt").exists());
+}
+
+#[test]
+fn cli_flags_override_config_values() {
+    let (tmp, root, home) = setup();
+    let other = tmp.path().join("other");
+    fs::create_dir_all(&other).unwrap();
+    write_user_config(
+        &home,
+        &format!(
+            "root = {:?}\ntarget = {:?}\n",
+            other,
+            tmp.path().join("missing")
+        ),
+    );
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--target")
+        .arg(&home)
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success().stdout(contains("Summary: 2 linked"));
+    assert!(home.join("a.txt").is_symlink());
+}
+
+#[test]
+fn no_flags_turn_off_config_booleans() {
+    let (_tmp, root, home) = setup();
+    write_user_config(
+        &home,
+        &format!(
+            "root = {:?}
+dry_run = true
+verbose = true
+",
+            root
+        ),
+    );
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.args(["--no-color", "--no-dry-run", "--no-verbose"]);
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Summary: 2 linked"))
+        .stdout(contains("Would symlink").not());
+    assert!(home.join("a.txt").is_symlink());
+}
+
+#[test]
+fn user_config_overrides_root_config() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join("dotty.toml"),
+        "dry_run = false\nexcludes = [\"*.md\"]\n",
+    )
+    .unwrap();
+    write_user_config(&home, "dry_run = true\n");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Summary: 1 planned"))
+        .stdout(contains("1 excluded"))
+        .stdout(contains("dotty.toml").not());
+}
+
+#[test]
+fn no_config_ignores_config_files() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("dotty.toml"), "excludes = [\"*.md\"]\n").unwrap();
+    write_user_config(&home, "dry_run = true\n");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--no-config")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success().stdout(contains("Summary: 2 linked"));
+}
+
+#[test]
+fn config_errors_name_file_and_key() {
+    let (_tmp, root, home) = setup();
+    write_user_config(&home, "dry_run = \"yes\"\n");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains(".config/dotty/dotty.toml"))
+        .stderr(contains("dry_run"));
+
+    write_user_config(&home, "colour = false\n");
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("unknown field `colour`"));
+}
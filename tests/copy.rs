@@ -0,0 +1,96 @@
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+mod common;
+use common::{dirs, dotty};
+
+#[test]
+fn copy_companion_copies_file_with_mode() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("karabiner.json"), b"{}").unwrap();
+    fs::set_permissions(
+        root.join("karabiner.json"),
+        fs::Permissions::from_mode(0o600),
+    )
+    .unwrap();
+    fs::write(root.join("karabiner.json.lua"), b"return { copy = true }").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Wrote copy ~/karabiner.json"))
+        .stdout(contains(
+            "Summary: 1 linked, 0 conflicts, 0 skipped by lua, 0 overrides, 1 copied",
+        ));
+
+    let target = home.join("karabiner.json");
+    assert!(!target.is_symlink());
+    assert_eq!(fs::read(&target).unwrap(), b"{}");
+    assert_eq!(
+        fs::metadata(&target).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+
+    // Repeated runs find the copy already in place
+    dotty(&root, &home)
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stdout(contains("Would write (already in place) ~/karabiner.json"));
+}
+
+#[test]
+fn unstow_removes_unmodified_copies_only() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(root.join("a.txt.lua"), b"return { copy = true }").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    fs::write(root.join("b.txt.lua"), b"return { copy = true }").unwrap();
+    fs::write(home.join("a.txt"), b"A").unwrap();
+    fs::write(home.join("b.txt"), b"edited").unwrap();
+
+    dotty(&root, &home)
+        .arg("--unstow")
+        .assert()
+        .success()
+        .stdout(contains("Summary: 1 removed, 1 kept, 0 missing"));
+    assert!(!home.join("a.txt").exists());
+    assert!(home.join("b.txt").exists());
+}
+
+#[test]
+fn copy_flag_copies_everything_and_reports_existing_links() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::write(root.join(".config/app/a.txt"), b"A").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    fs::write(root.join("b.txt.lua"), b"return { rename_to = '.b' }").unwrap();
+
+    dotty(&root, &home).arg("--no-folding").assert().success();
+    assert!(home.join(".b").is_symlink());
+    fs::remove_file(home.join(".config/app/a.txt")).unwrap();
+
+    dotty(&root, &home)
+        .arg("--copy")
+        .assert()
+        .success()
+        .stdout(contains("is a symlink, expected regular file: ~/.b"))
+        .stdout(contains(
+            "0 linked, 1 conflict (1 symlink), 0 skipped by lua, 0 overrides, 1 copied",
+        ));
+    assert!(!home.join(".config/app/a.txt").is_symlink());
+    assert_eq!(fs::read(home.join(".config/app/a.txt")).unwrap(), b"A");
+
+    dotty(&root, &home)
+        .arg("--copy")
+        .arg("--override-identical")
+        .assert()
+        .success()
+        .stdout(contains(
+            "0 linked, 1 already in place, 0 conflicts, 0 skipped by lua, 1 overrides, 1 copied",
+        ));
+    assert!(!home.join(".b").is_symlink());
+    assert_eq!(fs::read(home.join(".b")).unwrap(), b"B");
+}
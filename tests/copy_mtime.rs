@@ -0,0 +1,82 @@
+use predicates::prelude::*;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("app.conf"), "x = 1\n").unwrap();
+    fs::write(root.join("app.conf.lua"), "return { copy = true }").unwrap();
+    set_mtime(&root.join("app.conf"), 1_000_000);
+    (tmp, root, home)
+}
+
+fn set_mtime(path: &Path, secs: u64) {
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+        .unwrap();
+}
+
+fn mtime(path: &Path) -> SystemTime {
+    fs::metadata(path).unwrap().modified().unwrap()
+}
+
+#[test]
+fn copies_carry_the_source_timestamp() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home).assert().success();
+
+    assert_eq!(
+        mtime(&home.join("app.conf")),
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)
+    );
+}
+
+#[test]
+fn repeat_runs_leave_the_copy_alone() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+    let before = fs::metadata(home.join("app.conf")).unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 already in place"));
+
+    let after = fs::metadata(home.join("app.conf")).unwrap();
+    assert_eq!(after.modified().unwrap(), before.modified().unwrap());
+    assert_eq!(
+        std::os::unix::fs::MetadataExt::ino(&after),
+        std::os::unix::fs::MetadataExt::ino(&before)
+    );
+}
+
+#[test]
+fn changed_sources_are_copied_again_with_their_new_timestamp() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+
+    fs::write(root.join("app.conf"), "x = 2\n").unwrap();
+    set_mtime(&root.join("app.conf"), 2_000_000);
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Overwrote copy"));
+
+    assert_eq!(
+        fs::read_to_string(home.join("app.conf")).unwrap(),
+        "x = 2\n"
+    );
+    assert_eq!(
+        mtime(&home.join("app.conf")),
+        SystemTime::UNIX_EPOCH + Duration::from_secs(2_000_000)
+    );
+}
@@ -0,0 +1,65 @@
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs as unix_fs;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".gitconfig"), b"[user]").unwrap();
+    // Left behind by a dotfiles repo that has since moved
+    unix_fs::symlink(
+        tmp.path().join("old-repo/.gitconfig"),
+        home.join(".gitconfig"),
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn dangling_symlink_is_replaced_by_the_link() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("Would replace (dangling) ~/.gitconfig -> "))
+        .stdout(contains("Would symlink ~/.gitconfig -> "))
+        .stdout(contains("Summary: 1 planned, 0 conflicts"))
+        .stdout(contains("1 dangling to replace"));
+    assert!(!home.join(".gitconfig").exists());
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("replaced dangling symlink ~/.gitconfig -> "))
+        .stdout(contains("Summary: 1 linked, 0 conflicts"))
+        .stdout(contains("1 dangling replaced"));
+    assert_eq!(
+        fs::read_link(home.join(".gitconfig")).unwrap(),
+        root.join(".gitconfig")
+    );
+}
+
+#[test]
+fn dangling_symlink_is_replaced_by_a_transformed_file() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "\n" end }"#,
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("replaced dangling symlink ~/.gitconfig -> "))
+        .stdout(contains("Wrote transformed file ~/.gitconfig"))
+        .stdout(contains("0 conflicts"));
+    let target = home.join(".gitconfig");
+    assert!(!target.is_symlink());
+    assert_eq!(fs::read(target).unwrap(), b"[user]\n");
+}
@@ -0,0 +1,62 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".git/objects")).unwrap();
+    fs::create_dir_all(root.join(".config")).unwrap();
+    fs::write(root.join(".git/HEAD"), b"ref").unwrap();
+    fs::write(root.join(".DS_Store"), b"junk").unwrap();
+    fs::write(root.join(".config/.DS_Store"), b"junk").unwrap();
+    fs::write(root.join(".vimrc.swp"), b"swap").unwrap();
+    fs::write(root.join(".vimrc~"), b"backup").unwrap();
+    fs::write(root.join(".#lock"), b"emacs").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn junk_files_are_ignored_by_default() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--verbose")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains(
+            "Ignored (default rule '.DS_Store'): ~/.config/.DS_Store",
+        ))
+        .stdout(contains("Ignored (default rule '.git'): ~/.git"))
+        .stdout(contains("Summary: 1 linked"))
+        .stdout(contains("6 ignored"));
+
+    assert!(home.join(".vimrc").is_symlink());
+    assert!(!home.join(".git").exists());
+    assert!(!home.join(".DS_Store").exists());
+}
+
+#[test]
+fn no_default_ignores_links_everything() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--no-default-ignores")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success().stdout(contains("Summary: 7 linked"));
+
+    // Nothing inside .git is hidden any more, so it is folded into one link
+    assert_eq!(fs::read_link(home.join(".git")).unwrap(), root.join(".git"));
+    assert!(home.join(".DS_Store").is_symlink());
+}
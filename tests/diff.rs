@@ -0,0 +1,73 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".vimrc"), b"set number\nsyntax on\n").unwrap();
+    fs::write(home.join(".vimrc"), b"set nonumber\nsyntax on\n").unwrap();
+    (tmp, root, home)
+}
+
+fn dotty(root: &std::path::Path, home: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).arg("--diff");
+    cmd.env("HOME", home);
+    cmd
+}
+
+#[test]
+fn conflicts_show_a_unified_diff() {
+    let (_tmp, root, home) = setup();
+
+    for dry_run in [true, false] {
+        let mut cmd = dotty(&root, &home);
+        if dry_run {
+            cmd.arg("--dry-run");
+        }
+        cmd.arg("--no-color")
+            .assert()
+            .success()
+            .stdout(contains("exists (file) ~/.vimrc"))
+            .stdout(contains("--- ~/.vimrc\n"))
+            .stdout(contains("@@ -1,2 +1,2 @@"))
+            .stdout(contains("\n-set nonumber\n+set number\n syntax on\n"));
+    }
+}
+
+#[test]
+fn identical_and_binary_conflicts() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".vimrc"), b"set number\nsyntax on\n").unwrap();
+    fs::write(root.join("logo.png"), b"\x89PNG\0one").unwrap();
+    fs::write(home.join("logo.png"), b"\x89PNG\0two").unwrap();
+
+    dotty(&root, &home)
+        .arg("--no-color")
+        .assert()
+        .success()
+        .stdout(contains("--- ~/.vimrc").not())
+        .stdout(contains("Binary files ~/logo.png and"));
+}
+
+#[test]
+fn long_diffs_are_cut_off() {
+    let (_tmp, root, home) = setup();
+    let lines: String = (0..50).map(|i| format!("line {i}\n")).collect();
+    fs::write(root.join(".vimrc"), &lines).unwrap();
+
+    dotty(&root, &home)
+        .arg("--diff-lines")
+        .arg("5")
+        .arg("--no-color")
+        .assert()
+        .success()
+        .stdout(contains("+line 1\n"))
+        .stdout(contains("+line 2").not())
+        .stdout(contains("... 48 more diff lines"));
+}
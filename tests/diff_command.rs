@@ -0,0 +1,83 @@
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/git")).unwrap();
+    fs::write(root.join(".config/git/config"), "[user]\nname = me\n").unwrap();
+    fs::write(
+        root.join(".config/git/config.lua"),
+        br#"return { transform = function(c) return c:gsub("= me", "= Me") end }"#,
+    )
+    .unwrap();
+    fs::write(root.join(".zshrc"), "zsh\n").unwrap();
+    // Would fail the run if it were evaluated
+    fs::write(root.join(".zshrc.lua"), "error('boom')").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn diff_by_root_path_runs_only_that_companion() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".config/git")).unwrap();
+    fs::write(home.join(".config/git/config"), "[user]\nname = old\n").unwrap();
+
+    dotty(&root, &home)
+        .arg("diff")
+        .arg(".config/git/config")
+        .assert()
+        .code(1)
+        .stdout(contains("--- ~/.config/git/config\n"))
+        .stdout(contains("-name = old"))
+        .stdout(contains("+name = Me"))
+        .stdout(contains("\x1b[").not());
+    assert_eq!(
+        fs::read_to_string(home.join(".config/git/config")).unwrap(),
+        "[user]\nname = old\n"
+    );
+}
+
+#[test]
+fn diff_by_target_exits_zero_when_identical() {
+    let (_tmp, root, home) = setup();
+    fs::remove_file(root.join(".zshrc.lua")).unwrap();
+    dotty(&root, &home).assert().success();
+
+    dotty(&root, &home)
+        .arg("diff")
+        .arg(home.join(".config/git/config"))
+        .assert()
+        .code(0)
+        .stdout(contains("~/.config/git/config matches the plan"));
+    dotty(&root, &home)
+        .arg("diff")
+        .arg(home.join(".zshrc"))
+        .assert()
+        .code(0);
+
+    fs::remove_file(home.join(".zshrc")).unwrap();
+    dotty(&root, &home)
+        .arg("diff")
+        .arg(home.join(".zshrc"))
+        .assert()
+        .code(1)
+        .stdout(contains("--- ~/.zshrc (missing)"))
+        .stdout(contains("+zsh"));
+}
+
+#[test]
+fn diff_of_unknown_paths_fails_with_two() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home)
+        .arg("diff")
+        .arg(".bashrc")
+        .assert()
+        .code(2)
+        .stderr(contains("Nothing in the root is planned from .bashrc"));
+}
@@ -0,0 +1,89 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+
+mod common;
+use common::dirs;
+
+fn run(root: &std::path::Path, home: &std::path::Path) -> assert_cmd::assert::Assert {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).arg("--no-color");
+    cmd.env("HOME", home);
+    cmd.assert()
+}
+
+#[test]
+fn dir_companion_skips_whole_subtree() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("work/nested")).unwrap();
+    fs::write(root.join("work/a.txt"), b"A").unwrap();
+    fs::write(root.join("work/nested/b.txt"), b"B").unwrap();
+    fs::write(root.join("work/.dotty.lua"), b"return { include = false }").unwrap();
+    fs::write(root.join("c.txt"), b"C").unwrap();
+
+    run(&root, &home)
+        .success()
+        .stdout(contains("1 linked, 0 conflicts, 1 skipped by lua"));
+
+    assert!(!home.join("work").exists());
+    assert!(home.join("c.txt").is_symlink());
+}
+
+#[test]
+fn dir_companion_defaults_apply_to_every_file_and_are_never_linked() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("bin")).unwrap();
+    fs::write(root.join("bin/a.sh"), b"A").unwrap();
+    fs::write(root.join("bin/b.sh"), b"B").unwrap();
+    fs::write(
+        root.join("bin/.dotty.lua"),
+        br#"return {
+  rename = function(name) return (name:gsub("%.sh$", "")) end,
+  transform = function(c) return c .. "!" end,
+}"#,
+    )
+    .unwrap();
+    // A per-file companion overrides the directory-level rename but keeps the transform
+    fs::write(root.join("bin/b.sh.lua"), b"return { rename_to = 'bee' }").unwrap();
+    // One returning plain `true` opts out of the defaults altogether
+    fs::write(root.join("bin/c.sh"), b"C").unwrap();
+    fs::write(root.join("bin/c.sh.lua"), b"return true").unwrap();
+
+    run(&root, &home).success();
+
+    assert_eq!(fs::read(home.join("bin/a")).unwrap(), b"A!");
+    assert_eq!(fs::read(home.join("bin/bee")).unwrap(), b"B!");
+    assert!(home.join("bin/c.sh").is_symlink());
+    assert!(!home.join("bin/c").exists());
+    assert!(!home.join("bin/.dotty.lua").exists());
+    assert!(!home.join("bin/a.sh").exists());
+}
+
+#[test]
+fn nested_dir_companions_layer_with_deepest_winning() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("conf/inner")).unwrap();
+    fs::write(root.join("conf/a.txt"), b"A").unwrap();
+    fs::write(root.join("conf/inner/b.txt"), b"B").unwrap();
+    fs::write(
+        root.join("conf/.dotty.lua"),
+        br#"return {
+  rename = function(name) return "outer-" .. name end,
+  transform = function(c) return c .. "-outer" end,
+}"#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("conf/inner/.dotty.lua"),
+        br#"return { transform = function(c) return c .. "-inner" end }"#,
+    )
+    .unwrap();
+
+    run(&root, &home).success();
+
+    assert_eq!(fs::read(home.join("conf/outer-a.txt")).unwrap(), b"A-outer");
+    assert_eq!(
+        fs::read(home.join("conf/inner/outer-b.txt")).unwrap(),
+        b"B-inner"
+    );
+}
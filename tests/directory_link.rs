@@ -82,7 +82,9 @@ fn directory_link_already_in_place() {
     cmd.assert()
         .success()
         .stdout(contains("Would link dir (already in place)"))
-        .stdout(contains("Summary: 1 planned, 0 conflicts"));
+        .stdout(contains(
+            "Summary: 0 planned, 1 already in place, 0 conflicts",
+        ));
 }
 
 #[test]
@@ -186,3 +188,43 @@ fn directory_without_lua_recurses_normally() {
         .stdout(contains("Would symlink"))
         .stdout(contains("Summary: 1 planned, 0 conflicts"));
 }
+
+#[test]
+fn link_dir_renames_and_reports_foreign_symlink_conflict() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(root.join("nvim")).unwrap();
+    fs::create_dir_all(root.join("kitty")).unwrap();
+    fs::create_dir_all(home.join(".config")).unwrap();
+
+    fs::write(root.join("nvim/init.lua"), b"init").unwrap();
+    fs::write(root.join("kitty/kitty.conf"), b"kitty").unwrap();
+    fs::write(
+        root.join("nvim.lua"),
+        b"return { link_dir = true, rename_to = '.nvim' }",
+    )
+    .unwrap();
+    fs::write(root.join("kitty.lua"), b"return { link_dir = true }").unwrap();
+    let elsewhere = tmp.path().join("elsewhere");
+    fs::create_dir_all(&elsewhere).unwrap();
+    unix_fs::symlink(&elsewhere, home.join("kitty")).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(root.to_string_lossy().to_string())
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+
+    cmd.assert()
+        .success()
+        .stdout(contains("✗ exists (symlink → "))
+        .stdout(contains("elsewhere) ~/kitty <- "))
+        .stdout(contains("Summary: 1 linked, 1 conflict (1 symlink)"));
+
+    assert_eq!(
+        fs::read_link(home.join(".nvim")).unwrap(),
+        root.join("nvim")
+    );
+    assert_eq!(fs::read_link(home.join("kitty")).unwrap(), elsewhere);
+}
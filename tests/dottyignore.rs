@@ -0,0 +1,53 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn dottyignore_rules_apply_during_traversal() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("scripts")).unwrap();
+    fs::create_dir_all(root.join(".config/app/cache")).unwrap();
+
+    fs::write(
+        root.join(".dottyignore"),
+        "# repo tooling\nscripts/\n*.log\n!keep.log\n/README.md\n",
+    )
+    .unwrap();
+    fs::write(root.join("README.md"), b"docs").unwrap();
+    fs::write(root.join("scripts/install.sh"), b"sh").unwrap();
+    fs::write(root.join("debug.log"), b"log").unwrap();
+    fs::write(root.join("keep.log"), b"log").unwrap();
+    fs::write(root.join(".config/app/README.md"), b"nested readme").unwrap();
+    fs::write(root.join(".config/app/config"), b"cfg").unwrap();
+    fs::write(root.join(".config/app/cache/blob"), b"blob").unwrap();
+    fs::write(root.join(".config/app/.dottyignore"), "cache/\n").unwrap();
+    fs::write(root.join("scripts.lua"), b"error('should not run')").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--verbose")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Ignored (.dottyignore: 'scripts/'): ~/scripts"))
+        .stdout(contains("Ignored (.dottyignore: '*.log'): ~/debug.log"))
+        .stdout(contains(
+            "Ignored (.dottyignore: '/README.md'): ~/README.md",
+        ))
+        .stdout(contains(
+            "Ignored (.config/app/.dottyignore: 'cache/'): ~/.config/app/cache",
+        ))
+        .stdout(contains("Summary: 3 linked"))
+        .stdout(contains("4 ignored"));
+
+    assert!(home.join("keep.log").is_symlink());
+    assert!(home.join(".config/app/README.md").is_symlink());
+    assert!(home.join(".config/app/config").is_symlink());
+    assert!(!home.join(".dottyignore").exists());
+    assert!(!home.join(".config/app/.dottyignore").exists());
+}
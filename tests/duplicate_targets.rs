@@ -0,0 +1,86 @@
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), "x").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn renamed_file_and_literal_file_with_the_same_target_are_both_skipped() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("a.conf"), "a").unwrap();
+    fs::write(root.join("a.conf.lua"), "return { rename_to = 'app.conf' }").unwrap();
+    fs::write(root.join("app.conf"), "b").unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "✗ Duplicate target: ~/app.conf is claimed by both {} and {}, placing neither",
+            root.join("a.conf").display(),
+            root.join("app.conf").display()
+        )))
+        .stdout(contains("1 duplicate targets"));
+    assert!(!home.join("app.conf").exists());
+    assert!(home.join(".zshrc").is_symlink());
+}
+
+#[test]
+fn relocated_target_inside_a_linked_directory_is_a_duplicate() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(root.join(".config")).unwrap();
+    fs::create_dir_all(root.join("extra")).unwrap();
+    fs::write(root.join(".config/app.conf"), "a").unwrap();
+    fs::write(root.join("extra/app.conf"), "b").unwrap();
+    fs::write(
+        root.join("extra/app.conf.lua"),
+        "return { target_path = '.config/app.conf' }",
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains(
+            "Duplicate target: ~/.config/app.conf is claimed by both",
+        ))
+        .stdout(contains("1 duplicate targets"));
+
+    dotty(&root, &home).assert().success();
+    assert!(!home.join(".config").exists());
+    assert!(home.join(".zshrc").is_symlink());
+}
+
+#[test]
+fn blocks_injected_into_one_file_are_no_duplicates() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("path.sh"), "export PATH=$PATH:~/bin\n").unwrap();
+    fs::write(
+        root.join("path.sh.lua"),
+        "return { target_path = '.profile', inject = true, markers = { '# >>> path', '# <<< path' } }",
+    )
+    .unwrap();
+    fs::write(root.join("editor.sh"), "export EDITOR=vim\n").unwrap();
+    fs::write(
+        root.join("editor.sh.lua"),
+        "return { target_path = '.profile', inject = true }",
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Duplicate").not());
+    let profile = fs::read_to_string(home.join(".profile")).unwrap();
+    assert!(profile.contains("export PATH=$PATH:~/bin"));
+    assert!(profile.contains("export EDITOR=vim"));
+}
@@ -0,0 +1,97 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, PathBuf, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh\n").unwrap();
+    fs::write(root.join(".gitconfig"), b"[user]\n").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "-- generated\n" end }"#,
+    )
+    .unwrap();
+    // An "editor" that appends a line to the file it is given
+    let editor = tmp.path().join("editor");
+    fs::write(&editor, "#!/bin/sh\necho edited >> \"$1\"\n").unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    (tmp, root, home, editor)
+}
+
+fn edit(root: &Path, home: &Path, path: &str) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(root)
+        .arg("--no-color")
+        .arg("edit")
+        .arg(home.join(path));
+    cmd.env("HOME", home).env_remove("VISUAL");
+    cmd
+}
+
+#[test]
+fn edit_transformed_file_warns_and_refreshes() {
+    let (_tmp, root, home, editor) = setup();
+    edit(&root, &home, ".gitconfig")
+        .env("EDITOR", &editor)
+        .env("DOTTY_ASSUME_TTY", "1")
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stdout(contains("~/.gitconfig is a transform written from"))
+        .stdout(contains("edits made to it are lost on the next run"))
+        .stdout(contains("Refresh ~/.gitconfig now? [y/N]"));
+    assert_eq!(
+        fs::read_to_string(root.join(".gitconfig")).unwrap(),
+        "[user]\nedited\n"
+    );
+    assert_eq!(
+        fs::read_to_string(home.join(".gitconfig")).unwrap(),
+        "[user]\nedited\n-- generated\n"
+    );
+}
+
+#[test]
+fn edit_linked_file_opens_the_source() {
+    let (_tmp, root, home, editor) = setup();
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .env("HOME", &home)
+        .assert()
+        .success();
+
+    edit(&root, &home, ".zshrc")
+        .env("EDITOR", &editor)
+        .assert()
+        .success()
+        .stdout(contains("lost on the next run").not())
+        .stdout(contains("Refresh").not());
+    assert_eq!(
+        fs::read_to_string(root.join(".zshrc")).unwrap(),
+        "zsh\nedited\n"
+    );
+}
+
+#[test]
+fn edit_without_editor_fails_clearly() {
+    let (_tmp, root, home, _editor) = setup();
+    edit(&root, &home, ".zshrc")
+        .env_remove("EDITOR")
+        .assert()
+        .failure()
+        .stderr(contains("Neither $EDITOR nor $VISUAL is set"));
+    edit(&root, &home, ".bashrc")
+        .env("EDITOR", "true")
+        .assert()
+        .failure()
+        .stderr(contains("No source in the root maps to ~/.bashrc"));
+}
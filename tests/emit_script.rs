@@ -0,0 +1,127 @@
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("Library/Application Support/Code/User")).unwrap();
+    fs::create_dir_all(root.join(".config/nvim")).unwrap();
+    fs::write(root.join(".zshrc"), b"zsh\n").unwrap();
+    fs::write(
+        root.join("Library/Application Support/Code/User/settings.json"),
+        b"{}\n",
+    )
+    .unwrap();
+    fs::write(root.join("it's.conf"), b"quoted\n").unwrap();
+    fs::write(root.join(".config/nvim/init.lua"), b"-- nvim\n").unwrap();
+    fs::write(root.join(".gitconfig"), b"[user]\nEOF\n").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "$HOME `x`\n" end, mode = "0600" }"#,
+    )
+    .unwrap();
+    fs::write(root.join(".npmrc"), b"npm").unwrap();
+    fs::write(
+        root.join(".npmrc.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    fs::write(root.join(".vimrc"), b"vim\n").unwrap();
+    fs::write(root.join(".vimrc.lua"), b"return false").unwrap();
+    (tmp, root, home)
+}
+
+/// Everything below `dir` except dotty's own state: links with their destination,
+/// files with their content and mode.
+fn snapshot(dir: &Path) -> BTreeMap<PathBuf, String> {
+    let mut found = BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current).unwrap() {
+            let path = entry.unwrap().path();
+            let rel = path.strip_prefix(dir).unwrap().to_path_buf();
+            if rel.starts_with(".local") {
+                continue;
+            }
+            let meta = fs::symlink_metadata(&path).unwrap();
+            let state = if meta.is_symlink() {
+                format!("link {}", fs::read_link(&path).unwrap().display())
+            } else if meta.is_dir() {
+                pending.push(path);
+                "dir".to_string()
+            } else {
+                format!(
+                    "file {:o} {}",
+                    meta.permissions().mode() & 0o7777,
+                    fs::read_to_string(&path).unwrap()
+                )
+            };
+            found.insert(rel, state);
+        }
+    }
+    found
+}
+
+#[test]
+fn script_produces_the_same_tree_as_a_real_run() {
+    let (tmp, root, home) = setup();
+    let script = tmp.path().join("install.sh");
+    // Folds the Code directory, whose link has a space in its path
+    fs::create_dir_all(home.join("Library/Application Support")).unwrap();
+
+    dotty(&root, &home)
+        .arg("--emit-script")
+        .arg(&script)
+        .assert()
+        .success()
+        .stdout(contains("Wrote script to"));
+    assert!(!home.join(".zshrc").exists());
+    assert_eq!(
+        fs::metadata(&script).unwrap().permissions().mode() & 0o111,
+        0o111
+    );
+    let content = fs::read_to_string(&script).unwrap();
+    assert!(content.contains("# skipped by lua: ~/.vimrc"));
+    assert!(content.contains("'\\''"));
+
+    std::process::Command::new("sh")
+        .arg(&script)
+        .status()
+        .unwrap()
+        .success()
+        .then_some(())
+        .unwrap();
+    let scripted = snapshot(&home);
+    fs::remove_dir_all(&home).unwrap();
+    fs::create_dir_all(home.join("Library/Application Support")).unwrap();
+
+    dotty(&root, &home).assert().success();
+    assert_eq!(scripted, snapshot(&home));
+    assert!(scripted[Path::new("Library/Application Support/Code")].starts_with("link "));
+    assert!(scripted.contains_key(Path::new("it's.conf")));
+}
+
+#[test]
+fn conflicts_become_commented_warnings_on_stdout() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+
+    dotty(&root, &home)
+        .arg("--emit-script")
+        .arg("-")
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("#!/bin/sh\n"))
+        .stdout(contains(
+            "# WARNING: conflict at ~/.zshrc (file), left alone",
+        ))
+        .stdout(contains("Wrote script").not());
+    assert_eq!(fs::read(home.join(".zshrc")).unwrap(), b"mine");
+}
@@ -0,0 +1,95 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::write(root.join(".config/app/a.conf"), b"a").unwrap();
+    fs::write(root.join(".config/app/b.conf"), b"b").unwrap();
+    (tmp, root, home)
+}
+
+fn dotty(root: &Path, home: &Path) -> Command {
+    let mut cmd = common::dotty(root, home);
+    cmd.arg("--no-folding");
+    cmd
+}
+
+#[test]
+fn unstow_removes_directories_dotty_created() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+    assert!(home.join(".config/app/a.conf").is_symlink());
+
+    dotty(&root, &home)
+        .arg("--unstow")
+        .assert()
+        .success()
+        .stdout(contains("Removed empty directory ~/.config/app/"))
+        .stdout(contains("Removed empty directory ~/.config/"))
+        .stdout(contains(
+            "Summary: 2 removed, 0 kept, 0 missing, 2 empty dirs removed",
+        ));
+    assert!(!home.join(".config").exists());
+
+    // Undoing the unstow recreates the directories before the links
+    dotty(&root, &home)
+        .arg("undo")
+        .assert()
+        .success()
+        .stdout(contains("Restored directory ~/.config/app/"));
+    assert!(home.join(".config/app/a.conf").is_symlink());
+}
+
+#[test]
+fn directories_that_existed_before_are_kept() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".config")).unwrap();
+    dotty(&root, &home).assert().success();
+
+    dotty(&root, &home)
+        .arg("--unstow")
+        .assert()
+        .success()
+        .stdout(contains("Removed empty directory ~/.config/app/"))
+        .stdout(contains("Removed empty directory ~/.config/\n").not());
+    assert!(!home.join(".config/app").exists());
+    assert!(home.join(".config").is_dir());
+}
+
+#[test]
+fn directories_with_other_files_are_kept() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+    fs::write(home.join(".config/app/notes.txt"), b"mine").unwrap();
+
+    dotty(&root, &home)
+        .arg("--unstow")
+        .assert()
+        .success()
+        .stdout(contains("empty directory").not());
+    assert!(home.join(".config/app/notes.txt").is_file());
+}
+
+#[test]
+fn dry_run_lists_directories_to_remove() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+
+    dotty(&root, &home)
+        .arg("--unstow")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("Would remove empty directory ~/.config/app/"))
+        .stdout(contains("Would remove empty directory ~/.config/"))
+        .stdout(contains("2 empty dirs to remove"));
+    assert!(home.join(".config/app/a.conf").is_symlink());
+}
@@ -0,0 +1,73 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn env_vars_provide_root_target_and_dry_run() {
+    let (tmp, root, home) = setup();
+    let target = tmp.path().join("target");
+    fs::create_dir_all(&target).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--verbose");
+    cmd.env("HOME", &home)
+        .env("DOTTY_ROOT", &root)
+        .env("DOTTY_TARGET", &target)
+        .env("DOTTY_DRY_RUN", "yes")
+        .env("DOTTY_NO_COLOR", "1");
+    cmd.assert()
+        .success()
+        .stdout(contains(format!("root from DOTTY_ROOT={}", root.display())))
+        .stdout(contains("dry_run from DOTTY_DRY_RUN (true)"))
+        .stdout(contains("no_color from DOTTY_NO_COLOR (true)"))
+        .stdout(contains("Would symlink ~/a.txt"))
+        .stdout(contains("Summary: 1 planned"));
+    assert!(!target.join("a.txt").exists());
+}
+
+#[test]
+fn cli_flags_win_over_env_vars() {
+    let (tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home)
+        .env("DOTTY_ROOT", tmp.path().join("missing"))
+        .env("DOTTY_DRY_RUN", "false");
+    cmd.assert().success().stdout(contains("Summary: 1 linked"));
+    assert!(home.join("a.txt").is_symlink());
+}
+
+#[test]
+fn env_vars_win_over_config() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".config/dotty")).unwrap();
+    fs::write(home.join(".config/dotty/dotty.toml"), "dry_run = true\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_DRY_RUN", "0");
+    cmd.assert().success().stdout(contains("Summary: 1 linked"));
+}
+
+#[test]
+fn invalid_boolean_env_var_is_an_error() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root);
+    cmd.env("HOME", &home).env("DOTTY_DRY_RUN", "maybe");
+    cmd.assert()
+        .failure()
+        .stderr(contains("Invalid value for DOTTY_DRY_RUN: 'maybe'"));
+}
@@ -0,0 +1,64 @@
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+/// A home whose `~/.config` is a symlink to a directory outside of it.
+fn setup() -> (TempDir, PathBuf, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    let old = tmp.path().join("old-volume/config");
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::create_dir_all(&old).unwrap();
+    symlink(&old, home.join(".config")).unwrap();
+    fs::write(root.join(".config/app/app.conf"), "x").unwrap();
+    fs::write(root.join(".zshrc"), "x").unwrap();
+    (tmp, root, home, old)
+}
+
+#[test]
+fn targets_below_a_symlink_leading_out_of_home_are_conflicts() {
+    let (_tmp, root, home, old) = setup();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "Conflict: escapes target, ~/.config/app would be placed in {}",
+            old.canonicalize().unwrap().display()
+        )))
+        .stdout(contains("1 conflict"));
+    assert!(!old.join("app").exists());
+    assert!(home.join(".zshrc").is_symlink());
+}
+
+#[test]
+fn allow_escaped_targets_places_them_anyway() {
+    let (_tmp, root, home, old) = setup();
+
+    dotty(&root, &home)
+        .arg("--allow-escaped-targets")
+        .assert()
+        .success();
+    // The directory is folded into one link, placed through ~/.config
+    assert_eq!(
+        fs::read_link(old.join("app")).unwrap(),
+        root.join(".config/app")
+    );
+}
+
+#[test]
+fn declared_absolute_targets_are_not_checked() {
+    let (_tmp, root, home, old) = setup();
+    fs::write(
+        root.join(".config/app/app.conf.lua"),
+        "return { target = '~/.config/app/app.conf' }",
+    )
+    .unwrap();
+
+    dotty(&root, &home).assert().success();
+    assert!(old.join("app/app.conf").is_symlink());
+}
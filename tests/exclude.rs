@@ -0,0 +1,61 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn exclude_prunes_matching_paths() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("scripts/deep")).unwrap();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+
+    fs::write(root.join("README.md"), b"docs").unwrap();
+    fs::write(root.join(".config/app/NOTES.md"), b"notes").unwrap();
+    fs::write(root.join(".config/app/config"), b"cfg").unwrap();
+    fs::write(root.join("scripts/deep/install.sh"), b"sh").unwrap();
+    // The companion must never run for excluded entries
+    fs::write(root.join("scripts.lua"), b"error('should not run')").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--exclude")
+        .arg("scripts/**")
+        .arg("--exclude")
+        .arg("*.md")
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Excluded by 'scripts/**': ~/scripts"))
+        .stdout(contains("Excluded by '*.md': ~/README.md"))
+        .stdout(contains("Excluded by '*.md': ~/.config/app/NOTES.md"))
+        .stdout(contains("Summary: 1 planned"))
+        .stdout(contains("3 excluded"));
+}
+
+#[test]
+fn exclude_with_path_only_matches_full_relative_path() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::write(root.join("a/x.txt"), b"x").unwrap();
+    fs::write(root.join("x.txt"), b"x").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--exclude")
+        .arg("a/*.txt")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Summary: 1 linked"))
+        .stdout(contains("1 excluded"));
+
+    assert!(home.join("x.txt").is_symlink());
+    assert!(!home.join("a/x.txt").exists());
+}
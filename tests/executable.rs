@@ -0,0 +1,80 @@
+use assert_cmd::Command;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("bin")).unwrap();
+    fs::create_dir_all(home.join("bin")).unwrap();
+    fs::write(root.join("bin/hello"), b"#!/bin/sh\necho GREETING\n").unwrap();
+    fs::set_permissions(root.join("bin/hello"), fs::Permissions::from_mode(0o644)).unwrap();
+    (tmp, root, home)
+}
+
+fn mode_of(path: &std::path::Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o7777
+}
+
+fn run_dotty(root: &std::path::Path, home: &std::path::Path) {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).arg("--no-color");
+    cmd.env("HOME", home);
+    cmd.assert().success();
+}
+
+#[test]
+fn executable_transform_output_runs_from_target() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join("bin/hello.lua"),
+        r#"return {
+            executable = true,
+            transform = function(content) return (content:gsub("GREETING", "hello")) end,
+        }"#,
+    )
+    .unwrap();
+
+    run_dotty(&root, &home);
+
+    let script = home.join("bin/hello");
+    assert_eq!(mode_of(&script), 0o755);
+    let output = std::process::Command::new(&script).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+}
+
+#[test]
+fn executable_on_symlinked_script_chmods_source() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("bin/hello.lua"), "return { executable = true }").unwrap();
+
+    run_dotty(&root, &home);
+
+    assert!(home.join("bin/hello").is_symlink());
+    assert_eq!(mode_of(&root.join("bin/hello")), 0o755);
+    let output = std::process::Command::new(home.join("bin/hello"))
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "GREETING\n");
+}
+
+#[test]
+fn executable_composes_with_mode_and_copy() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join("bin/hello.lua"),
+        r#"return { copy = true, mode = "0600", executable = true }"#,
+    )
+    .unwrap();
+
+    run_dotty(&root, &home);
+
+    let script = home.join("bin/hello");
+    assert!(!script.is_symlink());
+    assert_eq!(mode_of(&script), 0o711);
+    let output = std::process::Command::new(&script).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "GREETING\n");
+}
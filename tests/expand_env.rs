@@ -0,0 +1,94 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(
+        root.join(".gitconfig"),
+        b"email = {{env.DOTTY_TEST_EMAIL}}\neditor = {{ env.DOTTY_TEST_EDITOR|vim }}\nkeep = {{other}}\n",
+    )
+    .unwrap();
+    fs::write(root.join(".gitconfig.lua"), b"return { expand_env = true }").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn expand_env_fills_in_placeholders() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home)
+        .env("DOTTY_TEST_EMAIL", "me@corp.com")
+        .env_remove("DOTTY_TEST_EDITOR");
+    cmd.assert()
+        .success()
+        .stdout(contains("Wrote transformed file"));
+
+    let target = home.join(".gitconfig");
+    assert!(!target.is_symlink());
+    assert_eq!(
+        fs::read_to_string(&target).unwrap(),
+        "email = me@corp.com\neditor = vim\nkeep = {{other}}\n"
+    );
+
+    // A second run finds the expanded file in place
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home)
+        .env("DOTTY_TEST_EMAIL", "me@corp.com")
+        .env_remove("DOTTY_TEST_EDITOR");
+    cmd.assert()
+        .success()
+        .stdout(contains("Would write (already in place)"));
+}
+
+#[test]
+fn unset_variable_without_default_is_an_error() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home).env_remove("DOTTY_TEST_EMAIL");
+    cmd.assert()
+        .failure()
+        .stderr(contains(
+            "Environment variable DOTTY_TEST_EMAIL used by {{env.DOTTY_TEST_EMAIL}} in",
+        ))
+        .stderr(contains(".gitconfig is not set"));
+    assert!(!home.join(".gitconfig").exists());
+}
+
+#[test]
+fn expand_env_applies_to_transform_output() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("greeting.txt"), b"").unwrap();
+    fs::write(
+        root.join("greeting.txt.lua"),
+        r#"return {
+            expand_env = true,
+            transform = function() return "hello {{env.DOTTY_TEST_EMAIL}}" end,
+        }"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home)
+        .env("DOTTY_TEST_EMAIL", "me@corp.com");
+    cmd.assert().success().stdout(contains("error").not());
+
+    assert_eq!(
+        fs::read_to_string(home.join("greeting.txt")).unwrap(),
+        "hello me@corp.com"
+    );
+}
@@ -0,0 +1,64 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"repo").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    (tmp, root, home)
+}
+
+fn dotty(root: &std::path::Path, home: &std::path::Path) -> Command {
+    let mut cmd = common::dotty(root, home);
+    cmd.arg("--fail-on-conflict");
+    cmd
+}
+
+#[test]
+fn conflicts_exit_with_code_2_after_the_full_walk() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".vimrc"), b"local edit").unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .code(2)
+        .stdout(contains("exists (file) ~/.vimrc"))
+        .stdout(contains("Summary: 1 linked, 1 conflict"));
+
+    // The rest of the walk still happened
+    assert!(home.join(".zshrc").is_symlink());
+    assert_eq!(fs::read(home.join(".vimrc")).unwrap(), b"local edit");
+}
+
+#[test]
+fn dry_run_checks_the_plan() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".vimrc"), b"local edit").unwrap();
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .code(2)
+        .stdout(contains("Summary: 1 planned, 1 conflict"));
+    assert!(!home.join(".zshrc").exists());
+
+    fs::remove_file(home.join(".vimrc")).unwrap();
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .code(0)
+        .stdout(contains("Summary: 2 planned, 0 conflicts"));
+}
+
+#[test]
+fn hard_errors_keep_exit_code_1() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join(".vimrc.lua"), b"this is not lua").unwrap();
+
+    dotty(&root, &home).assert().code(1);
+}
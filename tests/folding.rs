@@ -0,0 +1,99 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/nvim/lua")).unwrap();
+    fs::write(root.join(".config/nvim/init.lua"), b"init").unwrap();
+    fs::write(root.join(".config/nvim/lua/plugins.lua"), b"plugins").unwrap();
+    (tmp, root, home)
+}
+
+fn run(root: &std::path::Path, home: &std::path::Path) -> assert_cmd::assert::Assert {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).arg("--no-color");
+    cmd.env("HOME", home);
+    cmd.assert()
+}
+
+#[test]
+fn missing_target_dir_is_folded_into_one_link() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".config")).unwrap();
+
+    run(&root, &home)
+        .success()
+        .stdout(contains("Linked dir ~/.config/nvim"))
+        .stdout(contains("Summary: 1 linked, 0 conflicts"))
+        .stdout(contains("1 folded dir"));
+    assert_eq!(
+        fs::read_link(home.join(".config/nvim")).unwrap(),
+        root.join(".config/nvim")
+    );
+
+    // A second run finds the folded link already in place
+    run(&root, &home)
+        .success()
+        .stdout(contains(
+            "Summary: 0 linked, 1 already in place, 0 conflicts",
+        ))
+        .stdout(contains("folded dir").not());
+}
+
+#[test]
+fn existing_target_dir_gets_per_file_links() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".config/nvim")).unwrap();
+
+    run(&root, &home)
+        .success()
+        .stdout(contains("Summary: 2 linked, 0 conflicts"))
+        .stdout(contains("1 folded dir"));
+    assert!(home.join(".config/nvim/init.lua").is_symlink());
+}
+
+#[test]
+fn extra_files_in_the_target_dir_are_a_fold_conflict() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".config/nvim")).unwrap();
+    fs::write(home.join(".config/nvim/local.lua"), b"local").unwrap();
+
+    run(&root, &home)
+        .success()
+        .stdout(contains(
+            "✗ Conflict: cannot fold dir ~/.config/nvim, it has 1 other entry; linking its files one by one",
+        ))
+        .stdout(contains("Summary: 2 linked, 1 conflict (1 directory)"))
+        .stdout(contains("1 folded dir"));
+    assert!(home.join(".config/nvim/init.lua").is_symlink());
+    assert!(home.join(".config/nvim/local.lua").is_file());
+    assert_eq!(
+        fs::read_link(home.join(".config/nvim/lua")).unwrap(),
+        root.join(".config/nvim/lua")
+    );
+}
+
+#[test]
+fn subtrees_with_companions_are_not_folded() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".config")).unwrap();
+    fs::write(
+        root.join(".config/nvim/init.lua.lua"),
+        b"return { rename_to = 'init2.lua' }",
+    )
+    .unwrap();
+
+    run(&root, &home)
+        .success()
+        .stdout(contains("Summary: 2 linked, 0 conflicts"))
+        .stdout(contains("1 folded dir"));
+    assert!(home.join(".config/nvim").is_dir() && !home.join(".config/nvim").is_symlink());
+    assert!(home.join(".config/nvim/init2.lua").is_symlink());
+    assert!(home.join(".config/nvim/lua").is_symlink());
+}
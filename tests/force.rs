@@ -0,0 +1,94 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"repo").unwrap();
+    fs::write(root.join("app.conf"), b"template").unwrap();
+    fs::write(
+        root.join("app.conf.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    fs::write(home.join(".zshrc"), b"edited in home").unwrap();
+    std::os::unix::fs::symlink("/elsewhere", home.join("app.conf")).unwrap();
+    (tmp, root, home)
+}
+
+fn dotty(root: &std::path::Path, home: &std::path::Path) -> Command {
+    let mut cmd = common::dotty(root, home);
+    cmd.arg("--force");
+    cmd
+}
+
+#[test]
+fn force_replaces_differing_targets_and_backs_them_up() {
+    let (tmp, root, home) = setup();
+    let backups = tmp.path().join("backups");
+
+    dotty(&root, &home)
+        .arg("--backup-dir")
+        .arg(&backups)
+        .assert()
+        .success()
+        .stdout(contains("↻ forced: ~/.zshrc <- "))
+        .stdout(contains("Summary: 2 linked, 0 conflicts"))
+        .stdout(contains("1 forced"));
+
+    assert!(home.join(".zshrc").is_symlink());
+    assert_eq!(
+        fs::read_to_string(backups.join(".zshrc")).unwrap(),
+        "edited in home"
+    );
+}
+
+#[test]
+fn dry_run_previews_forced_replacements() {
+    let (_tmp, root, home) = setup();
+    fs::remove_file(home.join("app.conf")).unwrap();
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains(
+            "Would back up ~/.zshrc to ~/.local/state/dotty/backups/",
+        ))
+        .stdout(contains("Would force ~/.zshrc <- "))
+        .stdout(contains("Would symlink ~/.zshrc"))
+        .stdout(contains("1 to force"));
+    assert_eq!(
+        fs::read_to_string(home.join(".zshrc")).unwrap(),
+        "edited in home"
+    );
+}
+
+#[test]
+fn force_never_replaces_directories() {
+    let (_tmp, root, home) = setup();
+    fs::remove_file(home.join(".zshrc")).unwrap();
+    fs::create_dir(home.join(".zshrc")).unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("exists (directory) ~/.zshrc"))
+        .stdout(contains("1 conflict (1 directory)"));
+    assert!(home.join(".zshrc").is_dir());
+}
+
+#[test]
+fn force_conflicts_with_on_conflict() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home)
+        .arg("--on-conflict=skip")
+        .assert()
+        .failure()
+        .stderr(contains("cannot be used with"));
+}
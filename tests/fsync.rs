@@ -0,0 +1,61 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn fsync_writes_transformed_and_copied_files() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config")).unwrap();
+    fs::write(root.join(".gitconfig"), "name = me\n").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        "return { transform = function(content) return content:upper() end }",
+    )
+    .unwrap();
+    fs::write(root.join(".config/app.conf"), "x = 1\n").unwrap();
+    fs::write(root.join(".config/app.conf.lua"), "return { copy = true }").unwrap();
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--fsync")
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Failed to sync").not());
+
+    assert_eq!(
+        fs::read_to_string(home.join(".gitconfig")).unwrap(),
+        "NAME = ME\n"
+    );
+    assert_eq!(
+        fs::read_to_string(home.join(".config/app.conf")).unwrap(),
+        "x = 1\n"
+    );
+}
+
+#[test]
+fn fsync_changes_nothing_in_a_dry_run() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("app.conf"), "x = 1\n").unwrap();
+    fs::write(root.join("app.conf.lua"), "return { copy = true }").unwrap();
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--fsync")
+        .arg("--dry-run")
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Would write copy ~/app.conf"));
+
+    assert!(!home.join("app.conf").exists());
+}
@@ -0,0 +1,79 @@
+use assert_cmd::Command;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    for name in [".zshrc", ".bashrc", ".vimrc", ".config/app/a.conf"] {
+        fs::write(root.join(name), name).unwrap();
+    }
+    fs::write(root.join(".vimrc.lua"), b"return false").unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+    fs::write(home.join(".bashrc"), b"mine").unwrap();
+    (tmp, root, home)
+}
+
+fn run(root: &Path, home: &Path, args: &[&str]) -> String {
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(root)
+        .arg("--no-color")
+        .arg("--no-folding")
+        .arg("--group-output")
+        .args(args)
+        .env("HOME", home)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Where the first line containing `needle` is in `out`.
+fn position(out: &str, needle: &str) -> usize {
+    out.find(needle)
+        .unwrap_or_else(|| panic!("{needle} not in {out}"))
+}
+
+#[test]
+fn lines_are_printed_in_sorted_sections() {
+    let (_tmp, root, home) = setup();
+    let out = run(&root, &home, &["--dry-run"]);
+
+    let links = position(&out, "==> New links (1)");
+    let written = position(&out, "==> Written (1)");
+    let conflicts = position(&out, "==> Conflicts (2)");
+    let skipped = position(&out, "==> Skipped (1)");
+    let summary = position(&out, "Summary:");
+    assert!(links < written && written < conflicts && conflicts < skipped);
+    assert!(skipped < summary);
+
+    assert!(links < position(&out, "Would symlink ~/.config/app/a.conf"));
+    assert!(written < position(&out, "Would write transformed file ~/.gitconfig"));
+    let (bashrc, zshrc) = (
+        position(&out, "(file) ~/.bashrc"),
+        position(&out, "(file) ~/.zshrc"),
+    );
+    assert!(conflicts < bashrc && bashrc < zshrc && zshrc < skipped);
+    assert!(skipped < position(&out, "Skipped by lua: ~/.vimrc"));
+}
+
+#[test]
+fn sections_respect_quiet() {
+    let (_tmp, root, home) = setup();
+    let out = run(&root, &home, &["--quiet"]);
+    assert!(out.starts_with("==> Conflicts (2)\n"), "{out}");
+    assert!(!out.contains("New links"), "{out}");
+    assert!(home.join(".config/app/a.conf").is_symlink());
+}
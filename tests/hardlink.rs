@@ -0,0 +1,79 @@
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+mod common;
+use common::{dirs, dotty};
+
+#[test]
+fn hardlink_companion_creates_hard_link() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(root.join("a.txt.lua"), b"return { hardlink = true }").unwrap();
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("Would hardlink ~/a.txt"));
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Hardlinked ~/a.txt"));
+    let target = fs::symlink_metadata(home.join("a.txt")).unwrap();
+    assert!(target.is_file());
+    assert_eq!(
+        target.ino(),
+        fs::metadata(root.join("a.txt")).unwrap().ino()
+    );
+
+    dotty(&root, &home)
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stdout(contains("Would hardlink (already in place) ~/a.txt"));
+}
+
+#[test]
+fn hardlink_flag_reports_conflicts_and_unstows_own_links() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    fs::write(home.join("b.txt"), b"local").unwrap();
+
+    dotty(&root, &home)
+        .arg("--hardlink")
+        .assert()
+        .success()
+        .stdout(contains("✗ exists (file) ~/b.txt"))
+        .stdout(contains("Summary: 1 linked, 1 conflict"));
+
+    dotty(&root, &home)
+        .arg("--hardlink")
+        .arg("--unstow")
+        .assert()
+        .success()
+        .stdout(contains("Summary: 1 removed, 1 kept, 0 missing"));
+    assert!(!home.join("a.txt").exists());
+    assert_eq!(fs::read(home.join("b.txt")).unwrap(), b"local");
+}
+
+#[test]
+fn override_identical_replaces_the_target_with_a_hard_link() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(home.join("a.txt"), b"A").unwrap();
+
+    dotty(&root, &home)
+        .args(["--hardlink", "--override-identical"])
+        .assert()
+        .success()
+        .stdout(contains("override identical: ~/a.txt"))
+        .stdout(contains("Hardlinked ~/a.txt"))
+        .stdout(contains("1 override"));
+    assert_eq!(
+        fs::metadata(home.join("a.txt")).unwrap().ino(),
+        fs::metadata(root.join("a.txt")).unwrap().ino()
+    );
+}
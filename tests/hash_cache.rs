@@ -0,0 +1,86 @@
+use assert_cmd::Command;
+use std::fs::{self, File};
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("font.ttf"), vec![7u8; 300_000]).unwrap();
+    fs::write(home.join("font.ttf"), vec![7u8; 300_000]).unwrap();
+    (tmp, root, home)
+}
+
+fn conflict_state(root: &Path, home: &Path, args: &[&str]) -> String {
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(root)
+        .arg("--no-color")
+        .arg("--verbose")
+        .args(args)
+        .env("HOME", home)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout
+        .lines()
+        .find(|line| line.contains("~/font.ttf <- "))
+        .unwrap_or_else(|| panic!("{stdout}"));
+    line.rsplit(' ').next().unwrap().to_string()
+}
+
+/// Change the content of `path` but keep its size and modification time.
+fn rewrite_keeping_mtime(path: &Path) {
+    let modified = fs::metadata(path).unwrap().modified().unwrap();
+    let mut content = fs::read(path).unwrap();
+    content[150_000] = 8;
+    fs::write(path, content).unwrap();
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+#[test]
+fn unchanged_files_are_compared_from_the_cache() {
+    let (_tmp, root, home) = setup();
+    assert_eq!(conflict_state(&root, &home, &[]), "(identical)");
+    let cache = home.join(".local/state/dotty/hashes.json");
+    assert!(fs::read_to_string(&cache).unwrap().contains("font.ttf"));
+
+    // Same size and mtime: the cached hash is trusted
+    rewrite_keeping_mtime(&home.join("font.ttf"));
+    assert_eq!(conflict_state(&root, &home, &[]), "(identical)");
+}
+
+#[test]
+fn no_cache_hashes_the_files_again() {
+    let (_tmp, root, home) = setup();
+    assert_eq!(conflict_state(&root, &home, &[]), "(identical)");
+
+    rewrite_keeping_mtime(&home.join("font.ttf"));
+    assert_eq!(conflict_state(&root, &home, &["--no-cache"]), "(differs)");
+}
+
+#[test]
+fn changed_files_are_hashed_again() {
+    let (_tmp, root, home) = setup();
+    assert_eq!(conflict_state(&root, &home, &[]), "(identical)");
+
+    let mut content = fs::read(home.join("font.ttf")).unwrap();
+    content[0] = 8;
+    fs::write(home.join("font.ttf"), content).unwrap();
+    File::options()
+        .write(true)
+        .open(home.join("font.ttf"))
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(conflict_state(&root, &home, &[]), "(differs)");
+}
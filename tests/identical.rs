@@ -58,3 +58,51 @@ fn dry_run_conflict_identical_symlink_points_to_source() {
         .stdout(contains("exists ").or(contains("Would link (already in place)")))
         .stdout(contains("identical").or(contains("Would link (already in place)")));
 }
+
+#[test]
+fn dry_run_override_identical_matches_real_run() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+    fs::write(root.join("a.txt"), b"SAME").unwrap();
+    fs::write(home.join("a.txt"), b"SAME").unwrap();
+
+    let summary = |dry_run: bool| {
+        let mut cmd = Command::cargo_bin("dotty").unwrap();
+        cmd.arg("--root")
+            .arg(&root)
+            .arg("--override-identical")
+            .arg("--backup-dir")
+            .arg(tmp.path().join("backups"))
+            .arg("--no-color");
+        if dry_run {
+            cmd.arg("--dry-run");
+        }
+        cmd.env("HOME", &home);
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let stdout = String::from_utf8(output).unwrap();
+        let line = stdout.lines().find(|l| l.starts_with("Summary:")).unwrap();
+        (stdout.clone(), line.to_string())
+    };
+
+    let (dry_stdout, dry_summary) = summary(true);
+    assert!(dry_stdout.contains("Would override identical ~/a.txt <- "));
+    assert!(!dry_stdout.contains("exists "));
+    assert!(home.join("a.txt").is_file() && !home.join("a.txt").is_symlink());
+
+    let (real_stdout, real_summary) = summary(false);
+    assert!(real_stdout.contains("override identical: ~/a.txt <- "));
+    assert!(home.join("a.txt").is_symlink());
+    assert!(real_summary.contains("1 overrides"));
+    assert!(real_summary.contains("0 conflicts"));
+    // Same counts, only the verbs differ
+    let numbers = |s: &str| {
+        s.split(|c: char| !c.is_ascii_digit())
+            .filter(|n| !n.is_empty())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    assert_eq!(numbers(&dry_summary), numbers(&real_summary));
+}
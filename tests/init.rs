@@ -0,0 +1,83 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn dotty(home: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--no-color");
+    cmd.env("HOME", home);
+    cmd
+}
+
+#[test]
+fn init_scaffold_plans_cleanly() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("dots");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&home).unwrap();
+
+    dotty(&home)
+        .arg("init")
+        .arg(&root)
+        .arg("--with-config")
+        .assert()
+        .success()
+        .stdout(contains("Created"))
+        .stdout(contains("Next steps:"));
+    for name in [
+        ".example-rc",
+        ".example-rc.lua",
+        "dotty_helpers.lua",
+        ".dottyignore",
+        "dotty.toml",
+    ] {
+        assert!(root.join(name).is_file(), "{name} missing");
+    }
+
+    dotty(&home)
+        .arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .env("DOTTY_HOSTNAME", "laptop")
+        .assert()
+        .success()
+        .stdout(contains("Would write transformed file ~/.example-rc"));
+    dotty(&home)
+        .arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .env("DOTTY_HOSTNAME", "work-mbp")
+        .assert()
+        .success()
+        .stdout(contains("Skipped by lua: ~/.example-rc"));
+    assert!(fs::read_dir(&home).unwrap().next().is_none());
+}
+
+#[test]
+fn init_refuses_non_empty_dir_without_force() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("dots");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join(".dottyignore"), b"mine\n").unwrap();
+
+    dotty(tmp.path())
+        .arg("init")
+        .arg(&root)
+        .assert()
+        .failure()
+        .stderr(contains("is not empty, pass --force"));
+    assert!(!root.join(".example-rc").exists());
+
+    dotty(tmp.path())
+        .arg("init")
+        .arg(&root)
+        .arg("--force")
+        .assert()
+        .success()
+        .stdout(contains("kept existing"));
+    assert_eq!(fs::read(root.join(".dottyignore")).unwrap(), b"mine\n");
+    assert!(root.join(".example-rc.lua").is_file());
+    assert!(!root.join("dotty.toml").exists());
+}
@@ -0,0 +1,109 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"alias ll='ls -l'\n").unwrap();
+    fs::write(root.join(".zshrc.lua"), b"return { inject = true }").unwrap();
+    (tmp, root, home)
+}
+
+fn run(
+    root: &std::path::Path,
+    home: &std::path::Path,
+    args: &[&str],
+) -> assert_cmd::assert::Assert {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).args(args).arg("--no-color");
+    cmd.env("HOME", home);
+    cmd.assert()
+}
+
+#[test]
+fn inject_adds_and_updates_only_the_block() {
+    let (_tmp, root, home) = setup();
+    let zshrc = home.join(".zshrc");
+    fs::write(&zshrc, b"export ADMIN=1").unwrap();
+
+    run(&root, &home, &["--dry-run"])
+        .success()
+        .stdout(contains("Would add block to ~/.zshrc"));
+    run(&root, &home, &[])
+        .success()
+        .stdout(contains("Added block to ~/.zshrc"));
+    assert_eq!(
+        fs::read_to_string(&zshrc).unwrap(),
+        "export ADMIN=1\n# >>> dotty\nalias ll='ls -l'\n# <<< dotty\n"
+    );
+
+    // The admin tooling appends below the block; dotty only rewrites its region
+    fs::write(
+        &zshrc,
+        "export ADMIN=1\n# >>> dotty\nalias ll='ls -l'\n# <<< dotty\nexport MORE=1\n",
+    )
+    .unwrap();
+    run(&root, &home, &["--dry-run"])
+        .success()
+        .stdout(contains("Block already in place in ~/.zshrc"));
+
+    fs::write(root.join(".zshrc"), b"alias la='ls -a'\n").unwrap();
+    run(&root, &home, &["--dry-run"])
+        .success()
+        .stdout(contains("Would update block in ~/.zshrc"));
+    run(&root, &home, &[])
+        .success()
+        .stdout(contains("Updated block in ~/.zshrc"));
+    assert_eq!(
+        fs::read_to_string(&zshrc).unwrap(),
+        "export ADMIN=1\n# >>> dotty\nalias la='ls -a'\n# <<< dotty\nexport MORE=1\n"
+    );
+}
+
+#[test]
+fn inject_creates_missing_file_with_custom_markers() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join(".zshrc.lua"),
+        r#"return { inject = true, markers = { "-- BEGIN", "-- END" } }"#,
+    )
+    .unwrap();
+
+    run(&root, &home, &[])
+        .success()
+        .stdout(contains("Created block in new file ~/.zshrc"));
+    let zshrc = home.join(".zshrc");
+    assert!(!zshrc.is_symlink());
+    assert_eq!(
+        fs::read_to_string(&zshrc).unwrap(),
+        "-- BEGIN\nalias ll='ls -l'\n-- END\n"
+    );
+}
+
+#[test]
+fn unstow_removes_just_the_block() {
+    let (_tmp, root, home) = setup();
+    let zshrc = home.join(".zshrc");
+    fs::write(&zshrc, b"before\n").unwrap();
+    run(&root, &home, &[]).success();
+    fs::write(&zshrc, fs::read_to_string(&zshrc).unwrap() + "after\n").unwrap();
+
+    run(&root, &home, &["--unstow"])
+        .success()
+        .stdout(contains("Removed block from ~/.zshrc"));
+    assert_eq!(fs::read_to_string(&zshrc).unwrap(), "before\nafter\n");
+}
+
+#[test]
+fn unterminated_block_is_a_conflict() {
+    let (_tmp, root, home) = setup();
+    let zshrc = home.join(".zshrc");
+    fs::write(&zshrc, b"# >>> dotty\nstray\n").unwrap();
+
+    run(&root, &home, &[]).stdout(contains("'# >>> dotty' has no matching '# <<< dotty' line"));
+    assert_eq!(fs::read_to_string(&zshrc).unwrap(), "# >>> dotty\nstray\n");
+}
@@ -0,0 +1,116 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".vimrc"), b"set number\n").unwrap();
+    fs::write(root.join(".zshrc"), b"alias ll='ls -l'\n").unwrap();
+    fs::write(home.join(".vimrc"), b"set nonumber\n").unwrap();
+    fs::write(home.join(".zshrc"), b"alias la='ls -a'\n").unwrap();
+    (tmp, root, home)
+}
+
+/// `dotty --interactive` with a pipe on stdin that is treated like a terminal.
+fn dotty(root: &std::path::Path, home: &std::path::Path, input: &str) -> Command {
+    let mut cmd = common::dotty(root, home);
+    cmd.arg("--interactive")
+        .arg("--backup-dir")
+        .arg(home.join("backups"))
+        .env("DOTTY_ASSUME_TTY", "1")
+        .write_stdin(input);
+    cmd
+}
+
+#[test]
+fn diff_then_backup() {
+    let (_tmp, root, home) = setup();
+    fs::remove_file(home.join(".zshrc")).unwrap();
+
+    dotty(&root, &home, "d\nb\n")
+        .assert()
+        .success()
+        .stdout(contains("Conflict at ~/.vimrc <- "))
+        .stdout(contains("-set nonumber"))
+        .stdout(contains("+set number"))
+        .stdout(contains("Summary: 2 linked, 0 conflicts"))
+        .stdout(contains("1 backed up"));
+
+    assert!(home.join(".vimrc").is_symlink());
+    assert_eq!(
+        fs::read_to_string(home.join("backups/.vimrc")).unwrap(),
+        "set nonumber\n"
+    );
+}
+
+#[test]
+fn overwrite_answers_are_counted() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home, "o\no\n")
+        .assert()
+        .success()
+        .stdout(contains("Overwrote ~/.zshrc"))
+        .stdout(contains("Overwrote ~/.vimrc"))
+        .stdout(contains("Summary: 2 linked, 0 conflicts"))
+        .stdout(contains("2 overwritten"));
+
+    assert!(home.join(".vimrc").is_symlink());
+    assert!(home.join(".zshrc").is_symlink());
+}
+
+#[test]
+fn skip_and_end_of_input_leave_conflicts() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home, "s\n")
+        .assert()
+        .success()
+        .stdout(contains("Summary: 0 linked, 2 conflicts"));
+    assert_eq!(
+        fs::read_to_string(home.join(".zshrc")).unwrap(),
+        "alias la='ls -a'\n"
+    );
+}
+
+#[test]
+fn quit_stops_the_run() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home, "q\n")
+        .assert()
+        .failure()
+        .stderr(contains("Stopped at the conflict at ~/."));
+    assert!(!home.join(".vimrc").is_symlink());
+    assert!(!home.join(".zshrc").is_symlink());
+}
+
+#[test]
+fn no_prompt_without_terminal_or_in_dry_run() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--interactive")
+        .arg("--no-color");
+    cmd.env("HOME", &home)
+        .env_remove("DOTTY_ASSUME_TTY")
+        .write_stdin("o\no\n");
+    cmd.assert()
+        .success()
+        .stdout(contains("[s]kip").not())
+        .stdout(contains("2 conflicts"));
+
+    dotty(&root, &home, "o\no\n")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("[s]kip").not())
+        .stdout(contains("2 conflicts"));
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".slow"), "x").unwrap();
+    // Keeps the run busy planning while it is interrupted
+    fs::write(
+        root.join(".slow.lua"),
+        "local start = os.time() while os.time() - start < 2 do end return true",
+    )
+    .unwrap();
+    fs::write(root.join(".zshrc"), "x").unwrap();
+    (tmp, root, home)
+}
+
+/// Start a run in the background and wait until it holds the lock.
+fn spawn_slow_run(root: &Path, home: &Path) -> Child {
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin("dotty"))
+        .arg("--root")
+        .arg(root)
+        .arg("--lua-timeout")
+        .arg("10")
+        .arg("--no-color")
+        .env("HOME", home)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let lock = home.join(".local/state/dotty/lock");
+    while fs::read_to_string(&lock).map_or(true, |pid| pid.is_empty()) {
+        thread::sleep(Duration::from_millis(20));
+    }
+    child
+}
+
+fn interrupt(child: &Child) {
+    let status = std::process::Command::new("kill")
+        .arg("-INT")
+        .arg(child.id().to_string())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn ctrl_c_stops_the_walk_and_prints_the_summary_so_far() {
+    let (_tmp, root, home) = setup();
+    let child = spawn_slow_run(&root, &home);
+    interrupt(&child);
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert!(stdout.contains("Interrupted, the remaining entries were not processed"));
+    assert!(stdout.contains("(interrupted)"), "{stdout}");
+    assert!(!home.join(".zshrc").exists());
+}
+
+#[test]
+fn a_second_ctrl_c_exits_at_once() {
+    let (_tmp, root, home) = setup();
+    let child = spawn_slow_run(&root, &home);
+    interrupt(&child);
+    thread::sleep(Duration::from_millis(100));
+    interrupt(&child);
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert!(!stdout.contains("Summary"), "{stdout}");
+}
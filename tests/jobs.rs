@@ -0,0 +1,244 @@
+use assert_cmd::Command;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::create_dir_all(root.join(".local/share/fonts")).unwrap();
+    fs::create_dir_all(home.join(".config/app")).unwrap();
+    for i in 0..300 {
+        fs::write(
+            root.join(format!(".local/share/fonts/font-{i:03}.ttf")),
+            b"font",
+        )
+        .unwrap();
+        fs::write(root.join(format!(".config/app/{i:03}.conf")), b"conf").unwrap();
+    }
+    for i in (0..300).step_by(7) {
+        fs::write(home.join(format!(".config/app/{i:03}.conf")), b"mine").unwrap();
+    }
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+fn run(root: &Path, home: &Path, jobs: &str, dry_run: bool) -> String {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(root)
+        .arg("--no-color")
+        .arg("--no-folding")
+        .arg("--jobs")
+        .arg(jobs)
+        .env("HOME", home);
+    if dry_run {
+        cmd.arg("--dry-run");
+    }
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn parallel_runs_print_like_serial_ones() {
+    let (_tmp, root, home) = setup();
+    let serial = run(&root, &home, "1", true);
+    let parallel = run(&root, &home, "8", true);
+    assert_eq!(serial, parallel);
+    assert!(parallel.contains("Summary: 558 planned, 43 conflicts (43 files)"));
+}
+
+#[test]
+fn parallel_runs_link_every_file() {
+    let (_tmp, root, home) = setup();
+    let out = run(&root, &home, "8", false);
+    assert!(out.contains("Summary: 558 linked, 43 conflicts"), "{out}");
+    for i in 0..300 {
+        assert!(
+            home.join(format!(".local/share/fonts/font-{i:03}.ttf"))
+                .is_symlink()
+        );
+    }
+    assert_eq!(fs::read(home.join(".gitconfig")).unwrap(), b"git!");
+    assert_eq!(
+        fs::read(home.join(".config/app/007.conf")).unwrap(),
+        b"mine"
+    );
+
+    let out = run(&root, &home, "8", false);
+    assert!(
+        out.contains("Summary: 0 linked, 558 already in place"),
+        "{out}"
+    );
+}
+
+#[test]
+fn zero_jobs_are_rejected() {
+    let (_tmp, root, home) = setup();
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--jobs")
+        .arg("0")
+        .env("HOME", &home)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--jobs must be at least 1"));
+}
+
+#[test]
+fn files_below_a_folded_dir_wait_for_the_unfold() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("dir/sub")).unwrap();
+    for name in ["f1", "f2", "f3"] {
+        fs::write(root.join("dir/sub").join(name), name).unwrap();
+    }
+    fs::write(root.join("dir/top"), b"top").unwrap();
+    common::dotty(&root, &home).assert().success();
+    assert!(home.join("dir").is_symlink());
+
+    let out = run(&root, &home, "4", false);
+    assert!(out.contains("Summary: 4 linked"), "{out}");
+    assert!(!home.join("dir").is_symlink());
+    assert!(!home.join("dir/sub").is_symlink());
+    for name in ["sub/f1", "sub/f2", "sub/f3", "top"] {
+        let target = home.join("dir").join(name);
+        assert_eq!(fs::read_link(&target).unwrap(), root.join("dir").join(name));
+    }
+}
+
+#[test]
+fn a_failing_conflict_stops_before_later_files_are_placed() {
+    let (_tmp, root, home) = dirs();
+    for name in ["a", "b", "c", "d", "e"] {
+        fs::write(root.join(name), name).unwrap();
+    }
+    fs::write(home.join("a"), b"mine").unwrap();
+
+    common::dotty(&root, &home)
+        .arg("--on-conflict=fail")
+        .arg("--jobs")
+        .arg("4")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Conflict at ~/a"));
+    for name in ["b", "c", "d", "e"] {
+        assert!(!home.join(name).exists(), "{name} was placed");
+    }
+}
+
+fn transform_root() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("conf")).unwrap();
+    for i in 0..200 {
+        fs::write(root.join(format!("conf/{i:03}.txt")), format!("file {i}")).unwrap();
+    }
+    fs::write(
+        root.join("conf/.dotty.lua"),
+        br#"return { transform = function(c, ctx) return c:upper() .. " " .. ctx.path end }"#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("conf/007.txt.lua"),
+        br#"return { transform = function() return nil end }"#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("conf/011.txt.lua"),
+        br#"return { transform = function(c) return { content = c .. "!", mode = "0600" } end }"#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("conf/012.txt.lua"),
+        br#"return {
+            targets = { "a/012.txt", "b/012.txt" },
+            template = true,
+            transform = function(c) return c .. " {{ dotty.os }}" end,
+        }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn parallel_transforms_match_serial_ones() {
+    let (_tmp, root, home) = transform_root();
+    let serial = run(&root, &home, "1", true);
+    let parallel = run(&root, &home, "8", true);
+    assert_eq!(serial, parallel);
+
+    let out = run(&root, &home, "8", false);
+    assert!(out.contains("Summary: 200 linked, 0 conflicts"), "{out}");
+    assert_eq!(
+        fs::read_to_string(home.join("conf/000.txt")).unwrap(),
+        "FILE 0 conf/000.txt"
+    );
+    assert!(!home.join("conf/007.txt").exists());
+    assert_eq!(
+        fs::read_to_string(home.join("conf/011.txt")).unwrap(),
+        "file 11!"
+    );
+    assert_eq!(
+        fs::metadata(home.join("conf/011.txt"))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777,
+        0o600
+    );
+    assert_eq!(
+        fs::read_to_string(home.join("a/012.txt")).unwrap(),
+        fs::read_to_string(home.join("b/012.txt")).unwrap()
+    );
+    assert!(
+        !fs::read_to_string(home.join("a/012.txt"))
+            .unwrap()
+            .contains("{{")
+    );
+}
+
+#[test]
+fn parallel_transforms_print_once_and_report_errors() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+    fs::write(
+        root.join("a.txt.lua"),
+        br#"print("evaluated")
+        return { transform = function(c) print("transformed") return c end }"#,
+    )
+    .unwrap();
+    let output = common::dotty(&root, &home)
+        .arg("--trace-lua")
+        .arg("--jobs")
+        .arg("4")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("evaluated").count(), 1, "{stderr}");
+    assert_eq!(stderr.matches("transformed").count(), 1, "{stderr}");
+
+    fs::write(
+        root.join("a.txt.lua"),
+        br#"return { transform = function() error("boom") end }"#,
+    )
+    .unwrap();
+    common::dotty(&root, &home)
+        .arg("--jobs")
+        .arg("4")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Lua transform function error in"))
+        .stderr(predicates::str::contains("boom"));
+}
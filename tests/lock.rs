@@ -0,0 +1,96 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".slow"), "x").unwrap();
+    // Keeps the first run busy while the second one starts
+    fs::write(
+        root.join(".slow.lua"),
+        "local start = os.time() while os.time() - start < 2 do end return true",
+    )
+    .unwrap();
+    fs::write(root.join(".zshrc"), "x").unwrap();
+    (tmp, root, home)
+}
+
+/// Start a run in the background and wait until it holds the lock.
+fn spawn_slow_run(root: &Path, home: &Path) -> Child {
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin("dotty"))
+        .arg("--root")
+        .arg(root)
+        .arg("--lua-timeout")
+        .arg("10")
+        .env("HOME", home)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let lock = home.join(".local/state/dotty/lock");
+    while fs::read_to_string(&lock).map_or(true, |pid| pid.is_empty()) {
+        thread::sleep(Duration::from_millis(20));
+    }
+    child
+}
+
+#[test]
+fn a_second_run_fails_naming_the_holder() {
+    let (_tmp, root, home) = setup();
+    let mut first = spawn_slow_run(&root, &home);
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .env("HOME", &home)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "Another dotty run (PID {}) is changing the target",
+            first.id()
+        )));
+
+    // Dry runs change nothing and need no lock
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--lua-timeout")
+        .arg("10")
+        .env("HOME", &home)
+        .assert()
+        .success();
+
+    assert!(first.wait().unwrap().success());
+}
+
+#[test]
+fn wait_blocks_until_the_lock_is_free() {
+    let (_tmp, root, home) = setup();
+    let mut first = spawn_slow_run(&root, &home);
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--wait")
+        .arg("--lua-timeout")
+        .arg("10")
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Waiting for another dotty run"))
+        .stdout(predicate::str::contains("2 already in place"));
+
+    assert!(first.wait().unwrap().success());
+}
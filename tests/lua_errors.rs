@@ -0,0 +1,77 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup(lua_script: &str) -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("config.txt"), b"content").unwrap();
+    fs::write(root.join("config.txt.lua"), lua_script).unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn transform_error_has_file_line_and_traceback() {
+    let (_tmp, root, home) = setup(
+        r#"local function check(content)
+    error("bad content: " .. content)
+end
+
+local function prepare(content)
+    local checked = check(content)
+    return checked
+end
+
+return {
+    transform = function(content)
+        local prepared = prepare(content)
+        return prepared
+    end,
+}
+"#,
+    );
+    let companion = root.join("config.txt.lua");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains(format!(
+            "while processing {}",
+            root.join("config.txt").display()
+        )))
+        .stderr(contains(format!(
+            "{}:2: bad content: content",
+            companion.display()
+        )))
+        .stderr(contains("stack traceback:"))
+        .stderr(contains(format!("{}:6: in", companion.display())));
+}
+
+#[test]
+fn companion_error_names_source_and_line() {
+    let (_tmp, root, home) = setup(
+        r#"local function pick()
+    return nil + 1
+end
+return pick()
+"#,
+    );
+    let companion = root.join("config.txt.lua");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains(format!(
+            "Lua error while processing {}",
+            root.join("config.txt").display()
+        )))
+        .stderr(contains(format!("{}:2:", companion.display())))
+        .stderr(contains("stack traceback:"));
+}
@@ -0,0 +1,314 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".work-gitconfig"), b"[user]").unwrap();
+    fs::write(
+        root.join(".work-gitconfig.lua"),
+        r#"return dotty.hostname() == "work-mbp""#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn hostname_decides_per_machine() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "work-mbp");
+    cmd.assert()
+        .success()
+        .stdout(contains("Would symlink ~/.work-gitconfig"));
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "home-mini");
+    cmd.assert()
+        .success()
+        .stdout(contains("Skipped by lua: ~/.work-gitconfig"));
+}
+
+#[test]
+fn helpers_report_home_env_and_user() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("facts.txt"), b"").unwrap();
+    fs::write(
+        root.join("facts.txt.lua"),
+        r#"return {
+            transform = function()
+                return table.concat({
+                    dotty.home(),
+                    dotty.env("DOTTY_TEST_VALUE"),
+                    tostring(dotty.env("DOTTY_TEST_UNSET")),
+                    dotty.username(),
+                    tostring(dotty.os() ~= ""),
+                    tostring(dotty.arch() ~= ""),
+                }, "\n")
+            end,
+        }"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home)
+        .env("DOTTY_TEST_VALUE", "42")
+        .env_remove("DOTTY_TEST_UNSET")
+        .env("USER", "alice");
+    cmd.assert().success();
+
+    let content = fs::read_to_string(home.join("facts.txt")).unwrap();
+    assert_eq!(
+        content,
+        format!("{}\n42\nnil\nalice\ntrue\ntrue", home.display())
+    );
+}
+
+#[test]
+fn command_exists_searches_path() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (tmp, root, home) = setup();
+    let bin = tmp.path().join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    fs::write(bin.join("kitty"), b"#!/bin/sh\n").unwrap();
+    fs::set_permissions(bin.join("kitty"), fs::Permissions::from_mode(0o755)).unwrap();
+    // Not executable, so it does not count
+    fs::write(bin.join("tmux"), b"").unwrap();
+
+    for (name, check) in [
+        ("kitty.conf", r#"dotty.command_exists("kitty")"#),
+        ("tmux.conf", r#"dotty.command_exists("tmux")"#),
+        ("absent.conf", r#"dotty.command_exists("no-such-program")"#),
+    ] {
+        fs::write(root.join(name), b"").unwrap();
+        fs::write(root.join(format!("{name}.lua")), format!("return {check}")).unwrap();
+    }
+    fs::write(root.join("abs.conf"), b"").unwrap();
+    fs::write(
+        root.join("abs.conf.lua"),
+        format!(
+            "return dotty.command_exists({:?}) and dotty.command_exists({:?})",
+            bin.join("kitty").display().to_string(),
+            bin.join("kitty").display().to_string()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("PATH", &bin);
+    cmd.assert()
+        .success()
+        .stdout(contains("Would symlink ~/kitty.conf"))
+        .stdout(contains("Would symlink ~/abs.conf"))
+        .stdout(contains("Skipped by lua: ~/tmux.conf"))
+        .stdout(contains("Skipped by lua: ~/absent.conf"));
+}
+
+#[test]
+fn read_returns_sibling_files_from_root() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(root.join("shared")).unwrap();
+    fs::write(root.join("shared/header.txt"), b"# header\n").unwrap();
+    fs::write(root.join("shared.lua"), "return false").unwrap();
+    fs::write(root.join(".zshrc"), b"export A=1\n").unwrap();
+    fs::write(
+        root.join(".zshrc.lua"),
+        r#"return { transform = function(content) return dotty.read("shared/header.txt") .. content end }"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(home.join(".zshrc")).unwrap(),
+        "# header\nexport A=1\n"
+    );
+}
+
+#[test]
+fn read_rejects_escaping_and_missing_paths() {
+    let (tmp, root, home) = setup();
+    fs::write(tmp.path().join("secret"), b"secret").unwrap();
+    fs::write(root.join(".zshrc"), b"").unwrap();
+    fs::write(
+        root.join(".zshrc.lua"),
+        r#"return { transform = function() return dotty.read("../secret") end }"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().failure().stderr(contains(
+        "dotty.read: '../secret' must be a path relative to the root",
+    ));
+
+    fs::write(
+        root.join(".zshrc.lua"),
+        r#"return { transform = function() return dotty.read("missing.txt") end }"#,
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("dotty.read: cannot read"))
+        .stderr(contains("missing.txt"));
+
+    std::os::unix::fs::symlink(tmp.path().join("secret"), root.join("link")).unwrap();
+    fs::write(
+        root.join(".zshrc.lua"),
+        r#"return { transform = function() return dotty.read("link") end }"#,
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("dotty.read: 'link' leads outside of the root"));
+}
+
+#[test]
+fn require_loads_shared_modules_from_root() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(root.join("lib")).unwrap();
+    fs::write(root.join("lib.lua"), "return false").unwrap();
+    fs::write(
+        root.join("lib/helpers.lua"),
+        r#"
+        local M = { loads = (M_LOADS or 0) + 1 }
+        M_LOADS = M.loads
+        function M.is_work() return dotty.env("DOTTY_TEST_WORK") == "1" end
+        return M
+        "#,
+    )
+    .unwrap();
+    for name in [".work-vimrc", ".work-zshrc"] {
+        fs::write(root.join(name), b"").unwrap();
+        fs::write(
+            root.join(format!("{name}.lua")),
+            r#"
+            local helpers = require("lib.helpers")
+            assert(helpers.loads == 1, "module should be loaded once")
+            return helpers.is_work()
+            "#,
+        )
+        .unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_TEST_WORK", "1");
+    cmd.assert()
+        .success()
+        .stdout(contains("Would symlink ~/.work-vimrc"))
+        .stdout(contains("Would symlink ~/.work-zshrc"));
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_TEST_WORK", "0");
+    cmd.assert()
+        .success()
+        .stdout(contains("Skipped by lua: ~/.work-vimrc"))
+        .stdout(contains("Skipped by lua: ~/.work-zshrc"));
+}
+
+#[test]
+fn helpers_file_globals_are_shared_with_companions() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join("dotty_helpers.lua"),
+        r#"function is_work_machine() return dotty.hostname() == "work-mbp" end"#,
+    )
+    .unwrap();
+    fs::write(root.join(".work-gitconfig.lua"), "return is_work_machine()").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "work-mbp");
+    cmd.assert()
+        .success()
+        .stdout(contains("Would symlink ~/.work-gitconfig"))
+        .stdout(contains("dotty_helpers.lua").not())
+        .stdout(contains("Summary: 1 planned"));
+}
+
+#[test]
+fn helpers_file_syntax_error_aborts_with_its_name() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("dotty.lua"), "function broken(").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("Failed to load Lua helpers"))
+        .stderr(contains("dotty.lua"));
+    assert!(!home.join(".work-gitconfig").exists());
+}
+
+#[test]
+fn companion_globals_do_not_leak_between_files() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("a.txt"), b"").unwrap();
+    fs::write(root.join("z.txt"), b"").unwrap();
+    fs::write(
+        root.join("a.txt.lua"),
+        r#"suffix = ".from-a" _G.dotty = nil _G.tostring = nil return { rename_to = "a" .. suffix }"#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("z.txt.lua"),
+        r#"suffix = suffix or ".own" return { rename_to = "z" .. suffix .. tostring(type(dotty)) }"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("~/a.from-a"))
+        .stdout(contains("~/z.owntable"));
+}
@@ -0,0 +1,70 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup(lua_script: &str) -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("slow.txt"), b"").unwrap();
+    fs::write(root.join("slow.txt.lua"), lua_script).unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn endless_companion_times_out_and_names_the_file() {
+    let (_tmp, root, home) = setup("while true do end");
+
+    let started = Instant::now();
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--lua-timeout")
+        .arg("0.5")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("slow.txt.lua"))
+        .stderr(contains("timed out after 0.5 seconds"));
+    assert!(started.elapsed() < Duration::from_secs(30));
+    assert!(!home.join("slow.txt").exists());
+}
+
+#[test]
+fn slow_transform_times_out() {
+    let (_tmp, root, home) =
+        setup("return { transform = function(content) while true do end end }");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--lua-timeout")
+        .arg("0.5")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("Lua transform function error in"))
+        .stderr(contains("slow.txt.lua"))
+        .stderr(contains("timed out"));
+}
+
+#[test]
+fn invalid_timeout_is_rejected() {
+    let (_tmp, root, home) = setup("return true");
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--lua-timeout")
+        .arg("0")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().failure().stderr(contains(
+        "--lua-timeout must be a positive number of seconds",
+    ));
+}
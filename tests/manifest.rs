@@ -0,0 +1,90 @@
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    fs::write(root.join(".npmrc"), b"npm").unwrap();
+    fs::write(root.join(".npmrc.lua"), b"return { copy = true }").unwrap();
+    (tmp, root, home)
+}
+
+fn read_manifest(home: &Path) -> Value {
+    let path = home.join(".local/state/dotty/manifest.json");
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn entry<'a>(manifest: &'a Value, target: &Path) -> &'a Value {
+    &manifest["entries"][target.to_str().unwrap()]
+}
+
+#[test]
+fn manifest_records_each_placed_target() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home).assert().success();
+
+    let manifest = read_manifest(&home);
+    assert_eq!(manifest["version"], 1);
+    let zshrc = entry(&manifest, &home.join(".zshrc"));
+    assert_eq!(zshrc["kind"], "symlink");
+    assert_eq!(zshrc["source"], root.join(".zshrc").to_str().unwrap());
+    assert!(zshrc.get("hash").is_none());
+    let gitconfig = entry(&manifest, &home.join(".gitconfig"));
+    assert_eq!(gitconfig["kind"], "transform");
+    // sha256 of "git!"
+    assert_eq!(
+        gitconfig["hash"],
+        "sha256:78ff603938cce9f8fbc6ac506e892c17a4f1b45f5f201c86adb2ad0fd1209d49"
+    );
+    assert_eq!(entry(&manifest, &home.join(".npmrc"))["kind"], "copy");
+}
+
+#[test]
+fn manifest_is_merged_across_runs() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home)
+        .arg("--only")
+        .arg(".zshrc")
+        .assert()
+        .success();
+    dotty(&root, &home)
+        .arg("--only")
+        .arg(".npmrc")
+        .assert()
+        .success();
+
+    let manifest = read_manifest(&home);
+    assert_eq!(entry(&manifest, &home.join(".zshrc"))["kind"], "symlink");
+    assert_eq!(entry(&manifest, &home.join(".npmrc"))["kind"], "copy");
+
+    // Unstowing drops the targets it removed and keeps the rest
+    dotty(&root, &home)
+        .arg("--unstow")
+        .arg("--only")
+        .arg(".zshrc")
+        .assert()
+        .success();
+    let manifest = read_manifest(&home);
+    assert!(entry(&manifest, &home.join(".zshrc")).is_null());
+    assert_eq!(entry(&manifest, &home.join(".npmrc"))["kind"], "copy");
+}
+
+#[test]
+fn dry_run_writes_no_manifest() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home).arg("--dry-run").assert().success();
+    assert!(!home.join(".local").exists());
+}
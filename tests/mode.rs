@@ -0,0 +1,102 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".netrc"), b"machine example.com").unwrap();
+    fs::set_permissions(root.join(".netrc"), fs::Permissions::from_mode(0o644)).unwrap();
+    (tmp, root, home)
+}
+
+fn mode_of(path: &std::path::Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o7777
+}
+
+#[test]
+fn mode_on_symlinked_file_is_applied_to_source() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join(".netrc.lua"), r#"return { mode = "0600" }"#).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would change mode of source"));
+    assert_eq!(mode_of(&root.join(".netrc")), 0o644);
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("from 0644 to 0600"));
+    assert!(home.join(".netrc").is_symlink());
+    assert_eq!(mode_of(&root.join(".netrc")), 0o600);
+}
+
+#[test]
+fn mode_fixes_copy_with_correct_content_but_wrong_permissions() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join(".netrc.lua"),
+        r#"return { copy = true, mode = "0600" }"#,
+    )
+    .unwrap();
+    let target = home.join(".netrc");
+    fs::write(&target, b"machine example.com").unwrap();
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Overwrote copy ~/.netrc"));
+    assert_eq!(mode_of(&target), 0o600);
+    // The source keeps its own mode, only the copy gets the declared one
+    assert_eq!(mode_of(&root.join(".netrc")), 0o644);
+}
+
+#[test]
+fn mode_applies_to_transformed_output() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join(".netrc.lua"),
+        r#"return { mode = "0600", transform = function(content) return content .. "\n" end }"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+    assert_eq!(mode_of(&home.join(".netrc")), 0o600);
+}
+
+#[test]
+fn invalid_mode_errors_during_planning() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join(".netrc.lua"), r#"return { mode = "0999" }"#).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid mode in"))
+        .stderr(predicate::str::contains("'0999' is not an octal file mode"));
+    assert!(!home.join(".netrc").exists());
+}
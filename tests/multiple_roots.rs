@@ -0,0 +1,76 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn multiple_roots_aggregate_into_one_summary() {
+    let tmp = TempDir::new().unwrap();
+    let shared = tmp.path().join("shared");
+    let machine = tmp.path().join("machine");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&shared).unwrap();
+    fs::create_dir_all(&machine).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(shared.join("a.txt"), b"A").unwrap();
+    fs::write(machine.join("b.txt"), b"B").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&shared)
+        .arg("--root")
+        .arg(&machine)
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains(format!("==> {}", shared.display())))
+        .stdout(contains(format!("==> {}", machine.display())))
+        .stdout(contains("Summary: 2 linked, 0 conflicts"))
+        .stdout(contains("0 collisions"));
+
+    assert_eq!(
+        fs::read_link(home.join("a.txt")).unwrap(),
+        shared.join("a.txt")
+    );
+    assert_eq!(
+        fs::read_link(home.join("b.txt")).unwrap(),
+        machine.join("b.txt")
+    );
+}
+
+#[test]
+fn later_root_wins_collisions_and_reports_them() {
+    let tmp = TempDir::new().unwrap();
+    let shared = tmp.path().join("shared");
+    let machine = tmp.path().join("machine");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&shared).unwrap();
+    fs::create_dir_all(&machine).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(shared.join("gitconfig"), b"shared").unwrap();
+    fs::write(machine.join("work-gitconfig"), b"work").unwrap();
+    fs::write(
+        machine.join("work-gitconfig.lua"),
+        b"return { rename_to = 'gitconfig' }",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(format!("{},{}", shared.display(), machine.display()))
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("collision ~/gitconfig"))
+        .stdout(contains("Summary: 1 linked, 0 conflicts"))
+        .stdout(contains("1 collisions"));
+
+    assert_eq!(
+        fs::read_link(home.join("gitconfig")).unwrap(),
+        machine.join("work-gitconfig")
+    );
+}
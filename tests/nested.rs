@@ -35,5 +35,7 @@ fn nested_counts_aggregate_across_subdirs() {
         .stdout(contains("exists "))
         .stdout(contains("Skipped by lua"))
         // Summary must count across subdirs: 1 planned, 1 conflict, 1 skipped by lua
-        .stdout(contains("Summary: 1 planned, 1 conflict, 1 skipped by lua"));
+        .stdout(contains(
+            "Summary: 1 planned, 1 conflict (1 file), 1 skipped by lua",
+        ));
 }
@@ -0,0 +1,82 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app/themes")).unwrap();
+    fs::create_dir_all(home.join(".config")).unwrap();
+    fs::write(root.join(".config/app/config.toml"), b"config").unwrap();
+    fs::write(root.join(".config/app/themes/dark.toml"), b"dark").unwrap();
+    // Copies keep the directories from being folded into one link
+    for file in ["config.toml", "themes/dark.toml"] {
+        let companion = root.join(format!(".config/app/{file}.lua"));
+        fs::write(companion, b"return { copy = true }").unwrap();
+    }
+    (tmp, root, home)
+}
+
+#[test]
+fn dry_run_lists_each_missing_directory_once() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    let output = cmd
+        .assert()
+        .success()
+        .stdout(contains("Would create directory ~/.config/app/\n"))
+        .stdout(contains("Would create directory ~/.config/app/themes/\n"))
+        .stdout(contains("Would create directory ~/.config/\n").not())
+        .stdout(contains("2 dirs to create"))
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).unwrap();
+    assert_eq!(
+        stdout
+            .matches("Would create directory ~/.config/app/\n")
+            .count(),
+        1
+    );
+    assert!(!home.join(".config/app").exists());
+}
+
+#[test]
+fn verbose_run_logs_created_directories() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--verbose")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Created directory ~/.config/app/\n"))
+        .stdout(contains("Created directory ~/.config/app/themes/\n"))
+        .stdout(contains("2 dirs created"));
+    assert!(home.join(".config/app/themes/dark.toml").is_file());
+
+    // Nothing is missing the second time
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--verbose")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Created directory").not())
+        .stdout(contains("dirs created").not());
+}
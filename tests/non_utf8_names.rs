@@ -0,0 +1,81 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn files_with_non_utf8_names_are_linked_under_their_exact_name() {
+    let (_tmp, root, home) = dirs();
+    let name = OsStr::from_bytes(b"caf\xe9.conf");
+    fs::write(root.join(name), "x").unwrap();
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("caf\u{fffd}.conf"));
+
+    assert_eq!(fs::read_link(home.join(name)).unwrap(), root.join(name));
+    assert!(!home.join("caf\u{fffd}.conf").exists());
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 already in place"));
+}
+
+#[test]
+fn companions_of_non_utf8_names_are_found() {
+    let (_tmp, root, home) = dirs();
+    // Two names that only differ in the invalid byte, which lossy conversion would merge
+    fs::write(root.join(OsStr::from_bytes(b"a\xe9")), "kept").unwrap();
+    fs::write(root.join(OsStr::from_bytes(b"a\xe8")), "skipped").unwrap();
+    fs::write(root.join(OsStr::from_bytes(b"a\xe8.lua")), "return false").unwrap();
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .success();
+
+    assert!(home.join(OsStr::from_bytes(b"a\xe9")).is_symlink());
+    assert!(!home.join(OsStr::from_bytes(b"a\xe8")).exists());
+    assert!(!home.join(OsStr::from_bytes(b"a\xe8.lua")).exists());
+}
+
+#[test]
+fn rename_to_keeps_the_exact_bytes() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("latin1"), "x").unwrap();
+    fs::write(
+        root.join("latin1.lua"),
+        r#"return { rename_to = "caf\xe9" }"#,
+    )
+    .unwrap();
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .env("HOME", &home)
+        .assert()
+        .success();
+
+    assert!(home.join(OsStr::from_bytes(b"caf\xe9")).is_symlink());
+}
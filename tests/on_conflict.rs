@@ -0,0 +1,220 @@
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"repo").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(home.join(".zshrc"), b"local edit").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn skip_reports_and_continues_by_default() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("exists (file) ~/.zshrc"))
+        .stdout(contains("Summary: 1 linked, 1 conflict"));
+    assert_eq!(
+        fs::read_to_string(home.join(".zshrc")).unwrap(),
+        "local edit"
+    );
+    assert!(home.join(".vimrc").is_symlink());
+}
+
+#[test]
+fn fail_aborts_at_the_first_conflict() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home)
+        .arg("--on-conflict=fail")
+        .assert()
+        .failure()
+        .stderr(contains("Conflict at ~/.zshrc (--on-conflict=fail)"))
+        .stdout(contains("Summary").not());
+    assert_eq!(
+        fs::read_to_string(home.join(".zshrc")).unwrap(),
+        "local edit"
+    );
+}
+
+#[test]
+fn backup_moves_the_target_aside() {
+    let (tmp, root, home) = setup();
+    let backups = tmp.path().join("backups");
+
+    dotty(&root, &home)
+        .arg("--on-conflict=backup")
+        .arg("--backup-dir")
+        .arg(&backups)
+        .assert()
+        .success()
+        .stdout(contains("Backed up ~/.zshrc"))
+        .stdout(contains("Summary: 2 linked, 0 conflicts"))
+        .stdout(contains("1 backed up"));
+    assert!(home.join(".zshrc").is_symlink());
+    assert_eq!(
+        fs::read_to_string(backups.join(".zshrc")).unwrap(),
+        "local edit"
+    );
+}
+
+#[test]
+fn conflicts_name_what_is_in_the_way() {
+    let (tmp, root, home) = setup();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(root.join(".tmux.conf"), b"tmux").unwrap();
+    let old = tmp.path().join("old-dotfiles/gitconfig");
+    fs::create_dir_all(old.parent().unwrap()).unwrap();
+    fs::write(&old, b"old").unwrap();
+    std::os::unix::fs::symlink(&old, home.join(".gitconfig")).unwrap();
+    fs::create_dir(home.join(".tmux.conf")).unwrap();
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("✗ exists (file) ~/.zshrc <- "))
+        .stdout(contains("✗ exists (directory) ~/.tmux.conf <- "))
+        .stdout(contains(format!(
+            "✗ exists (symlink → {}) ~/.gitconfig <- ",
+            old.display()
+        )))
+        .stdout(contains(
+            "3 conflicts (1 file, 1 directory, 1 symlink), 0 skipped by lua",
+        ));
+}
+
+#[test]
+fn overwrite_replaces_symlink_and_copy_targets() {
+    let (tmp, root, home) = setup();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(root.join(".gitconfig.lua"), b"return { copy = true }").unwrap();
+    let elsewhere = tmp.path().join("elsewhere");
+    fs::write(&elsewhere, b"other").unwrap();
+    std::os::unix::fs::symlink(&elsewhere, home.join(".gitconfig")).unwrap();
+
+    dotty(&root, &home)
+        .arg("--on-conflict=overwrite")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("Would overwrite ~/.zshrc"))
+        .stdout(contains("Would symlink ~/.zshrc"))
+        .stdout(contains("Would overwrite ~/.gitconfig"))
+        .stdout(contains("2 to overwrite"));
+    assert_eq!(
+        fs::read_to_string(home.join(".zshrc")).unwrap(),
+        "local edit"
+    );
+
+    dotty(&root, &home)
+        .arg("--on-conflict=overwrite")
+        .assert()
+        .success()
+        .stdout(contains("Overwrote ~/.zshrc"))
+        .stdout(contains("2 overwritten"));
+    assert!(home.join(".zshrc").is_symlink());
+    let gitconfig = home.join(".gitconfig");
+    assert!(!gitconfig.is_symlink());
+    assert_eq!(fs::read_to_string(gitconfig).unwrap(), "git");
+}
+
+#[test]
+fn directories_are_never_replaced() {
+    let (_tmp, root, home) = setup();
+    fs::remove_file(home.join(".zshrc")).unwrap();
+    fs::create_dir(home.join(".zshrc")).unwrap();
+
+    dotty(&root, &home)
+        .arg("--on-conflict=overwrite")
+        .assert()
+        .success()
+        .stdout(contains("1 conflict"));
+    assert!(home.join(".zshrc").is_dir());
+}
+
+#[test]
+fn companion_policy_wins_over_cli_policy() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join(".zshrc.lua"),
+        br#"return { on_conflict = "skip" }"#,
+    )
+    .unwrap();
+    fs::write(root.join("karabiner.json"), b"repo").unwrap();
+    fs::write(
+        root.join("karabiner.json.lua"),
+        br#"return { on_conflict = "overwrite" }"#,
+    )
+    .unwrap();
+    fs::write(home.join("karabiner.json"), b"rewritten by the app").unwrap();
+
+    // The CLI says overwrite, but .zshrc asks to be left alone
+    dotty(&root, &home)
+        .arg("--on-conflict=overwrite")
+        .assert()
+        .success()
+        .stdout(contains("exists (file) ~/.zshrc"))
+        .stdout(contains("1 conflict"));
+    assert_eq!(
+        fs::read_to_string(home.join(".zshrc")).unwrap(),
+        "local edit"
+    );
+
+    // The CLI says skip, but karabiner.json is always overwritten
+    fs::remove_file(home.join("karabiner.json")).unwrap();
+    fs::write(home.join("karabiner.json"), b"rewritten again").unwrap();
+    dotty(&root, &home)
+        .arg("--on-conflict=skip")
+        .assert()
+        .success()
+        .stdout(contains("Overwrote ~/karabiner.json"));
+    assert!(home.join("karabiner.json").is_symlink());
+}
+
+#[test]
+fn companion_fail_policy_aborts() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join(".zshrc.lua"),
+        br#"return { on_conflict = "fail" }"#,
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .arg("--on-conflict=backup")
+        .assert()
+        .failure()
+        .stderr(contains("Conflict at ~/.zshrc (on_conflict = \"fail\")"));
+    assert_eq!(
+        fs::read_to_string(home.join(".zshrc")).unwrap(),
+        "local edit"
+    );
+}
+
+#[test]
+fn invalid_companion_policy_is_rejected() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join(".zshrc.lua"),
+        br#"return { on_conflict = "stomp" }"#,
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .failure()
+        .stderr(contains("Invalid on_conflict 'stomp' in"))
+        .stderr(contains(
+            ".zshrc.lua: expected skip, fail, backup or overwrite",
+        ));
+}
@@ -0,0 +1,68 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/nvim/lua")).unwrap();
+    fs::create_dir_all(root.join(".config/kitty")).unwrap();
+    // Existing target dirs keep the links per file instead of folding them
+    fs::create_dir_all(home.join(".config/nvim/lua")).unwrap();
+    fs::create_dir_all(home.join(".config/kitty")).unwrap();
+    fs::write(root.join(".config/nvim/init.lua"), b"init").unwrap();
+    fs::write(root.join(".config/nvim/lua/plugins.lua"), b"plugins").unwrap();
+    fs::write(root.join(".config/kitty/kitty.conf"), b"kitty").unwrap();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn only_descends_into_parents_of_matches() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--only")
+        .arg(".config/nvim/**")
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("~/.config/nvim/init.lua"))
+        .stdout(contains("~/.config/nvim/lua/plugins.lua"))
+        .stdout(contains("kitty").not())
+        .stdout(contains("Summary: 2 planned"))
+        .stdout(contains("2 outside --only"));
+}
+
+#[test]
+fn only_composes_with_exclude() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--only")
+        .arg(".config/**")
+        .arg("--exclude")
+        .arg(".config/nvim/lua/**")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Summary: 2 linked"))
+        .stdout(contains("1 excluded"))
+        .stdout(contains("1 outside --only"));
+
+    assert!(home.join(".config/nvim/init.lua").is_symlink());
+    assert!(home.join(".config/kitty/kitty.conf").is_symlink());
+    assert!(!home.join(".config/nvim/lua/plugins.lua").exists());
+    assert!(!home.join(".zshrc").exists());
+}
@@ -0,0 +1,41 @@
+use assert_cmd::Command;
+use std::fs;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn entries_are_visited_in_name_order() {
+    let (_tmp, root, home) = dirs();
+    let names = [".m", "B", ".a", "z", ".Z", "a", ".k", "_b", "0"];
+    for name in names {
+        fs::create_dir_all(root.join(name)).unwrap();
+        fs::write(root.join(name).join("f"), name).unwrap();
+        fs::write(root.join(format!("{name}rc")), name).unwrap();
+    }
+
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .arg("--no-folding")
+        .arg("--dry-run")
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let targets: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("✔ Would symlink ~/"))
+        .map(|rest| rest.split(" -> ").next().unwrap())
+        .collect();
+
+    let mut expected: Vec<String> = names
+        .iter()
+        .flat_map(|name| [format!("{name}/f"), format!("{name}rc")])
+        .collect();
+    expected.sort();
+    assert_eq!(targets, expected);
+}
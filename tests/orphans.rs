@@ -0,0 +1,96 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs as unix_fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::create_dir_all(home.join(".config/app")).unwrap();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".config/app/app.toml"), b"app").unwrap();
+    unix_fs::symlink(root.join(".zshrc"), home.join(".zshrc")).unwrap();
+    unix_fs::symlink(
+        root.join(".config/app/old.toml"),
+        home.join(".config/app/old.toml"),
+    )
+    .unwrap();
+    // Links pointing elsewhere are none of dotty's business
+    unix_fs::symlink(tmp.path().join("gone"), home.join(".foreign")).unwrap();
+    (tmp, root, home)
+}
+
+fn orphans(root: &std::path::Path, home: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("orphans").arg("--root").arg(root).arg("--no-color");
+    cmd.env("HOME", home);
+    cmd
+}
+
+#[test]
+fn orphans_lists_links_into_the_root_and_fails_on_missing_sources() {
+    let (_tmp, root, home) = setup();
+
+    let output = orphans(&root, &home)
+        .assert()
+        .failure()
+        .stdout(contains("✗ ~/.config/app/old.toml -> "))
+        .stdout(contains("old.toml (missing, not planned)"))
+        .stdout(contains("✔ ~/.zshrc -> "))
+        .stdout(contains(".zshrc (exists, planned)"))
+        .stdout(contains(".foreign").not())
+        .stdout(contains("Summary: 2 links into the root, 1 orphaned"))
+        .stderr(contains("Found 1 orphaned symlink"))
+        .get_output()
+        .stdout
+        .clone();
+    // Sorted by path
+    let stdout = String::from_utf8(output).unwrap();
+    assert!(stdout.find("~/.config/app/old.toml").unwrap() < stdout.find("~/.zshrc").unwrap());
+    // Read-only
+    assert!(home.join(".config/app/old.toml").is_symlink());
+    assert!(!home.join(".config/app/app.toml").exists());
+}
+
+#[test]
+fn orphans_succeeds_when_every_source_exists() {
+    let (_tmp, root, home) = setup();
+    fs::remove_file(home.join(".config/app/old.toml")).unwrap();
+
+    orphans(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Summary: 1 links into the root, 0 orphaned"));
+}
+
+#[test]
+fn orphans_json_lists_each_link() {
+    let (_tmp, root, home) = setup();
+
+    let output = orphans(&root, &home)
+        .arg("--json")
+        .assert()
+        .failure()
+        .stdout(contains("Summary").not())
+        .get_output()
+        .stdout
+        .clone();
+    let links: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let links = links.as_array().unwrap();
+    assert!(links.contains(&serde_json::json!({
+        "link": home.join(".config/app/old.toml"),
+        "destination": root.join(".config/app/old.toml"),
+        "exists": false,
+        "planned": false,
+    })));
+    assert!(
+        links
+            .iter()
+            .any(|link| link["exists"] == true && link["planned"] == true)
+    );
+}
@@ -0,0 +1,134 @@
+use assert_cmd::Command;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+#[derive(Deserialize)]
+struct Document {
+    version: u32,
+    dry_run: bool,
+    entries: Vec<Record>,
+    summary: Summary,
+}
+
+#[derive(Deserialize)]
+struct Record {
+    source: String,
+    target: String,
+    action: String,
+    state: String,
+    reason: Option<String>,
+    executed: bool,
+}
+
+#[derive(Deserialize)]
+struct Summary {
+    planned: usize,
+    in_place: usize,
+    conflicts: usize,
+    skipped: usize,
+}
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".vimrc.lua"), b"return false").unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+fn run_json(root: &Path, home: &Path, dry_run: bool) -> (String, Document) {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).arg("--output").arg("json");
+    if dry_run {
+        cmd.arg("--dry-run");
+    }
+    let output = cmd.env("HOME", home).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let document = serde_json::from_str(&stdout).unwrap();
+    (stdout, document)
+}
+
+fn record<'a>(document: &'a Document, name: &str) -> &'a Record {
+    document
+        .entries
+        .iter()
+        .find(|record| record.target.ends_with(name))
+        .unwrap()
+}
+
+#[test]
+fn dry_run_is_one_json_document() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+
+    let (stdout, document) = run_json(&root, &home, true);
+    assert!(!stdout.contains('\x1b'));
+    assert!(!stdout.contains('✔') && !stdout.contains('✗'));
+    assert_eq!(document.version, 1);
+    assert!(document.dry_run);
+
+    let zshrc = record(&document, "/.zshrc");
+    assert_eq!(zshrc.state, "conflict");
+    assert_eq!(zshrc.action, "symlink");
+    assert_eq!(zshrc.reason.as_deref(), Some("file"));
+    assert!(Path::new(&zshrc.source).is_absolute());
+    assert!(Path::new(&zshrc.target).is_absolute());
+
+    let gitconfig = record(&document, "/.gitconfig");
+    assert_eq!(gitconfig.action, "transform");
+    assert_eq!(gitconfig.state, "planned");
+    assert!(!gitconfig.executed);
+
+    assert_eq!(record(&document, "/.vimrc").state, "skipped");
+    assert_eq!(document.summary.planned, 1);
+    assert_eq!(document.summary.conflicts, 1);
+    assert_eq!(document.summary.skipped, 1);
+    assert!(!home.join(".gitconfig").exists());
+}
+
+#[test]
+fn real_runs_mark_executed_entries() {
+    let (_tmp, root, home) = setup();
+    let (_, document) = run_json(&root, &home, false);
+    assert!(!document.dry_run);
+    let zshrc = record(&document, "/.zshrc");
+    assert_eq!(zshrc.state, "planned");
+    assert!(zshrc.executed);
+    assert!(home.join(".zshrc").is_symlink());
+
+    let (_, document) = run_json(&root, &home, false);
+    assert_eq!(record(&document, "/.zshrc").state, "in_place");
+    assert!(!record(&document, "/.zshrc").executed);
+    assert_eq!(document.summary.in_place, 2);
+    assert_eq!(document.summary.planned, 0);
+}
+
+#[test]
+fn output_json_is_rejected_for_subcommands() {
+    let (_tmp, root, home) = setup();
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--output")
+        .arg("json")
+        .arg("status")
+        .env("HOME", &home)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "only apply to stowing, not to subcommands",
+        ));
+}
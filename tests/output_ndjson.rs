@@ -0,0 +1,103 @@
+use assert_cmd::Command;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".vimrc.lua"), b"return false").unwrap();
+    fs::write(root.join("odd\nname"), b"x").unwrap();
+    (tmp, root, home)
+}
+
+/// Run with `--output ndjson` and parse every line on its own.
+fn events(root: &Path, home: &Path, args: &[&str]) -> (bool, Vec<Value>) {
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(root)
+        .arg("--output")
+        .arg("ndjson")
+        .args(args)
+        .env("HOME", home)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let events = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    (output.status.success(), events)
+}
+
+fn event<'a>(events: &'a [Value], name: &str) -> &'a Value {
+    events
+        .iter()
+        .find(|event| event["target"].as_str().is_some_and(|t| t.ends_with(name)))
+        .unwrap()
+}
+
+#[test]
+fn each_entry_is_one_line_and_the_summary_comes_last() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+
+    let (success, events) = events(&root, &home, &["--dry-run"]);
+    assert!(success);
+    assert_eq!(events.len(), 4);
+    assert_eq!(event(&events, "/.zshrc")["event"], "conflict");
+    assert_eq!(event(&events, "/.vimrc")["event"], "skipped");
+    let odd = event(&events, "/odd\nname");
+    assert_eq!(odd["event"], "planned");
+    assert_eq!(odd["version"], 1);
+
+    let summary = events.last().unwrap();
+    assert_eq!(summary["event"], "summary");
+    assert_eq!(summary["dry_run"], true);
+    assert_eq!(summary["summary"]["conflicts"], 1);
+    assert_eq!(summary["summary"]["planned"], 1);
+}
+
+#[test]
+fn real_runs_emit_linked_events() {
+    let (_tmp, root, home) = setup();
+    let (success, events) = events(&root, &home, &[]);
+    assert!(success);
+    let zshrc = event(&events, "/.zshrc");
+    assert_eq!(zshrc["event"], "linked");
+    assert_eq!(zshrc["executed"], true);
+    assert!(home.join(".zshrc").is_symlink());
+    assert_eq!(events.last().unwrap()["summary"]["planned"], 2);
+}
+
+#[test]
+fn failures_emit_an_error_event() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::write(root.join(".config/app/app.toml"), b"app").unwrap();
+    fs::write(
+        root.join(".config/app/app.toml.lua"),
+        b"return { copy = true }",
+    )
+    .unwrap();
+    // A file where a parent directory has to go
+    fs::write(home.join(".config"), b"not a directory").unwrap();
+
+    let (success, events) = events(&root, &home, &["--no-folding"]);
+    assert!(!success);
+    let error = events.last().unwrap();
+    assert_eq!(error["event"], "error");
+    assert!(
+        error["target"]
+            .as_str()
+            .unwrap()
+            .ends_with("/.config/app/app.toml")
+    );
+    assert!(error["message"].as_str().is_some());
+}
@@ -0,0 +1,96 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("zsh")).unwrap();
+    fs::create_dir_all(root.join("git")).unwrap();
+    fs::create_dir_all(root.join("kitty/.config/kitty")).unwrap();
+    // An existing ~/.config keeps the kitty files linked one by one
+    fs::create_dir_all(home.join(".config/kitty")).unwrap();
+    fs::write(root.join("zsh/.zshrc"), b"zsh").unwrap();
+    fs::write(root.join("git/.gitconfig"), b"git").unwrap();
+    fs::write(root.join("kitty/.config/kitty/kitty.conf"), b"kitty").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn packages_restrict_walk_and_are_transparent() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .arg("zsh")
+        .arg("kitty");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Packages:"))
+        .stdout(contains("zsh: 1 linked, 0 conflicts, 0 skipped by lua"))
+        .stdout(contains("kitty: 1 linked, 0 conflicts, 0 skipped by lua"))
+        .stdout(contains("Summary: 2 linked, 0 conflicts"));
+
+    assert_eq!(
+        fs::read_link(home.join(".zshrc")).unwrap(),
+        root.join("zsh/.zshrc")
+    );
+    assert!(home.join(".config/kitty/kitty.conf").is_symlink());
+    assert!(!home.join(".gitconfig").exists());
+    assert!(!home.join("zsh").exists());
+}
+
+#[test]
+fn unknown_package_lists_available_ones() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color").arg("vim");
+    cmd.env("HOME", &home);
+    cmd.assert().failure().stderr(contains(
+        "Unknown package 'vim'. Available packages: git, kitty, zsh",
+    ));
+}
+
+#[test]
+fn no_packages_walks_whole_root() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Packages:").not())
+        .stdout(contains("~/zsh"))
+        .stdout(contains("Summary: 3 planned"));
+}
+
+#[test]
+fn root_dottyignore_applies_to_packages() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join(".dottyignore"), "*.bak\nzsh/.zprofile\n").unwrap();
+    fs::write(root.join("zsh/.zshrc.bak"), b"old").unwrap();
+    fs::write(root.join("zsh/.zprofile"), b"profile").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color").arg("zsh");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Summary: 1 linked"))
+        .stdout(contains("2 ignored"));
+    assert!(home.join(".zshrc").is_symlink());
+    assert!(!home.join(".zshrc.bak").exists());
+    assert!(!home.join(".zprofile").exists());
+}
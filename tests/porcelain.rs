@@ -0,0 +1,86 @@
+use assert_cmd::Command;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".vimrc.lua"), b"return false").unwrap();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    (tmp, root, home)
+}
+
+fn porcelain(root: &Path, home: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).arg("--porcelain");
+    cmd.env("HOME", home);
+    cmd
+}
+
+#[test]
+fn porcelain_lines_have_a_fixed_field_order() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+    let (r, h) = (root.display(), home.display());
+
+    porcelain(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(format!(
+            "WRITE\t{h}/.gitconfig\t{r}/.gitconfig\n\
+         SKIP\t{h}/.vimrc\t{r}/.vimrc\n\
+         CONFLICT\t{h}/.zshrc\t{r}/.zshrc\n\
+         SUMMARY\tplanned=1\tin_place=0\tconflicts=1\tskipped=1\toverrides=0\n"
+        ));
+}
+
+#[test]
+fn porcelain_reports_links_in_place_and_overrides() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"zsh").unwrap();
+    let (r, h) = (root.display(), home.display());
+
+    porcelain(&root, &home)
+        .arg("--override-identical")
+        .assert()
+        .success()
+        .stdout(format!(
+            "WRITE\t{h}/.gitconfig\t{r}/.gitconfig\n\
+             SKIP\t{h}/.vimrc\t{r}/.vimrc\n\
+             OVERRIDE\t{h}/.zshrc\t{r}/.zshrc\n\
+             SUMMARY\tplanned=2\tin_place=0\tconflicts=0\tskipped=1\toverrides=1\n"
+        ));
+    fs::write(root.join(".vimrc.lua"), b"return true").unwrap();
+
+    porcelain(&root, &home).assert().success().stdout(format!(
+        "INPLACE\t{h}/.gitconfig\t{r}/.gitconfig\n\
+         LINK\t{h}/.vimrc\t{r}/.vimrc\n\
+         INPLACE\t{h}/.zshrc\t{r}/.zshrc\n\
+         SUMMARY\tplanned=1\tin_place=2\tconflicts=0\tskipped=0\toverrides=0\n"
+    ));
+}
+
+#[test]
+fn porcelain_escapes_tabs_and_newlines() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("a\tb\nc"), b"x").unwrap();
+    let output = porcelain(&root, &home).arg("--dry-run").output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("/a\\tb\\nc\t"));
+    assert!(
+        stdout
+            .lines()
+            .all(|line| line.split('\t').count() == 3 || line.starts_with("SUMMARY"))
+    );
+}
@@ -0,0 +1,62 @@
+use assert_cmd::Command;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup(files: usize) -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("fonts")).unwrap();
+    for i in 0..files {
+        fs::write(root.join(format!("fonts/font-{i:04}.ttf")), b"font").unwrap();
+    }
+    (tmp, root, home)
+}
+
+fn run(root: &Path, home: &Path, args: &[&str]) -> (String, String) {
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(root)
+        .arg("--no-folding")
+        .arg("--dry-run")
+        .args(args)
+        .env("HOME", home)
+        .env("DOTTY_ASSUME_TTY", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn large_plans_show_a_counter_on_stderr() {
+    let (_tmp, root, home) = setup(250);
+    let (stdout, stderr) = run(&root, &home, &[]);
+    assert!(stderr.contains("[1/250] ~/fonts/font-0000.ttf"), "{stderr}");
+    assert!(
+        stderr.contains("[250/250] ~/fonts/font-0249.ttf"),
+        "{stderr}"
+    );
+    assert!(stderr.ends_with("\r\x1b[2K"), "{stderr}");
+    assert!(!stdout.contains('\r'));
+    assert!(stdout.contains("Summary: 250 planned"));
+}
+
+#[test]
+fn no_counter_for_small_plans_or_without_color() {
+    let (_tmp, root, home) = setup(20);
+    let (_, stderr) = run(&root, &home, &[]);
+    assert!(!stderr.contains("/20]"), "{stderr}");
+
+    let (_tmp, root, home) = setup(250);
+    for args in [&["--no-color"][..], &["--output", "json"]] {
+        let (_, stderr) = run(&root, &home, args);
+        assert!(!stderr.contains("/250]"), "{stderr}");
+    }
+}
@@ -0,0 +1,69 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs as unix_fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::create_dir_all(home.join(".config/app")).unwrap();
+    fs::write(root.join(".config/app/new.toml"), b"new").unwrap();
+    // Links to files that were renamed or deleted in the repo
+    unix_fs::symlink(
+        root.join(".config/app/old.toml"),
+        home.join(".config/app/old.toml"),
+    )
+    .unwrap();
+    unix_fs::symlink(root.join(".oldrc"), home.join(".oldrc")).unwrap();
+    // Broken, but not dotty's
+    unix_fs::symlink(tmp.path().join("gone"), home.join(".config/app/foreign")).unwrap();
+    (tmp, root, home)
+}
+
+fn dotty(root: &std::path::Path, home: &std::path::Path) -> Command {
+    let mut cmd = common::dotty(root, home);
+    cmd.arg("--prune");
+    cmd
+}
+
+#[test]
+fn prune_removes_stale_links_into_the_root() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("Would prune ~/.config/app/old.toml -> "))
+        .stdout(contains("Would prune ~/.oldrc -> "))
+        .stdout(contains("foreign").not())
+        .stdout(contains("2 to prune"));
+    assert!(home.join(".oldrc").is_symlink());
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Pruned ~/.config/app/old.toml -> "))
+        .stdout(contains("Pruned ~/.oldrc -> "))
+        .stdout(contains("2 pruned"));
+    assert!(!home.join(".config/app/old.toml").is_symlink());
+    assert!(!home.join(".oldrc").is_symlink());
+    assert!(home.join(".config/app/foreign").is_symlink());
+    assert!(home.join(".config/app/new.toml").is_symlink());
+}
+
+#[test]
+fn stale_links_are_kept_without_prune() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success().stdout(contains("prune").not());
+    assert!(home.join(".oldrc").is_symlink());
+}
@@ -0,0 +1,81 @@
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn renamed_sources_leave_no_stale_links() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+    fs::rename(root.join(".vimrc"), root.join(".vimrc2")).unwrap();
+
+    dotty(&root, &home)
+        .arg("--prune-stale")
+        .assert()
+        .success()
+        .stdout(contains("Removed stale ~/.vimrc (symlink from"))
+        .stdout(contains("1 stale removed"));
+    assert!(!home.join(".vimrc").is_symlink());
+    assert!(home.join(".vimrc2").is_symlink());
+    assert!(home.join(".zshrc").is_symlink());
+}
+
+#[test]
+fn files_skipped_by_lua_are_removed_while_unchanged() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+    fs::write(root.join(".gitconfig.lua"), b"return false").unwrap();
+
+    dotty(&root, &home)
+        .arg("--prune-stale")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains(
+            "Would remove stale ~/.gitconfig (transformed file from",
+        ))
+        .stdout(contains("1 stale to remove"));
+    assert!(home.join(".gitconfig").is_file());
+
+    fs::write(home.join(".gitconfig"), b"edited").unwrap();
+    dotty(&root, &home)
+        .arg("--prune-stale")
+        .assert()
+        .success()
+        .stdout(contains("kept stale ~/.gitconfig (transformed file from"))
+        .stdout(contains("changed since dotty placed it"))
+        .stdout(contains("stale removed").not());
+    assert_eq!(fs::read(home.join(".gitconfig")).unwrap(), b"edited");
+}
+
+#[test]
+fn only_does_not_make_other_targets_stale() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+
+    dotty(&root, &home)
+        .arg("--prune-stale")
+        .arg("--only")
+        .arg(".zshrc")
+        .assert()
+        .success()
+        .stdout(contains("stale").not());
+    assert!(home.join(".vimrc").is_symlink());
+    assert!(home.join(".gitconfig").is_file());
+}
@@ -0,0 +1,66 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(root.join(".gitconfig.lua"), b"return false").unwrap();
+    (tmp, root, home)
+}
+
+fn stdout(cmd: &mut Command) -> String {
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn clean_run_prints_only_the_summary() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+
+    for dry_run in [false, true] {
+        let mut cmd = dotty(&root, &home);
+        cmd.arg("--quiet");
+        if dry_run {
+            cmd.arg("--dry-run");
+        }
+        let out = stdout(&mut cmd);
+        assert_eq!(out.lines().count(), 1, "{out}");
+        assert!(out.starts_with("Summary: 0 "), "{out}");
+        assert!(out.contains("2 already in place"), "{out}");
+    }
+}
+
+#[test]
+fn quiet_runs_still_print_conflicts() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+
+    let out = stdout(dotty(&root, &home).arg("--quiet"));
+    assert!(out.contains("✗ exists (file) ~/.zshrc <- "), "{out}");
+    assert!(!out.contains("Linked"), "{out}");
+    assert!(!out.contains("Skipped by lua"), "{out}");
+    assert!(out.contains("Summary: 1 linked, 1 conflict"), "{out}");
+    assert!(home.join(".vimrc").is_symlink());
+}
+
+#[test]
+fn quiet_conflicts_with_verbose() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home)
+        .arg("--quiet")
+        .arg("--verbose")
+        .assert()
+        .failure()
+        .stderr(contains("cannot be used with"))
+        .stdout(contains("Summary").not());
+}
@@ -0,0 +1,70 @@
+use predicates::str::contains;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dotty;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let tmp = TempDir::new().unwrap();
+    // Canonical paths so the expected relative links are stable
+    let base = fs::canonicalize(tmp.path()).unwrap();
+    let root = base.join("root");
+    let home = base.join("home");
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::create_dir_all(home.join(".config/app")).unwrap();
+    fs::write(root.join(".config/app/a.txt"), b"A").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn relative_flag_creates_relative_links() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home).arg("--relative").assert().success();
+    assert_eq!(
+        fs::read_link(home.join(".config/app/a.txt")).unwrap(),
+        Path::new("../../../root/.config/app/a.txt")
+    );
+    assert_eq!(
+        fs::read_link(home.join("b.txt")).unwrap(),
+        Path::new("../root/b.txt")
+    );
+    assert_eq!(fs::read(home.join(".config/app/a.txt")).unwrap(), b"A");
+
+    // Relative links are recognized as in place without --relative too
+    dotty(&root, &home)
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stdout(contains("Would link (already in place) ~/b.txt"))
+        .stdout(contains(
+            "Summary: 0 linked, 2 already in place, 0 conflicts",
+        ));
+}
+
+#[test]
+fn relative_companion_field_applies_per_file() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("b.txt.lua"), b"return { relative = true }").unwrap();
+
+    dotty(&root, &home).assert().success();
+    assert_eq!(
+        fs::read_link(home.join("b.txt")).unwrap(),
+        Path::new("../root/b.txt")
+    );
+    assert_eq!(
+        fs::read_link(home.join(".config/app/a.txt")).unwrap(),
+        root.join(".config/app/a.txt")
+    );
+
+    dotty(&root, &home)
+        .arg("--relative")
+        .assert()
+        .success()
+        .stdout(contains(
+            "Summary: 0 linked, 2 already in place, 0 conflicts",
+        ));
+}
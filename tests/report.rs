@@ -0,0 +1,125 @@
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".vimrc.lua"), b"return false").unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    fs::write(root.join(".bashrc"), b"bash").unwrap();
+    fs::write(root.join(".tmux.conf"), b"tmux").unwrap();
+    (tmp, root, home)
+}
+
+/// The lines of the `## {title}` section of `report`.
+fn section<'a>(report: &'a str, title: &str) -> Vec<&'a str> {
+    report
+        .split(&format!("## {title}\n\n"))
+        .nth(1)
+        .unwrap()
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .collect()
+}
+
+#[test]
+fn report_lists_every_outcome_alongside_console_output() {
+    let (tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+    dotty(&root, &home)
+        .arg("--only")
+        .arg(".bashrc")
+        .assert()
+        .success();
+    let report_path = tmp.path().join("report.md");
+
+    dotty(&root, &home)
+        .arg("--report")
+        .arg(&report_path)
+        .assert()
+        .success()
+        .stdout(contains("Summary:"))
+        .stdout(contains("Wrote report to"));
+
+    let report = fs::read_to_string(&report_path).unwrap();
+    assert!(report.starts_with("# dotty report\n\nGenerated "));
+    assert!(report.contains(" UTC (run) for target "));
+    let r = root.display();
+    assert_eq!(
+        section(&report, "Linked"),
+        [format!("- `~/.tmux.conf` ← `{r}/.tmux.conf` (symlink)")]
+    );
+    assert_eq!(
+        section(&report, "Written"),
+        [format!("- `~/.gitconfig` ← `{r}/.gitconfig` (transform)")]
+    );
+    assert_eq!(
+        section(&report, "Already in place"),
+        [format!("- `~/.bashrc` ← `{r}/.bashrc` (symlink)")]
+    );
+    assert_eq!(
+        section(&report, "Conflicts"),
+        [format!("- `~/.zshrc` ← `{r}/.zshrc` (symlink): file")]
+    );
+    assert_eq!(
+        section(&report, "Skipped"),
+        [format!("- `~/.vimrc` ← `{r}/.vimrc`: skipped by lua")]
+    );
+    assert_eq!(section(&report, "Errors"), ["None."]);
+    assert!(report.contains("| already in place | 1 |"));
+    assert!(report.contains("| conflicts | 1 |"));
+}
+
+#[test]
+fn failed_runs_record_the_error() {
+    let (tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+    let report_path = tmp.path().join("report.md");
+
+    dotty(&root, &home)
+        .arg("--on-conflict")
+        .arg("fail")
+        .arg("--report")
+        .arg(&report_path)
+        .assert()
+        .failure()
+        .stderr(contains("Conflict at ~/.zshrc"));
+
+    let report = fs::read_to_string(&report_path).unwrap();
+    let errors = section(&report, "Errors");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("- `~/.zshrc` ← `"));
+    assert!(errors[0].ends_with("`: Conflict at ~/.zshrc (--on-conflict=fail)"));
+    assert!(report.contains("| conflicts | 1 |"));
+}
+
+#[test]
+fn report_is_not_written_for_rejected_arguments() {
+    let (tmp, root, home) = setup();
+    let report_path = tmp.path().join("report.md");
+
+    dotty(&root, &home)
+        .arg("--report")
+        .arg(&report_path)
+        .arg("--unstow")
+        .assert()
+        .failure();
+    dotty(&root, &home)
+        .arg("--report")
+        .arg(&report_path)
+        .arg("status")
+        .assert()
+        .failure()
+        .stderr(contains("only apply to stowing, not to subcommands"));
+    assert!(!report_path.exists());
+}
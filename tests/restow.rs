@@ -0,0 +1,73 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs as unix_fs;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn restow_relinks_stale_links_into_root() {
+    let (_tmp, root, home) = dirs();
+
+    // The source was renamed from old.txt to new.txt with rename_to keeping the target name
+    fs::write(root.join("new.txt"), b"A").unwrap();
+    fs::write(root.join("new.txt.lua"), b"return { rename_to = 'a.txt' }").unwrap();
+    unix_fs::symlink(root.join("old.txt"), home.join("a.txt")).unwrap();
+
+    let mut dry = Command::cargo_bin("dotty").unwrap();
+    dry.arg("--root")
+        .arg(&root)
+        .arg("--restow")
+        .arg("--dry-run")
+        .arg("--no-color");
+    dry.env("HOME", &home);
+    dry.assert()
+        .success()
+        .stdout(contains("Would relink"))
+        .stdout(contains(
+            "0 planned, 0 conflicts, 0 skipped by lua, 0 overrides, 1 relinked",
+        ));
+    assert_eq!(
+        fs::read_link(home.join("a.txt")).unwrap(),
+        root.join("old.txt")
+    );
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--restow")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Relinked"))
+        .stdout(contains("1 relinked"));
+    assert_eq!(
+        fs::read_link(home.join("a.txt")).unwrap(),
+        root.join("new.txt")
+    );
+}
+
+#[test]
+fn restow_keeps_foreign_links_and_files_as_conflicts() {
+    let (tmp, root, home) = dirs();
+
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    let elsewhere = tmp.path().join("elsewhere");
+    fs::write(&elsewhere, b"A").unwrap();
+    unix_fs::symlink(&elsewhere, home.join("a.txt")).unwrap();
+    fs::write(home.join("b.txt"), b"local").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--restow")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success().stdout(contains(
+        "0 linked, 2 conflicts (1 file, 1 symlink), 0 skipped by lua, 0 overrides, 0 relinked",
+    ));
+    assert_eq!(fs::read_link(home.join("a.txt")).unwrap(), elsewhere);
+}
@@ -0,0 +1,115 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("libs.txt"), b"").unwrap();
+    fs::write(
+        root.join("libs.txt.lua"),
+        r#"return {
+            transform = function()
+                return table.concat({
+                    type(os.execute),
+                    type(os.remove),
+                    type(io),
+                    type(debug),
+                    type(dofile),
+                    type(loadfile),
+                    type(load),
+                    type(os.time),
+                    type(os.date),
+                    tostring(os.getenv("DOTTY_TEST_VALUE")),
+                }, " ")
+            end,
+        }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn companions_cannot_reach_the_system_by_default() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_TEST_VALUE", "42");
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(home.join("libs.txt")).unwrap(),
+        "nil nil nil nil nil nil nil function function 42"
+    );
+}
+
+#[test]
+fn require_does_not_hand_back_the_removed_libraries() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join("libs.txt.lua"),
+        r#"return {
+            transform = function()
+                local has_io = pcall(require, "io")
+                local has_debug = pcall(require, "debug")
+                return table.concat({
+                    type(require("os").execute),
+                    type(require("os").time),
+                    tostring(has_io),
+                    tostring(has_debug),
+                }, " ")
+            end,
+        }"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(home.join("libs.txt")).unwrap(),
+        "nil function false false"
+    );
+}
+
+#[test]
+fn unsafe_lua_restores_the_full_standard_library() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--unsafe-lua")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_TEST_VALUE", "42");
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(home.join("libs.txt")).unwrap(),
+        "function function table table function function function function function 42"
+    );
+}
+
+#[test]
+fn calling_a_removed_function_fails() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join("libs.txt.lua"),
+        r#"os.execute("true") return true"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().failure().stderr(contains("execute"));
+}
@@ -0,0 +1,73 @@
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dotty;
+
+#[test]
+fn root_that_is_the_home_directory_is_refused() {
+    let tmp = TempDir::new().unwrap();
+    let home = tmp.path().join("home");
+    fs::create_dir_all(home.join("dotfiles")).unwrap();
+    fs::write(home.join(".zshrc"), "x").unwrap();
+
+    dotty(&home, &home)
+        .assert()
+        .failure()
+        .stderr(contains("is the target directory itself"));
+    assert!(!home.join(".zshrc").is_symlink());
+
+    // Also when the target only resolves to the root through a symlink
+    std::os::unix::fs::symlink(&home, tmp.path().join("link")).unwrap();
+    dotty(&home, &home)
+        .arg("--target")
+        .arg(tmp.path().join("link"))
+        .assert()
+        .failure()
+        .stderr(contains("is the target directory itself"));
+}
+
+#[test]
+fn target_inside_the_root_is_refused() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    fs::create_dir_all(root.join("home")).unwrap();
+
+    dotty(&root, &root.join("home"))
+        .assert()
+        .failure()
+        .stderr(contains("is inside root"));
+}
+
+#[test]
+fn root_nested_under_home_skips_targets_that_are_their_source() {
+    let tmp = TempDir::new().unwrap();
+    let home = tmp.path().join("home");
+    let root = home.join("dotfiles");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join(".zshrc"), "export A=1\n").unwrap();
+    fs::write(
+        root.join(".zshrc.lua"),
+        "return { target_path = 'dotfiles/.zshrc' }",
+    )
+    .unwrap();
+    fs::write(root.join(".vimrc"), "x").unwrap();
+
+    dotty(&root, &home)
+        .arg("--override-identical")
+        .assert()
+        .success()
+        .stdout(contains(
+            "⚠ Skipped ~/dotfiles/.zshrc: it is the source itself",
+        ));
+    assert!(!root.join(".zshrc").is_symlink());
+    assert_eq!(
+        fs::read_to_string(root.join(".zshrc")).unwrap(),
+        "export A=1\n"
+    );
+    assert_eq!(
+        fs::read_link(home.join(".vimrc")).unwrap(),
+        root.join(".vimrc")
+    );
+}
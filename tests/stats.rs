@@ -0,0 +1,74 @@
+use assert_cmd::Command;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+/// The value of the `--stats` line starting with `label`.
+fn value<'a>(stdout: &'a str, label: &str) -> &'a str {
+    stdout
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix(label))
+        .unwrap_or_else(|| panic!("no {label} in {stdout}"))
+        .trim()
+}
+
+/// Whether `text` is a duration like `12.3ms` or `1.50s`.
+fn is_duration(text: &str) -> bool {
+    let number = text
+        .strip_suffix("ms")
+        .or_else(|| text.strip_suffix('s'))
+        .unwrap_or("x");
+    number.parse::<f64>().is_ok()
+}
+
+#[test]
+fn stats_follow_the_summary() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .arg("--stats")
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.find("Summary:").unwrap() < stdout.find("\nStats:\n").unwrap());
+
+    assert!(is_duration(value(&stdout, "wall time")));
+    assert_eq!(value(&stdout, "files examined"), "4");
+    // The companion and its transform
+    let lua = value(&stdout, "lua evaluations");
+    let (count, time) = lua.split_once(' ').unwrap();
+    assert_eq!(count, "2");
+    assert!(is_duration(time.trim_matches(['(', ')'])));
+    assert!(is_duration(value(&stdout, "content comparisons")));
+    assert!(is_duration(value(&stdout, "filesystem changes")));
+}
+
+#[test]
+fn no_stats_without_the_flag() {
+    let tmp = TempDir::new().unwrap();
+    fs::create_dir_all(tmp.path().join("root")).unwrap();
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(tmp.path().join("root"))
+        .env("HOME", tmp.path())
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("Stats:"));
+}
@@ -0,0 +1,76 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs as unix_fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/new-app")).unwrap();
+    let transform = br#"return { transform = function(c) return c .. "!" end }"#;
+
+    // linked
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    unix_fs::symlink(root.join(".zshrc"), home.join(".zshrc")).unwrap();
+    // identical
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(root.join(".gitconfig.lua"), transform).unwrap();
+    fs::write(home.join(".gitconfig"), b"git!").unwrap();
+    // differs
+    fs::write(root.join(".npmrc"), b"npm").unwrap();
+    fs::write(root.join(".npmrc.lua"), transform).unwrap();
+    fs::write(home.join(".npmrc"), b"old npm!").unwrap();
+    // missing, below a directory that does not exist yet
+    fs::write(root.join(".config/new-app/app.toml"), b"app").unwrap();
+    // conflict
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(home.join(".vimrc"), b"local vim").unwrap();
+    // skipped
+    fs::write(root.join(".work"), b"work").unwrap();
+    fs::write(root.join(".work.lua"), b"return false").unwrap();
+    (tmp, root, home)
+}
+
+fn status(root: &std::path::Path, home: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("status").arg("--root").arg(root).arg("--no-color");
+    cmd.env("HOME", home);
+    cmd
+}
+
+#[test]
+fn status_reports_each_state() {
+    let (_tmp, root, home) = setup();
+
+    status(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("linked    ~/.zshrc\n"))
+        .stdout(contains("identical ~/.gitconfig\n"))
+        .stdout(contains("differs   ~/.npmrc\n"))
+        .stdout(contains("missing   ~/.config\n"))
+        .stdout(contains("conflict  ~/.vimrc (file)\n"))
+        .stdout(contains("skipped   ~/.work\n"))
+        .stdout(contains("Would").not())
+        .stdout(contains(
+            "Summary: 2 planned, 2 already in place, 1 conflict (1 file), 1 skipped by lua",
+        ));
+}
+
+#[test]
+fn status_changes_nothing() {
+    let (_tmp, root, home) = setup();
+    fs::create_dir_all(home.join(".config")).unwrap();
+
+    status(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("missing   ~/.config/new-app\n"));
+    assert!(!home.join(".config/new-app").exists());
+    assert_eq!(fs::read(home.join(".npmrc")).unwrap(), b"old npm!");
+    assert_eq!(fs::read(home.join(".vimrc")).unwrap(), b"local vim");
+}
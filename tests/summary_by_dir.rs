@@ -0,0 +1,65 @@
+use assert_cmd::Command;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn counts_are_listed_per_top_level_directory() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/kitty")).unwrap();
+    fs::create_dir_all(root.join("bin")).unwrap();
+    fs::create_dir_all(home.join(".config/kitty")).unwrap();
+    fs::write(root.join(".config/kitty/kitty.conf"), b"kitty").unwrap();
+    fs::write(root.join(".config/kitty/theme.conf"), b"theme").unwrap();
+    fs::write(home.join(".config/kitty/kitty.conf"), b"mine").unwrap();
+    fs::write(root.join("bin/tool"), b"tool").unwrap();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".vimrc.lua"), b"return false").unwrap();
+
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .arg("--no-folding")
+        .arg("--dry-run")
+        .arg("--summary-by-dir")
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let table = "\
+  directory  planned  in place  conflicts  skipped
+  .                1         0          0        1
+  .config          1         0          1        0
+  bin              1         0          0        0
+";
+    let at = stdout.find(table).unwrap_or_else(|| panic!("{stdout}"));
+    assert!(at < stdout.find("Summary: 3 planned").unwrap());
+}
+
+#[test]
+fn no_table_without_the_flag() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+
+    let output = Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .env("HOME", tmp.path())
+        .output()
+        .unwrap();
+    assert!(
+        !String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("directory")
+    );
+}
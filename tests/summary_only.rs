@@ -0,0 +1,56 @@
+use assert_cmd::Command;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(root.join(".gitconfig.lua"), b"return false").unwrap();
+    (tmp, root, home)
+}
+
+fn dotty(root: &Path, home: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(root).arg("--summary-only");
+    cmd.env("HOME", home);
+    cmd
+}
+
+#[test]
+fn conflicts_are_only_counted() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+
+    let output = dotty(&root, &home).arg("--no-color").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "Summary: 1 linked, 1 conflict (1 file), 1 skipped by lua, 0 overrides\n"
+    );
+    assert!(home.join(".vimrc").is_symlink());
+}
+
+#[test]
+fn fail_on_conflict_still_sets_the_exit_code() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"mine").unwrap();
+
+    let output = dotty(&root, &home)
+        .arg("--no-color")
+        .arg("--dry-run")
+        .arg("--fail-on-conflict")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+    assert!(stdout.starts_with("Summary: 1 planned, 1 conflict"));
+    assert!(!stdout.contains('\x1b'));
+}
@@ -0,0 +1,62 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn target_flag_links_into_given_directory() {
+    let (tmp, root, home) = dirs();
+    let target = tmp.path().join("sandbox");
+    fs::create_dir_all(root.join(".config")).unwrap();
+    fs::create_dir_all(target.join(".config")).unwrap();
+
+    fs::write(root.join(".config/a.txt"), b"A").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    fs::write(root.join("b.txt.lua"), b"return { rename_to = '.b' }").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--target")
+        .arg(&target)
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Linked ~/.config/a.txt"))
+        .stdout(contains("Summary: 2 linked, 0 conflicts"));
+
+    assert_eq!(
+        fs::read_link(target.join(".config/a.txt")).unwrap(),
+        root.join(".config/a.txt")
+    );
+    assert!(target.join(".b").is_symlink());
+    // Only the manifest is written below HOME
+    let names: Vec<_> = fs::read_dir(&home)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, [".local"]);
+    assert!(home.join(".local/state/dotty/manifest.json").is_file());
+}
+
+#[test]
+fn target_flag_requires_existing_directory() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    fs::create_dir_all(&root).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--target")
+        .arg(tmp.path().join("missing"))
+        .arg("--no-color");
+    cmd.env("HOME", tmp.path());
+    cmd.assert()
+        .failure()
+        .stderr(contains("Target directory is not a directory"));
+}
@@ -0,0 +1,209 @@
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+
+mod common;
+use common::{dirs, dotty};
+
+#[test]
+fn target_path_relocates_file_below_target_base() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("karabiner.json"), b"{}").unwrap();
+    fs::write(
+        root.join("karabiner.json.lua"),
+        b"return { target_path = '.config/karabiner/karabiner.json' }",
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("Would symlink ~/.config/karabiner/karabiner.json"));
+
+    dotty(&root, &home).assert().success();
+    assert_eq!(
+        fs::read_link(home.join(".config/karabiner/karabiner.json")).unwrap(),
+        root.join("karabiner.json")
+    );
+
+    // Identity checks follow the relocated path
+    dotty(&root, &home)
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stdout(contains(
+            "Would link (already in place) ~/.config/karabiner/karabiner.json",
+        ));
+}
+
+#[test]
+fn target_path_rejects_parent_and_absolute_components() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+
+    for path in ["../outside.txt", "/etc/a.txt", "x/../../a.txt"] {
+        fs::write(
+            root.join("a.txt.lua"),
+            format!("return {{ target_path = '{path}' }}"),
+        )
+        .unwrap();
+        dotty(&root, &home)
+            .assert()
+            .failure()
+            .stderr(contains("target_path must be a relative path without '..'"));
+    }
+}
+
+#[test]
+fn target_path_and_rename_to_cannot_be_combined() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(
+        root.join("a.txt.lua"),
+        b"return { target_path = 'x/a.txt', rename_to = 'b.txt' }",
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .failure()
+        .stderr(contains("rename_to and target_path cannot be combined"));
+}
+
+#[test]
+fn absolute_target_overrides_layout_and_is_checked_against_target_base() {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("settings.json"), b"{}").unwrap();
+    fs::write(
+        root.join("settings.json.lua"),
+        b"return { target = '~/Library/Application Support/Code/User/settings.json' }",
+    )
+    .unwrap();
+
+    dotty(&root, &home).assert().success();
+    assert_eq!(
+        fs::read_link(home.join("Library/Application Support/Code/User/settings.json")).unwrap(),
+        root.join("settings.json")
+    );
+
+    let outside = tmp.path().join("outside/settings.json");
+    fs::write(
+        root.join("settings.json.lua"),
+        format!("return {{ target = '{}' }}", outside.display()),
+    )
+    .unwrap();
+    dotty(&root, &home)
+        .assert()
+        .failure()
+        .stderr(contains("pass --allow-outside-home"));
+
+    dotty(&root, &home)
+        .arg("--allow-outside-home")
+        .assert()
+        .success();
+    assert_eq!(fs::read_link(&outside).unwrap(), root.join("settings.json"));
+}
+
+#[test]
+fn targets_plan_one_action_per_destination() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(home.join("Developer")).unwrap();
+    fs::write(root.join("editorconfig"), b"root = true").unwrap();
+    fs::write(
+        root.join("editorconfig.lua"),
+        br#"return { targets = { "~/.editorconfig", "Developer/.editorconfig" } }"#,
+    )
+    .unwrap();
+    fs::write(home.join("Developer/.editorconfig"), b"local").unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("✗ exists (file) ~/Developer/.editorconfig"))
+        .stdout(contains("Summary: 1 linked, 1 conflict"));
+    assert_eq!(
+        fs::read_link(home.join(".editorconfig")).unwrap(),
+        root.join("editorconfig")
+    );
+
+    // The transform runs once and its output is written to every target
+    fs::write(
+        root.join("editorconfig.lua"),
+        br#"return {
+  targets = { "~/.editorconfig", "Developer/.editorconfig" },
+  transform = function(c) return c .. "\n" end,
+}"#,
+    )
+    .unwrap();
+    fs::remove_file(home.join(".editorconfig")).unwrap();
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Summary: 2 linked, 0 conflicts"));
+    assert_eq!(
+        fs::read(home.join(".editorconfig")).unwrap(),
+        b"root = true\n"
+    );
+    assert_eq!(
+        fs::read(home.join("Developer/.editorconfig")).unwrap(),
+        b"root = true\n"
+    );
+}
+
+#[test]
+fn targets_and_rename_to_cannot_be_combined() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(
+        root.join("a.txt.lua"),
+        b"return { targets = { 'x/a.txt' }, rename_to = 'b.txt' }",
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .failure()
+        .stderr(contains("rename_to and targets cannot be combined"));
+}
+
+#[test]
+fn paths_next_to_the_target_base_are_not_shortened() {
+    let (tmp, root, home) = dirs();
+    let outside = tmp.path().join("home2/app.conf");
+    fs::write(root.join("app.conf"), b"x").unwrap();
+    fs::write(
+        root.join("app.conf.lua"),
+        format!("return {{ target = '{}' }}", outside.display()),
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .arg("--allow-outside-home")
+        .assert()
+        .success()
+        .stdout(contains(format!("Linked {}", outside.display())))
+        .stdout(contains("~2").not());
+}
+
+#[test]
+fn tilde_in_declared_targets_is_the_target_base() {
+    let (tmp, root, home) = dirs();
+    let base = tmp.path().join("base");
+    fs::create_dir_all(&base).unwrap();
+    fs::write(root.join("app.conf"), b"x").unwrap();
+    fs::write(
+        root.join("app.conf.lua"),
+        b"return { targets = { '~/.app.conf', '~/.config/app.conf' } }",
+    )
+    .unwrap();
+
+    dotty(&root, &home)
+        .arg("--target")
+        .arg(&base)
+        .assert()
+        .success();
+    assert!(base.join(".app.conf").is_symlink());
+    assert!(base.join(".config/app.conf").is_symlink());
+    assert!(!home.join(".app.conf").exists());
+}
@@ -0,0 +1,101 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(
+        root.join(".gitconfig"),
+        "[user]\n\temail = {{ email }}\n\
+         {% if profile == \"work\" %}\tsigningkey = WORK\n{% endif %}\
+         {% for name, value in vars|items %}# {{ name }}={{ value }}\n{% endfor %}\
+         # host {{ dotty.hostname }}\n",
+    )
+    .unwrap();
+    fs::write(root.join(".gitconfig.lua"), b"return { template = true }").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn template_renders_vars_and_facts() {
+    let (_tmp, root, home) = setup();
+    fs::write(
+        root.join("dotty.toml"),
+        "[vars]\nemail = \"root@example.com\"\nprofile = \"home\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--var")
+        .arg("profile=work")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "work-mbp");
+    cmd.assert()
+        .success()
+        .stdout(contains("Wrote transformed file"));
+
+    assert_eq!(
+        fs::read_to_string(home.join(".gitconfig")).unwrap(),
+        "[user]\n\temail = root@example.com\n\tsigningkey = WORK\n\
+         # email=root@example.com\n# profile=work\n# host work-mbp\n"
+    );
+
+    // Rendering the same output again finds it in place
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--var")
+        .arg("profile=work")
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home).env("DOTTY_HOSTNAME", "work-mbp");
+    cmd.assert()
+        .success()
+        .stdout(contains("Would write (already in place)"));
+}
+
+#[test]
+fn undefined_variable_reports_file_and_line() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--var")
+        .arg("profile=work")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("Failed to render template"))
+        .stderr(contains("undefined value"))
+        .stderr(contains(".gitconfig:2)"));
+    assert!(!home.join(".gitconfig").exists());
+}
+
+#[test]
+fn template_syntax_error_is_reported() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join("broken.txt"), b"line\n{% if %}\n").unwrap();
+    fs::write(root.join("broken.txt.lua"), b"return { template = true }").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--var")
+        .arg("email=me@corp.com")
+        .arg("--var")
+        .arg("profile=home")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("syntax error"))
+        .stderr(contains("broken.txt:2)"));
+}
@@ -0,0 +1,79 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/git")).unwrap();
+    fs::write(root.join(".config/git/config"), b"[user]").unwrap();
+    fs::write(
+        root.join(".config/git/config.lua"),
+        r#"
+        print("deciding", 42, nil)
+        return {
+            transform = function(content)
+                print("transforming")
+                return content
+            end,
+        }
+        "#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn trace_lua_attributes_prints_to_the_companion() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--trace-lua")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stderr(contains("lua[.config/git/config.lua]: deciding\t42\tnil"))
+        .stderr(contains("lua[.config/git/config.lua]: transforming"))
+        .stdout(contains("deciding").not());
+}
+
+#[test]
+fn verbose_shows_prints_too() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--verbose")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stderr(contains("lua[.config/git/config.lua]: deciding"));
+}
+
+#[test]
+fn prints_are_swallowed_by_default() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("deciding").not())
+        .stdout(contains("transforming").not())
+        .stdout(contains("Would write transformed file"));
+}
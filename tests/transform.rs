@@ -128,6 +128,63 @@ fn transform_overwrites_existing_different_file() {
     assert_eq!(content, "new");
 }
 
+#[test]
+fn show_changes_prints_diff_of_rewritten_file() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(root.join("config.txt"), b"name = dotty\ncolor = red\n").unwrap();
+    let lua_script = r#"
+        return { transform = function(content) return (content:gsub("red", "blue")) end }
+    "#;
+    fs::write(root.join("config.txt.lua"), lua_script).unwrap();
+    fs::write(home.join("config.txt"), b"name = dotty\ncolor = green\n").unwrap();
+
+    // Without the flag only the action is reported
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("-color = green").not());
+
+    for dry_run in [true, false] {
+        let mut cmd = Command::cargo_bin("dotty").unwrap();
+        cmd.arg("--root")
+            .arg(&root)
+            .arg("--show-changes")
+            .arg("--no-color");
+        if dry_run {
+            cmd.arg("--dry-run");
+        }
+        cmd.env("HOME", &home);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("transformed file ~/config.txt"))
+            .stdout(predicate::str::contains(
+                "@@ -1,2 +1,2 @@\n name = dotty\n-color = green\n+color = blue\n",
+            ));
+    }
+
+    // Once written, there is nothing left to show
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--verbose")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("already in place"))
+        .stdout(predicate::str::contains("@@").not());
+}
+
 #[test]
 fn transform_skips_writing_identical_file() {
     let tmp = TempDir::new().unwrap();
@@ -156,7 +213,10 @@ fn transform_skips_writing_identical_file() {
     cmd_dry
         .assert()
         .success()
-        .stdout(predicate::str::contains("Would write (already in place)"));
+        .stdout(predicate::str::contains("Would write (already in place)"))
+        .stdout(predicate::str::contains(
+            "Summary: 0 planned, 1 already in place, 0 conflicts",
+        ));
 }
 
 #[test]
@@ -187,3 +247,270 @@ fn transform_without_rename_to_uses_original_name() {
     let content = fs::read_to_string(target_path).unwrap();
     assert_eq!(content, "output");
 }
+
+#[test]
+fn transform_receives_file_context() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::create_dir_all(home.join(".config/app")).unwrap();
+
+    fs::write(root.join(".config/app/settings.in"), b"body").unwrap();
+    let lua_script = r#"
+        return {
+            rename_to = "settings.conf",
+            transform = function(content, file)
+                return table.concat({ content, file.path, file.name, file.target }, "\n")
+            end
+        }
+    "#;
+    fs::write(root.join(".config/app/settings.in.lua"), lua_script).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+
+    let target_path = home.join(".config/app/settings.conf");
+    let content = fs::read_to_string(&target_path).unwrap();
+    assert_eq!(
+        content,
+        format!(
+            "body\n.config/app/settings.in\nsettings.in\n{}",
+            target_path.display()
+        )
+    );
+}
+
+#[test]
+fn transform_context_path_is_relative_to_the_root_for_packages() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+    fs::create_dir_all(root.join("git")).unwrap();
+    fs::write(root.join("git/.gitconfig"), b"body").unwrap();
+    fs::write(
+        root.join("git/.gitconfig.lua"),
+        b"return { transform = function(content, file) return file.path end }",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color").arg("git");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(home.join(".gitconfig")).unwrap(),
+        "git/.gitconfig"
+    );
+}
+
+#[test]
+fn transform_returning_nil_skips_file() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(root.join("snippet.txt"), b"-- placeholder").unwrap();
+    fs::write(root.join("other.txt"), b"real").unwrap();
+    let lua_script = r#"
+        return {
+            transform = function(content)
+                if content:match("^%-%- placeholder") then return nil end
+                return content
+            end
+        }
+    "#;
+    fs::write(root.join("snippet.txt.lua"), lua_script).unwrap();
+    fs::write(
+        root.join("other.txt.lua"),
+        "return { transform = function() return false end }",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Skipped by lua: ~/snippet.txt"))
+        .stdout(predicate::str::contains("Skipped by lua: ~/other.txt"))
+        .stdout(predicate::str::contains("2 skipped by lua"));
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+    assert!(!home.join("snippet.txt").exists());
+    assert!(!home.join("other.txt").exists());
+}
+
+#[test]
+fn transform_returning_other_type_names_companion() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(root.join("config.txt"), b"old").unwrap();
+    fs::write(
+        root.join("config.txt.lua"),
+        "return { transform = function() return 42 end }",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "config.txt.lua must return a string, a table, nil or false",
+        ))
+        .stderr(predicate::str::contains("Got integer"));
+    assert!(!home.join("config.txt").exists());
+}
+
+#[test]
+fn transform_list_pipes_content_through_each_function() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(root.join("config.txt"), b"# comment\nname = NAME\n").unwrap();
+    let lua_script = r##"
+        local function strip_comments(content)
+            return (content:gsub("#[^\n]*\n", ""))
+        end
+        local function substitute(content)
+            return (content:gsub("NAME", "dotty"))
+        end
+        local function footer(content)
+            return content .. "# managed by dotty\n"
+        end
+        return { transform = { strip_comments, substitute, footer } }
+    "##;
+    fs::write(root.join("config.txt.lua"), lua_script).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+
+    let content = fs::read_to_string(home.join("config.txt")).unwrap();
+    assert_eq!(content, "name = dotty\n# managed by dotty\n");
+}
+
+#[test]
+fn transform_list_reports_failing_index() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(root.join("config.txt"), b"old").unwrap();
+    let lua_script = r#"
+        return {
+            transform = {
+                function(content) return content end,
+                function(content) error("boom") end,
+            }
+        }
+    "#;
+    fs::write(root.join("config.txt.lua"), lua_script).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Lua transform function error in transform[2]",
+        ))
+        .stderr(predicate::str::contains("boom"));
+}
+
+#[test]
+fn transform_list_rejects_non_functions() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(root.join("config.txt"), b"old").unwrap();
+    let lua_script = r#"
+        return { transform = { function(content) return content end, "upper" } }
+    "#;
+    fs::write(root.join("config.txt.lua"), lua_script).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("transform[2] in"))
+        .stderr(predicate::str::contains("must be a function, got string"));
+    assert!(!home.join("config.txt").exists());
+}
+
+#[test]
+fn dry_run_shows_line_stats() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let home = tmp.path().join("home");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&home).unwrap();
+
+    fs::write(root.join("config.txt"), b"a = 1\nb = 2\nc = 3\n").unwrap();
+    let lua_script = r#"
+        return { transform = function(content) return (content:gsub("= 2", "= 20")) .. "d = 4\n" end }
+    "#;
+    fs::write(root.join("config.txt.lua"), lua_script).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Would write transformed file ~/config.txt from ",
+        ))
+        .stdout(predicate::str::contains("config.txt (new file, 4 lines)"));
+
+    fs::write(home.join("config.txt"), b"a = 1\nb = 2\nc = 3\n").unwrap();
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("config.txt (+2/-1)"));
+
+    // Real runs only add the stats with --verbose
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Overwrote transformed file"))
+        .stdout(predicate::str::contains("(+2/-1)").not());
+}
@@ -0,0 +1,103 @@
+use predicates::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup(name: &str, content: &[u8], lua: &str) -> (TempDir, PathBuf, PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(name), content).unwrap();
+    fs::write(root.join(format!("{name}.lua")), lua).unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn invalid_utf8_is_kept_byte_for_byte() {
+    let lua = r#"
+        return {
+            transform_bytes = function(bytes)
+                return (bytes:gsub("old", "new"))
+            end
+        }
+    "#;
+    let (_tmp, root, home) = setup("config", b"# caf\xe9\nhost = old\n", lua);
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote transformed file"));
+    assert_eq!(
+        fs::read(home.join("config")).unwrap(),
+        b"# caf\xe9\nhost = new\n"
+    );
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 already in place"));
+}
+
+#[test]
+fn binary_output_shows_its_size_in_dry_runs() {
+    let lua = r#"
+        return {
+            transform_bytes = function(bytes, ctx)
+                return bytes .. "\0\1", "0600"
+            end,
+            rename_to = "blob.bin",
+        }
+    "#;
+    let (_tmp, root, home) = setup("blob", b"\x7fELF\x00", lua);
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(binary, 7 bytes)"))
+        .stdout(predicate::str::contains("blob.bin"));
+
+    dotty(&root, &home).assert().success();
+    assert_eq!(
+        fs::read(home.join("blob.bin")).unwrap(),
+        b"\x7fELF\x00\x00\x01"
+    );
+    assert!(!home.join("blob").exists());
+}
+
+#[test]
+fn transform_and_transform_bytes_cannot_be_mixed() {
+    let lua = r#"
+        return {
+            transform = function(content) return content end,
+            transform_bytes = function(bytes) return bytes end,
+        }
+    "#;
+    let (_tmp, root, home) = setup("config", b"x\n", lua);
+
+    dotty(&root, &home)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "transform_bytes cannot be combined with transform",
+        ));
+}
+
+#[test]
+fn text_transforms_point_to_transform_bytes() {
+    let lua = r#"
+        return {
+            transform = function(content) return content end,
+        }
+    "#;
+    let (_tmp, root, home) = setup("config", b"caf\xe9\n", lua);
+
+    dotty(&root, &home)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "use transform_bytes for raw content",
+        ));
+}
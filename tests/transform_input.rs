@@ -0,0 +1,105 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+const UPPERCASE: &str = r#"
+    return {
+        transform = function(content)
+            return content:upper()
+        end
+    }
+"#;
+
+fn setup(content: &[u8]) -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("config"), content).unwrap();
+    fs::write(root.join("config.lua"), UPPERCASE).unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn files_above_the_size_limit_are_refused() {
+    let (_tmp, root, home) = setup(&[b'a'; 2048]);
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--max-transform-size")
+        .arg("1K")
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "is 2048 bytes, more than the 1024 bytes --max-transform-size allows",
+        ))
+        .stderr(predicate::str::contains("config.lua"));
+    assert!(!home.join("config").exists());
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--max-transform-size")
+        .arg("4096")
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .success();
+    assert_eq!(fs::read(home.join("config")).unwrap(), vec![b'A'; 2048]);
+}
+
+#[test]
+fn binary_sources_need_text_content() {
+    let (_tmp, root, home) = setup(b"\x7fELF\x00\x01\x02");
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("transform requires text content"))
+        .stderr(predicate::str::contains("config is binary"))
+        .stderr(predicate::str::contains("config.lua"));
+}
+
+#[test]
+fn invalid_utf8_sources_need_text_content() {
+    let (_tmp, root, home) = setup(b"# caf\xe9\n");
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--no-color")
+        .env("HOME", &home)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("config is not valid UTF-8"));
+}
+
+#[test]
+fn invalid_sizes_are_rejected() {
+    let (_tmp, root, home) = setup(b"x");
+
+    Command::cargo_bin("dotty")
+        .unwrap()
+        .arg("--root")
+        .arg(&root)
+        .arg("--max-transform-size")
+        .arg("4X")
+        .env("HOME", &home)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'4X' is not a size like 512K or 4M",
+        ));
+}
@@ -0,0 +1,142 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup(lua_script: &str) -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("hello.sh"), b"echo NAME").unwrap();
+    fs::write(root.join("hello.sh.lua"), lua_script).unwrap();
+    (tmp, root, home)
+}
+
+fn mode_of(path: &std::path::Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o7777
+}
+
+#[test]
+fn transform_returning_two_values_sets_mode() {
+    let (_tmp, root, home) = setup(
+        r#"return { transform = function(content) return content:gsub("NAME", "hi"), "0755" end }"#,
+    );
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Would write transformed file ~/hello.sh",
+        ))
+        .stdout(predicate::str::contains("(mode 0755)"));
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+
+    let target = home.join("hello.sh");
+    assert_eq!(fs::read_to_string(&target).unwrap(), "echo hi");
+    assert_eq!(mode_of(&target), 0o755);
+}
+
+#[test]
+fn transform_table_mode_rewrites_on_chmod_only_difference() {
+    let (_tmp, root, home) = setup(
+        r#"return { transform = function(content) return { content = content, mode = "0600" } end }"#,
+    );
+    let target = home.join("hello.sh");
+    fs::write(&target, b"echo NAME").unwrap();
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Overwrote transformed file ~/hello.sh",
+    ));
+    assert_eq!(mode_of(&target), 0o600);
+
+    // Content and mode both match now, so nothing is written
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would write (already in place)"));
+}
+
+#[test]
+fn invalid_mode_is_rejected_at_plan_time() {
+    let (_tmp, root, home) =
+        setup(r#"return { transform = function(content) return content, "rwxr-xr-x" end }"#);
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid mode returned by transform",
+        ))
+        .stderr(predicate::str::contains(
+            "'rwxr-xr-x' is not an octal file mode",
+        ));
+}
+
+#[test]
+fn transform_without_mode_keeps_source_permissions() {
+    let (_tmp, root, home) =
+        setup(r#"return { transform = function(content) return content .. "\n" end }"#);
+    fs::set_permissions(root.join("hello.sh"), fs::Permissions::from_mode(0o600)).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+
+    let target = home.join("hello.sh");
+    assert_eq!(mode_of(&target), mode_of(&root.join("hello.sh")));
+}
+
+#[test]
+fn transform_overwrite_applies_source_permissions() {
+    let (_tmp, root, home) =
+        setup(r#"return { transform = function(content) return content .. "\n" end }"#);
+    fs::set_permissions(root.join("hello.sh"), fs::Permissions::from_mode(0o600)).unwrap();
+    let target = home.join("hello.sh");
+    fs::write(&target, b"stale").unwrap();
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("(mode 0644 -> 0600)"));
+    assert_eq!(mode_of(&target), 0o644);
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root").arg(&root).arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Overwrote transformed file ~/hello.sh",
+    ));
+    assert_eq!(mode_of(&target), mode_of(&root.join("hello.sh")));
+}
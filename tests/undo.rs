@@ -0,0 +1,153 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    fs::write(root.join(".config/app/app.toml"), b"app").unwrap();
+    fs::write(
+        root.join(".config/app/app.toml.lua"),
+        br#"return { transform = function(c) return c .. "!" end }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+fn undo(root: &Path, home: &Path) -> Command {
+    let mut cmd = dotty(root, home);
+    cmd.arg("undo");
+    cmd
+}
+
+#[test]
+fn undo_reverts_the_last_run() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+    assert!(home.join(".zshrc").is_symlink());
+    assert!(home.join(".config/app/app.toml").is_file());
+
+    undo(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Removed link ~/.zshrc"))
+        .stdout(contains("Removed file ~/.config/app/app.toml"))
+        .stdout(contains("Removed directory ~/.config/app/"))
+        .stdout(contains("Summary: 4 reverted, 0 left alone"));
+    assert!(!home.join(".zshrc").exists());
+    assert!(!home.join(".config").exists());
+
+    undo(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Nothing to undo"));
+}
+
+#[test]
+fn undo_restores_backed_up_files() {
+    let (_tmp, root, home) = setup();
+    fs::write(home.join(".zshrc"), b"local zsh").unwrap();
+    dotty(&root, &home)
+        .arg("--on-conflict=backup")
+        .arg("--only")
+        .arg(".zshrc")
+        .assert()
+        .success();
+    assert!(home.join(".zshrc").is_symlink());
+
+    undo(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Removed link ~/.zshrc"))
+        .stdout(contains("Restored ~/.zshrc from"));
+    assert_eq!(fs::read(home.join(".zshrc")).unwrap(), b"local zsh");
+}
+
+#[test]
+fn undo_leaves_changed_targets_alone() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).assert().success();
+    fs::write(home.join(".config/app/app.toml"), b"edited").unwrap();
+
+    undo(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains(
+            "left alone: ~/.config/app/app.toml (changed since the run)",
+        ))
+        .stdout(contains("left alone: ~/.config/app (not empty)"))
+        .stdout(contains("Removed link ~/.zshrc"));
+    assert_eq!(
+        fs::read(home.join(".config/app/app.toml")).unwrap(),
+        b"edited"
+    );
+}
+
+#[test]
+fn undo_list_and_dry_run_change_nothing() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home)
+        .arg("--only")
+        .arg(".zshrc")
+        .assert()
+        .success();
+    dotty(&root, &home).assert().success();
+    // A run that changes nothing is not recorded
+    dotty(&root, &home).assert().success();
+
+    undo(&root, &home)
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(contains("  1  "))
+        .stdout(contains("  2  "))
+        .stdout(contains("--only .zshrc (1 change)"))
+        .stdout(contains("  3  ").not());
+
+    undo(&root, &home)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(contains("Would remove file ~/.config/app/app.toml"))
+        .stdout(contains("Would remove directory ~/.config/"))
+        .stdout(contains("~/.zshrc").not());
+    assert!(home.join(".config/app/app.toml").is_file());
+    let journal = fs::read_to_string(home.join(".local/state/dotty/journal.jsonl")).unwrap();
+    assert_eq!(journal.lines().count(), 2);
+}
+
+#[test]
+fn a_failed_run_still_records_what_it_did() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a"), b"a").unwrap();
+    fs::write(root.join("b"), b"b").unwrap();
+    fs::write(home.join("b"), b"mine").unwrap();
+    dotty(&root, &home)
+        .arg("--on-conflict=fail")
+        .assert()
+        .failure()
+        .stderr(contains("Conflict at ~/b"));
+    assert!(home.join("a").is_symlink());
+
+    undo(&root, &home)
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(contains("--on-conflict=fail (1 change)"));
+    let manifest = fs::read_to_string(home.join(".local/state/dotty/manifest.json")).unwrap();
+    assert!(manifest.contains(&home.join("a").display().to_string()));
+
+    undo(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("Removed link ~/a"));
+    assert!(!home.join("a").exists());
+    assert_eq!(fs::read(home.join("b")).unwrap(), b"mine");
+}
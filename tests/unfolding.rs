@@ -0,0 +1,99 @@
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("nvim/.config/nvim/lua")).unwrap();
+    fs::create_dir_all(root.join("kitty/.config/kitty")).unwrap();
+    fs::write(root.join("nvim/.config/nvim/init.lua"), b"init").unwrap();
+    fs::write(root.join("nvim/.config/nvim/lua/plugins.lua"), b"plugins").unwrap();
+    fs::write(root.join("kitty/.config/kitty/kitty.conf"), b"kitty").unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn no_folding_links_files_one_by_one() {
+    let (_tmp, root, home) = setup();
+
+    dotty(&root, &home)
+        .arg("--no-folding")
+        .arg("nvim")
+        .assert()
+        .success()
+        .stdout(contains("Summary: 2 linked, 0 conflicts"));
+
+    assert!(!home.join(".config").is_symlink());
+    assert!(!home.join(".config/nvim/lua").is_symlink());
+    assert!(home.join(".config/nvim/lua/plugins.lua").is_symlink());
+}
+
+#[test]
+fn no_folding_unfolds_nested_folded_dirs() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).arg("nvim").assert().success();
+    assert!(home.join(".config").is_symlink());
+
+    dotty(&root, &home)
+        .arg("--no-folding")
+        .arg("--dry-run")
+        .arg("nvim")
+        .assert()
+        .success()
+        .stdout(contains("Would unfold dir ~/.config"))
+        .stdout(contains("1 unfolded dir"));
+    assert!(home.join(".config").is_symlink());
+
+    dotty(&root, &home)
+        .arg("--no-folding")
+        .arg("nvim")
+        .assert()
+        .success()
+        .stdout(contains("Unfolded dir ~/.config"))
+        .stdout(contains("Summary: 2 linked, 0 conflicts"));
+    assert!(home.join(".config/nvim").is_dir() && !home.join(".config/nvim").is_symlink());
+    assert_eq!(
+        fs::read_link(home.join(".config/nvim/lua/plugins.lua")).unwrap(),
+        root.join("nvim/.config/nvim/lua/plugins.lua")
+    );
+}
+
+#[test]
+fn new_package_unfolds_directory_link_of_another() {
+    let (_tmp, root, home) = setup();
+    dotty(&root, &home).arg("nvim").assert().success();
+    assert_eq!(
+        fs::read_link(home.join(".config")).unwrap(),
+        root.join("nvim/.config")
+    );
+
+    dotty(&root, &home)
+        .arg("--dry-run")
+        .arg("kitty")
+        .assert()
+        .success()
+        .stdout(contains("Would unfold dir ~/.config (1 link into"))
+        .stdout(contains("Would symlink dir ~/.config/kitty"));
+
+    dotty(&root, &home)
+        .arg("kitty")
+        .assert()
+        .success()
+        .stdout(contains("Summary: 1 linked, 0 conflicts"))
+        .stdout(contains("1 folded dir, 1 unfolded dir"));
+
+    assert!(!home.join(".config").is_symlink());
+    // The old link swapped out of the way is gone too
+    assert!(fs::symlink_metadata(home.join("..config.dotty-unfold")).is_err());
+    assert_eq!(
+        fs::read_link(home.join(".config/nvim")).unwrap(),
+        root.join("nvim/.config/nvim")
+    );
+    assert_eq!(
+        fs::read_link(home.join(".config/kitty")).unwrap(),
+        root.join("kitty/.config/kitty")
+    );
+}
@@ -0,0 +1,56 @@
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs::symlink;
+
+mod common;
+use common::{dirs, dotty};
+
+/// `café` composed (NFC), as sources are usually named.
+const NFC: &str = "caf\u{e9}";
+/// `café` decomposed (NFD), as macOS volumes and some tools store it.
+const NFD: &str = "cafe\u{301}";
+
+#[test]
+fn link_under_the_other_normalization_is_already_in_place() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join(NFC), "x").unwrap();
+    symlink(root.join(NFC), home.join(NFD)).unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains("1 already in place"));
+    assert!(!home.join(NFC).exists());
+    assert!(home.join(NFD).is_symlink());
+}
+
+#[test]
+fn file_under_the_other_normalization_is_the_conflicting_target() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join(NFD), "new").unwrap();
+    fs::write(home.join(NFC), "old").unwrap();
+
+    dotty(&root, &home)
+        .assert()
+        .success()
+        .stdout(contains(format!("~/{NFC}")))
+        .stdout(contains("1 conflict"));
+    assert!(fs::symlink_metadata(home.join(NFD)).is_err());
+    assert_eq!(fs::read_to_string(home.join(NFC)).unwrap(), "old");
+}
+
+#[test]
+fn files_go_into_the_existing_directory_of_the_other_normalization() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(NFC)).unwrap();
+    fs::write(root.join(NFC).join("menu.txt"), "x").unwrap();
+    fs::create_dir_all(home.join(NFD)).unwrap();
+    fs::write(home.join(NFD).join("other.txt"), "y").unwrap();
+
+    dotty(&root, &home).assert().success();
+    assert_eq!(
+        fs::read_link(home.join(NFD).join("menu.txt")).unwrap(),
+        root.join(NFC).join("menu.txt")
+    );
+    assert!(fs::symlink_metadata(home.join(NFC)).is_err());
+}
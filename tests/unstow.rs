@@ -0,0 +1,112 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs as unix_fs;
+
+mod common;
+use common::dirs;
+
+#[test]
+fn unstow_removes_links_into_root() {
+    let (_tmp, root, home) = dirs();
+    fs::create_dir_all(root.join(".config/app")).unwrap();
+    fs::create_dir_all(home.join(".config/app")).unwrap();
+    fs::write(root.join(".config/app/a.txt"), b"A").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    fs::write(root.join("b.txt.lua"), b"return { rename_to = '.b' }").unwrap();
+
+    let mut stow = Command::cargo_bin("dotty").unwrap();
+    stow.arg("--root").arg(&root).arg("--no-color");
+    stow.env("HOME", &home);
+    stow.assert().success();
+    assert!(home.join(".config/app/a.txt").is_symlink());
+    assert!(home.join(".b").is_symlink());
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--unstow")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Removed"))
+        .stdout(contains("Summary: 2 removed, 0 kept, 0 missing"));
+
+    assert!(!home.join(".config/app/a.txt").exists());
+    assert!(!home.join(".b").is_symlink());
+}
+
+#[test]
+fn unstow_dry_run_keeps_links() {
+    let (_tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(root.join("c.txt"), b"C").unwrap();
+    unix_fs::symlink(root.join("a.txt"), home.join("a.txt")).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--unstow")
+        .arg("--dry-run")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("Would remove"))
+        .stdout(contains("Not present"))
+        .stdout(contains("Summary: 1 to remove, 0 kept, 1 missing"));
+
+    assert!(home.join("a.txt").is_symlink());
+}
+
+#[test]
+fn unstow_leaves_foreign_files_and_links() {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    fs::write(home.join("a.txt"), b"A").unwrap();
+    let elsewhere = tmp.path().join("elsewhere");
+    fs::write(&elsewhere, b"B").unwrap();
+    unix_fs::symlink(&elsewhere, home.join("b.txt")).unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--unstow")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("kept"))
+        .stdout(contains("Summary: 0 removed, 2 kept, 0 missing"));
+
+    assert!(home.join("a.txt").is_file());
+    assert!(home.join("b.txt").is_symlink());
+}
+
+#[test]
+fn unstow_transformed_files_only_when_unmodified() {
+    let (_tmp, root, home) = dirs();
+    let lua_script = r#"return { transform = function(c) return c .. "!" end }"#;
+    fs::write(root.join("a.txt"), b"A").unwrap();
+    fs::write(root.join("a.txt.lua"), lua_script).unwrap();
+    fs::write(root.join("b.txt"), b"B").unwrap();
+    fs::write(root.join("b.txt.lua"), lua_script).unwrap();
+    fs::write(home.join("a.txt"), b"A!").unwrap();
+    fs::write(home.join("b.txt"), b"edited by hand").unwrap();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--unstow")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .success()
+        .stdout(contains("modified, not removed"))
+        .stdout(contains("Summary: 1 removed, 1 kept, 0 missing"));
+
+    assert!(!home.join("a.txt").exists());
+    assert_eq!(fs::read(home.join("b.txt")).unwrap(), b"edited by hand");
+}
@@ -0,0 +1,88 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".gitconfig"), b"").unwrap();
+    fs::write(
+        root.join(".gitconfig.lua"),
+        r#"return {
+            transform = function()
+                local names = {}
+                for name in pairs(dotty.vars) do
+                    table.insert(names, name)
+                end
+                table.sort(names)
+                return table.concat({
+                    dotty.var("profile"),
+                    dotty.var("email"),
+                    tostring(dotty.var("missing")),
+                    table.concat(names, ","),
+                }, "\n")
+            end,
+        }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+#[test]
+fn vars_reach_companions() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--var")
+        .arg("profile=work")
+        .arg("--var")
+        .arg("email=me@corp.com")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(home.join(".gitconfig")).unwrap(),
+        "work\nme@corp.com\nnil\nemail,profile"
+    );
+}
+
+#[test]
+fn duplicate_var_is_rejected() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--var")
+        .arg("profile=work")
+        .arg("--var")
+        .arg("profile=home")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("--var profile is given more than once"));
+    assert!(!home.join(".gitconfig").exists());
+}
+
+#[test]
+fn var_without_key_is_rejected() {
+    let (_tmp, root, home) = setup();
+
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("--root")
+        .arg(&root)
+        .arg("--var")
+        .arg("=work")
+        .arg("--no-color");
+    cmd.env("HOME", &home);
+    cmd.assert()
+        .failure()
+        .stderr(contains("Invalid --var '=work': expected KEY=VALUE"));
+}
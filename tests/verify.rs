@@ -0,0 +1,94 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::os::unix::fs as unix_fs;
+use tempfile::TempDir;
+
+mod common;
+use common::dirs;
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::write(root.join(".zshrc"), b"zsh").unwrap();
+    unix_fs::symlink(root.join(".zshrc"), home.join(".zshrc")).unwrap();
+    fs::write(root.join(".gitconfig"), b"git").unwrap();
+    fs::write(root.join(".gitconfig.lua"), b"return { copy = true }").unwrap();
+    fs::write(home.join(".gitconfig"), b"git").unwrap();
+    (tmp, root, home)
+}
+
+fn verify(root: &std::path::Path, home: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("dotty").unwrap();
+    cmd.arg("verify").arg("--root").arg(root).arg("--no-color");
+    cmd.env("HOME", home);
+    cmd
+}
+
+#[test]
+fn verify_succeeds_when_everything_is_in_place() {
+    let (_tmp, root, home) = setup();
+
+    verify(&root, &home)
+        .assert()
+        .code(0)
+        .stdout(contains("Everything is in place (2 entries)"))
+        .stdout(contains("~/.zshrc").not());
+}
+
+#[test]
+fn verify_lists_what_would_change() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(home.join(".gitconfig"), b"old git").unwrap();
+
+    verify(&root, &home)
+        .assert()
+        .code(1)
+        .stdout(contains("missing   ~/.vimrc\n"))
+        .stdout(contains("differs   ~/.gitconfig\n"))
+        .stdout(contains("~/.zshrc").not())
+        .stdout(contains("Verify: 1 in place, 2 to change, 0 conflicts"));
+
+    assert!(!home.join(".vimrc").exists());
+    assert_eq!(fs::read(home.join(".gitconfig")).unwrap(), b"old git");
+}
+
+#[test]
+fn verify_fails_harder_on_conflicts() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+    fs::write(home.join(".vimrc"), b"local vim").unwrap();
+    fs::write(root.join(".npmrc"), b"npm").unwrap();
+
+    verify(&root, &home)
+        .assert()
+        .code(2)
+        .stdout(contains("conflict  ~/.vimrc (file)\n"))
+        .stdout(contains("missing   ~/.npmrc\n"))
+        .stdout(contains("1 to change, 1 conflict"));
+
+    assert_eq!(fs::read(home.join(".vimrc")).unwrap(), b"local vim");
+    assert!(!home.join(".npmrc").exists());
+}
+
+#[test]
+fn verify_quiet_prints_nothing() {
+    let (_tmp, root, home) = setup();
+    fs::write(root.join(".vimrc"), b"vim").unwrap();
+
+    verify(&root, &home)
+        .arg("--quiet")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::is_empty());
+
+    // Errors keep the conflict exit code and stay quiet too
+    fs::write(root.join(".vimrc.lua"), b"this is not lua").unwrap();
+    verify(&root, &home)
+        .arg("--quiet")
+        .assert()
+        .code(2)
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::is_empty());
+}
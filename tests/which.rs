@@ -0,0 +1,87 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod common;
+use common::{dirs, dotty};
+
+fn setup() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let (tmp, root, home) = dirs();
+    fs::create_dir_all(root.join("kitty")).unwrap();
+    fs::create_dir_all(root.join("nvim")).unwrap();
+    fs::write(root.join("kitty/kitty.conf"), b"font_size 13").unwrap();
+    fs::write(
+        root.join("kitty/kitty.conf.lua"),
+        br#"return { target_path = ".config/kitty/kitty.conf" }"#,
+    )
+    .unwrap();
+    fs::write(root.join("nvim/init.lua"), b"-- nvim").unwrap();
+    fs::write(
+        root.join("nvim.lua"),
+        br#"return { link_dir = true, target_path = ".config/nvim" }"#,
+    )
+    .unwrap();
+    fs::write(root.join("gitconfig"), b"[user]").unwrap();
+    fs::write(
+        root.join("gitconfig.lua"),
+        br#"return { rename_to = ".gitconfig", transform = function(c) return c end }"#,
+    )
+    .unwrap();
+    (tmp, root, home)
+}
+
+fn which(root: &Path, home: &Path, path: &str) -> Command {
+    let mut cmd = dotty(root, home);
+    cmd.arg("which").arg(home.join(path));
+    cmd
+}
+
+#[test]
+fn which_follows_target_fields_and_reports_placement() {
+    let (_tmp, root, home) = setup();
+    which(&root, &home, ".config/kitty/kitty.conf")
+        .assert()
+        .success()
+        .stdout(contains("~/.config/kitty/kitty.conf <- "))
+        .stdout(contains(
+            "kitty/kitty.conf (symlink, not in place: missing)",
+        ));
+    which(&root, &home, ".gitconfig")
+        .assert()
+        .success()
+        .stdout(contains("gitconfig (transform, not in place: missing)"));
+
+    dotty(&root, &home).assert().success();
+    which(&root, &home, ".config/kitty/kitty.conf")
+        .assert()
+        .success()
+        .stdout(contains("(symlink, in place)"));
+    which(&root, &home, ".config/nvim/init.lua")
+        .assert()
+        .success()
+        .stdout(contains(
+            "nvim/init.lua (inside directory symlink ~/.config/nvim, in place)",
+        ));
+}
+
+#[test]
+fn which_fails_when_nothing_maps_there() {
+    let (_tmp, root, home) = setup();
+    which(&root, &home, ".bashrc")
+        .assert()
+        .failure()
+        .stderr(contains("No source in the root maps to ~/.bashrc"));
+}
+
+#[test]
+fn which_reports_unplanned_symlinks_into_the_root() {
+    let (_tmp, root, home) = setup();
+    std::os::unix::fs::symlink(root.join("kitty/kitty.conf"), home.join(".kitty.conf")).unwrap();
+    which(&root, &home, ".kitty.conf")
+        .assert()
+        .success()
+        .stdout(contains("~/.kitty.conf -> "))
+        .stdout(contains("(symlink into the root, but nothing plans it)"));
+}